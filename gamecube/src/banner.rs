@@ -0,0 +1,229 @@
+use crate::disc::{decode_latin1_c_string, encode_latin1_c_string};
+use crate::error::{Error, Result};
+use crate::ReadBytesExt;
+
+/// The disc path of the banner file.
+pub const PATH: &str = "opening.bnr";
+
+pub const IMAGE_WIDTH: usize = 96;
+pub const IMAGE_HEIGHT: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BannerKind {
+    /// Used by NTSC discs. Holds a single set of metadata.
+    Bnr1,
+    /// Used by PAL discs. Holds metadata for six languages.
+    Bnr2,
+}
+
+impl BannerKind {
+    fn magic(self) -> &'static [u8; 4] {
+        match self {
+            Self::Bnr1 => b"BNR1",
+            Self::Bnr2 => b"BNR2",
+        }
+    }
+
+    fn metadata_count(self) -> usize {
+        match self {
+            Self::Bnr1 => 1,
+            Self::Bnr2 => 6,
+        }
+    }
+
+    pub fn size(self) -> usize {
+        Banner::METADATA_OFFSET + BannerMetadata::SIZE * self.metadata_count()
+    }
+}
+
+/// The `opening.bnr` file shown in the GameCube IPL and emulator game lists.
+#[derive(Clone, Debug)]
+pub struct Banner {
+    kind: BannerKind,
+    /// 96x32 RGB5A3 pixels in GX tile order.
+    image: Vec<u8>,
+    metadata: Vec<BannerMetadata>,
+}
+
+impl Banner {
+    const IMAGE_OFFSET: usize = 0x20;
+    const IMAGE_SIZE: usize = IMAGE_WIDTH * IMAGE_HEIGHT * 2;
+    const METADATA_OFFSET: usize = Self::IMAGE_OFFSET + Self::IMAGE_SIZE;
+
+    pub fn new(data: &[u8]) -> Result<Self> {
+        let kind = match &data[..4] {
+            b"BNR1" => BannerKind::Bnr1,
+            b"BNR2" => BannerKind::Bnr2,
+            magic => {
+                return Err(Error::BadMagic {
+                    format: "banner",
+                    found: magic.to_vec(),
+                })
+            }
+        };
+        if data.len() < kind.size() {
+            return Err(Error::Truncated {
+                what: "banner",
+                len: data.len(),
+                want: kind.size(),
+            });
+        }
+
+        let image = data[Self::IMAGE_OFFSET..Self::METADATA_OFFSET].to_vec();
+        let metadata = data[Self::METADATA_OFFSET..kind.size()]
+            .chunks_exact(BannerMetadata::SIZE)
+            .map(BannerMetadata::new)
+            .collect();
+
+        Ok(Self {
+            kind,
+            image,
+            metadata,
+        })
+    }
+
+    /// Writes the banner over `data`, which must be the existing banner file's bytes. Any trailing
+    /// bytes past the banner's fixed size are left intact.
+    pub fn write_to(&self, data: &mut [u8]) -> Result<()> {
+        if data.len() < self.kind.size() {
+            return Err(Error::InvalidData(format!(
+                "banner destination is too small: {} bytes, want {}",
+                data.len(),
+                self.kind.size()
+            )));
+        }
+
+        data[..4].copy_from_slice(self.kind.magic());
+        data[Self::IMAGE_OFFSET..Self::METADATA_OFFSET].copy_from_slice(&self.image);
+        for (metadata, dst) in self.metadata.iter().zip(
+            data[Self::METADATA_OFFSET..self.kind.size()].chunks_exact_mut(BannerMetadata::SIZE),
+        ) {
+            metadata.write_to(dst)?;
+        }
+        Ok(())
+    }
+
+    pub fn kind(&self) -> BannerKind {
+        self.kind
+    }
+
+    pub fn metadata(&self) -> &[BannerMetadata] {
+        &self.metadata
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut [BannerMetadata] {
+        &mut self.metadata
+    }
+
+    /// Decodes the banner image to 96x32 RGBA8 pixels in row-major order.
+    pub fn image_rgba(&self) -> Result<Vec<u8>> {
+        let mut rgba = vec![0; IMAGE_WIDTH * IMAGE_HEIGHT * 4];
+        for y in 0..IMAGE_HEIGHT {
+            for x in 0..IMAGE_WIDTH {
+                let encoded = (&self.image[Self::tiled_offset(x, y)..]).read_u16()?;
+                let offset = 4 * (IMAGE_WIDTH * y + x);
+                rgba[offset..offset + 4].copy_from_slice(&decode_rgb5a3(encoded));
+            }
+        }
+        Ok(rgba)
+    }
+
+    /// Replaces the banner image with 96x32 RGBA8 pixels in row-major order.
+    pub fn set_image_rgba(&mut self, rgba: &[u8]) -> Result<()> {
+        if rgba.len() != IMAGE_WIDTH * IMAGE_HEIGHT * 4 {
+            return Err(Error::InvalidData(format!(
+                "banner image must be {IMAGE_WIDTH}x{IMAGE_HEIGHT} RGBA8, got {} bytes",
+                rgba.len()
+            )));
+        }
+        for y in 0..IMAGE_HEIGHT {
+            for x in 0..IMAGE_WIDTH {
+                let offset = 4 * (IMAGE_WIDTH * y + x);
+                let encoded = encode_rgb5a3(rgba[offset..offset + 4].try_into().unwrap());
+                let offset = Self::tiled_offset(x, y);
+                self.image[offset..offset + 2].copy_from_slice(&encoded.to_be_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    /// Byte offset of a pixel in the 4x4-tiled RGB5A3 image.
+    fn tiled_offset(x: usize, y: usize) -> usize {
+        let blocks_wide = IMAGE_WIDTH / 4;
+        32 * (blocks_wide * (y / 4) + x / 4) + 2 * (4 * (y % 4) + x % 4)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BannerMetadata {
+    pub short_title: String,
+    pub short_maker: String,
+    pub long_title: String,
+    pub long_maker: String,
+    pub description: String,
+}
+
+impl BannerMetadata {
+    const SIZE: usize = 0x140;
+    const FIELDS: [(usize, usize); 5] = [
+        (0x00, 0x20),
+        (0x20, 0x20),
+        (0x40, 0x40),
+        (0x80, 0x40),
+        (0xc0, 0x80),
+    ];
+
+    fn new(data: &[u8]) -> Self {
+        let field =
+            |(offset, len): (usize, usize)| decode_latin1_c_string(&data[offset..offset + len]);
+        Self {
+            short_title: field(Self::FIELDS[0]),
+            short_maker: field(Self::FIELDS[1]),
+            long_title: field(Self::FIELDS[2]),
+            long_maker: field(Self::FIELDS[3]),
+            description: field(Self::FIELDS[4]),
+        }
+    }
+
+    fn write_to(&self, data: &mut [u8]) -> Result<()> {
+        for ((offset, len), value) in Self::FIELDS.into_iter().zip([
+            &self.short_title,
+            &self.short_maker,
+            &self.long_title,
+            &self.long_maker,
+            &self.description,
+        ]) {
+            encode_latin1_c_string(value, &mut data[offset..offset + len])?;
+        }
+        Ok(())
+    }
+}
+
+fn decode_rgb5a3(encoded: u16) -> [u8; 4] {
+    if encoded & 0x8000 == 0 {
+        let extend3 = |x| (x << 5) | (x << 2) | (x >> 1);
+        let extend4 = |x| (x << 4) | x;
+        [
+            extend4(((encoded >> 8) & 0xf) as u8),
+            extend4(((encoded >> 4) & 0xf) as u8),
+            extend4((encoded & 0xf) as u8),
+            extend3((encoded >> 12) as u8),
+        ]
+    } else {
+        let extend5 = |x| (x << 3) | (x >> 2);
+        [
+            extend5(((encoded >> 10) & 0x1f) as u8),
+            extend5(((encoded >> 5) & 0x1f) as u8),
+            extend5((encoded & 0x1f) as u8),
+            0xff,
+        ]
+    }
+}
+
+fn encode_rgb5a3([r, g, b, a]: [u8; 4]) -> u16 {
+    if a == 0xff {
+        0x8000 | ((r as u16 >> 3) << 10) | ((g as u16 >> 3) << 5) | (b as u16 >> 3)
+    } else {
+        ((a as u16 >> 5) << 12) | ((r as u16 >> 4) << 8) | ((g as u16 >> 4) << 4) | (b as u16 >> 4)
+    }
+}
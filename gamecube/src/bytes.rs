@@ -1,44 +1,65 @@
-use std::io::{self, Read};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 
-use anyhow::{anyhow, bail, Result};
+use anyhow::{Context, Result};
 use arrayvec::ArrayVec;
-use byteorder::BigEndian;
+pub use byteorder::{BigEndian, ByteOrder, LittleEndian};
 
+use crate::error::Error;
+
+/// Reads integers in a chosen byte order. The plain methods read big-endian, the GameCube's native
+/// order; the `_as` forms take the byte order as a type parameter so parsers can be written once
+/// for either order.
 pub trait ReadBytesExt: Read {
+    fn read_i16_as<E: ByteOrder>(&mut self) -> io::Result<i16>;
+    fn read_i32_as<E: ByteOrder>(&mut self) -> io::Result<i32>;
+    fn read_u16_as<E: ByteOrder>(&mut self) -> io::Result<u16>;
+    fn read_u32_as<E: ByteOrder>(&mut self) -> io::Result<u32>;
     fn read_i8(&mut self) -> io::Result<i8>;
-    fn read_i16(&mut self) -> io::Result<i16>;
-    fn read_i32(&mut self) -> io::Result<i32>;
     fn read_u8(&mut self) -> io::Result<u8>;
-    fn read_u16(&mut self) -> io::Result<u16>;
-    fn read_u32(&mut self) -> io::Result<u32>;
+
+    fn read_i16(&mut self) -> io::Result<i16> {
+        self.read_i16_as::<BigEndian>()
+    }
+
+    fn read_i32(&mut self) -> io::Result<i32> {
+        self.read_i32_as::<BigEndian>()
+    }
+
+    fn read_u16(&mut self) -> io::Result<u16> {
+        self.read_u16_as::<BigEndian>()
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        self.read_u32_as::<BigEndian>()
+    }
 }
 
 impl<T> ReadBytesExt for T
 where
     T: Read,
 {
-    fn read_i8(&mut self) -> io::Result<i8> {
-        <Self as byteorder::ReadBytesExt>::read_i8(self)
+    fn read_i16_as<E: ByteOrder>(&mut self) -> io::Result<i16> {
+        <Self as byteorder::ReadBytesExt>::read_i16::<E>(self)
     }
 
-    fn read_i16(&mut self) -> io::Result<i16> {
-        <Self as byteorder::ReadBytesExt>::read_i16::<BigEndian>(self)
+    fn read_i32_as<E: ByteOrder>(&mut self) -> io::Result<i32> {
+        <Self as byteorder::ReadBytesExt>::read_i32::<E>(self)
     }
 
-    fn read_i32(&mut self) -> io::Result<i32> {
-        <Self as byteorder::ReadBytesExt>::read_i32::<BigEndian>(self)
+    fn read_u16_as<E: ByteOrder>(&mut self) -> io::Result<u16> {
+        <Self as byteorder::ReadBytesExt>::read_u16::<E>(self)
     }
 
-    fn read_u8(&mut self) -> io::Result<u8> {
-        <Self as byteorder::ReadBytesExt>::read_u8(self)
+    fn read_u32_as<E: ByteOrder>(&mut self) -> io::Result<u32> {
+        <Self as byteorder::ReadBytesExt>::read_u32::<E>(self)
     }
 
-    fn read_u16(&mut self) -> io::Result<u16> {
-        <Self as byteorder::ReadBytesExt>::read_u16::<BigEndian>(self)
+    fn read_i8(&mut self) -> io::Result<i8> {
+        <Self as byteorder::ReadBytesExt>::read_i8(self)
     }
 
-    fn read_u32(&mut self) -> io::Result<u32> {
-        <Self as byteorder::ReadBytesExt>::read_u32::<BigEndian>(self)
+    fn read_u8(&mut self) -> io::Result<u8> {
+        <Self as byteorder::ReadBytesExt>::read_u8(self)
     }
 }
 
@@ -54,13 +75,26 @@ impl ReadFrom for u32 {
     }
 }
 
+/// The inverse of [`ReadFrom`], for formats that can be written back. Writing a value that was
+/// just read should reproduce the bytes it was read from.
+pub trait WriteTo {
+    fn write_to<W: Write>(&self, w: &mut W) -> Result<()>;
+}
+
+impl WriteTo for u32 {
+    fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        w.write_all(&self.to_be_bytes())?;
+        Ok(())
+    }
+}
+
 pub trait ReadTypedExt: Read {
     fn read_typed<T: ReadFrom>(&mut self) -> Result<T>;
 }
 
 impl<R: Read> ReadTypedExt for R {
     fn read_typed<T: ReadFrom>(&mut self) -> Result<T> {
-        Ok(T::read_from(self)?)
+        T::read_from(self)
     }
 }
 
@@ -78,7 +112,7 @@ pub trait ReadTypedWithContextExt: Read {
 
 impl<R: Read> ReadTypedWithContextExt for R {
     fn read_typed_with_context<T: ReadFromWithContext>(&mut self, ctx: T::Context) -> Result<T> {
-        Ok(T::read_from_with_context(self, ctx)?)
+        T::read_from_with_context(self, ctx)
     }
 }
 
@@ -88,28 +122,28 @@ pub trait ReadArrayExt: ReadTypedExt {
 
 impl<R: Read> ReadArrayExt for R {
     fn read_array<T: ReadFrom, const N: usize>(&mut self) -> Result<[T; N]> {
-        Ok(std::iter::from_fn(|| Some(self.read_typed()))
+        std::iter::from_fn(|| Some(self.read_typed()))
             .take(N)
             .collect::<Result<ArrayVec<T, N>>>()
             .map(|v| match v.into_inner() {
                 Ok(result) => result,
                 Err(_) => panic!(),
-            })?)
+            })
     }
 }
 
 pub trait ReadLengthPrefixedStringExt: Read {
-    fn read_length_prefixed_string(&mut self) -> Result<String>;
+    fn read_length_prefixed_string(&mut self) -> crate::Result<String>;
 }
 
 impl<R: Read> ReadLengthPrefixedStringExt for R {
-    fn read_length_prefixed_string(&mut self) -> Result<String> {
+    fn read_length_prefixed_string(&mut self) -> crate::Result<String> {
         let len = self.read_u32()?;
         let mut s = String::with_capacity(len as usize);
         for _ in 0..len {
             let b = self.read_u8()?;
             if !b.is_ascii() {
-                bail!("Non-ASCII byte: 0x{:02x}", b)
+                return Err(Error::InvalidData(format!("Non-ASCII byte: 0x{b:02x}")));
             }
             s.push(b as char);
         }
@@ -118,11 +152,11 @@ impl<R: Read> ReadLengthPrefixedStringExt for R {
 }
 
 pub trait ReadAsciiCStringExt: Read {
-    fn read_ascii_c_string(&mut self) -> Result<String>;
+    fn read_ascii_c_string(&mut self) -> crate::Result<String>;
 }
 
 impl<R: Read> ReadAsciiCStringExt for R {
-    fn read_ascii_c_string(&mut self) -> Result<String> {
+    fn read_ascii_c_string(&mut self) -> crate::Result<String> {
         let mut s = String::new();
         loop {
             let b = self.read_u8()?;
@@ -130,7 +164,7 @@ impl<R: Read> ReadAsciiCStringExt for R {
                 break;
             }
             if !b.is_ascii() {
-                bail!("Non-ASCII byte: 0x{:02x}", b)
+                return Err(Error::InvalidData(format!("Non-ASCII byte: 0x{b:02x}")));
             }
             s.push(b as char);
         }
@@ -139,11 +173,11 @@ impl<R: Read> ReadAsciiCStringExt for R {
 }
 
 pub trait ReadFixedCapacityAsciiCStringExt: Read {
-    fn read_fixed_capacity_ascii_c_string(&mut self, len: usize) -> Result<String>;
+    fn read_fixed_capacity_ascii_c_string(&mut self, len: usize) -> crate::Result<String>;
 }
 
 impl<R: Read> ReadFixedCapacityAsciiCStringExt for R {
-    fn read_fixed_capacity_ascii_c_string(&mut self, len: usize) -> Result<String> {
+    fn read_fixed_capacity_ascii_c_string(&mut self, len: usize) -> crate::Result<String> {
         let mut data = Vec::with_capacity(len);
         while data.len() < len {
             data.push(self.read_u8()?);
@@ -153,8 +187,126 @@ impl<R: Read> ReadFixedCapacityAsciiCStringExt for R {
             .scan((), |_, b| match b {
                 0 => None,
                 b if b.is_ascii() => Some(Ok(b as char)),
-                _ => Some(Err(anyhow!("Non-ASCII character"))),
+                _ => Some(Err(Error::InvalidData(format!(
+                    "Non-ASCII byte: 0x{b:02x}"
+                )))),
             })
             .collect()
     }
 }
+
+/// A cursor over a byte slice with random access, for formats driven by offset tables. Unlike a
+/// plain `&[u8]` reader, it knows its absolute position, so it can seek back and forth, hand out
+/// borrowed subslices, and report where parsing failed.
+#[derive(Clone, Copy, Debug)]
+pub struct SliceReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> SliceReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Moves to an absolute offset, which may be at most the end of the data.
+    pub fn seek_to(&mut self, offset: usize) -> crate::Result<()> {
+        if offset > self.data.len() {
+            return Err(Error::Truncated {
+                what: "data",
+                len: self.data.len(),
+                want: offset,
+            });
+        }
+        self.position = offset;
+        Ok(())
+    }
+
+    /// Advances to the next multiple of `alignment`.
+    pub fn align_to(&mut self, alignment: usize) -> crate::Result<()> {
+        self.seek_to(self.position.next_multiple_of(alignment))
+    }
+
+    /// Borrows `len` bytes at an absolute offset without moving.
+    pub fn slice_at(&self, offset: usize, len: usize) -> crate::Result<&'a [u8]> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or(Error::Truncated {
+                what: "data",
+                len: self.data.len(),
+                want: offset.saturating_add(len),
+            })
+    }
+
+    /// Borrows the next `len` bytes and moves past them.
+    pub fn take(&mut self, len: usize) -> crate::Result<&'a [u8]> {
+        let slice = self.slice_at(self.position, len)?;
+        self.position += len;
+        Ok(slice)
+    }
+
+    /// Parses a value at the current position, naming the position in any error.
+    pub fn read_positioned<T: ReadFrom>(&mut self) -> Result<T> {
+        let start = self.position;
+        T::read_from(self).with_context(|| format!("at offset 0x{start:x}"))
+    }
+
+    /// Parses a value that may borrow from the data at the current position.
+    pub fn read_borrowed<T: ReadFromSlice<'a>>(&mut self) -> Result<T> {
+        let start = self.position;
+        T::read_from_slice(self).with_context(|| format!("at offset 0x{start:x}"))
+    }
+}
+
+impl Read for SliceReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = &self.data[self.position.min(self.data.len())..];
+        let len = buf.len().min(remaining.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl Seek for SliceReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => (self.data.len() as u64).checked_add_signed(delta),
+            SeekFrom::Current(delta) => (self.position as u64).checked_add_signed(delta),
+        };
+        match target {
+            Some(target) => {
+                self.position = target as usize;
+                Ok(target)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek to a negative offset",
+            )),
+        }
+    }
+}
+
+/// Like [`ReadFrom`], but for types parsed with random access that may borrow from the source
+/// data.
+pub trait ReadFromSlice<'a>: Sized {
+    fn read_from_slice(r: &mut SliceReader<'a>) -> Result<Self>;
+}
@@ -1,10 +1,11 @@
 use std::io::Read;
+use std::ops::{Bound, Range, RangeBounds};
 use std::path::{Path, PathBuf};
-
-use anyhow::{bail, Result};
+use std::slice::SliceIndex;
 
 use crate::bytes::{ReadAsciiCStringExt, ReadFixedCapacityAsciiCStringExt};
-use crate::{Dol, ReadBytesExt, ReadTypedExt};
+use crate::error::{Error, Result};
+use crate::{Dol, ReadBytesExt};
 
 /// The size of a GameCube disc image.
 pub const SIZE: u32 = 1459978240;
@@ -15,25 +16,46 @@ pub struct Header {
     maker_code: String,
     disc_id: u8,
     version: u8,
+    game_name: String,
 }
 
 impl Header {
-    pub const SIZE: u32 = 8;
+    pub const SIZE: u32 = 0x400;
+
+    const GAME_NAME_OFFSET: usize = 0x20;
+    const GAME_NAME_CAPACITY: usize = 0x3e0;
 
     pub fn new(mut data: &[u8]) -> Result<Self> {
+        let game_name_data =
+            &data[Self::GAME_NAME_OFFSET..Self::GAME_NAME_OFFSET + Self::GAME_NAME_CAPACITY];
         let game_code = data.read_fixed_capacity_ascii_c_string(4)?;
         let maker_code = data.read_fixed_capacity_ascii_c_string(2)?;
         let disc_id = data.read_u8()?;
         let version = data.read_u8()?;
+        let game_name = decode_latin1_c_string(game_name_data);
 
         Ok(Self {
             game_code,
             maker_code,
             disc_id,
             version,
+            game_name,
         })
     }
 
+    /// Writes the header fields over the start of a disc image, leaving all other bytes intact.
+    pub fn write_to(&self, data: &mut [u8]) -> Result<()> {
+        data[0..4].copy_from_slice(self.game_code.as_bytes());
+        data[4..6].copy_from_slice(self.maker_code.as_bytes());
+        data[6] = self.disc_id;
+        data[7] = self.version;
+        encode_latin1_c_string(
+            &self.game_name,
+            &mut data[Self::GAME_NAME_OFFSET..Self::GAME_NAME_OFFSET + Self::GAME_NAME_CAPACITY],
+        )?;
+        Ok(())
+    }
+
     pub fn game_code(&self) -> &str {
         &self.game_code
     }
@@ -49,6 +71,93 @@ impl Header {
     pub fn version(&self) -> u8 {
         self.version
     }
+
+    pub fn game_name(&self) -> &str {
+        &self.game_name
+    }
+
+    pub fn set_game_code(&mut self, game_code: &str) -> Result<()> {
+        if game_code.len() != 4 || !game_code.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(Error::InvalidData(format!(
+                "game code must be four ASCII alphanumeric characters: {game_code:?}"
+            )));
+        }
+        self.game_code = game_code.to_string();
+        Ok(())
+    }
+
+    pub fn set_maker_code(&mut self, maker_code: &str) -> Result<()> {
+        if maker_code.len() != 2 || !maker_code.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(Error::InvalidData(format!(
+                "maker code must be two ASCII alphanumeric characters: {maker_code:?}"
+            )));
+        }
+        self.maker_code = maker_code.to_string();
+        Ok(())
+    }
+
+    pub fn set_game_name(&mut self, game_name: &str) -> Result<()> {
+        // Validate the encoding up front so a later write can't fail halfway through.
+        encode_latin1_c_string(game_name, &mut [0; Self::GAME_NAME_CAPACITY])?;
+        self.game_name = game_name.to_string();
+        Ok(())
+    }
+}
+
+/// Decodes a NUL-terminated string in a fixed-capacity field. Text on non-Japanese discs is
+/// Windows-1252, which agrees with Latin-1 for everything the games actually use.
+pub(crate) fn decode_latin1_c_string(data: &[u8]) -> String {
+    data.iter()
+        .take_while(|&&b| b != 0)
+        .map(|&b| b as char)
+        .collect()
+}
+
+/// Encodes a string into a fixed-capacity field, zero-filling the remainder. At least one NUL
+/// terminator must fit.
+pub(crate) fn encode_latin1_c_string(s: &str, data: &mut [u8]) -> Result<()> {
+    let mut len = 0;
+    for c in s.chars() {
+        if len + 1 >= data.len() {
+            return Err(Error::InvalidData(format!(
+                "string is too long for a {} byte field: {s:?}",
+                data.len()
+            )));
+        }
+        let b: u8 = match c.try_into() {
+            Ok(b) => b,
+            Err(_) => {
+                return Err(Error::InvalidData(format!(
+                    "character {c:?} cannot be encoded"
+                )))
+            }
+        };
+        data[len] = b;
+        len += 1;
+    }
+    data[len..].fill(0);
+    Ok(())
+}
+
+/// `data[range]`, or [`Error::Truncated`] naming `what` if `data` ends first.
+fn slice<'a, R>(data: &'a [u8], what: &'static str, range: R) -> Result<&'a [u8]>
+where
+    R: RangeBounds<usize> + SliceIndex<[u8], Output = [u8]>,
+{
+    let want = match range.end_bound() {
+        Bound::Included(&end) => end.saturating_add(1),
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        },
+    };
+    data.get(range).ok_or(Error::Truncated {
+        what,
+        len: data.len(),
+        want,
+    })
 }
 
 #[derive(Clone)]
@@ -56,6 +165,8 @@ pub struct Disc<'a> {
     data: &'a [u8],
     header: Header,
     main_executable: Dol,
+    main_executable_offset: u32,
+    file_table_offset: u32,
     file_table: &'a [u8],
     root_entry_count: u32,
     string_table: &'a [u8],
@@ -63,29 +174,71 @@ pub struct Disc<'a> {
 
 impl<'a> Disc<'a> {
     const HEADER_OFFSET: usize = 0;
+    const EXTRA_HEADER_OFFSET: usize = 0x440;
+    const APPLOADER_OFFSET: usize = 0x2440;
     const MAIN_EXECUTABLE_OFFSET: usize = 0x420;
     const FILE_TABLE_PTR_OFFSET: usize = 0x424;
     const FILE_TABLE_SIZE_OFFSET: usize = 0x428;
 
+    /// Offsets within the apploader of its code size and trailer size, which follow its header.
+    const APPLOADER_SIZE_OFFSET: usize = 0x14;
+    const APPLOADER_TRAILER_SIZE_OFFSET: usize = 0x18;
+    const APPLOADER_HEADER_SIZE: usize = 0x20;
+
     const ROOT_ENTRY_COUNT_OFFSET: usize = 8;
     const FILE_TABLE_ENTRY_SIZE: usize = 12;
 
     pub fn new(data: &'a [u8]) -> Result<Self> {
-        let header = Header::new(&data[Self::HEADER_OFFSET..])?;
-        let main_executable = (&data[Self::MAIN_EXECUTABLE_OFFSET..]).read_typed()?;
-        let filesystem_table_ptr = (&data[Self::FILE_TABLE_PTR_OFFSET..]).read_u32()?;
-        let filesystem_table_size = (&data[Self::FILE_TABLE_SIZE_OFFSET..]).read_u32()?;
-        let filesystem_table = &data[filesystem_table_ptr as usize
-            ..(filesystem_table_ptr + filesystem_table_size) as usize];
+        let header = Header::new(slice(
+            data,
+            "disc image",
+            Self::HEADER_OFFSET..Header::SIZE as usize,
+        )?)?;
+        let main_executable_offset =
+            slice(data, "disc image", Self::MAIN_EXECUTABLE_OFFSET..)?.read_u32()?;
+        let main_executable = Dol::new(slice(
+            data,
+            "disc image",
+            main_executable_offset as usize..,
+        )?)?;
+        let filesystem_table_ptr =
+            slice(data, "disc image", Self::FILE_TABLE_PTR_OFFSET..)?.read_u32()?;
+        let filesystem_table_size =
+            slice(data, "disc image", Self::FILE_TABLE_SIZE_OFFSET..)?.read_u32()?;
+        let filesystem_table_end = (filesystem_table_ptr as usize)
+            .checked_add(filesystem_table_size as usize)
+            .ok_or(Error::Truncated {
+                what: "disc image",
+                len: data.len(),
+                want: usize::MAX,
+            })?;
+        let filesystem_table = slice(
+            data,
+            "disc image",
+            filesystem_table_ptr as usize..filesystem_table_end,
+        )?;
 
-        let root_entry_count = (&filesystem_table[Self::ROOT_ENTRY_COUNT_OFFSET..]).read_u32()?;
-        let string_table =
-            &filesystem_table[root_entry_count as usize * Self::FILE_TABLE_ENTRY_SIZE..];
+        let root_entry_count = slice(
+            filesystem_table,
+            "file table",
+            Self::ROOT_ENTRY_COUNT_OFFSET..,
+        )?
+        .read_u32()?;
+        let string_table_offset = (root_entry_count as usize)
+            .checked_mul(Self::FILE_TABLE_ENTRY_SIZE)
+            .ok_or(Error::Truncated {
+                what: "file table",
+                len: filesystem_table.len(),
+                want: usize::MAX,
+            })?;
+        let string_table = slice(filesystem_table, "file table", string_table_offset..)?;
 
         Ok(Self {
             data,
             header,
             main_executable,
+            main_executable_offset,
+            file_table_offset: filesystem_table_ptr,
             file_table: filesystem_table,
             root_entry_count,
             string_table,
@@ -100,7 +253,48 @@ impl<'a> Disc<'a> {
         &self.main_executable
     }
 
-    pub fn iter_files(&self) -> impl Iterator<Item = Result<File>> + '_ {
+    /// The data outside the filesystem, named as Dolphin names it when extracting a disc: the disc
+    /// header, the extra header, the apploader, the main executable, and the file table.
+    pub fn system_files(&self) -> Result<[(&'static str, &'a [u8]); 5]> {
+        let apploader = slice(self.data, "disc image", Self::APPLOADER_OFFSET..)?;
+        let code_size = slice(apploader, "apploader", Self::APPLOADER_SIZE_OFFSET..)?.read_u32()?;
+        let trailer_size = slice(
+            apploader,
+            "apploader",
+            Self::APPLOADER_TRAILER_SIZE_OFFSET..,
+        )?
+        .read_u32()?;
+        let apploader_size =
+            Self::APPLOADER_HEADER_SIZE + code_size as usize + trailer_size as usize;
+        let main_executable_offset = self.main_executable_offset as usize;
+        let slice = |range: Range<usize>| slice(self.data, "disc image", range);
+        Ok([
+            (
+                "boot.bin",
+                slice(Self::HEADER_OFFSET..Self::EXTRA_HEADER_OFFSET)?,
+            ),
+            (
+                "bi2.bin",
+                slice(Self::EXTRA_HEADER_OFFSET..Self::APPLOADER_OFFSET)?,
+            ),
+            (
+                "apploader.img",
+                slice(Self::APPLOADER_OFFSET..Self::APPLOADER_OFFSET + apploader_size)?,
+            ),
+            (
+                "main.dol",
+                slice(
+                    main_executable_offset
+                        ..main_executable_offset + self.main_executable.file_size() as usize,
+                )?,
+            ),
+            ("fst.bin", self.file_table),
+        ])
+    }
+
+    /// Every file on the disc, in file table order. An entry whose data lies outside the image is
+    /// an [`Error::FileOutOfBounds`] naming it, and the entries after it are still visited.
+    pub fn iter_files(&self) -> impl Iterator<Item = Result<File<'_>>> + '_ {
         let mut r = &self.file_table[Self::FILE_TABLE_ENTRY_SIZE..];
         let mut path = PathBuf::new();
         let mut dir_ends = Vec::new();
@@ -116,9 +310,23 @@ impl<'a> Disc<'a> {
                     FileTableEntryData::File { offset, size } => {
                         let mut file_path = path.clone();
                         file_path.push(entry.name);
+                        let Some(data) = self
+                            .data
+                            .get(offset as usize..offset as usize + size as usize)
+                        else {
+                            return Err(Error::FileOutOfBounds {
+                                path: file_path,
+                                offset,
+                                size,
+                                image_len: self.data.len(),
+                            });
+                        };
                         Ok(Some(File {
                             path: file_path,
-                            data: &self.data[offset as usize..(offset + size) as usize],
+                            entry_offset: self.file_table_offset as usize
+                                + index as usize * Self::FILE_TABLE_ENTRY_SIZE,
+                            offset,
+                            data,
                         }))
                     }
                     FileTableEntryData::Directory { end_index } => {
@@ -132,15 +340,69 @@ impl<'a> Disc<'a> {
         })
     }
 
-    pub fn find_file(&self, path: &Path) -> Result<Option<File>> {
+    /// The file at `path`. Other files lying outside the image are passed over.
+    pub fn find_file(&self, path: &Path) -> Result<Option<File<'_>>> {
         for file in self.iter_files() {
-            let file = file?;
-            if &file.path == path {
+            let file = match file {
+                Err(Error::FileOutOfBounds { path: other, .. }) if other != path => continue,
+                file => file?,
+            };
+            if file.path == path {
                 return Ok(Some(file));
             }
         }
         Ok(None)
     }
+
+    /// Overwrites the file at `path` within a disc image and updates its size in the file table,
+    /// leaving everything else where it is. The replacement may be shorter than the file, in which
+    /// case the rest of the old data is zeroed, or longer if it stops short of whatever follows the
+    /// file on the disc. Returns the ranges of the image written: the file's data, covering both
+    /// the old and new sizes, and its file table entry.
+    pub fn inject_file(image: &mut [u8], path: &Path, data: &[u8]) -> Result<[Range<usize>; 2]> {
+        let (file_offset, old_size, entry_offset, capacity) = {
+            let disc = Disc::new(image)?;
+            let file = disc.find_file(path)?.ok_or_else(|| {
+                Error::InvalidData(format!("no file at {} on the disc", path.display()))
+            })?;
+            let mut next_offset = image.len() as u32;
+            for other in disc.iter_files() {
+                let other = other?;
+                if other.offset > file.offset {
+                    next_offset = next_offset.min(other.offset);
+                }
+            }
+            for offset in [disc.main_executable_offset, disc.file_table_offset] {
+                if offset > file.offset {
+                    next_offset = next_offset.min(offset);
+                }
+            }
+            (
+                file.offset as usize,
+                file.data.len(),
+                file.entry_offset,
+                (next_offset - file.offset) as usize,
+            )
+        };
+        if data.len() > capacity {
+            return Err(Error::InvalidData(format!(
+                "the replacement for {} is {} bytes, but only {capacity} fit",
+                path.display(),
+                data.len(),
+            )));
+        }
+
+        image[file_offset..file_offset + data.len()].copy_from_slice(data);
+        if data.len() < old_size {
+            image[file_offset + data.len()..file_offset + old_size].fill(0);
+        }
+        image[entry_offset + 8..entry_offset + 12]
+            .copy_from_slice(&(data.len() as u32).to_be_bytes());
+        Ok([
+            file_offset..file_offset + data.len().max(old_size),
+            entry_offset..entry_offset + Self::FILE_TABLE_ENTRY_SIZE,
+        ])
+    }
 }
 
 struct FileTableEntry {
@@ -158,19 +420,27 @@ impl FileTableEntry {
         let tmp = r.read_u32()?;
         let flags = (tmp >> 24) as u8;
         let name_offset = tmp & 0x00ffffff;
-        let name = (&string_table[name_offset as usize..]).read_ascii_c_string()?;
+        let word_1 = r.read_u32()?;
+        let word_2 = r.read_u32()?;
+        let name = string_table
+            .get(name_offset as usize..)
+            .ok_or_else(|| {
+                Error::InvalidData(format!(
+                    "filesystem entry name offset 0x{name_offset:06x} is past the string table"
+                ))
+            })?
+            .read_ascii_c_string()?;
         let data = match flags {
-            0 => {
-                let offset = r.read_u32()?;
-                let size = r.read_u32()?;
-                FileTableEntryData::File { offset, size }
-            }
-            1 => {
-                r.read_u32()?;
-                let end_index = r.read_u32()?;
-                FileTableEntryData::Directory { end_index }
+            0 => FileTableEntryData::File {
+                offset: word_1,
+                size: word_2,
+            },
+            1 => FileTableEntryData::Directory { end_index: word_2 },
+            _ => {
+                return Err(Error::InvalidData(format!(
+                    "unexpected filesystem entry flags: 0x{flags:02x}"
+                )))
             }
-            _ => bail!("unexpected filesystem entry flags: 0x{:02x}", flags),
         };
         Ok(FileTableEntry { name, data })
     }
@@ -179,6 +449,9 @@ impl FileTableEntry {
 #[derive(Clone, Debug)]
 pub struct File<'a> {
     path: PathBuf,
+    /// Byte offset of the file's entry in the file table.
+    entry_offset: usize,
+    offset: u32,
     data: &'a [u8],
 }
 
@@ -187,6 +460,11 @@ impl<'a> File<'a> {
         &self.path
     }
 
+    /// Byte offset of the file's data within the disc image.
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
     pub fn data(&self) -> &'a [u8] {
         self.data
     }
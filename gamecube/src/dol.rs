@@ -1,9 +1,8 @@
 use std::io::Read;
 
-use anyhow::Result;
-
 use crate::bytes::ReadFrom;
-use crate::{ReadArrayExt, ReadBytesExt};
+use crate::error::Result;
+use crate::ReadBytesExt;
 
 #[derive(Clone)]
 pub struct Dol {
@@ -13,16 +12,23 @@ pub struct Dol {
     entry_point: u32,
 }
 
-impl Dol {}
+impl Dol {
+    const HEADER_SIZE: u32 = 0x100;
 
-impl ReadFrom for Dol {
-    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
-        let section_offsets = r.read_array()?;
-        let section_load_addrs = r.read_array()?;
-        let section_sizes = r.read_array()?;
-        r.read_u32()?;
-        r.read_u32()?;
-        let entry_point = r.read_u32()?;
+    pub fn new(mut data: &[u8]) -> Result<Self> {
+        let mut read_array = || -> Result<[u32; 18]> {
+            let mut array = [0; 18];
+            for x in &mut array {
+                *x = data.read_u32()?;
+            }
+            Ok(array)
+        };
+        let section_offsets = read_array()?;
+        let section_load_addrs = read_array()?;
+        let section_sizes = read_array()?;
+        data.read_u32()?;
+        data.read_u32()?;
+        let entry_point = data.read_u32()?;
 
         Ok(Self {
             section_offsets,
@@ -31,4 +37,37 @@ impl ReadFrom for Dol {
             entry_point,
         })
     }
+
+    pub fn section_offsets(&self) -> &[u32; 18] {
+        &self.section_offsets
+    }
+
+    pub fn section_load_addrs(&self) -> &[u32; 18] {
+        &self.section_load_addrs
+    }
+
+    pub fn section_sizes(&self) -> &[u32; 18] {
+        &self.section_sizes
+    }
+
+    pub fn entry_point(&self) -> u32 {
+        self.entry_point
+    }
+
+    /// The size of the executable as a file: its header and every section's data.
+    pub fn file_size(&self) -> u32 {
+        self.section_offsets
+            .iter()
+            .zip(&self.section_sizes)
+            .map(|(&offset, &size)| offset.saturating_add(size))
+            .fold(Self::HEADER_SIZE, u32::max)
+    }
+}
+
+impl ReadFrom for Dol {
+    fn read_from<R: Read>(r: &mut R) -> anyhow::Result<Self> {
+        let mut data = [0; 0xe4];
+        r.read_exact(&mut data)?;
+        Ok(Self::new(&data)?)
+    }
 }
@@ -0,0 +1,117 @@
+use crate::error::{Error, Result};
+use crate::{ReadBytesExt, SliceReader};
+
+/// File extension of standalone DSP ADPCM streams in the disc filesystem.
+pub const EXTENSION: &str = "dsp";
+
+/// A mono stream in the Dolphin SDK's standard DSP ADPCM format, decoded to 16-bit PCM.
+#[derive(Clone, Debug)]
+pub struct DspAudio {
+    sample_rate: u32,
+    loop_range: Option<(u32, u32)>,
+    samples: Vec<i16>,
+}
+
+/// Bytes in each DSP ADPCM frame: a predictor and scale byte, then 14 4-bit samples.
+pub const FRAME_SIZE: usize = 8;
+pub const SAMPLES_PER_FRAME: usize = 14;
+
+impl DspAudio {
+    const HEADER_SIZE: usize = 0x60;
+
+    /// Decodes a `.dsp` file: a 0x60-byte header followed by ADPCM frames.
+    pub fn new(data: &[u8]) -> Result<Self> {
+        let mut r = SliceReader::new(data);
+        let sample_count = r.read_u32()? as usize;
+        let _nibble_count = r.read_u32()?;
+        let sample_rate = r.read_u32()?;
+        let looped = r.read_u16()? != 0;
+        let format = r.read_u16()?;
+        if format != 0 {
+            return Err(Error::InvalidData(format!(
+                "unexpected DSP format: {format}"
+            )));
+        }
+        let loop_start = r.read_u32()?;
+        let loop_end = r.read_u32()?;
+        let _current_address = r.read_u32()?;
+        let mut coefficients = [[0; 2]; 8];
+        for pair in &mut coefficients {
+            for c in pair {
+                *c = r.read_i16()? as i32;
+            }
+        }
+        let _gain = r.read_u16()?;
+        let _initial_predictor_scale = r.read_u16()?;
+        let history = [r.read_i16()? as i32, r.read_i16()? as i32];
+
+        let frames = &data[Self::HEADER_SIZE.min(data.len())..];
+        let samples = decode_adpcm(frames, sample_count, &coefficients, history)?;
+
+        let loop_range = looped.then(|| {
+            (
+                nibble_address_to_sample(loop_start),
+                nibble_address_to_sample(loop_end),
+            )
+        });
+        Ok(Self {
+            sample_rate,
+            loop_range,
+            samples,
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The first and last sample of the looped section, inclusive.
+    pub fn loop_range(&self) -> Option<(u32, u32)> {
+        self.loop_range
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+}
+
+/// Decodes `sample_count` samples of DSP ADPCM frames to 16-bit PCM. `coefficients` are the eight
+/// predictor pairs, and `history` the two samples before the first, most recent first.
+pub fn decode_adpcm(
+    frames: &[u8],
+    sample_count: usize,
+    coefficients: &[[i32; 2]; 8],
+    mut history: [i32; 2],
+) -> Result<Vec<i16>> {
+    let frame_count = sample_count.div_ceil(SAMPLES_PER_FRAME);
+    if frames.len() < frame_count * FRAME_SIZE {
+        return Err(Error::Truncated {
+            what: "DSP data",
+            len: frames.len(),
+            want: frame_count * FRAME_SIZE,
+        });
+    }
+
+    let mut samples = Vec::with_capacity(sample_count);
+    for frame in frames.chunks_exact(FRAME_SIZE).take(frame_count) {
+        let [c1, c2] = coefficients[(frame[0] >> 4) as usize & 7];
+        let scale = 1 << (frame[0] & 0xf);
+        for &byte in &frame[1..] {
+            for nibble in [byte >> 4, byte & 0xf] {
+                // Sign-extend the 4-bit value.
+                let nibble = ((nibble as i8) << 4 >> 4) as i32;
+                let sample = ((nibble * scale) << 11) + c1 * history[0] + c2 * history[1];
+                let sample = ((sample + 1024) >> 11).clamp(i16::MIN as i32, i16::MAX as i32);
+                history = [sample, history[0]];
+                samples.push(sample as i16);
+            }
+        }
+    }
+    samples.truncate(sample_count);
+    Ok(samples)
+}
+
+/// Converts an address in nibbles, which counts each frame's header byte, to a sample index.
+fn nibble_address_to_sample(address: u32) -> u32 {
+    (address / 16 * 14 + (address % 16)).saturating_sub(2)
+}
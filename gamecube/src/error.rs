@@ -0,0 +1,40 @@
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// Why parsing or writing a GameCube format failed, for callers that handle some failures
+/// differently from others.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unexpected {format} magic: {found:02x?}")]
+    BadMagic {
+        format: &'static str,
+        found: Vec<u8>,
+    },
+    #[error("{what} is truncated: {len} bytes, want {want}")]
+    Truncated {
+        what: &'static str,
+        len: usize,
+        want: usize,
+    },
+    /// A file table entry whose data would run past the end of the disc image, as in images that
+    /// trim or scramble padding regions.
+    #[error(
+        "{} lies outside the disc image: {size} bytes at 0x{offset:08x}, but the image is {image_len} bytes",
+        path.display(),
+    )]
+    FileOutOfBounds {
+        path: PathBuf,
+        offset: u32,
+        size: u32,
+        image_len: usize,
+    },
+    /// A value that the format doesn't allow, or that can't be encoded in it.
+    #[error("{0}")]
+    InvalidData(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
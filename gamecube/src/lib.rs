@@ -1,7 +1,24 @@
+//! Parsers and writers for GameCube disc images and the SDK formats found on them.
+//!
+//! Concrete parsers return the typed [`Error`]. The [`bytes::ReadFrom`] family of traits is the
+//! extension point for formats defined elsewhere and returns [`anyhow::Result`]; errors raised
+//! here still reach those callers as an [`Error`] that can be recovered with
+//! `anyhow::Error::downcast_ref`.
+
+pub mod banner;
 pub mod bytes;
 pub mod disc;
 pub mod dol;
+pub mod dsp;
+pub mod error;
+pub mod thp;
 
-pub use crate::bytes::{ReadArrayExt, ReadBytesExt, ReadTypedExt};
+pub use crate::banner::Banner;
+pub use crate::bytes::{
+    BigEndian, LittleEndian, ReadArrayExt, ReadBytesExt, ReadTypedExt, SliceReader,
+};
 pub use crate::disc::Disc;
 pub use crate::dol::Dol;
+pub use crate::dsp::DspAudio;
+pub use crate::error::{Error, Result};
+pub use crate::thp::ThpHeader;
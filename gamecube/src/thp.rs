@@ -0,0 +1,119 @@
+use crate::error::{Error, Result};
+use crate::{ReadBytesExt, SliceReader};
+
+/// File extension of THP videos in the disc filesystem.
+pub const EXTENSION: &str = "thp";
+
+/// The header of a THP video, the Dolphin SDK's Motion JPEG container.
+#[derive(Clone, Debug)]
+pub struct ThpHeader {
+    version: u32,
+    frames_per_second: f32,
+    frame_count: u32,
+    video: Option<ThpVideoInfo>,
+    audio: Option<ThpAudioInfo>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ThpVideoInfo {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ThpAudioInfo {
+    pub channel_count: u32,
+    pub sample_rate: u32,
+    pub sample_count: u32,
+}
+
+impl ThpHeader {
+    const COMPONENT_VIDEO: u8 = 0;
+    const COMPONENT_AUDIO: u8 = 1;
+    const COMPONENT_NONE: u8 = 0xff;
+
+    /// Parses the header and component table at the start of a THP file.
+    pub fn new(data: &[u8]) -> Result<Self> {
+        if data.get(..4) != Some(b"THP\0") {
+            return Err(Error::BadMagic {
+                format: "THP",
+                found: data[..data.len().min(4)].to_vec(),
+            });
+        }
+        let mut r = SliceReader::new(data);
+        r.seek_to(4)?;
+        let version = r.read_u32()?;
+        let _max_buffer_size = r.read_u32()?;
+        let _max_audio_samples = r.read_u32()?;
+        let frames_per_second = f32::from_bits(r.read_u32()?);
+        let frame_count = r.read_u32()?;
+        let _first_frame_size = r.read_u32()?;
+        let _movie_data_size = r.read_u32()?;
+        let component_info_offset = r.read_u32()?;
+
+        r.seek_to(component_info_offset as usize)?;
+        let component_count = r.read_u32()?;
+        let component_kinds = r.take(16)?;
+        let mut video = None;
+        let mut audio = None;
+        for &kind in component_kinds.iter().take(component_count as usize) {
+            match kind {
+                Self::COMPONENT_VIDEO => {
+                    video = Some(ThpVideoInfo {
+                        width: r.read_u32()?,
+                        height: r.read_u32()?,
+                    });
+                    // Version 1.1 adds a field for the video format.
+                    if version >= 0x11000 {
+                        r.read_u32()?;
+                    }
+                }
+                Self::COMPONENT_AUDIO => {
+                    audio = Some(ThpAudioInfo {
+                        channel_count: r.read_u32()?,
+                        sample_rate: r.read_u32()?,
+                        sample_count: r.read_u32()?,
+                    });
+                    // Version 1.1 adds a count of audio tracks.
+                    if version >= 0x11000 {
+                        r.read_u32()?;
+                    }
+                }
+                Self::COMPONENT_NONE => (),
+                _ => {
+                    return Err(Error::InvalidData(format!(
+                        "unexpected THP component type: {kind}"
+                    )))
+                }
+            }
+        }
+
+        Ok(Self {
+            version,
+            frames_per_second,
+            frame_count,
+            video,
+            audio,
+        })
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn frames_per_second(&self) -> f32 {
+        self.frames_per_second
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    pub fn video(&self) -> Option<&ThpVideoInfo> {
+        self.video.as_ref()
+    }
+
+    pub fn audio(&self) -> Option<&ThpAudioInfo> {
+        self.audio.as_ref()
+    }
+}
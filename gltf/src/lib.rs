@@ -46,6 +46,11 @@ impl<'a> Serialize for GltfVector4<'a> {
 #[serde(transparent)]
 pub struct AccessorIndex(pub usize);
 
+/// Indexes the samplers of the animation that holds it, not a document-wide list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(transparent)]
+pub struct AnimationSamplerIndex(pub usize);
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 #[serde(transparent)]
 pub struct BufferIndex(pub usize);
@@ -54,6 +59,10 @@ pub struct BufferIndex(pub usize);
 #[serde(transparent)]
 pub struct BufferViewIndex(pub usize);
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(transparent)]
+pub struct CameraIndex(pub usize);
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 #[serde(transparent)]
 pub struct ImageIndex(pub usize);
@@ -89,14 +98,22 @@ pub struct TextureIndex(pub usize);
 #[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Gltf {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extensions_used: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extensions_required: Vec<String>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub accessors: Vec<Accessor>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub animations: Vec<Animation>,
     pub asset: Asset,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub buffers: Vec<Buffer>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub buffer_views: Vec<BufferView>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cameras: Vec<Camera>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub images: Vec<Image>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub materials: Vec<Material>,
@@ -106,6 +123,7 @@ pub struct Gltf {
     pub nodes: Vec<Node>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub samplers: Vec<Sampler>,
+    /// The scene to show on load. Viewers may offer the others in `scenes` to switch to.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scene: Option<SceneIndex>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
@@ -135,6 +153,9 @@ pub struct Accessor {
     #[serde(rename = "type")]
     pub type_: AccessorType,
     pub component_type: AccessorComponentType,
+    /// Whether integer components map to [0, 1] (unsigned) or [-1, 1] (signed) when read.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub normalized: bool,
     pub count: usize,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub min: Option<Vec<f32>>,
@@ -190,6 +211,73 @@ impl Serialize for AccessorType {
     }
 }
 
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Animation {
+    pub name: String,
+    pub channels: Vec<AnimationChannel>,
+    pub samplers: Vec<AnimationSampler>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AnimationChannel {
+    pub sampler: AnimationSamplerIndex,
+    pub target: AnimationChannelTarget,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AnimationChannelTarget {
+    pub node: NodeIndex,
+    pub path: AnimationTargetPath,
+}
+
+/// The node property an animation channel drives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AnimationTargetPath {
+    Translation,
+    Rotation,
+    Scale,
+    /// Morph target weights.
+    Weights,
+}
+
+impl Serialize for AnimationTargetPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            Self::Translation => "translation",
+            Self::Rotation => "rotation",
+            Self::Scale => "scale",
+            Self::Weights => "weights",
+        })
+    }
+}
+
+/// Pairs keyframe times in seconds (`input`, a scalar float accessor) with the values at those
+/// times (`output`). Cubic spline outputs hold an in-tangent, value, and out-tangent per keyframe.
+#[derive(Clone, Debug, Serialize)]
+pub struct AnimationSampler {
+    pub input: AccessorIndex,
+    pub interpolation: AnimationInterpolation,
+    pub output: AccessorIndex,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AnimationInterpolation {
+    #[default]
+    Linear,
+    Step,
+    CubicSpline,
+}
+
+impl Serialize for AnimationInterpolation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            Self::Linear => "LINEAR",
+            Self::Step => "STEP",
+            Self::CubicSpline => "CUBICSPLINE",
+        })
+    }
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct Asset {
     pub version: Version,
@@ -212,6 +300,43 @@ pub struct BufferView {
     pub byte_stride: Option<usize>,
 }
 
+/// Looks down the -Z axis of its node, with +Y up.
+#[derive(Clone, Debug, Serialize)]
+pub struct Camera {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: CameraType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub perspective: Option<CameraPerspective>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CameraType {
+    Perspective,
+}
+
+impl Serialize for CameraType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            Self::Perspective => "perspective",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CameraPerspective {
+    /// Width over height. Viewers use their viewport's if this is absent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aspect_ratio: Option<f32>,
+    /// Vertical field of view in radians.
+    pub yfov: f32,
+    /// The far clipping plane, or none for an infinite projection.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zfar: Option<f32>,
+    pub znear: f32,
+}
+
 #[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Image {
@@ -228,10 +353,61 @@ pub struct Image {
 pub struct Material {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pbr_metallic_roughness: Option<PbrMetallicRoughness>,
+    /// How the base color's alpha is used. glTF treats a missing mode as opaque.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha_mode: Option<AlphaMode>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub double_sided: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<MaterialExtensions>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AlphaMode {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+impl Serialize for AlphaMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            Self::Opaque => "OPAQUE",
+            Self::Mask => "MASK",
+            Self::Blend => "BLEND",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MaterialExtensions {
+    #[serde(
+        rename = "KHR_materials_unlit",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub khr_materials_unlit: Option<KhrMaterialsUnlit>,
+}
+
+/// Marks a material as shadeless. The extension has no properties.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct KhrMaterialsUnlit {}
+
+impl KhrMaterialsUnlit {
+    pub const NAME: &'static str = "KHR_materials_unlit";
+}
+
+/// Allows integer vertex attributes beyond those of core glTF, such as normalized short positions.
+/// The extension has no properties.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct KhrMeshQuantization {}
+
+impl KhrMeshQuantization {
+    pub const NAME: &'static str = "KHR_mesh_quantization";
 }
 
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct Mesh {
+    pub name: String,
     pub primitives: Vec<MeshPrimitive>,
 }
 
@@ -297,7 +473,12 @@ pub struct Node {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mesh: Option<MeshIndex>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera: Option<CameraIndex>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub skin: Option<SkinIndex>,
+    /// Application-specific data, which glTF tools generally preserve and display.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extras: Option<serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Default, Serialize)]
@@ -409,6 +590,28 @@ pub struct TextureInfo {
     pub index: TextureIndex,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tex_coord: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<TextureInfoExtensions>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TextureInfoExtensions {
+    #[serde(
+        rename = "KHR_texture_transform",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub khr_texture_transform: Option<KhrTextureTransform>,
+}
+
+/// Maps texture coordinates through `offset + scale * uv` before sampling.
+#[derive(Clone, Debug, Serialize)]
+pub struct KhrTextureTransform {
+    pub offset: [f32; 2],
+    pub scale: [f32; 2],
+}
+
+impl KhrTextureTransform {
+    pub const NAME: &'static str = "KHR_texture_transform";
 }
 
 #[derive(Clone, Copy, Debug)]
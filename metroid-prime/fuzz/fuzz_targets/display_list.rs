@@ -0,0 +1,46 @@
+//! Feeds arbitrary display lists and attribute arrays to the display list parser and
+//! disassembler, which must report malformed data as errors rather than panicking.
+//!
+//! Run with `cargo fuzz run display_list` from the metroid-prime directory.
+
+#![no_main]
+
+use gamecube::bytes::ReadFrom;
+use libfuzzer_sys::fuzz_target;
+
+// metroid-prime is a binary, so the module is compiled in directly.
+#[allow(dead_code)]
+#[path = "../../src/gx.rs"]
+mod gx;
+
+use gx::{DisplayList, SkinnedVertexDescriptor, VertexArrays, VertexInputs};
+
+fuzz_target!(|data: &[u8]| {
+    // The input is the material's vertex attribute flags, then the attribute arrays, then the
+    // display list, with the split point given by one byte.
+    let [f0, f1, f2, f3, split, rest @ ..] = data else {
+        return;
+    };
+    let inputs = VertexInputs::from_flags(u32::from_be_bytes([*f0, *f1, *f2, *f3]));
+    let (array_data, mut display_list_data) =
+        rest.split_at((*split as usize * rest.len() / 255).min(rest.len()));
+    let Ok(display_list) = DisplayList::read_from(&mut display_list_data) else {
+        return;
+    };
+
+    let arrays = VertexArrays {
+        positions: array_data,
+        normals: array_data,
+        colors: array_data,
+        texcoords: array_data,
+        short_texcoords: array_data,
+    };
+    let joints: Vec<[u32; 4]> = array_data
+        .chunks_exact(16)
+        .map(|c| [0, 1, 2, 3].map(|i| u32::from_be_bytes(c[4 * i..4 * i + 4].try_into().unwrap())))
+        .collect();
+    let weights: Vec<[f32; 4]> = joints.iter().map(|j| j.map(f32::from_bits)).collect();
+
+    let _ = display_list.parse::<SkinnedVertexDescriptor>(&inputs, &arrays, &joints, &weights);
+    let _ = display_list.disassemble(&inputs);
+});
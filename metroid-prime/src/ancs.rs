@@ -7,8 +7,9 @@ use gamecube::bytes::{
 };
 use gamecube::{ReadBytesExt, ReadTypedExt};
 use pretty_hex::PrettyHex;
+use serde::Serialize;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Ancs {
     pub character_set: CharacterSet,
     pub animation_set: AnimationSet,
@@ -28,7 +29,7 @@ impl ReadFrom for Ancs {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct CharacterSet {
     pub version: u16,
     pub characters: Vec<Character>,
@@ -50,7 +51,7 @@ impl ReadFrom for CharacterSet {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Character {
     pub id: u32,
     pub version: u16,
@@ -61,6 +62,8 @@ pub struct Character {
     pub animations: Vec<AnimationName>,
     pub pas_database: PasDatabase,
     pub particle_resource_data: ParticleResourceData,
+    /// Values of unknown purpose following the particle resources: one, or two from version 10.
+    pub unknown: Vec<u32>,
     pub animation_aabbs: Vec<AnimationAabb>,
     pub effects: Vec<Effect>,
     pub frozen_model_id: u32,
@@ -90,9 +93,9 @@ impl ReadFrom for Character {
         let particle_resource_data =
             r.read_typed_with_context(ParticleResourceDataContext { version })?;
 
-        let _ = r.read_u32()?;
+        let mut unknown = vec![r.read_u32()?];
         if version >= 10 {
-            let _ = r.read_u32()?;
+            unknown.push(r.read_u32()?);
         }
 
         let mut animation_aabbs = Vec::new();
@@ -138,6 +141,7 @@ impl ReadFrom for Character {
             animations: animation_names,
             pas_database,
             particle_resource_data,
+            unknown,
             animation_aabbs,
             effects,
             frozen_model_id,
@@ -147,9 +151,11 @@ impl ReadFrom for Character {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct AnimationName {
     pub id: u32,
+    /// A second string stored before the name until version 10.
+    pub unknown: Option<String>,
     pub name: String,
 }
 
@@ -162,15 +168,21 @@ impl ReadFromWithContext for AnimationName {
 
     fn read_from_with_context<R: Read>(r: &mut R, ctx: AnimationContext) -> Result<Self> {
         let index = r.read_u32()?;
-        if ctx.version < 10 {
-            let _ = r.read_ascii_c_string()?;
-        }
+        let unknown = if ctx.version < 10 {
+            Some(r.read_ascii_c_string()?)
+        } else {
+            None
+        };
         let name = r.read_ascii_c_string()?;
-        Ok(AnimationName { id: index, name })
+        Ok(AnimationName {
+            id: index,
+            unknown,
+            name,
+        })
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct PasDatabase {
     pub default_anim_state: u32,
     pub anim_states: Vec<AnimState>,
@@ -193,7 +205,7 @@ impl ReadFrom for PasDatabase {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct AnimState {
     pub kind: u32,
     pub parm_infos: Vec<ParmInfo>,
@@ -226,7 +238,7 @@ impl ReadFrom for AnimState {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct ParmInfo {
     pub kind: ParmKind,
     pub function: u32,
@@ -252,7 +264,7 @@ impl ReadFrom for ParmInfo {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize)]
 pub enum ParmKind {
     I32,
     U32,
@@ -274,7 +286,7 @@ impl ReadFrom for ParmKind {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum ParmValue {
     I32(i32),
     U32(u32),
@@ -297,7 +309,7 @@ impl ReadFromWithContext for ParmValue {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct AnimInfo {
     pub id: u32,
     pub parm_values: Vec<ParmValue>,
@@ -320,10 +332,12 @@ impl ReadFromWithContext for AnimInfo {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct ParticleResourceData {
     pub generic_particle_ids: Vec<u32>,
     pub swoosh_particle_ids: Vec<u32>,
+    /// A value of unknown purpose added in version 6.
+    pub unknown: Option<u32>,
     pub electric_particle_ids: Vec<u32>,
 }
 
@@ -350,9 +364,11 @@ impl ReadFromWithContext for ParticleResourceData {
             swoosh_particle_ids.push(r.read_typed()?);
         }
 
-        if ctx.version >= 6 {
-            let _ = r.read_u32()?;
-        }
+        let unknown = if ctx.version >= 6 {
+            Some(r.read_u32()?)
+        } else {
+            None
+        };
 
         let count = r.read_u32()?;
         let mut electric_particle_ids = Vec::new();
@@ -367,12 +383,13 @@ impl ReadFromWithContext for ParticleResourceData {
         Ok(Self {
             generic_particle_ids,
             swoosh_particle_ids,
+            unknown,
             electric_particle_ids,
         })
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct AnimationAabb {
     pub name: String,
     pub min_x: f32,
@@ -404,7 +421,7 @@ impl ReadFrom for AnimationAabb {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Effect {
     pub name: String,
     pub components: Vec<EffectComponent>,
@@ -422,7 +439,7 @@ impl ReadFrom for Effect {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct EffectComponent {
     pub name: String,
     pub particle_asset_type: String,
@@ -454,7 +471,7 @@ impl ReadFrom for EffectComponent {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct AnimationSet {
     pub version: u16,
     pub animations: Vec<Animation>,
@@ -470,7 +487,7 @@ pub struct AnimationSet {
 impl ReadFrom for AnimationSet {
     fn read_from<R: Read>(r: &mut R) -> Result<Self> {
         let version = r.read_u16()?;
-        if version < 2 || version > 4 {
+        if !(2..=4).contains(&version) {
             bail!("unexpected animation set version: {}", version);
         }
 
@@ -492,7 +509,7 @@ impl ReadFrom for AnimationSet {
             if count1 != 0 {
                 let mut buf = [0; 256];
                 let n = r.read(&mut buf)?;
-                println!("{:?}", (&buf[..n]).hex_dump());
+                println!("{:?}", buf[..n].hex_dump());
                 bail!("AnimationSet version 2 count1 is nonzero: {}", count1);
             }
 
@@ -500,7 +517,7 @@ impl ReadFrom for AnimationSet {
             if count2 != 0 {
                 let mut buf = [0; 256];
                 let n = r.read(&mut buf)?;
-                println!("{:?}", (&buf[..n]).hex_dump());
+                println!("{:?}", buf[..n].hex_dump());
                 bail!("AnimationSet version 2 count2 is nonzero: {}", count2);
             }
 
@@ -508,7 +525,7 @@ impl ReadFrom for AnimationSet {
             if count3 != 0 {
                 let mut buf = [0; 256];
                 let n = r.read(&mut buf)?;
-                println!("{:?}", (&buf[..n]).hex_dump());
+                println!("{:?}", buf[..n].hex_dump());
                 bail!("AnimationSet version 2 count3 is nonzero: {}", count3);
             }
 
@@ -563,7 +580,7 @@ impl ReadFrom for AnimationSet {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Animation {
     pub name: String,
     pub meta_animation: MetaAnimation,
@@ -580,7 +597,7 @@ impl ReadFrom for Animation {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum MetaAnimation {
     Play {
         animation_id: u32,
@@ -631,7 +648,7 @@ impl ReadFrom for MetaAnimation {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct CharAnimTime {
     pub time: f32,
     pub differential_state: u32,
@@ -648,7 +665,7 @@ impl ReadFrom for CharAnimTime {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Transition {
     pub unknown: u32,
     pub animation_id_a: u32,
@@ -671,7 +688,7 @@ impl ReadFrom for Transition {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum MetaTransition {
     Animation(MetaAnimation),
     Transition {
@@ -733,7 +750,7 @@ impl ReadFrom for MetaTransition {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct AdditiveAnimation {
     pub animation_id: u32,
     pub fade_in_time: f32,
@@ -753,7 +770,7 @@ impl ReadFrom for AdditiveAnimation {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct HalfTransition {
     pub animation_id: u32,
     pub meta_transition: MetaTransition,
@@ -770,7 +787,7 @@ impl ReadFrom for HalfTransition {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct AnimationResource {
     pub animation_id: u32,
     pub event_id: u32,
@@ -786,3 +803,57 @@ impl ReadFrom for AnimationResource {
         })
     }
 }
+
+/// The metadata of characters in an ANCS resource and of the animation set they share, for
+/// cataloguing without exporting any meshes.
+#[derive(Serialize)]
+pub struct AncsSummary<'a> {
+    pub characters: Vec<CharacterSummary<'a>>,
+    pub animations: &'a [Animation],
+    pub transitions: &'a [Transition],
+    pub default_transition: &'a MetaTransition,
+    pub half_transitions: &'a [HalfTransition],
+}
+
+#[derive(Serialize)]
+pub struct CharacterSummary<'a> {
+    pub character_id: u32,
+    pub character_name: &'a str,
+    pub model_id: u32,
+    pub skin_id: u32,
+    pub skeleton_id: u32,
+    pub frozen_model_id: u32,
+    pub frozen_skin_id: u32,
+    pub animation_names: &'a [AnimationName],
+    pub animation_aabbs: &'a [AnimationAabb],
+    pub particles: &'a ParticleResourceData,
+    pub effects: &'a [Effect],
+}
+
+impl<'a> AncsSummary<'a> {
+    /// Summarizes `characters`, in order, along with `ancs`'s animation set.
+    pub fn new(ancs: &'a Ancs, characters: impl IntoIterator<Item = &'a Character>) -> Self {
+        Self {
+            characters: characters
+                .into_iter()
+                .map(|character| CharacterSummary {
+                    character_id: character.id,
+                    character_name: &character.name,
+                    model_id: character.model_id,
+                    skin_id: character.skin_id,
+                    skeleton_id: character.skeleton_id,
+                    frozen_model_id: character.frozen_model_id,
+                    frozen_skin_id: character.frozen_skin_id,
+                    animation_names: &character.animations,
+                    animation_aabbs: &character.animation_aabbs,
+                    particles: &character.particle_resource_data,
+                    effects: &character.effects,
+                })
+                .collect(),
+            animations: &ancs.animation_set.animations,
+            transitions: &ancs.animation_set.transitions,
+            default_transition: &ancs.animation_set.default_transition,
+            half_transitions: &ancs.animation_set.half_transitions,
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Skeletal animations (ANIM). Retail animations use the compressed version 2 layout: a bitmap
+//! marking which frames have keys, an initial quantized rotation and translation per bone, and a
+//! bitstream of per-key deltas.
+
+use std::io::Read;
+
+use anyhow::{bail, Result};
+use gamecube::bytes::ReadFrom;
+use gamecube::ReadBytesExt;
+
+#[derive(Clone, Debug)]
+pub struct Anim {
+    pub duration: f32,
+    pub looping: bool,
+    /// The time of each key in seconds, shared by every channel.
+    pub times: Vec<f32>,
+    pub channels: Vec<AnimChannel>,
+}
+
+/// The keys of one bone. Either list may be empty, leaving that property at its bind pose.
+#[derive(Clone, Debug)]
+pub struct AnimChannel {
+    pub bone_id: u32,
+    /// Rotations relative to the parent bone, as unit quaternions in x, y, z, w order.
+    pub rotations: Vec<[f32; 4]>,
+    /// Offsets from the parent bone.
+    pub translations: Vec<[f32; 3]>,
+}
+
+impl ReadFrom for Anim {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let version = r.read_u32()?;
+        if version != 2 {
+            bail!("unsupported ANIM version: {version}");
+        }
+        let _scratch_size = r.read_u32()?;
+        let _event_id = r.read_u32()?;
+        let _unknown = r.read_u32()?;
+        let duration = f32::from_bits(r.read_u32()?);
+        let interval = f32::from_bits(r.read_u32()?);
+        let _root_bone_id = r.read_u32()?;
+        let looping = r.read_u32()? != 0;
+        let rotation_divisor = r.read_u32()?;
+        let translation_multiplier = f32::from_bits(r.read_u32()?);
+        let channel_count = r.read_u32()?;
+        let _unknown = r.read_u32()?;
+
+        // Frame 0 always has a key, holding the initial values below; the bitstream has one entry
+        // per later key.
+        let frame_count = r.read_u32()?;
+        let mut bitmap = BitReader::new(read_words(r, frame_count.div_ceil(32) as usize)?);
+        let mut times = Vec::new();
+        for frame in 0..frame_count {
+            if bitmap.read(1) != 0 {
+                times.push(frame as f32 * interval);
+            }
+        }
+        if times.is_empty() {
+            bail!("ANIM has no keys");
+        }
+        let _channel_count = r.read_u32()?;
+        let _unknown = r.read_u32()?;
+
+        let mut descriptors = Vec::new();
+        for _ in 0..channel_count {
+            descriptors.push(ChannelDescriptor::read_from(r)?);
+        }
+        let bits_per_key: usize = descriptors
+            .iter()
+            .map(|descriptor| {
+                descriptor
+                    .rotation
+                    .as_ref()
+                    .map_or(0, |q| 1 + q.bit_count())
+                    + descriptor.translation.as_ref().map_or(0, |q| q.bit_count())
+            })
+            .sum();
+        let word_count = (bits_per_key * (times.len() - 1)).div_ceil(32);
+        let mut bits = BitReader::new(read_words(r, word_count)?);
+
+        let rotation_scale = std::f32::consts::FRAC_PI_2 / rotation_divisor.max(1) as f32;
+        let dequantize_rotation = |values: [i32; 3], w_negative: bool| {
+            let [x, y, z] = values.map(|value| (value as i16 as f32 * rotation_scale).sin());
+            let w = (1.0 - (x * x + y * y + z * z)).max(0.0).sqrt();
+            [x, y, z, if w_negative { -w } else { w }]
+        };
+        let dequantize_translation =
+            |values: [i32; 3]| values.map(|value| value as f32 * translation_multiplier);
+
+        let mut channels: Vec<AnimChannel> = descriptors
+            .iter()
+            .map(|descriptor| AnimChannel {
+                bone_id: descriptor.bone_id,
+                rotations: descriptor
+                    .rotation
+                    .iter()
+                    .map(|q| dequantize_rotation(q.values, false))
+                    .collect(),
+                translations: descriptor
+                    .translation
+                    .iter()
+                    .map(|q| dequantize_translation(q.values))
+                    .collect(),
+            })
+            .collect();
+        for _ in 1..times.len() {
+            for (descriptor, channel) in descriptors.iter_mut().zip(&mut channels) {
+                if let Some(q) = &mut descriptor.rotation {
+                    let w_negative = bits.read(1) != 0;
+                    q.advance(&mut bits);
+                    channel
+                        .rotations
+                        .push(dequantize_rotation(q.values, w_negative));
+                }
+                if let Some(q) = &mut descriptor.translation {
+                    q.advance(&mut bits);
+                    channel.translations.push(dequantize_translation(q.values));
+                }
+            }
+        }
+
+        Ok(Self {
+            duration,
+            looping,
+            times,
+            channels,
+        })
+    }
+}
+
+struct ChannelDescriptor {
+    bone_id: u32,
+    rotation: Option<QuantizedVector>,
+    translation: Option<QuantizedVector>,
+}
+
+impl ReadFrom for ChannelDescriptor {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let bone_id = r.read_u32()?;
+        let rotation_key_count = r.read_u16()?;
+        let rotation = (rotation_key_count > 0)
+            .then(|| QuantizedVector::read_from(r))
+            .transpose()?;
+        let translation_key_count = r.read_u16()?;
+        let translation = (translation_key_count > 0)
+            .then(|| QuantizedVector::read_from(r))
+            .transpose()?;
+        Ok(Self {
+            bone_id,
+            rotation,
+            translation,
+        })
+    }
+}
+
+/// A running quantized value per axis, with the width of the signed delta each key adds to it.
+struct QuantizedVector {
+    values: [i32; 3],
+    bits: [u8; 3],
+}
+
+impl QuantizedVector {
+    fn bit_count(&self) -> usize {
+        self.bits.iter().map(|&bits| bits as usize).sum()
+    }
+
+    fn advance(&mut self, r: &mut BitReader) {
+        for (value, &bits) in self.values.iter_mut().zip(&self.bits) {
+            *value = value.wrapping_add(r.read_signed(bits));
+        }
+    }
+}
+
+impl ReadFrom for QuantizedVector {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let mut values = [0; 3];
+        let mut bits = [0; 3];
+        for (value, bits) in values.iter_mut().zip(&mut bits) {
+            *value = r.read_u16()? as i16 as i32;
+            *bits = r.read_u8()?;
+            if *bits > 32 {
+                bail!("ANIM delta is {bits} bits wide");
+            }
+        }
+        Ok(Self { values, bits })
+    }
+}
+
+fn read_words<R: Read>(r: &mut R, count: usize) -> Result<Vec<u32>> {
+    let mut words = Vec::new();
+    for _ in 0..count {
+        words.push(r.read_u32()?);
+    }
+    Ok(words)
+}
+
+/// Reads bit fields packed from the least significant bit of each big-endian word up.
+struct BitReader {
+    words: Vec<u32>,
+    position: usize,
+}
+
+impl BitReader {
+    fn new(words: Vec<u32>) -> Self {
+        Self { words, position: 0 }
+    }
+
+    /// Reads an unsigned field of up to 32 bits. Bits past the end read as zero.
+    fn read(&mut self, bits: u8) -> u32 {
+        let mut value = 0;
+        for i in 0..bits as usize {
+            let word = self.words.get(self.position / 32).copied().unwrap_or(0);
+            value |= ((word >> (self.position % 32)) & 1) << i;
+            self.position += 1;
+        }
+        value
+    }
+
+    /// Reads a two's complement field of up to 32 bits.
+    fn read_signed(&mut self, bits: u8) -> i32 {
+        if bits == 0 {
+            return 0;
+        }
+        let value = self.read(bits);
+        let shift = 32 - bits as u32;
+        ((value << shift) as i32) >> shift
+    }
+}
@@ -0,0 +1,143 @@
+//! Per-area statistics, for comparing rooms by how much geometry, texture data, and lighting they
+//! carry.
+
+use std::collections::HashSet;
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::cache::ResourceCache;
+use crate::gx::StaticVertexDescriptor;
+use crate::mlvl::Mlvl;
+use crate::mrea::MreaSections;
+use crate::names::AreaNames;
+use crate::pak::{Pak, PakFiles};
+use crate::parse;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AreaStats {
+    pub pak: String,
+    pub mrea_id: String,
+    pub world: Option<String>,
+    pub area: Option<String>,
+    pub world_model_count: usize,
+    pub surface_count: usize,
+    pub triangle_count: usize,
+    pub material_count: usize,
+    /// Distinct textures, however many times the material set lists each.
+    pub texture_count: usize,
+    /// Total size of the area's textures as the GPU reads them, before any pak compression.
+    pub texture_bytes: usize,
+    /// Lights across both light layers, or none for area versions whose lights aren't understood.
+    pub light_count: Option<usize>,
+}
+
+impl AreaStats {
+    /// Measures one area. Textures are looked up in `pak`, which should be the area's world pak,
+    /// found at `pak_path`.
+    pub fn new(pak_path: &str, pak: &Pak, mrea_id: u32, names: &AreaNames) -> Result<Self> {
+        let data = pak
+            .data_with_fourcc(mrea_id, "MREA")?
+            .ok_or_else(|| anyhow!("MREA 0x{mrea_id:08x} not found"))?;
+        let sections = MreaSections::new(&data)?;
+        let material_set = sections.material_set()?;
+
+        let models = sections.world_models()?;
+        let mut surface_count = 0;
+        let mut triangle_count = 0;
+        for model in &models {
+            for index in 0..model.surfaces.len() {
+                let surface = model.surface(index)?;
+                let material = material_set
+                    .materials
+                    .get(surface.material_index as usize)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Surface refers to missing material {}",
+                            surface.material_index
+                        )
+                    })?;
+                let batches = surface.display_list.parse::<StaticVertexDescriptor>(
+                    &material.vertex_inputs(),
+                    &model.vertex_arrays(),
+                    &(),
+                    &(),
+                )?;
+                surface_count += 1;
+                triangle_count += batches
+                    .iter()
+                    .map(|batch| batch.positions.len() / 3)
+                    .sum::<usize>();
+            }
+        }
+
+        let texture_ids: HashSet<u32> = material_set.texture_ids.iter().copied().collect();
+        let mut texture_bytes = 0;
+        for &id in &texture_ids {
+            match pak.data_with_fourcc(id, "TXTR")? {
+                Some(data) => texture_bytes += data.len(),
+                None => println!("Texture 0x{id:08x} for area 0x{mrea_id:08x} not found"),
+            }
+        }
+
+        let name = names.get(mrea_id);
+        Ok(Self {
+            pak: pak_path.to_string(),
+            mrea_id: format!("0x{mrea_id:08x}"),
+            world: name.map(|name| name.world.clone()),
+            area: name.map(|name| name.area.clone()),
+            world_model_count: models.len(),
+            surface_count,
+            triangle_count,
+            material_count: material_set.materials.len(),
+            texture_count: texture_ids.len(),
+            texture_bytes,
+            light_count: sections.light_count()?,
+        })
+    }
+}
+
+/// Measures every area of every world, in world and area order. Areas that fail to parse are
+/// reported and left out.
+pub fn build(paks: &PakFiles, cache: Option<&ResourceCache>) -> Result<Vec<AreaStats>> {
+    let names = AreaNames::load(paks, cache)?;
+    let mut stats = Vec::new();
+    for file in paks.iter() {
+        let file = file?;
+        let pak_path = file.path().to_string();
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        for entry in pak.iter_resources() {
+            if entry.fourcc() != "MLVL" {
+                continue;
+            }
+            let mlvl: Mlvl = match entry
+                .data()
+                .and_then(|data| parse::read_resource("MLVL", &data))
+            {
+                Ok(mlvl) => mlvl,
+                Err(e) => {
+                    println!("Error in {pak_path} MLVL 0x{:08x}: {e}", entry.file_id());
+                    continue;
+                }
+            };
+            for area in &mlvl.areas {
+                match AreaStats::new(&pak_path, &pak, area.mrea_id, &names) {
+                    Ok(area_stats) => stats.push(area_stats),
+                    Err(e) => println!(
+                        "Error in {pak_path} MREA {}: {e}",
+                        names.describe(area.mrea_id),
+                    ),
+                }
+            }
+        }
+    }
+    Ok(stats)
+}
+
+pub fn write<W: Write>(stats: &[AreaStats], mut w: W) -> Result<()> {
+    serde_json::to_writer_pretty(&mut w, stats)?;
+    writeln!(w)?;
+    w.flush()?;
+    Ok(())
+}
@@ -0,0 +1,205 @@
+//! Sound effects: AGSC audio groups, which bundle a MusyX sample directory with the sample data it
+//! describes, the decoding of those samples to PCM, and the ATBL table translating the game's
+//! sound IDs to sound effects.
+
+use std::io::Read;
+
+use anyhow::{anyhow, bail, Result};
+use gamecube::bytes::{ReadAsciiCStringExt, ReadFrom};
+use gamecube::{dsp, ReadBytesExt, SliceReader};
+use serde::Serialize;
+
+/// An AGSC resource: one MusyX audio group, as the pool, project, sample data, and sample
+/// directory blobs MusyX loads. Only the sample directory is parsed.
+#[derive(Clone, Debug)]
+pub struct Agsc {
+    /// The directory MusyX built the group from. Example: Audio/
+    pub directory: String,
+    pub name: String,
+    pub pool: Vec<u8>,
+    pub project: Vec<u8>,
+    pub sample_data: Vec<u8>,
+    pub samples: Vec<Sample>,
+}
+
+/// An ATBL resource: the table translating the sound IDs script objects and effects play to the
+/// MusyX sound effect IDs of the audio groups.
+#[derive(Clone, Debug)]
+pub struct Atbl {
+    /// Sound effect IDs, indexed by sound ID.
+    pub sfx_ids: Vec<u16>,
+}
+
+impl Atbl {
+    /// Marks a sound ID without a sound effect.
+    const NO_SFX: u16 = 0xffff;
+
+    /// The sound effect a sound ID plays, if it has one.
+    pub fn translate(&self, sound_id: u32) -> Option<u16> {
+        self.sfx_ids
+            .get(sound_id as usize)
+            .copied()
+            .filter(|&sfx_id| sfx_id != Self::NO_SFX)
+    }
+}
+
+/// One entry of a sample directory.
+#[derive(Clone, Debug, Serialize)]
+pub struct Sample {
+    pub id: u16,
+    /// Offset of the sample's frames in the sample data.
+    pub offset: u32,
+    /// The MIDI note the sample plays at its own rate.
+    pub base_note: u8,
+    pub sample_rate: u16,
+    pub sample_count: u32,
+    /// How the sample is encoded. Only [`Sample::FORMAT_DSP_ADPCM`] can be decoded.
+    pub format: u8,
+    /// The first looped sample and the loop's length, if it loops.
+    pub loop_range: Option<(u32, u32)>,
+    #[serde(skip)]
+    pub adpcm: Option<AdpcmParameters>,
+}
+
+/// The decoder state a DSP ADPCM sample starts from.
+#[derive(Clone, Debug)]
+pub struct AdpcmParameters {
+    pub coefficients: [[i32; 2]; 8],
+    /// The two samples before the first, most recent first.
+    pub history: [i32; 2],
+}
+
+impl Sample {
+    pub const FORMAT_DSP_ADPCM: u8 = 0;
+
+    /// Size of a sample directory entry.
+    const ENTRY_SIZE: usize = 0x20;
+    /// The ID ending a sample directory.
+    const END_ID: u16 = 0xffff;
+
+    /// Decodes the sample to 16-bit PCM from its group's sample data.
+    pub fn decode(&self, sample_data: &[u8]) -> Result<Vec<i16>> {
+        let params = match (&self.adpcm, self.format) {
+            (Some(params), Self::FORMAT_DSP_ADPCM) => params,
+            _ => bail!(
+                "sample 0x{:04x} has unsupported format {}",
+                self.id,
+                self.format
+            ),
+        };
+        let frames = sample_data
+            .get(self.offset as usize..)
+            .ok_or_else(|| anyhow!("sample 0x{:04x} is past the sample data", self.id))?;
+        Ok(dsp::decode_adpcm(
+            frames,
+            self.sample_count as usize,
+            &params.coefficients,
+            params.history,
+        )?)
+    }
+
+    /// The first and last sample of the looped section, inclusive, as WAV files record it.
+    pub fn wav_loop_range(&self) -> Option<(u32, u32)> {
+        self.loop_range
+            .map(|(start, length)| (start, start + length - 1))
+    }
+}
+
+impl ReadFrom for Agsc {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let directory = r.read_ascii_c_string()?;
+        let name = r.read_ascii_c_string()?;
+        let mut read_blob = || -> Result<Vec<u8>> {
+            let size = r.read_u32()? as usize;
+            let mut blob = vec![0; size];
+            r.read_exact(&mut blob)?;
+            Ok(blob)
+        };
+        let pool = read_blob()?;
+        let project = read_blob()?;
+        let sample_data = read_blob()?;
+        let sample_directory = read_blob()?;
+        let samples = read_sample_directory(&sample_directory)?;
+        Ok(Self {
+            directory,
+            name,
+            pool,
+            project,
+            sample_data,
+            samples,
+        })
+    }
+}
+
+impl ReadFrom for Atbl {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let count = r.read_u32()?;
+        let mut sfx_ids = Vec::new();
+        for _ in 0..count {
+            sfx_ids.push(r.read_u16()?);
+        }
+        Ok(Self { sfx_ids })
+    }
+}
+
+/// Reads the entries of a sample directory, which ends with an ID of 0xffff, and the ADPCM
+/// parameters each points to.
+fn read_sample_directory(data: &[u8]) -> Result<Vec<Sample>> {
+    let mut samples = Vec::new();
+    for mut r in data.chunks(Sample::ENTRY_SIZE) {
+        let id = r.read_u16()?;
+        if id == Sample::END_ID {
+            return Ok(samples);
+        }
+        let _padding = r.read_u16()?;
+        let offset = r.read_u32()?;
+        let _unused = r.read_u32()?;
+        let base_note = r.read_u8()?;
+        let _padding = r.read_u8()?;
+        let sample_rate = r.read_u16()?;
+        let count_and_format = r.read_u32()?;
+        let loop_start = r.read_u32()?;
+        let loop_length = r.read_u32()?;
+        let adpcm_offset = r.read_u32()? as usize;
+
+        let format = (count_and_format >> 24) as u8;
+        let adpcm = match format {
+            Sample::FORMAT_DSP_ADPCM => Some(read_adpcm_parameters(
+                data.get(adpcm_offset..).ok_or_else(|| {
+                    anyhow!("sample 0x{id:04x} ADPCM parameters are past the sample directory")
+                })?,
+            )?),
+            _ => None,
+        };
+        samples.push(Sample {
+            id,
+            offset,
+            base_note,
+            sample_rate,
+            sample_count: count_and_format & 0xffffff,
+            format,
+            loop_range: (loop_length != 0).then_some((loop_start, loop_length)),
+            adpcm,
+        });
+    }
+    bail!("sample directory has no end marker")
+}
+
+fn read_adpcm_parameters(data: &[u8]) -> Result<AdpcmParameters> {
+    let mut r = SliceReader::new(data);
+    let _bytes_per_frame = r.read_u16()?;
+    let _predictor_scale = r.read_u8()?;
+    let _loop_predictor_scale = r.read_u8()?;
+    let history_2 = r.read_i16()? as i32;
+    let history_1 = r.read_i16()? as i32;
+    let mut coefficients = [[0; 2]; 8];
+    for pair in &mut coefficients {
+        for c in pair {
+            *c = r.read_i16()? as i32;
+        }
+    }
+    Ok(AdpcmParameters {
+        coefficients,
+        history: [history_1, history_2],
+    })
+}
@@ -0,0 +1,67 @@
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+/// A directory of decompressed resources, stored zstd-compressed and keyed by a hash of the
+/// resource's stored (zlib-compressed) bytes. Inflating zlib dominates repeated runs over the same
+/// paks, and zstd decodes several times faster.
+#[derive(Clone, Debug)]
+pub struct ResourceCache {
+    dir: PathBuf,
+}
+
+impl ResourceCache {
+    const ZSTD_LEVEL: i32 = 3;
+
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Returns the decompressed form of `stored`, either from the cache or by calling `decompress`
+    /// and caching its result.
+    pub fn get_or_insert_with(
+        &self,
+        stored: &[u8],
+        decompress: impl FnOnce() -> Result<Vec<u8>>,
+    ) -> Result<Vec<u8>> {
+        let path = self.path_for(stored);
+        match File::open(&path) {
+            Ok(file) => return Ok(zstd::decode_all(file)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e.into()),
+        }
+
+        let data = decompress()?;
+        self.insert(&path, &data)?;
+        Ok(data)
+    }
+
+    fn path_for(&self, stored: &[u8]) -> PathBuf {
+        let mut key = String::with_capacity(64);
+        for byte in Sha256::digest(stored) {
+            write!(key, "{byte:02x}").unwrap();
+        }
+        // Fan out by the first byte to keep directories small.
+        self.dir.join(&key[..2]).join(format!("{key}.zst"))
+    }
+
+    fn insert(&self, path: &Path, data: &[u8]) -> Result<()> {
+        fs::create_dir_all(path.parent().unwrap())?;
+
+        // Write to a temporary name and rename into place so a concurrent or interrupted run never
+        // observes a partial entry.
+        let temp_path = path.with_extension(format!("zst.{}.tmp", std::process::id()));
+        let mut w = BufWriter::new(File::create(&temp_path)?);
+        zstd::stream::copy_encode(data, &mut w, Self::ZSTD_LEVEL)?;
+        w.flush()?;
+        drop(w);
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::ancs::Ancs;
+use crate::cache::ResourceCache;
+use crate::cmdl::Cmdl;
+use crate::mesh::CanonicalMesh;
+use crate::pak::{Pak, PakFiles};
+use crate::parse::{self, Parsed, Warning};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum CatalogFormat {
+    /// A JSON array of entries.
+    #[default]
+    Json,
+    /// One row per entry with a header row.
+    Csv,
+}
+
+/// One model on the disc: a CMDL resource, or a character within an ANCS resource.
+#[derive(Clone, Debug, Serialize)]
+pub struct CatalogEntry {
+    pub pak: String,
+    pub fourcc: String,
+    pub file_id: String,
+    pub name: Option<String>,
+    pub character: Option<String>,
+    pub model_id: String,
+    pub triangle_count: usize,
+    pub texture_count: usize,
+    pub bounds_min: [f32; 3],
+    pub bounds_max: [f32; 3],
+    /// Oddities the parsers skipped over in the resource.
+    pub warnings: Vec<Warning>,
+}
+
+impl CatalogEntry {
+    const CSV_HEADER: &'static str = "pak,fourcc,file_id,name,character,model_id,triangle_count,\
+        texture_count,min_x,min_y,min_z,max_x,max_y,max_z,warnings";
+
+    fn write_csv_row<W: Write>(&self, w: &mut W) -> Result<()> {
+        let optional = |s: &Option<String>| s.as_deref().map(csv_field).unwrap_or_default();
+        writeln!(
+            w,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            csv_field(&self.pak),
+            self.fourcc,
+            self.file_id,
+            optional(&self.name),
+            optional(&self.character),
+            self.model_id,
+            self.triangle_count,
+            self.texture_count,
+            self.bounds_min[0],
+            self.bounds_min[1],
+            self.bounds_min[2],
+            self.bounds_max[0],
+            self.bounds_max[1],
+            self.bounds_max[2],
+            csv_field(
+                &self
+                    .warnings
+                    .iter()
+                    .map(|w| w.message.as_str())
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+        )?;
+        Ok(())
+    }
+}
+
+/// Lists every CMDL and ANCS character in every pak. Models that fail to parse are
+/// reported and left out.
+pub fn build(paks: &PakFiles, cache: Option<&ResourceCache>) -> Result<Vec<CatalogEntry>> {
+    let mut entries = Vec::new();
+    for file in paks.iter() {
+        let file = file?;
+        let pak_path = file.path().to_string();
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        for resource in pak.iter_resources() {
+            let file_id = resource.file_id();
+            let name = pak
+                .iter_names()
+                .find(|e| e.file_id() == file_id)
+                .map(|e| e.name().to_string());
+            let new_entry = |character, model_id, model: ModelStats| CatalogEntry {
+                pak: pak_path.clone(),
+                fourcc: resource.fourcc().to_string(),
+                file_id: format!("0x{file_id:08x}"),
+                name: name.clone(),
+                character,
+                model_id: format!("0x{model_id:08x}"),
+                triangle_count: model.triangle_count,
+                texture_count: model.texture_count,
+                bounds_min: model.bounds_min,
+                bounds_max: model.bounds_max,
+                warnings: Vec::new(),
+            };
+            let first_entry = entries.len();
+            let result = Parsed::capture(|| match resource.fourcc() {
+                "CMDL" => resource
+                    .data()
+                    .and_then(|data| ModelStats::new(&data))
+                    .map(|model| entries.push(new_entry(None, file_id, model))),
+                "ANCS" => (|| {
+                    let ancs: Ancs = parse::read_resource("ANCS", &resource.data()?)?;
+                    for character in &ancs.character_set.characters {
+                        let data = pak
+                            .data_with_fourcc(character.model_id, "CMDL")?
+                            .ok_or_else(|| {
+                                anyhow!("Model 0x{:08x} not found", character.model_id)
+                            })?;
+                        entries.push(new_entry(
+                            Some(character.name.clone()),
+                            character.model_id,
+                            ModelStats::new(&data)?,
+                        ));
+                    }
+                    Ok(())
+                })(),
+                _ => Ok(()),
+            });
+            match result {
+                Ok(parsed) => {
+                    for entry in &mut entries[first_entry..] {
+                        entry.warnings = parsed.warnings.clone();
+                    }
+                }
+                Err(e) => println!(
+                    "Error in {pak_path} {:>4} 0x{file_id:08x} {name:?}: {e}",
+                    resource.fourcc(),
+                ),
+            }
+        }
+    }
+    Ok(entries)
+}
+
+pub fn write<W: Write>(entries: &[CatalogEntry], format: CatalogFormat, mut w: W) -> Result<()> {
+    match format {
+        CatalogFormat::Json => {
+            serde_json::to_writer_pretty(&mut w, entries)?;
+            writeln!(w)?;
+        }
+        CatalogFormat::Csv => {
+            writeln!(w, "{}", CatalogEntry::CSV_HEADER)?;
+            for entry in entries {
+                entry.write_csv_row(&mut w)?;
+            }
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
+
+struct ModelStats {
+    triangle_count: usize,
+    texture_count: usize,
+    bounds_min: [f32; 3],
+    bounds_max: [f32; 3],
+}
+
+impl ModelStats {
+    /// Measures a CMDL as it would be exported with its first material set.
+    fn new(data: &[u8]) -> Result<Self> {
+        let cmdl: Cmdl = parse::read_resource("CMDL", data)?;
+        let mesh = CanonicalMesh::from_cmdl(&cmdl, 0)?;
+        Ok(Self {
+            triangle_count: mesh.surfaces.iter().map(|s| s.positions.len() / 3).sum(),
+            texture_count: mesh.texture_ids.len(),
+            bounds_min: [cmdl.x_min, cmdl.y_min, cmdl.z_min],
+            bounds_max: [cmdl.x_max, cmdl.y_max, cmdl.z_max],
+        })
+    }
+}
+
+/// Quotes a CSV field if it contains a delimiter, quote, or line break.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
@@ -1,7 +1,8 @@
-use std::io::Read;
+use std::io::{Read, Write};
 
 use anyhow::Result;
-use gamecube::bytes::{ReadAsciiCStringExt, ReadFrom};
+use byteorder::{BigEndian, WriteBytesExt};
+use gamecube::bytes::{ReadAsciiCStringExt, ReadFrom, WriteTo};
 use gamecube::{ReadBytesExt, ReadTypedExt};
 
 #[derive(Clone, Debug)]
@@ -39,6 +40,26 @@ impl ReadFrom for Cinf {
     }
 }
 
+impl WriteTo for Cinf {
+    fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        w.write_u32::<BigEndian>(self.bones.len() as u32)?;
+        for bone in &self.bones {
+            bone.write_to(w)?;
+        }
+
+        w.write_u32::<BigEndian>(self.build_order_ids.len() as u32)?;
+        for id in &self.build_order_ids {
+            id.write_to(w)?;
+        }
+
+        w.write_u32::<BigEndian>(self.bone_names.len() as u32)?;
+        for bone_name in &self.bone_names {
+            bone_name.write_to(w)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Bone {
     pub bone_id: u32,
@@ -72,6 +93,22 @@ impl ReadFrom for Bone {
     }
 }
 
+impl WriteTo for Bone {
+    fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        w.write_u32::<BigEndian>(self.bone_id)?;
+        w.write_u32::<BigEndian>(self.parent_bone_id)?;
+        for x in self.position {
+            w.write_f32::<BigEndian>(x)?;
+        }
+
+        w.write_u32::<BigEndian>(self.linked_bones.len() as u32)?;
+        for id in &self.linked_bones {
+            id.write_to(w)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BoneName {
     pub name: String,
@@ -86,3 +123,12 @@ impl ReadFrom for BoneName {
         Ok(Self { name, id })
     }
 }
+
+impl WriteTo for BoneName {
+    fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        w.write_all(self.name.as_bytes())?;
+        w.write_u8(0)?;
+        w.write_u32::<BigEndian>(self.id)?;
+        Ok(())
+    }
+}
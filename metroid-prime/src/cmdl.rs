@@ -1,11 +1,13 @@
 use std::collections::VecDeque;
 use std::io::Read;
 
-use anyhow::Result;
-use gamecube::bytes::ReadFrom;
-use gamecube::{ReadBytesExt, ReadTypedExt};
+use anyhow::{anyhow, Context, Result};
+use gamecube::bytes::{ReadFrom, ReadFromSlice};
+use gamecube::{ReadBytesExt, ReadTypedExt, SliceReader};
+use serde::Serialize;
 
-use crate::gx::DisplayList;
+use crate::gx::{DisplayList, TevAlphaArg, TevColorArg, TevCombiner, VertexArrays, VertexInputs};
+use crate::parse;
 
 pub struct Cmdl {
     pub flags: u32,
@@ -24,8 +26,47 @@ pub struct Cmdl {
     pub surfaces: Vec<Surface>,
 }
 
+impl Cmdl {
+    pub fn vertex_arrays(&self) -> VertexArrays<'_> {
+        VertexArrays {
+            positions: &self.position_data,
+            normals: &self.normal_data,
+            colors: &self.color_data,
+            texcoords: &self.uv_float_data,
+            short_texcoords: &self.uv_short_data,
+        }
+    }
+}
+
 impl ReadFrom for Cmdl {
     fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let mut data = Vec::new();
+        r.read_to_end(&mut data)?;
+        CmdlSections::new(&data)?.parse()
+    }
+}
+
+/// A CMDL's header and section table, borrowing section data from the resource. Material sets and
+/// surfaces are parsed only on request.
+pub struct CmdlSections<'a> {
+    pub flags: u32,
+    pub x_min: f32,
+    pub y_min: f32,
+    pub z_min: f32,
+    pub x_max: f32,
+    pub y_max: f32,
+    pub z_max: f32,
+    material_sets: Vec<&'a [u8]>,
+    pub position_data: &'a [u8],
+    pub normal_data: &'a [u8],
+    pub color_data: &'a [u8],
+    pub uv_float_data: &'a [u8],
+    pub uv_short_data: &'a [u8],
+    surfaces: Vec<&'a [u8]>,
+}
+
+impl<'a> ReadFromSlice<'a> for CmdlSections<'a> {
+    fn read_from_slice(r: &mut SliceReader<'a>) -> Result<Self> {
         let magic = r.read_u32()?;
         assert_eq!(magic, 0xdeadbabe);
         let version = r.read_u32()?;
@@ -45,57 +86,45 @@ impl ReadFrom for Cmdl {
             section_sizes.push(r.read_u32()?);
         }
 
-        // Pad the read header to a 32 byte boundary.
-        let header_size = 0x2c + 4 * section_count as usize;
-        let remainder = header_size & 31;
-        if remainder > 0 {
-            let mut buf = [0; 31];
-            r.read_exact(&mut buf[..32 - remainder])?;
-        }
-
+        // Sections start at the 32 byte boundary after the header, and each is padded to a 32
+        // byte boundary.
+        r.align_to(32)?;
         let mut sections = VecDeque::new();
         for size in section_sizes {
-            let padded_size = (size + 31) & !31;
-            let mut data = vec![0; padded_size as usize];
-            r.read_exact(&mut data)?;
-            data.resize(size as usize, 0);
-            sections.push_back(data);
+            let offset = r.position();
+            sections.push_back(
+                r.take(size as usize)
+                    .with_context(|| format!("CMDL section at 0x{offset:x}"))?,
+            );
+            r.align_to(32)?;
         }
+        let mut next_section = || {
+            sections
+                .pop_front()
+                .ok_or_else(|| anyhow!("CMDL has too few sections"))
+        };
 
-        let mut materials = Vec::new();
+        let mut material_sets = Vec::new();
         for _ in 0..material_set_count {
-            let data = sections.pop_front().unwrap();
-            materials.push(data.as_slice().read_typed()?);
+            material_sets.push(next_section()?);
         }
 
-        let position_data = sections.pop_front().unwrap();
-        let normal_data = sections.pop_front().unwrap();
-        let color_data = sections.pop_front().unwrap();
-        let uv_float_data = sections.pop_front().unwrap();
-        let uv_short_data = if flags & 4 != 0 {
-            sections.pop_front().unwrap()
-        } else {
-            Vec::new()
-        };
-
-        let surface_end_offsets = {
-            let data = sections.pop_front().unwrap();
-            let mut r = data.as_slice();
-            let mut surface_end_offsets = Vec::new();
-            let count = r.read_u32()?;
-            for _ in 0..count {
-                surface_end_offsets.push(r.read_u32()?);
-            }
-            surface_end_offsets
-        };
+        let position_data = next_section()?;
+        let normal_data = next_section()?;
+        let color_data = next_section()?;
+        let uv_float_data = next_section()?;
+        let uv_short_data = if flags & 4 != 0 { next_section()? } else { &[] };
 
+        let surface_count = next_section()?.read_u32()?;
         let mut surfaces = Vec::new();
-        for _ in surface_end_offsets {
-            let data = sections.pop_front().unwrap();
-            surfaces.push(data.as_slice().read_typed()?);
+        for _ in 0..surface_count {
+            surfaces.push(next_section()?);
+        }
+        if !sections.is_empty() {
+            parse::unexpected(format_args!("CMDL has {} unused sections", sections.len()))?;
         }
 
-        Ok(Cmdl {
+        Ok(Self {
             flags,
             x_min,
             y_min,
@@ -103,7 +132,7 @@ impl ReadFrom for Cmdl {
             x_max,
             y_max,
             z_max,
-            materials,
+            material_sets,
             position_data,
             normal_data,
             color_data,
@@ -114,6 +143,61 @@ impl ReadFrom for Cmdl {
     }
 }
 
+impl<'a> CmdlSections<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        SliceReader::new(data).read_borrowed()
+    }
+
+    pub fn material_set_count(&self) -> usize {
+        self.material_sets.len()
+    }
+
+    pub fn material_set(&self, index: usize) -> Result<MaterialSet> {
+        let mut data = *self
+            .material_sets
+            .get(index)
+            .ok_or_else(|| anyhow!("CMDL has no material set {index}"))?;
+        data.read_typed()
+    }
+
+    pub fn surface_count(&self) -> usize {
+        self.surfaces.len()
+    }
+
+    pub fn surface(&self, index: usize) -> Result<Surface> {
+        let mut data = *self
+            .surfaces
+            .get(index)
+            .ok_or_else(|| anyhow!("CMDL has no surface {index}"))?;
+        data.read_typed()
+    }
+
+    /// Parses every section into an owned [`Cmdl`].
+    pub fn parse(&self) -> Result<Cmdl> {
+        Ok(Cmdl {
+            flags: self.flags,
+            x_min: self.x_min,
+            y_min: self.y_min,
+            z_min: self.z_min,
+            x_max: self.x_max,
+            y_max: self.y_max,
+            z_max: self.z_max,
+            materials: (0..self.material_set_count())
+                .map(|index| self.material_set(index))
+                .collect::<Result<_>>()?,
+            position_data: self.position_data.to_vec(),
+            normal_data: self.normal_data.to_vec(),
+            color_data: self.color_data.to_vec(),
+            uv_float_data: self.uv_float_data.to_vec(),
+            uv_short_data: self.uv_short_data.to_vec(),
+            surfaces: (0..self.surface_count())
+                .map(|index| self.surface(index))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+#[derive(Serialize)]
 pub struct MaterialSet {
     pub texture_ids: Vec<u32>,
     pub materials: Vec<Material>,
@@ -150,6 +234,7 @@ impl ReadFrom for MaterialSet {
     }
 }
 
+#[derive(Clone, Serialize)]
 pub struct Material {
     pub flags: u32,
     pub texture_indices: Vec<u32>,
@@ -159,10 +244,13 @@ pub struct Material {
     pub blend_dst_factor: u16,
     pub blend_src_factor: u16,
     pub reflection_indirect_texture_slot: Option<u32>,
-    pub color_channel_flags: Vec<u32>,
+    pub color_channels: Vec<ColorChannel>,
     pub tev_stages: Vec<TevStage>,
     pub tev_texture_inputs: Vec<TevTextureInput>,
     pub tev_texgen_flags: Vec<u32>,
+    /// The rest of the material, starting with its UV animations, kept as read.
+    #[serde(skip)]
+    pub unparsed: Vec<u8>,
 }
 
 impl ReadFrom for Material {
@@ -194,9 +282,9 @@ impl ReadFrom for Material {
         }
 
         let color_channel_count = r.read_u32()?;
-        let mut color_channel_flags = Vec::new();
+        let mut color_channels = Vec::new();
         for _ in 0..color_channel_count {
-            color_channel_flags.push(r.read_u32()?);
+            color_channels.push(ColorChannel::from_flags(r.read_u32()?));
         }
 
         let tev_stage_count = r.read_u32()?;
@@ -216,6 +304,7 @@ impl ReadFrom for Material {
         }
 
         // TODO: UV Animations
+        let unparsed = parse::read_unparsed(r)?;
 
         Ok(Self {
             flags,
@@ -226,19 +315,108 @@ impl ReadFrom for Material {
             blend_dst_factor,
             blend_src_factor,
             reflection_indirect_texture_slot,
-            color_channel_flags,
+            color_channels,
             tev_stages,
             tev_texture_inputs,
             tev_texgen_flags,
+            unparsed,
         })
     }
 }
 
+impl Material {
+    /// How the material's surfaces give each vertex attribute in their display lists.
+    pub fn vertex_inputs(&self) -> VertexInputs {
+        VertexInputs::from_flags(self.vertex_attr_flags)
+    }
+
+    /// Whether the material ignores scene lighting, judged by the first color channel.
+    pub fn is_unlit(&self) -> bool {
+        self.color_channels
+            .first()
+            .is_some_and(|channel| !channel.lighting_enabled)
+    }
+}
+
+/// The GX lighting configuration of one color channel.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct ColorChannel {
+    pub flags: u32,
+    pub lighting_enabled: bool,
+    pub light_mask: u8,
+    pub diffuse_function: DiffuseFunction,
+    pub attenuation_function: AttenuationFunction,
+    pub ambient_source: ColorSource,
+    pub material_source: ColorSource,
+}
+
+impl ColorChannel {
+    pub fn from_flags(flags: u32) -> Self {
+        Self {
+            flags,
+            lighting_enabled: flags & 0x1 != 0,
+            light_mask: (flags >> 3) as u8,
+            diffuse_function: match (flags >> 11) & 0x3 {
+                0 => DiffuseFunction::None,
+                1 => DiffuseFunction::Sign,
+                2 => DiffuseFunction::Clamp,
+                _ => DiffuseFunction::Reserved,
+            },
+            attenuation_function: match (flags >> 13) & 0x3 {
+                0 => AttenuationFunction::Specular,
+                1 => AttenuationFunction::Spot,
+                2 => AttenuationFunction::None,
+                _ => AttenuationFunction::Reserved,
+            },
+            ambient_source: ColorSource::from_bit(flags >> 15),
+            material_source: ColorSource::from_bit(flags >> 16),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum DiffuseFunction {
+    None,
+    Sign,
+    Clamp,
+    Reserved,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum AttenuationFunction {
+    Specular,
+    Spot,
+    None,
+    Reserved,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ColorSource {
+    /// The channel's constant color register.
+    Register,
+    /// The per-vertex color attribute.
+    Vertex,
+}
+
+impl ColorSource {
+    fn from_bit(bit: u32) -> Self {
+        if bit & 1 == 0 {
+            Self::Register
+        } else {
+            Self::Vertex
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
 pub struct TevStage {
-    pub color_in: u32,
-    pub alpha_in: u32,
-    pub color_op: u32,
-    pub alpha_op: u32,
+    /// Color combiner inputs `[a, b, c, d]`.
+    pub color_inputs: [TevColorArg; 4],
+    /// Alpha combiner inputs `[a, b, c, d]`.
+    pub alpha_inputs: [TevAlphaArg; 4],
+    pub color_op: TevCombiner,
+    pub alpha_op: TevCombiner,
+    pub padding: u8,
     pub alpha_konst: u8,
     pub color_konst: u8,
     pub rasterized_color: u8,
@@ -246,19 +424,20 @@ pub struct TevStage {
 
 impl ReadFrom for TevStage {
     fn read_from<R: Read>(r: &mut R) -> Result<Self> {
-        let color_in = r.read_u32()?;
-        let alpha_in = r.read_u32()?;
-        let color_op = r.read_u32()?;
-        let alpha_op = r.read_u32()?;
-        let _padding = r.read_u8()?;
+        let color_inputs = TevColorArg::unpack(r.read_u32()?);
+        let alpha_inputs = TevAlphaArg::unpack(r.read_u32()?);
+        let color_op = TevCombiner::unpack(r.read_u32()?)?;
+        let alpha_op = TevCombiner::unpack(r.read_u32()?)?;
+        let padding = r.read_u8()?;
         let alpha_konst = r.read_u8()?;
         let color_konst = r.read_u8()?;
         let rasterized_color = r.read_u8()?;
         Ok(Self {
-            color_in,
-            alpha_in,
+            color_inputs,
+            alpha_inputs,
             color_op,
             alpha_op,
+            padding,
             alpha_konst,
             color_konst,
             rasterized_color,
@@ -266,28 +445,39 @@ impl ReadFrom for TevStage {
     }
 }
 
+#[derive(Clone, Serialize)]
 pub struct TevTextureInput {
+    pub unknown: u16,
     pub texture_tev_input: u8,
     pub tex_coord_tev_input: u8,
 }
 
 impl ReadFrom for TevTextureInput {
     fn read_from<R: Read>(r: &mut R) -> Result<Self> {
-        let _ = r.read_u16()?;
+        let unknown = r.read_u16()?;
         let texture_tev_input = r.read_u8()?;
         let tex_coord_tev_input = r.read_u8()?;
         Ok(Self {
+            unknown,
             texture_tev_input,
             tex_coord_tev_input,
         })
     }
 }
 
+/// A surface's header and display list. Header fields the exporter ignores are kept as read so
+/// the surface can be written back unchanged.
 pub struct Surface {
     pub center: [f32; 3],
     pub material_index: u32,
     pub normal_divisor: u16,
+    /// The display list size as the header states it, which doesn't always match the data.
+    pub untrustworthy_display_list_size: u16,
+    /// Zero on disc; filled in with pointers at runtime.
+    pub placeholders: [u32; 2],
     pub reflective_normal: [f32; 3],
+    pub unused: [u16; 2],
+    pub extra_data: Vec<u8>,
     pub display_list: DisplayList,
 }
 
@@ -298,21 +488,16 @@ impl ReadFrom for Surface {
         let center_z = f32::from_bits(r.read_u32()?);
         let material_index = r.read_u32()?;
         let normal_divisor = r.read_u16()?;
-        let _untrustworthy_display_list_size = r.read_u16()?;
-        let _placeholder = r.read_u32()?;
-        let _placeholder = r.read_u32()?;
+        let untrustworthy_display_list_size = r.read_u16()?;
+        let placeholders = [r.read_u32()?, r.read_u32()?];
         let extra_data_size = r.read_u32()?;
         let reflective_normal_x = f32::from_bits(r.read_u32()?);
         let reflective_normal_y = f32::from_bits(r.read_u32()?);
         let reflective_normal_z = f32::from_bits(r.read_u32()?);
-        let _unused = r.read_u16()?;
-        let _unused = r.read_u16()?;
+        let unused = [r.read_u16()?, r.read_u16()?];
 
-        // Read and discard the extra data.
-        {
-            let mut buf = vec![0; extra_data_size as usize];
-            r.read_exact(&mut buf)?;
-        }
+        let mut extra_data = vec![0; extra_data_size as usize];
+        r.read_exact(&mut extra_data)?;
 
         // Pad the read header to a 32 byte boundary.
         let header_size = 0x30 + extra_data_size as usize;
@@ -329,11 +514,15 @@ impl ReadFrom for Surface {
             center: [center_x, center_y, center_z],
             material_index,
             normal_divisor,
+            untrustworthy_display_list_size,
+            placeholders,
             reflective_normal: [
                 reflective_normal_x,
                 reflective_normal_y,
                 reflective_normal_z,
             ],
+            unused,
+            extra_data,
             display_list,
         })
     }
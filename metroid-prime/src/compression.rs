@@ -0,0 +1,105 @@
+//! The compression schemes resources are stored with, shared by paks and MREA blocks.
+
+use std::io::{self, Write};
+
+use anyhow::{anyhow, bail, Result};
+use flate2::read::ZlibDecoder;
+use gamecube::ReadBytesExt;
+
+use crate::lzo;
+
+/// Decodes one compressed stream. Every scheme here finds the end of its own data, so trailing
+/// padding is ignored and nothing needs to know the decompressed size up front.
+pub trait Decompressor {
+    /// Decompresses `data`, writing the output to `w` as it's produced, and returns its size.
+    fn decompress_to(&self, data: &[u8], w: &mut dyn Write) -> Result<usize>;
+
+    /// Decompresses `data`, failing unless it produces exactly `decompressed_size` bytes.
+    fn decompress(&self, data: &[u8], decompressed_size: usize) -> Result<Vec<u8>> {
+        let mut decompressed = Vec::with_capacity(decompressed_size);
+        let len = self.decompress_to(data, &mut decompressed)?;
+        if len != decompressed_size {
+            bail!("Decompressed to {len} bytes, want {decompressed_size}");
+        }
+        Ok(decompressed)
+    }
+
+    /// Measures the decompressed size by decompressing and discarding the output, for streams
+    /// stored without one.
+    fn probe_size(&self, data: &[u8]) -> Result<usize> {
+        self.decompress_to(data, &mut io::sink())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Zlib,
+    Lzo,
+    /// A series of segments that each decompress to at most 0x4000 bytes. Each starts with a
+    /// signed 16-bit size: negative for a stored segment of that many bytes, otherwise a zlib or
+    /// LZO segment. Used by Metroid Prime 2 and later.
+    Segmented,
+}
+
+impl Compression {
+    /// Tells a zlib stream from segmented data by its header. Bare LZO is never stored at the top
+    /// level, so it isn't detected.
+    pub fn detect(data: &[u8]) -> Self {
+        if is_zlib(data) {
+            Self::Zlib
+        } else {
+            Self::Segmented
+        }
+    }
+}
+
+impl Decompressor for Compression {
+    fn decompress_to(&self, data: &[u8], w: &mut dyn Write) -> Result<usize> {
+        match self {
+            Self::None => {
+                w.write_all(data)?;
+                Ok(data.len())
+            }
+            Self::Zlib => Ok(io::copy(&mut ZlibDecoder::new(data), w)? as usize),
+            Self::Lzo => {
+                let decompressed = lzo::decompress(data)?;
+                w.write_all(&decompressed)?;
+                Ok(decompressed.len())
+            }
+            Self::Segmented => {
+                let mut data = data;
+                let mut len = 0;
+                while data.len() >= 2 {
+                    let size = data.read_u16()? as i16;
+                    // Segments run to the end of the data, which may be zero padded.
+                    if size == 0 {
+                        break;
+                    }
+                    let segment_len = size.unsigned_abs() as usize;
+                    let segment = data.get(..segment_len).ok_or_else(|| {
+                        anyhow!("Segment of {segment_len} bytes runs past the end of the data")
+                    })?;
+                    data = &data[segment_len..];
+                    len += if size < 0 {
+                        Self::None.decompress_to(segment, w)?
+                    } else if is_zlib(segment) {
+                        Self::Zlib.decompress_to(segment, w)?
+                    } else {
+                        Self::Lzo.decompress_to(segment, w)?
+                    };
+                }
+                Ok(len)
+            }
+        }
+    }
+}
+
+/// Whether data starts with a zlib header: deflate as the method, and the first two bytes forming
+/// a multiple of 31.
+fn is_zlib(data: &[u8]) -> bool {
+    match data {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && u16::from_be_bytes([*cmf, *flg]) % 31 == 0,
+        _ => false,
+    }
+}
@@ -0,0 +1,142 @@
+//! How much of each pak is compressed, and how much recompressing it at the highest level would
+//! save, for modders reclaiming space for added content.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::cache::ResourceCache;
+use crate::pak::{self, Pak, PakFiles};
+
+/// Paks align each resource's data to this many bytes.
+const ALIGNMENT: usize = 32;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PakCompressionStats {
+    pub pak: String,
+    pub total: CompressionStats,
+    pub by_type: BTreeMap<String, CompressionStats>,
+}
+
+/// Sizes of a set of resources. Each resource is counted once, however many table entries share
+/// it.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CompressionStats {
+    pub resource_count: usize,
+    pub compressed_count: usize,
+    /// Bytes the compressed resources take in the pak.
+    pub compressed_bytes: usize,
+    /// Bytes the stored resources take in the pak.
+    pub stored_bytes: usize,
+    /// Bytes every resource takes decompressed.
+    pub decompressed_bytes: usize,
+    /// Bytes every resource would take if compressed at the highest zlib level, or stored where
+    /// that's smaller, with the pak's alignment. This is an estimate: it doesn't check that the
+    /// game accepts every type compressed.
+    pub recompressed_bytes: usize,
+}
+
+impl CompressionStats {
+    /// Bytes that recompressing would free.
+    pub fn savings(&self) -> isize {
+        (self.compressed_bytes + self.stored_bytes) as isize - self.recompressed_bytes as isize
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.resource_count += other.resource_count;
+        self.compressed_count += other.compressed_count;
+        self.compressed_bytes += other.compressed_bytes;
+        self.stored_bytes += other.stored_bytes;
+        self.decompressed_bytes += other.decompressed_bytes;
+        self.recompressed_bytes += other.recompressed_bytes;
+    }
+}
+
+impl PakCompressionStats {
+    /// Measures one pak, recompressing each of its resources.
+    pub fn new(pak_path: &str, pak: &Pak) -> Result<Self> {
+        let mut total = CompressionStats::default();
+        let mut by_type = BTreeMap::<String, CompressionStats>::new();
+        let mut seen = HashSet::new();
+        for entry in pak.iter_resources() {
+            if !seen.insert(entry.file_id()) {
+                continue;
+            }
+            let info = entry.info()?;
+            let data = pak.data(entry.file_id())?.unwrap();
+            let recompressed = pak::compress(&data)?.len().min(data.len());
+            let stats = CompressionStats {
+                resource_count: 1,
+                compressed_count: info.compressed as usize,
+                compressed_bytes: if info.compressed {
+                    info.compressed_size as usize
+                } else {
+                    0
+                },
+                stored_bytes: if info.compressed {
+                    0
+                } else {
+                    info.compressed_size as usize
+                },
+                decompressed_bytes: info.decompressed_size as usize,
+                recompressed_bytes: recompressed.next_multiple_of(ALIGNMENT),
+            };
+            total.add(&stats);
+            by_type
+                .entry(entry.fourcc().to_string())
+                .or_default()
+                .add(&stats);
+        }
+        Ok(Self {
+            pak: pak_path.to_string(),
+            total,
+            by_type,
+        })
+    }
+}
+
+pub fn build(paks: &PakFiles, cache: Option<&ResourceCache>) -> Result<Vec<PakCompressionStats>> {
+    let mut stats = Vec::new();
+    for file in paks.iter() {
+        let file = file?;
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        match PakCompressionStats::new(file.path(), &pak) {
+            Ok(pak_stats) => stats.push(pak_stats),
+            Err(e) => println!("Error in {}: {e}", file.path()),
+        }
+    }
+    Ok(stats)
+}
+
+/// Prints a line for each pak and one for the whole set: how much of it is compressed, and what
+/// recompressing would save.
+pub fn print(stats: &[PakCompressionStats]) {
+    let mut total = CompressionStats::default();
+    for pak_stats in stats {
+        print_line(&pak_stats.pak, &pak_stats.total);
+        total.add(&pak_stats.total);
+    }
+    print_line("Total", &total);
+}
+
+fn print_line(label: &str, stats: &CompressionStats) {
+    let stored_size = stats.compressed_bytes + stats.stored_bytes;
+    println!(
+        "{label}: {}/{} resources compressed, {} of {stored_size} bytes; recompressing saves {} \
+         bytes ({:.1}%)",
+        stats.compressed_count,
+        stats.resource_count,
+        stats.compressed_bytes,
+        stats.savings(),
+        100.0 * stats.savings() as f64 / stored_size.max(1) as f64,
+    );
+}
+
+pub fn write<W: Write>(stats: &[PakCompressionStats], mut w: W) -> Result<()> {
+    serde_json::to_writer_pretty(&mut w, stats)?;
+    writeln!(w)?;
+    w.flush()?;
+    Ok(())
+}
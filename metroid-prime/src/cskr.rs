@@ -1,7 +1,8 @@
-use std::io::Read;
+use std::io::{Read, Write};
 
 use anyhow::Result;
-use gamecube::bytes::ReadFrom;
+use byteorder::{BigEndian, WriteBytesExt};
+use gamecube::bytes::{ReadFrom, WriteTo};
 use gamecube::{ReadBytesExt, ReadTypedExt};
 
 #[derive(Clone, Debug)]
@@ -26,6 +27,17 @@ impl ReadFrom for Cskr {
     }
 }
 
+impl WriteTo for Cskr {
+    fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        let weight_count: usize = self.vertex_groups.iter().map(|g| g.weights.len()).sum();
+        w.write_u32::<BigEndian>(weight_count as u32)?;
+        for vertex_group in &self.vertex_groups {
+            vertex_group.write_to(w)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct VertexGroup {
     pub weights: Vec<Weight>,
@@ -49,6 +61,17 @@ impl ReadFrom for VertexGroup {
     }
 }
 
+impl WriteTo for VertexGroup {
+    fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        w.write_u32::<BigEndian>(self.weights.len() as u32)?;
+        for weight in &self.weights {
+            weight.write_to(w)?;
+        }
+        w.write_u32::<BigEndian>(self.vertex_count)?;
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Weight {
     pub bone_id: u32,
@@ -63,3 +86,11 @@ impl ReadFrom for Weight {
         Ok(Self { bone_id, weight })
     }
 }
+
+impl WriteTo for Weight {
+    fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        w.write_u32::<BigEndian>(self.bone_id)?;
+        w.write_f32::<BigEndian>(self.weight)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+//! Working out which layout a resource uses, by parsing it every way we know and keeping whichever
+//! way parses most cleanly. Each layout lists the releases of Metroid Prime known to use it, and
+//! where layouts parse equally well, the one the disc's release is known to use is chosen. Where
+//! that still leaves more than one, the choice is reported as ambiguous rather than guessed
+//! silently.
+//!
+//! Parsers whose stated version doesn't parse fall back on the detected layout, so a revision that
+//! lays a resource out differently from what it states still loads.
+
+use std::fmt::{self, Display, Formatter};
+
+use anyhow::Result;
+use gamecube::disc::Header;
+
+use crate::mrea::MreaSections;
+use crate::parse::{ParseOptions, Parsed};
+use crate::resource::Resource;
+
+/// A release of Metroid Prime, as told by its disc header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Revision {
+    /// USA 1.00.
+    Ntsc0_00,
+    /// USA 1.01.
+    Ntsc0_01,
+    /// USA 1.02, the Player's Choice release.
+    Ntsc0_02,
+    Pal,
+    Japanese,
+}
+
+impl Revision {
+    /// Every retail release.
+    const RETAIL: &'static [Self] = &[
+        Self::Ntsc0_00,
+        Self::Ntsc0_01,
+        Self::Ntsc0_02,
+        Self::Pal,
+        Self::Japanese,
+    ];
+
+    /// The release a disc is, or `None` for discs of other games or unknown revisions.
+    pub fn from_header(header: &Header) -> Option<Self> {
+        match (header.game_code(), header.version()) {
+            ("GM8E", 0) => Some(Self::Ntsc0_00),
+            ("GM8E", 1) => Some(Self::Ntsc0_01),
+            ("GM8E", 2) => Some(Self::Ntsc0_02),
+            ("GM8P", _) => Some(Self::Pal),
+            ("GM8J", _) => Some(Self::Japanese),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Revision {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ntsc0_00 => "0-00",
+            Self::Ntsc0_01 => "0-01",
+            Self::Ntsc0_02 => "0-02",
+            Self::Pal => "PAL",
+            Self::Japanese => "JP",
+        })
+    }
+}
+
+/// One way of laying out a resource type.
+pub struct Layout {
+    pub name: &'static str,
+    /// The releases known to use this layout.
+    pub revisions: &'static [Revision],
+    /// The format version the layout reads, for parsers that take one.
+    pub version: Option<u32>,
+    probe: fn(&str, &[u8]) -> Result<()>,
+}
+
+impl Layout {
+    /// The layout every parser in [`Resource::parse`] reads, which is Metroid Prime's.
+    const PRIME: Self = Self {
+        name: "prime",
+        revisions: Revision::RETAIL,
+        version: None,
+        probe: |fourcc, data| Resource::parse(fourcc, data).map(drop),
+    };
+
+    const MREA: &'static [Self] = &[
+        Self {
+            name: "prime",
+            revisions: Revision::RETAIL,
+            version: Some(MreaSections::VERSION_PRIME),
+            probe: |_, data| {
+                MreaSections::new_with_layout(data, MreaSections::VERSION_PRIME).map(drop)
+            },
+        },
+        Self {
+            name: "echoes",
+            revisions: &[],
+            version: Some(MreaSections::VERSION_ECHOES),
+            probe: |_, data| {
+                MreaSections::new_with_layout(data, MreaSections::VERSION_ECHOES).map(drop)
+            },
+        },
+        Self {
+            name: "corruption",
+            revisions: &[],
+            version: Some(MreaSections::VERSION_CORRUPTION),
+            probe: |_, data| {
+                MreaSections::new_with_layout(data, MreaSections::VERSION_CORRUPTION).map(drop)
+            },
+        },
+    ];
+}
+
+/// How one layout fared.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    Clean,
+    /// Parsed, but with data the parsers skipped over.
+    Warnings(usize),
+    Failed(String),
+}
+
+#[derive(Clone)]
+pub struct Probe {
+    pub layout: &'static Layout,
+    pub outcome: Outcome,
+    /// Whether the disc's release is known to use the layout.
+    pub expected: bool,
+}
+
+impl Probe {
+    /// Orders probes from best to worst.
+    fn rank(&self) -> (&Outcome, bool) {
+        (&self.outcome, !self.expected)
+    }
+}
+
+#[derive(Clone)]
+pub struct Detection {
+    pub probes: Vec<Probe>,
+}
+
+impl Detection {
+    /// The layout that parsed best: cleanly if any did, otherwise with the fewest warnings, and
+    /// among equals one the disc's release is known to use. `None` if every layout failed.
+    pub fn best(&self) -> Option<&Probe> {
+        self.probes
+            .iter()
+            .filter(|probe| !matches!(probe.outcome, Outcome::Failed(_)))
+            .min_by(|a, b| a.rank().cmp(&b.rank()))
+    }
+
+    /// Whether another layout parsed as well as the best one, with nothing to choose between them.
+    pub fn is_ambiguous(&self) -> bool {
+        self.best().is_some_and(|best| {
+            self.probes
+                .iter()
+                .filter(|probe| probe.rank() == best.rank())
+                .count()
+                > 1
+        })
+    }
+}
+
+/// Probes each layout known for `fourcc`, or returns `None` for types without a parser.
+/// Parsing is lenient while probing, so that skipped data counts against a layout instead of
+/// failing it. The release layouts are expected for is the one in the current [`ParseOptions`].
+pub fn detect(fourcc: &str, data: &[u8]) -> Option<Detection> {
+    let layouts: &'static [Layout] = match fourcc {
+        "MREA" => Layout::MREA,
+        _ if Resource::FOURCCS.contains(&fourcc) => std::slice::from_ref(&Layout::PRIME),
+        _ => return None,
+    };
+    let revision = ParseOptions::current().revision;
+    let _options = ParseOptions {
+        strict: false,
+        ..ParseOptions::current()
+    }
+    .install_scoped();
+    let probes = layouts
+        .iter()
+        .map(|layout| {
+            let result = Parsed::capture(|| (layout.probe)(fourcc, data));
+            Probe {
+                layout,
+                outcome: match result {
+                    Ok(parsed) if parsed.warnings.is_empty() => Outcome::Clean,
+                    Ok(parsed) => Outcome::Warnings(parsed.warnings.len()),
+                    Err(e) => Outcome::Failed(e.to_string()),
+                },
+                expected: revision.is_some_and(|revision| layout.revisions.contains(&revision)),
+            }
+        })
+        .collect();
+    Some(Detection { probes })
+}
@@ -0,0 +1,100 @@
+//! Resources looked up across every pak at once, for references that cross paks, like an area
+//! whose skybox or a character whose textures live in another pak.
+
+use std::collections::{hash_map, BTreeMap, HashMap};
+
+use anyhow::Result;
+
+use crate::cache::ResourceCache;
+use crate::pak::{Pak, PakFile};
+
+/// An index of every resource in a set of paks by type and file ID. A resource stored in several
+/// paks is served from the first.
+pub struct DiscResources<'a> {
+    paks: Vec<(&'a str, Pak<'a>)>,
+    /// The pak holding each resource, by fourcc and file ID.
+    locations: HashMap<(String, u32), usize>,
+    /// The file IDs of each type, in order.
+    ids_by_type: BTreeMap<String, Vec<u32>>,
+    names: HashMap<u32, String>,
+    ids_by_name: HashMap<String, (String, u32)>,
+}
+
+impl<'a> DiscResources<'a> {
+    /// Indexes `files`, as loaded from [`PakFiles`](crate::pak::PakFiles). Paks that don't parse
+    /// are reported and left out.
+    pub fn new(files: &'a [PakFile], cache: Option<&ResourceCache>) -> Self {
+        let mut resources = Self {
+            paks: Vec::new(),
+            locations: HashMap::new(),
+            ids_by_type: BTreeMap::new(),
+            names: HashMap::new(),
+            ids_by_name: HashMap::new(),
+        };
+        for file in files {
+            let pak = match Pak::new(file.data()) {
+                Ok(pak) => pak.with_cache(cache.cloned()),
+                Err(e) => {
+                    println!("Skipping {}, which doesn't parse: {e}", file.path());
+                    continue;
+                }
+            };
+            let index = resources.paks.len();
+            for entry in pak.iter_resources() {
+                let key = (entry.fourcc().to_string(), entry.file_id());
+                if let hash_map::Entry::Vacant(location) = resources.locations.entry(key) {
+                    location.insert(index);
+                    resources
+                        .ids_by_type
+                        .entry(entry.fourcc().to_string())
+                        .or_default()
+                        .push(entry.file_id());
+                }
+            }
+            for entry in pak.iter_names() {
+                resources
+                    .names
+                    .entry(entry.file_id())
+                    .or_insert_with(|| entry.name().to_string());
+                resources
+                    .ids_by_name
+                    .entry(entry.name().to_string())
+                    .or_insert_with(|| (entry.fourcc().to_string(), entry.file_id()));
+            }
+            resources.paks.push((file.path(), pak));
+        }
+        resources
+    }
+
+    /// The decompressed data of a resource, from whichever pak holds it.
+    pub fn get(&self, fourcc: &str, file_id: u32) -> Result<Option<Vec<u8>>> {
+        match self.locations.get(&(fourcc.to_string(), file_id)) {
+            Some(&index) => self.paks[index].1.data_with_fourcc(file_id, fourcc),
+            None => Ok(None),
+        }
+    }
+
+    /// The path of the pak a resource is served from.
+    pub fn pak_path(&self, fourcc: &str, file_id: u32) -> Option<&str> {
+        self.locations
+            .get(&(fourcc.to_string(), file_id))
+            .map(|&index| self.paks[index].0)
+    }
+
+    /// The file ID of every resource of a type, each once.
+    pub fn iter_by_type(&self, fourcc: &str) -> impl Iterator<Item = u32> + '_ {
+        self.ids_by_type.get(fourcc).into_iter().flatten().copied()
+    }
+
+    /// The name a pak gives a resource, if any does.
+    pub fn name(&self, file_id: u32) -> Option<&str> {
+        self.names.get(&file_id).map(String::as_str)
+    }
+
+    /// The fourcc and file ID of the resource with a name.
+    pub fn find_by_name(&self, name: &str) -> Option<(&str, u32)> {
+        self.ids_by_name
+            .get(name)
+            .map(|(fourcc, file_id)| (fourcc.as_str(), *file_id))
+    }
+}
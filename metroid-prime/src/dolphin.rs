@@ -0,0 +1,62 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+
+/// Executable names Dolphin is commonly installed under.
+const EXECUTABLE_NAMES: &[&str] = &["dolphin-emu", "Dolphin", "Dolphin.exe"];
+
+/// Install locations checked when Dolphin isn't on the `PATH`.
+const WELL_KNOWN_PATHS: &[&str] = &[
+    "/Applications/Dolphin.app/Contents/MacOS/Dolphin",
+    "C:\\Program Files\\Dolphin\\Dolphin.exe",
+    "C:\\Program Files\\Dolphin-x64\\Dolphin.exe",
+    "/usr/games/dolphin-emu",
+];
+
+/// Finds a Dolphin executable, preferring `DOLPHIN_EMU` from the environment, then the `PATH`,
+/// then well-known install locations.
+pub fn locate() -> Result<PathBuf> {
+    if let Some(path) = env::var_os("DOLPHIN_EMU") {
+        return Ok(path.into());
+    }
+
+    if let Some(search_path) = env::var_os("PATH") {
+        for dir in env::split_paths(&search_path) {
+            for name in EXECUTABLE_NAMES {
+                let candidate = dir.join(name);
+                if candidate.is_file() {
+                    return Ok(candidate);
+                }
+            }
+        }
+    }
+
+    WELL_KNOWN_PATHS
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            anyhow!("Couldn't find Dolphin; pass its path to --run-dolphin or set DOLPHIN_EMU")
+        })
+}
+
+/// Launches Dolphin on a disc image without waiting for it to exit.
+pub fn launch(dolphin: Option<&Path>, image_path: &Path) -> Result<()> {
+    let dolphin = match dolphin {
+        Some(path) => path.to_path_buf(),
+        None => locate()?,
+    };
+    println!(
+        "Launching {} -e {}",
+        dolphin.display(),
+        image_path.display()
+    );
+    Command::new(&dolphin)
+        .arg("-e")
+        .arg(image_path)
+        .spawn()
+        .with_context(|| format!("Couldn't launch {}", dolphin.display()))?;
+    Ok(())
+}
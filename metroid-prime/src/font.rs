@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use gamecube::bytes::{ReadAsciiCStringExt, ReadFrom};
+use gamecube::{ReadBytesExt, ReadTypedExt};
+use serde::Serialize;
+
+use crate::txtr::DecodedTexture;
+
+/// How to write a font's metrics beside its glyph texture.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum FontMetrics {
+    /// The parsed FONT resource as JSON.
+    #[default]
+    Json,
+    /// An AngelCode BMFont text descriptor, which font tools and game engines can load directly.
+    Bmfont,
+}
+
+impl FontMetrics {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Bmfont => "fnt",
+        }
+    }
+}
+
+/// A FONT resource: a bitmap font whose glyphs are packed into a separate TXTR.
+#[derive(Clone, Debug, Serialize)]
+pub struct Font {
+    pub version: u32,
+    pub mono_width: u32,
+    pub mono_height: u32,
+    /// Distance from the top of a line to its baseline.
+    pub baseline: u32,
+    pub line_margin: u32,
+    pub unknown_flags: [bool; 2],
+    pub unknown: [u32; 2],
+    pub name: String,
+    pub texture_id: u32,
+    pub texture_mode: u32,
+    pub glyphs: Vec<Glyph>,
+    pub kerning: Vec<KerningPair>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Glyph {
+    pub character: u16,
+    /// Texture coordinates of the glyph's cell: left, top, right, bottom.
+    pub uv: [f32; 4],
+    /// Which texture layer holds the glyph, from version 4. Layers share the texture's pixels,
+    /// one color channel each: red, green, blue, then alpha.
+    pub layer: u8,
+    pub left_padding: i32,
+    pub advance: i32,
+    pub right_padding: i32,
+    pub width: i32,
+    pub height: i32,
+    /// Distance from the top of the glyph's cell to its baseline.
+    pub baseline: i32,
+    pub kerning_start: i32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct KerningPair {
+    pub first: u16,
+    pub second: u16,
+    pub adjustment: i32,
+}
+
+impl ReadFrom for Font {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != b"FONT" {
+            bail!("unexpected FONT magic: {magic:02x?}");
+        }
+        let version = r.read_u32()?;
+        if version > 4 {
+            bail!("unexpected FONT version: {version}");
+        }
+        let mono_width = r.read_u32()?;
+        let mono_height = r.read_u32()?;
+        let baseline = if version >= 1 {
+            r.read_u32()?
+        } else {
+            mono_height
+        };
+        let line_margin = if version >= 2 { r.read_u32()? } else { 0 };
+        let unknown_flags = [r.read_u8()? != 0, r.read_u8()? != 0];
+        let unknown = [r.read_u32()?, r.read_u32()?];
+        let name = r.read_ascii_c_string()?;
+        let texture_id = r.read_u32()?;
+        let texture_mode = r.read_u32()?;
+
+        let count = r.read_u32()?;
+        let mut glyphs = Vec::new();
+        for _ in 0..count {
+            let character = r.read_u16()?;
+            let uv = [
+                f32::from_bits(r.read_u32()?),
+                f32::from_bits(r.read_u32()?),
+                f32::from_bits(r.read_u32()?),
+                f32::from_bits(r.read_u32()?),
+            ];
+            // Version 4 packs the metrics into bytes and adds a layer.
+            let glyph = if version >= 4 {
+                Glyph {
+                    character,
+                    uv,
+                    layer: r.read_u8()?,
+                    left_padding: r.read_i8()? as i32,
+                    advance: r.read_i8()? as i32,
+                    right_padding: r.read_i8()? as i32,
+                    width: r.read_u8()? as i32,
+                    height: r.read_u8()? as i32,
+                    baseline: r.read_u8()? as i32,
+                    kerning_start: r.read_u16()? as i32,
+                }
+            } else {
+                Glyph {
+                    character,
+                    uv,
+                    layer: 0,
+                    left_padding: r.read_i32()?,
+                    advance: r.read_i32()?,
+                    right_padding: r.read_i32()?,
+                    width: r.read_i32()?,
+                    height: r.read_i32()?,
+                    baseline: r.read_i32()?,
+                    kerning_start: r.read_i32()?,
+                }
+            };
+            glyphs.push(glyph);
+        }
+
+        let count = r.read_u32()?;
+        let mut kerning = Vec::new();
+        for _ in 0..count {
+            kerning.push(r.read_typed()?);
+        }
+
+        Ok(Self {
+            version,
+            mono_width,
+            mono_height,
+            baseline,
+            line_margin,
+            unknown_flags,
+            unknown,
+            name,
+            texture_id,
+            texture_mode,
+            glyphs,
+            kerning,
+        })
+    }
+}
+
+impl ReadFrom for KerningPair {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let first = r.read_u16()?;
+        let second = r.read_u16()?;
+        let adjustment = r.read_i32()?;
+        Ok(Self {
+            first,
+            second,
+            adjustment,
+        })
+    }
+}
+
+impl Glyph {
+    /// The RGBA channel of the decoded texture that holds the glyph's layer.
+    fn channel(&self) -> Result<usize> {
+        if self.layer > 3 {
+            bail!(
+                "Glyph {} is on layer {}, past the texture's four channels",
+                self.character,
+                self.layer,
+            );
+        }
+        Ok(self.layer as usize)
+    }
+}
+
+impl Font {
+    /// Writes the font as an AngelCode BMFont text descriptor for a `texture_width` by
+    /// `texture_height` glyph texture saved as `texture_file`. Glyphs are placed the way
+    /// [`Font::render`] places them. The texture is the only page, and each glyph's layer is
+    /// written as its `chnl`, the BMFont mask of the channel holding it.
+    pub fn write_bmfont<W: Write>(
+        &self,
+        mut w: W,
+        texture_width: usize,
+        texture_height: usize,
+        texture_file: &str,
+    ) -> Result<()> {
+        // BMFont's channel masks, indexed by RGBA channel.
+        const CHANNEL_MASKS: [u8; 4] = [4, 2, 1, 8];
+
+        writeln!(
+            w,
+            "info face=\"{}\" size={} bold=0 italic=0 charset=\"\" unicode=1 stretchH=100 smooth=0 \
+             aa=1 padding=0,0,0,0 spacing=0,0",
+            self.name, self.mono_height,
+        )?;
+        writeln!(
+            w,
+            "common lineHeight={} base={} scaleW={texture_width} scaleH={texture_height} \
+             pages=1 packed=1",
+            self.mono_height + self.line_margin,
+            self.baseline,
+        )?;
+        writeln!(w, "page id=0 file=\"{texture_file}\"")?;
+        writeln!(w, "chars count={}", self.glyphs.len())?;
+        for glyph in &self.glyphs {
+            writeln!(
+                w,
+                "char id={} x={} y={} width={} height={} xoffset={} yoffset={} xadvance={} \
+                 page=0 chnl={}",
+                glyph.character,
+                (glyph.uv[0] * texture_width as f32).round() as i32,
+                (glyph.uv[1] * texture_height as f32).round() as i32,
+                glyph.width,
+                glyph.height,
+                glyph.left_padding,
+                self.baseline as i32 - glyph.baseline,
+                glyph.left_padding + glyph.advance + glyph.right_padding,
+                CHANNEL_MASKS[glyph.channel()?],
+            )?;
+        }
+        writeln!(w, "kernings count={}", self.kerning.len())?;
+        for pair in &self.kerning {
+            writeln!(
+                w,
+                "kerning first={} second={} amount={}",
+                pair.first, pair.second, pair.adjustment,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Draws `text` in white on a transparent background, laying glyphs out the way the game does:
+    /// each is drawn after its left padding and hangs from the line's baseline by its own, and the
+    /// pen then moves by its padding and advance plus any kerning with the next character. Glyph
+    /// coverage is read from the channel of the glyph's layer. Characters without a glyph are
+    /// skipped.
+    pub fn render(&self, texture: &DecodedTexture, text: &str) -> Result<DecodedTexture> {
+        let glyphs: HashMap<u16, &Glyph> = self.glyphs.iter().map(|g| (g.character, g)).collect();
+        let kerning: HashMap<(u16, u16), i32> = self
+            .kerning
+            .iter()
+            .map(|k| ((k.first, k.second), k.adjustment))
+            .collect();
+        let line_height = (self.mono_height + self.line_margin) as i32;
+
+        // Lay out every glyph first to size the image.
+        let mut placements = Vec::new();
+        let (mut width, mut height) = (0, 0);
+        for (line_index, line) in text.lines().enumerate() {
+            let line_top = line_index as i32 * line_height;
+            let mut x = 0;
+            let mut previous = None;
+            for c in line.encode_utf16() {
+                let Some(&glyph) = glyphs.get(&c) else {
+                    continue;
+                };
+                if let Some(previous) = previous {
+                    x += kerning.get(&(previous, c)).copied().unwrap_or(0);
+                }
+                let left = x + glyph.left_padding;
+                let top = line_top + self.baseline as i32 - glyph.baseline;
+                placements.push((glyph, left, top));
+                width = width.max(left + glyph.width);
+                height = height.max(top + glyph.height);
+                x += glyph.left_padding + glyph.advance + glyph.right_padding;
+                width = width.max(x);
+                previous = Some(c);
+            }
+            height = height.max(line_top + line_height);
+        }
+        if width <= 0 || height <= 0 {
+            bail!("Nothing to render; does the font have glyphs for this text?");
+        }
+
+        let (width, height) = (width as usize, height as usize);
+        let mut rgba = vec![0; width * height * 4];
+        for (glyph, left, top) in placements {
+            let channel = glyph.channel()?;
+            let source_left = (glyph.uv[0] * texture.width as f32).round() as i32;
+            let source_top = (glyph.uv[1] * texture.height as f32).round() as i32;
+            for y in 0..glyph.height {
+                for x in 0..glyph.width {
+                    let (sx, sy) = (source_left + x, source_top + y);
+                    let (dx, dy) = (left + x, top + y);
+                    if sx < 0
+                        || sy < 0
+                        || dx < 0
+                        || dy < 0
+                        || sx as usize >= texture.width
+                        || sy as usize >= texture.height
+                    {
+                        continue;
+                    }
+                    let coverage =
+                        texture.rgba[(sy as usize * texture.width + sx as usize) * 4 + channel];
+                    let pixel = &mut rgba[(dy as usize * width + dx as usize) * 4..][..4];
+                    pixel.copy_from_slice(&[0xff, 0xff, 0xff, pixel[3].max(coverage)]);
+                }
+            }
+        }
+        Ok(DecodedTexture {
+            width,
+            height,
+            rgba,
+        })
+    }
+}
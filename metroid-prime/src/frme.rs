@@ -0,0 +1,413 @@
+//! GUI frames (FRME): the widget hierarchy of a HUD or menu screen. Each widget names its parent,
+//! and its type decides what follows its common header.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use anyhow::{bail, Result};
+use gamecube::bytes::{ReadAsciiCStringExt, ReadFrom};
+use gamecube::ReadBytesExt;
+use serde::Serialize;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Frme {
+    pub version: u32,
+    pub unknown: [u32; 2],
+    pub model_count: u32,
+    pub widgets: Vec<Widget>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Widget {
+    pub name: String,
+    /// The name of the parent widget. The roots' parent is a placeholder no widget is named.
+    pub parent: String,
+    pub use_animation_controller: bool,
+    pub default_visible: bool,
+    pub default_active: bool,
+    pub cull_faces: bool,
+    pub color: [f32; 4],
+    pub model_draw_flags: u32,
+    #[serde(flatten)]
+    pub kind: WidgetKind,
+    /// The widget's index among its group's workers, if it's one of them.
+    pub worker_id: Option<u16>,
+    pub origin: [f32; 3],
+    /// The rows of the widget's rotation and scale.
+    pub basis: [[f32; 3]; 3],
+    pub rotation_center: [f32; 3],
+    pub unknown: (i32, i16),
+}
+
+/// What a widget is, by its fourcc, and the fields particular to that type.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum WidgetKind {
+    /// A plain widget, grouping its children.
+    #[serde(rename = "BWIG")]
+    Base,
+    /// The root of the frame's widgets.
+    #[serde(rename = "HWIG")]
+    Head,
+    #[serde(rename = "CAMR")]
+    Camera { projection: Projection },
+    #[serde(rename = "LITE")]
+    Light {
+        light_type: u32,
+        distance_falloff: [f32; 3],
+        angle_falloff: [f32; 3],
+        light_id: u32,
+        /// The cone angle, for spot lights.
+        cutoff: Option<f32>,
+    },
+    #[serde(rename = "ENRG")]
+    EnergyBar { texture_id: u32 },
+    #[serde(rename = "MODL")]
+    Model {
+        model_id: u32,
+        blend_mode: u32,
+        light_mask: u32,
+    },
+    #[serde(rename = "METR")]
+    Meter {
+        unknown: bool,
+        no_round_up: bool,
+        max_capacity: u32,
+        worker_count: u32,
+    },
+    #[serde(rename = "GRUP")]
+    Group { default_worker: i16, unknown: bool },
+    #[serde(rename = "PANE")]
+    Pane { pane: Pane },
+    #[serde(rename = "TXPN")]
+    TextPane {
+        pane: Pane,
+        font_id: u32,
+        word_wrap: bool,
+        horizontal: bool,
+        justification: u32,
+        vertical_justification: u32,
+        fill_color: [f32; 4],
+        outline_color: [f32; 4],
+        block_extent: [f32; 2],
+        /// The font and point scales used in place of the others for Japanese text, from version
+        /// 1.
+        japanese: Option<(u32, [i32; 2])>,
+    },
+    #[serde(rename = "IMGP")]
+    ImagePane {
+        texture_id: u32,
+        unknown: [u32; 2],
+        quad_coords: Vec<[f32; 3]>,
+        uv_coords: Vec<[f32; 2]>,
+    },
+    #[serde(rename = "TBGP")]
+    TableGroup {
+        element_count: u16,
+        default_selection: u16,
+        select_wraparound: bool,
+        /// Fields whose meaning isn't known, in file order.
+        #[allow(clippy::type_complexity)]
+        unknown: (u16, u32, u16, bool, [f32; 2], bool, f32, [u16; 4]),
+    },
+    #[serde(rename = "SLGP")]
+    SliderGroup {
+        min: f32,
+        max: f32,
+        current: f32,
+        increment: f32,
+    },
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Pane {
+    pub size: [f32; 2],
+    pub scale_center: [f32; 3],
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum Projection {
+    Perspective {
+        fov: f32,
+        aspect: f32,
+        near: f32,
+        far: f32,
+    },
+    Orthographic {
+        left: f32,
+        right: f32,
+        top: f32,
+        bottom: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+/// A frame with its widgets nested below their parents, for writing it out readably.
+#[derive(Serialize)]
+pub struct FrameTree<'a> {
+    pub version: u32,
+    pub model_count: u32,
+    pub widgets: Vec<WidgetTree<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct WidgetTree<'a> {
+    #[serde(flatten)]
+    pub widget: &'a Widget,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<WidgetTree<'a>>,
+}
+
+impl Frme {
+    /// Nests the widgets under their parents. Widgets whose parent isn't in the frame are roots.
+    pub fn tree(&self) -> FrameTree<'_> {
+        let index_by_name: HashMap<&str, usize> = self
+            .widgets
+            .iter()
+            .enumerate()
+            .map(|(index, widget)| (widget.name.as_str(), index))
+            .collect();
+        let mut children = vec![Vec::new(); self.widgets.len()];
+        let mut roots = Vec::new();
+        for (index, widget) in self.widgets.iter().enumerate() {
+            match index_by_name.get(widget.parent.as_str()) {
+                Some(&parent) if parent != index => children[parent].push(index),
+                _ => roots.push(index),
+            }
+        }
+
+        fn build<'a>(frme: &'a Frme, children: &[Vec<usize>], index: usize) -> WidgetTree<'a> {
+            WidgetTree {
+                widget: &frme.widgets[index],
+                children: children[index]
+                    .iter()
+                    .map(|&child| build(frme, children, child))
+                    .collect(),
+            }
+        }
+        // A cycle of parents leaves its widgets unreachable from any root, so they're dropped
+        // rather than recursed into forever.
+        FrameTree {
+            version: self.version,
+            model_count: self.model_count,
+            widgets: roots
+                .into_iter()
+                .map(|index| build(self, &children, index))
+                .collect(),
+        }
+    }
+}
+
+impl ReadFrom for Frme {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let version = r.read_u32()?;
+        if version > 1 {
+            bail!("unexpected FRME version: {version}");
+        }
+        let unknown0 = r.read_u32()?;
+        let model_count = r.read_u32()?;
+        let unknown1 = r.read_u32()?;
+        let count = r.read_u32()?;
+        let mut widgets = Vec::new();
+        for _ in 0..count {
+            widgets.push(read_widget(r, version)?);
+        }
+        Ok(Self {
+            version,
+            unknown: [unknown0, unknown1],
+            model_count,
+            widgets,
+        })
+    }
+}
+
+fn read_widget<R: Read>(r: &mut R, version: u32) -> Result<Widget> {
+    let mut fourcc = [0; 4];
+    r.read_exact(&mut fourcc)?;
+    let name = r.read_ascii_c_string()?;
+    let parent = r.read_ascii_c_string()?;
+    let use_animation_controller = read_bool(r)?;
+    let default_visible = read_bool(r)?;
+    let default_active = read_bool(r)?;
+    let cull_faces = read_bool(r)?;
+    let color = read_f32s(r)?;
+    let model_draw_flags = r.read_u32()?;
+
+    let kind = match &fourcc {
+        b"BWIG" => WidgetKind::Base,
+        b"HWIG" => WidgetKind::Head,
+        b"CAMR" => WidgetKind::Camera {
+            projection: match r.read_u32()? {
+                0 => {
+                    let [fov, aspect, near, far] = read_f32s(r)?;
+                    Projection::Perspective {
+                        fov,
+                        aspect,
+                        near,
+                        far,
+                    }
+                }
+                1 => {
+                    let [left, right, top, bottom, near, far] = read_f32s(r)?;
+                    Projection::Orthographic {
+                        left,
+                        right,
+                        top,
+                        bottom,
+                        near,
+                        far,
+                    }
+                }
+                projection => bail!("unexpected CAMR projection: {projection}"),
+            },
+        },
+        b"LITE" => {
+            let light_type = r.read_u32()?;
+            let distance_falloff = read_f32s(r)?;
+            let angle_falloff = read_f32s(r)?;
+            let light_id = r.read_u32()?;
+            // Only spot lights have a cone.
+            let cutoff = (light_type == 0).then(|| read_f32(r)).transpose()?;
+            WidgetKind::Light {
+                light_type,
+                distance_falloff,
+                angle_falloff,
+                light_id,
+                cutoff,
+            }
+        }
+        b"ENRG" => WidgetKind::EnergyBar {
+            texture_id: r.read_u32()?,
+        },
+        b"MODL" => WidgetKind::Model {
+            model_id: r.read_u32()?,
+            blend_mode: r.read_u32()?,
+            light_mask: r.read_u32()?,
+        },
+        b"METR" => WidgetKind::Meter {
+            unknown: read_bool(r)?,
+            no_round_up: read_bool(r)?,
+            max_capacity: r.read_u32()?,
+            worker_count: r.read_u32()?,
+        },
+        b"GRUP" => WidgetKind::Group {
+            default_worker: r.read_i16()?,
+            unknown: read_bool(r)?,
+        },
+        b"PANE" => WidgetKind::Pane {
+            pane: read_pane(r)?,
+        },
+        b"TXPN" => WidgetKind::TextPane {
+            pane: read_pane(r)?,
+            font_id: r.read_u32()?,
+            word_wrap: read_bool(r)?,
+            horizontal: read_bool(r)?,
+            justification: r.read_u32()?,
+            vertical_justification: r.read_u32()?,
+            fill_color: read_f32s(r)?,
+            outline_color: read_f32s(r)?,
+            block_extent: read_f32s(r)?,
+            japanese: if version >= 1 {
+                Some((r.read_u32()?, [r.read_i32()?, r.read_i32()?]))
+            } else {
+                None
+            },
+        },
+        b"IMGP" => {
+            let texture_id = r.read_u32()?;
+            let unknown = [r.read_u32()?, r.read_u32()?];
+            let mut quad_coords = Vec::new();
+            for _ in 0..r.read_u32()? {
+                quad_coords.push(read_f32s(r)?);
+            }
+            let mut uv_coords = Vec::new();
+            for _ in 0..r.read_u32()? {
+                uv_coords.push(read_f32s(r)?);
+            }
+            WidgetKind::ImagePane {
+                texture_id,
+                unknown,
+                quad_coords,
+                uv_coords,
+            }
+        }
+        b"TBGP" => {
+            let element_count = r.read_u16()?;
+            let unknown0 = r.read_u16()?;
+            let unknown1 = r.read_u32()?;
+            let default_selection = r.read_u16()?;
+            let unknown2 = r.read_u16()?;
+            let select_wraparound = read_bool(r)?;
+            let unknown3 = read_bool(r)?;
+            let unknown4 = read_f32s(r)?;
+            let unknown5 = read_bool(r)?;
+            let unknown6 = read_f32(r)?;
+            let unknown7 = [r.read_u16()?, r.read_u16()?, r.read_u16()?, r.read_u16()?];
+            WidgetKind::TableGroup {
+                element_count,
+                default_selection,
+                select_wraparound,
+                unknown: (
+                    unknown0, unknown1, unknown2, unknown3, unknown4, unknown5, unknown6, unknown7,
+                ),
+            }
+        }
+        b"SLGP" => {
+            let [min, max, current, increment] = read_f32s(r)?;
+            WidgetKind::SliderGroup {
+                min,
+                max,
+                current,
+                increment,
+            }
+        }
+        _ => bail!("unexpected FRME widget type: {fourcc:02x?}"),
+    };
+
+    let worker_id = read_bool(r)?.then(|| r.read_u16()).transpose()?;
+    let origin = read_f32s(r)?;
+    let basis = [read_f32s(r)?, read_f32s(r)?, read_f32s(r)?];
+    let rotation_center = read_f32s(r)?;
+    let unknown = (r.read_i32()?, r.read_i16()?);
+    Ok(Widget {
+        name,
+        parent,
+        use_animation_controller,
+        default_visible,
+        default_active,
+        cull_faces,
+        color,
+        model_draw_flags,
+        kind,
+        worker_id,
+        origin,
+        basis,
+        rotation_center,
+        unknown,
+    })
+}
+
+fn read_pane<R: Read>(r: &mut R) -> Result<Pane> {
+    Ok(Pane {
+        size: read_f32s(r)?,
+        scale_center: read_f32s(r)?,
+    })
+}
+
+fn read_bool<R: Read>(r: &mut R) -> Result<bool> {
+    Ok(r.read_u8()? != 0)
+}
+
+fn read_f32<R: Read>(r: &mut R) -> Result<f32> {
+    Ok(f32::from_bits(r.read_u32()?))
+}
+
+fn read_f32s<R: Read, const N: usize>(r: &mut R) -> Result<[f32; N]> {
+    let mut values = [0.0; N];
+    for value in &mut values {
+        *value = read_f32(r)?;
+    }
+    Ok(values)
+}
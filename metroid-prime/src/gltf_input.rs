@@ -0,0 +1,197 @@
+//! A minimal glTF reader for checking models before they're imported. Only what the checks use is
+//! deserialized: nodes, skins, meshes, and the accessors and buffers behind them. Both .gltf files
+//! with external buffers and .glb files are read.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Document {
+    #[serde(default)]
+    pub accessors: Vec<Accessor>,
+    #[serde(default)]
+    pub buffer_views: Vec<BufferView>,
+    #[serde(default)]
+    pub buffers: Vec<Buffer>,
+    #[serde(default)]
+    pub meshes: Vec<Mesh>,
+    #[serde(default)]
+    pub nodes: Vec<Node>,
+    #[serde(default)]
+    pub skins: Vec<Skin>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Accessor {
+    pub buffer_view: Option<usize>,
+    #[serde(default)]
+    pub byte_offset: usize,
+    pub component_type: u32,
+    #[serde(default)]
+    pub normalized: bool,
+    pub count: usize,
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferView {
+    pub buffer: usize,
+    #[serde(default)]
+    pub byte_offset: usize,
+    pub byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct Buffer {
+    pub uri: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Mesh {
+    pub name: Option<String>,
+    pub primitives: Vec<Primitive>,
+}
+
+#[derive(Deserialize)]
+pub struct Primitive {
+    pub attributes: HashMap<String, usize>,
+}
+
+#[derive(Deserialize)]
+pub struct Node {
+    pub name: Option<String>,
+    #[serde(default)]
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub skin: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct Skin {
+    pub joints: Vec<usize>,
+}
+
+/// Reads a .gltf or .glb file and the buffers it refers to.
+pub fn load(path: &Path) -> Result<(Document, Vec<Vec<u8>>)> {
+    const GLB_MAGIC: &[u8] = b"glTF";
+    const JSON_CHUNK: u32 = 0x4e4f534a;
+    const BIN_CHUNK: u32 = 0x004e4942;
+
+    let data = fs::read(path)?;
+    let (json, mut bin) = if data.starts_with(GLB_MAGIC) {
+        let mut json = None;
+        let mut bin = None;
+        let mut offset = 12;
+        while offset + 8 <= data.len() {
+            let length = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+            let type_ = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().unwrap());
+            let chunk = data
+                .get(offset + 8..offset + 8 + length)
+                .ok_or_else(|| anyhow!("The GLB chunk at 0x{offset:x} is truncated"))?;
+            match type_ {
+                JSON_CHUNK => json = Some(chunk),
+                BIN_CHUNK => bin = Some(chunk.to_vec()),
+                _ => (),
+            }
+            offset += 8 + length;
+        }
+        (
+            json.ok_or_else(|| anyhow!("The GLB file has no JSON chunk"))?,
+            bin,
+        )
+    } else {
+        (data.as_slice(), None)
+    };
+    let document: Document = serde_json::from_slice(json)?;
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let buffers = document
+        .buffers
+        .iter()
+        .map(|buffer| match &buffer.uri {
+            None => bin
+                .take()
+                .ok_or_else(|| anyhow!("A buffer has no URI and there's no GLB binary chunk")),
+            Some(uri) if uri.starts_with("data:") => {
+                bail!("Embedded buffers aren't supported; export with a separate .bin, or as .glb")
+            }
+            Some(uri) => Ok(fs::read(dir.join(uri))?),
+        })
+        .collect::<Result<_>>()?;
+    Ok((document, buffers))
+}
+
+/// Reads every element of an accessor as floats, scaling normalized integers to 0 through 1.
+pub fn read_accessor(
+    document: &Document,
+    buffers: &[Vec<u8>],
+    index: usize,
+) -> Result<Vec<Vec<f32>>> {
+    let accessor = document
+        .accessors
+        .get(index)
+        .ok_or_else(|| anyhow!("Accessor {index} is out of range"))?;
+    let components = match accessor.type_.as_str() {
+        "SCALAR" => 1,
+        "VEC2" => 2,
+        "VEC3" => 3,
+        "VEC4" => 4,
+        type_ => bail!("Accessor {index} has unsupported type {type_}"),
+    };
+    let (component_size, max): (usize, f32) = match accessor.component_type {
+        5121 => (1, u8::MAX as f32),
+        5123 => (2, u16::MAX as f32),
+        5125 => (4, u32::MAX as f32),
+        5126 => (4, 1.0),
+        component_type => {
+            bail!("Accessor {index} has unsupported component type {component_type}")
+        }
+    };
+    let Some(view) = accessor.buffer_view else {
+        return Ok(vec![vec![0.0; components]; accessor.count]);
+    };
+    let view = document
+        .buffer_views
+        .get(view)
+        .ok_or_else(|| anyhow!("Buffer view {view} is out of range"))?;
+    let buffer = buffers
+        .get(view.buffer)
+        .ok_or_else(|| anyhow!("Buffer {} is out of range", view.buffer))?;
+    let stride = view.byte_stride.unwrap_or(components * component_size);
+    let start = view.byte_offset + accessor.byte_offset;
+
+    let mut elements = Vec::with_capacity(accessor.count);
+    for element in 0..accessor.count {
+        let offset = start + element * stride;
+        let bytes = buffer
+            .get(offset..offset + components * component_size)
+            .ok_or_else(|| anyhow!("Accessor {index} runs past the end of its buffer"))?;
+        elements.push(
+            bytes
+                .chunks_exact(component_size)
+                .map(|c| {
+                    let value = match accessor.component_type {
+                        5121 => c[0] as f32,
+                        5123 => u16::from_le_bytes([c[0], c[1]]) as f32,
+                        5125 => u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f32,
+                        _ => f32::from_le_bytes([c[0], c[1], c[2], c[3]]),
+                    };
+                    if accessor.normalized {
+                        value / max
+                    } else {
+                        value
+                    }
+                })
+                .collect(),
+        );
+    }
+    Ok(elements)
+}
@@ -1,8 +1,10 @@
+use std::fmt::Write as _;
 use std::io::Read;
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use gamecube::bytes::ReadFrom;
 use gamecube::ReadBytesExt;
+use serde::Serialize;
 
 pub trait VertexDescriptor {
     type Joints: VertexAttribute;
@@ -12,31 +14,73 @@ pub trait VertexDescriptor {
 pub trait VertexAttribute: Copy + Default + Sized {
     type Data: ?Sized;
 
-    fn get(data: &Self::Data, index: usize) -> Self;
+    fn get(data: &Self::Data, index: usize) -> Result<Self>;
 }
 
 impl VertexAttribute for () {
     type Data = ();
 
-    fn get(_data: &(), _index: usize) -> Self {}
+    fn get(_data: &(), _index: usize) -> Result<Self> {
+        Ok(())
+    }
+}
+
+/// The bones a vertex is skinned to, up to four.
+impl VertexAttribute for [u32; 4] {
+    type Data = [[u32; 4]];
+
+    fn get(data: &[[u32; 4]], index: usize) -> Result<Self> {
+        IndexOutOfRange::check("joint", index, data.len())?;
+        Ok(data[index])
+    }
 }
 
-impl VertexAttribute for u32 {
-    type Data = [u32];
+/// The weight of each bone a vertex is skinned to.
+impl VertexAttribute for [f32; 4] {
+    type Data = [[f32; 4]];
 
-    fn get(data: &[u32], index: usize) -> Self {
-        data[index]
+    fn get(data: &[[f32; 4]], index: usize) -> Result<Self> {
+        IndexOutOfRange::check("weight", index, data.len())?;
+        Ok(data[index])
     }
 }
 
-impl VertexAttribute for f32 {
-    type Data = [f32];
+/// A vertex referring past the end of an attribute array, which happens with malformed data or
+/// when the vertex format is misread.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexOutOfRange {
+    pub attribute: &'static str,
+    pub index: usize,
+    /// The number of elements in the array.
+    pub array_len: usize,
+}
+
+impl IndexOutOfRange {
+    fn check(attribute: &'static str, index: usize, array_len: usize) -> Result<(), Self> {
+        if index < array_len {
+            Ok(())
+        } else {
+            Err(Self {
+                attribute,
+                index,
+                array_len,
+            })
+        }
+    }
+}
 
-    fn get(data: &[f32], index: usize) -> Self {
-        data[index]
+impl std::fmt::Display for IndexOutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} index {} is out of range for an array of {}",
+            self.attribute, self.index, self.array_len,
+        )
     }
 }
 
+impl std::error::Error for IndexOutOfRange {}
+
 pub struct StaticVertexDescriptor;
 
 impl VertexDescriptor for StaticVertexDescriptor {
@@ -47,8 +91,8 @@ impl VertexDescriptor for StaticVertexDescriptor {
 pub struct SkinnedVertexDescriptor;
 
 impl VertexDescriptor for SkinnedVertexDescriptor {
-    type Joints = u32;
-    type Weights = f32;
+    type Joints = [u32; 4];
+    type Weights = [f32; 4];
 }
 
 #[derive(Debug)]
@@ -59,10 +103,8 @@ pub struct DisplayList {
 impl DisplayList {
     pub fn parse<V>(
         &self,
-        vertex_attr_flags: u32,
-        position_data: &[u8],
-        normal_data: &[u8],
-        uv_float_data: &[u8],
+        inputs: &VertexInputs,
+        arrays: &VertexArrays,
         joints: &<V::Joints as VertexAttribute>::Data,
         weights: &<V::Weights as VertexAttribute>::Data,
     ) -> Result<Vec<Batch<V::Joints, V::Weights>>>
@@ -77,38 +119,32 @@ impl DisplayList {
                 break;
             }
             let primitive_type = opcode & 0xf8;
-            let vertex_format = opcode & 0x07;
+            let format = VertexFormat::prime(opcode & 0x07)?;
             match primitive_type {
                 0x90 => batches.push(Self::parse_batch(
                     &mut r,
-                    vertex_format,
+                    inputs,
+                    &format,
+                    arrays,
                     Triangles::new(),
-                    vertex_attr_flags,
-                    position_data,
-                    normal_data,
-                    uv_float_data,
                     joints,
                     weights,
                 )?),
                 0x98 => batches.push(Self::parse_batch(
                     &mut r,
-                    vertex_format,
+                    inputs,
+                    &format,
+                    arrays,
                     TriangleStrip::new(),
-                    vertex_attr_flags,
-                    position_data,
-                    normal_data,
-                    uv_float_data,
                     joints,
                     weights,
                 )?),
                 0xa0 => batches.push(Self::parse_batch(
                     &mut r,
-                    vertex_format,
+                    inputs,
+                    &format,
+                    arrays,
                     TriangleFan::new(),
-                    vertex_attr_flags,
-                    position_data,
-                    normal_data,
-                    uv_float_data,
                     joints,
                     weights,
                 )?),
@@ -120,12 +156,10 @@ impl DisplayList {
 
     fn parse_batch<R, H, BoneId, Weight>(
         r: &mut R,
-        vertex_format: u8,
+        inputs: &VertexInputs,
+        format: &VertexFormat,
+        arrays: &VertexArrays,
         mut vertex_handler: H,
-        vertex_attr_flags: u32,
-        position_data: &[u8],
-        normal_data: &[u8],
-        uv_float_data: &[u8],
         bone_ids: &BoneId::Data,
         weights: &Weight::Data,
     ) -> Result<Batch<BoneId, Weight>>
@@ -135,102 +169,323 @@ impl DisplayList {
         BoneId: VertexAttribute,
         Weight: VertexAttribute,
     {
-        assert!((0..=2).contains(&vertex_format));
-
         let count = r.read_u16()?;
         for _ in 0..count {
-            // Position
-            assert!((vertex_attr_flags & 0x3) != 0);
-            let (position, bone_id, weight) = {
-                let index = r.read_u16()?;
-                let mut data = &position_data[index as usize * 12..];
-                let x = f32::from_bits(data.read_u32()?);
-                let y = f32::from_bits(data.read_u32()?);
-                let z = f32::from_bits(data.read_u32()?);
-                let bone_id = BoneId::get(bone_ids, index as usize);
-                let weight = Weight::get(weights, index as usize);
-                ([x, y, z], bone_id, weight)
-            };
-            // Normal
-            assert!((vertex_attr_flags & 0xc) != 0);
-            let normal = match vertex_format {
-                0 => {
-                    let index = r.read_u16()?;
-                    let mut data = &normal_data[index as usize * 12..];
-                    let x = f32::from_bits(data.read_u32()?);
-                    let y = f32::from_bits(data.read_u32()?);
-                    let z = f32::from_bits(data.read_u32()?);
-                    [x, y, z]
-                }
-                1 | 2 => {
-                    let index = r.read_u16()?;
-                    let mut data = &normal_data[index as usize * 6..];
-                    let x = data.read_i16()? as f32;
-                    let y = data.read_i16()? as f32;
-                    let z = data.read_i16()? as f32;
-                    let s = (x * x + y * y + z * z).sqrt().recip();
-                    [s * x, s * y, s * z]
-                }
-                _ => unreachable!(),
-            };
-            // Color 0
-            if (vertex_attr_flags & 0x30) != 0 {
-                unimplemented!("Vertex attribute: Color 0")
-            }
-            // Color 1
-            if (vertex_attr_flags & 0xc0) != 0 {
-                unimplemented!("Vertex attribute: Color 1")
-            }
-            // Tex 0
-            let texcoord = if (vertex_attr_flags & 0x300) != 0 {
-                match vertex_format {
-                    0 | 1 => {
-                        let index = r.read_u16()?;
-                        let mut data = &uv_float_data[index as usize * 8..];
-                        let s = f32::from_bits(data.read_u32()?);
-                        let t = f32::from_bits(data.read_u32()?);
-                        Some([s, t])
-                    }
-                    2 => unimplemented!(),
-                    _ => unreachable!(),
-                }
-            } else {
-                None
+            let (position, position_index) = read_attribute(
+                r,
+                "position",
+                inputs.position,
+                &format.position,
+                arrays.positions,
+            )?
+            .ok_or_else(|| anyhow!("Vertices without positions aren't supported"))?;
+            // Skin weights are per position, so vertices with direct positions can't be skinned.
+            let (bone_id, weight) = match position_index {
+                Some(index) => (BoneId::get(bone_ids, index)?, Weight::get(weights, index)?),
+                None => Default::default(),
             };
-            // Tex 1
-            if (vertex_attr_flags & 0xc00) != 0 {
-                let _index = r.read_u16()?;
-                // TODO: Read and save the texture coordinate.
-            }
-            // Tex 2
-            if (vertex_attr_flags & 0x3000) != 0 {
-                let _index = r.read_u16()?;
-                // TODO: Read and save the texture coordinate.
-            }
-            // Tex 3
-            if (vertex_attr_flags & 0xc000) != 0 {
-                unimplemented!("Vertex attribute: Tex 3")
+
+            let (normal, _) =
+                read_attribute::<_, 3>(r, "normal", inputs.normal, &format.normal, arrays.normals)?
+                    .ok_or_else(|| anyhow!("Vertices without normals aren't supported"))?;
+            let length = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2])
+                .sqrt()
+                .recip();
+            let normal = normal.map(|x| x * length);
+
+            let mut colors = [None; 2];
+            for (color, (name, input)) in colors
+                .iter_mut()
+                .zip(COLOR_NAMES.into_iter().zip(inputs.colors))
+            {
+                *color =
+                    read_attribute::<_, 4>(r, name, input, &VertexFormat::COLOR, arrays.colors)?
+                        .map(|(value, _)| value.map(|x| x / 255.0));
             }
-            // Tex 4
-            if (vertex_attr_flags & 0x30000) != 0 {
-                unimplemented!("Vertex attribute: Tex 4")
+
+            let mut texcoords = [None; 8];
+            for (i, (&input, texcoord_format)) in
+                inputs.texcoords.iter().zip(&format.texcoords).enumerate()
+            {
+                let array = match texcoord_format.component_type {
+                    ComponentType::F32 => arrays.texcoords,
+                    _ => arrays.short_texcoords,
+                };
+                texcoords[i] =
+                    read_attribute::<_, 2>(r, TEXCOORD_NAMES[i], input, texcoord_format, array)?
+                        .map(|(value, _)| value);
             }
-            // Tex 5
-            if (vertex_attr_flags & 0xc0000) != 0 {
-                unimplemented!("Vertex attribute: Tex 5")
+
+            vertex_handler.handle_vertex(position, normal, texcoords, colors, bone_id, weight);
+        }
+
+        vertex_handler.finish()
+    }
+}
+
+impl DisplayList {
+    /// Lists the display list's primitives one vertex per line, with each attribute's index or
+    /// direct value, for debugging models that parse but render garbled. Unknown opcodes and
+    /// truncated data end the listing with an error line rather than failing.
+    pub fn disassemble(&self, inputs: &VertexInputs) -> String {
+        let mut listing = String::new();
+        if let Err(e) = self.disassemble_into(inputs, &mut listing) {
+            listing.push_str(&format!("error: {e}\n"));
+        }
+        listing
+    }
+
+    fn disassemble_into(&self, inputs: &VertexInputs, listing: &mut String) -> Result<()> {
+        let mut r = self.data.as_slice();
+        loop {
+            let offset = self.data.len() - r.len();
+            let opcode = r.read_u8()?;
+            if opcode == 0 {
+                writeln!(listing, "0x{offset:04x} end")?;
+                return Ok(());
             }
-            // Tex 6
-            if (vertex_attr_flags & 0x300000) != 0 {
-                unimplemented!("Vertex attribute: Tex 6")
+            let primitive = match opcode & 0xf8 {
+                0x80 => "quads",
+                0x90 => "triangles",
+                0x98 => "triangle strip",
+                0xa0 => "triangle fan",
+                0xa8 => "lines",
+                0xb0 => "line strip",
+                0xb8 => "points",
+                _ => bail!("unexpected opcode 0x{opcode:02x} at 0x{offset:04x}"),
+            };
+            let format = VertexFormat::prime(opcode & 0x07)?;
+            let count = r.read_u16()?;
+            writeln!(
+                listing,
+                "0x{offset:04x} {primitive}, format {}, {count} vertices",
+                opcode & 0x07,
+            )?;
+
+            let attributes = [
+                ("position", inputs.position, format.position),
+                ("normal", inputs.normal, format.normal),
+                (COLOR_NAMES[0], inputs.colors[0], VertexFormat::COLOR),
+                (COLOR_NAMES[1], inputs.colors[1], VertexFormat::COLOR),
+            ]
+            .into_iter()
+            .chain((0..8).map(|i| (TEXCOORD_NAMES[i], inputs.texcoords[i], format.texcoords[i])));
+            let attributes: Vec<_> = attributes
+                .filter(|&(_, input, _)| input != AttributeInput::None)
+                .collect();
+            for i in 0..count {
+                let mut fields = Vec::new();
+                for &(name, input, attribute_format) in &attributes {
+                    let value = match input {
+                        AttributeInput::None => unreachable!(),
+                        AttributeInput::Direct => {
+                            format!("{:?}", attribute_format.read_components(&mut r)?)
+                        }
+                        AttributeInput::Index8 => r.read_u8()?.to_string(),
+                        AttributeInput::Index16 => r.read_u16()?.to_string(),
+                    };
+                    fields.push(format!("{name} {value}"));
+                }
+                writeln!(listing, "  {i:4}: {}", fields.join(", "))?;
             }
+        }
+    }
+}
+
+const COLOR_NAMES: [&str; 2] = ["color0", "color1"];
+const TEXCOORD_NAMES: [&str; 8] = [
+    "tex0", "tex1", "tex2", "tex3", "tex4", "tex5", "tex6", "tex7",
+];
+
+/// Reads one attribute of a vertex as its descriptor says it's given, returning its value and, if
+/// indexed, its index. Indices past the end of the array fail with [`IndexOutOfRange`].
+fn read_attribute<R: Read, const N: usize>(
+    r: &mut R,
+    name: &'static str,
+    input: AttributeInput,
+    format: &AttributeFormat,
+    array: &[u8],
+) -> Result<Option<([f32; N], Option<usize>)>> {
+    let index = match input {
+        AttributeInput::None => return Ok(None),
+        AttributeInput::Direct => return Ok(Some((format.read(r)?, None))),
+        AttributeInput::Index8 => r.read_u8()? as usize,
+        AttributeInput::Index16 => r.read_u16()? as usize,
+    };
+    let size = format.size();
+    IndexOutOfRange::check(name, index, array.len() / size)?;
+    let mut data = &array[index * size..];
+    Ok(Some((format.read(&mut data)?, Some(index))))
+}
+
+/// How a display list gives one vertex attribute, `GXAttrType`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum AttributeInput {
+    /// The attribute is absent.
+    #[default]
+    None,
+    /// The value is stored in the display list.
+    Direct,
+    /// An 8-bit index into the attribute's array.
+    Index8,
+    /// A 16-bit index into the attribute's array.
+    Index16,
+}
+
+impl AttributeInput {
+    fn from_bits(bits: u32) -> Self {
+        match bits & 0x3 {
+            0 => Self::None,
+            1 => Self::Direct,
+            2 => Self::Index8,
+            _ => Self::Index16,
+        }
+    }
+}
+
+/// The vertex descriptor: which attributes each vertex has and how they're given. Materials
+/// encode it in their vertex attribute flags, two bits per attribute.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct VertexInputs {
+    pub position: AttributeInput,
+    pub normal: AttributeInput,
+    pub colors: [AttributeInput; 2],
+    pub texcoords: [AttributeInput; 8],
+}
+
+impl VertexInputs {
+    pub fn from_flags(flags: u32) -> Self {
+        let input = |attribute: u32| AttributeInput::from_bits(flags >> (2 * attribute));
+        Self {
+            position: input(0),
+            normal: input(1),
+            colors: [input(2), input(3)],
+            texcoords: std::array::from_fn(|i| input(4 + i as u32)),
+        }
+    }
+}
+
+/// A component type of vertex data, `GXCompType`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ComponentType {
+    U8,
+    S8,
+    U16,
+    S16,
+    F32,
+}
 
-            vertex_handler.handle_vertex(position, normal, texcoord, bone_id, weight);
+/// How an attribute's values are stored: the component type and count, and for integer types the
+/// number of fractional bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct AttributeFormat {
+    pub component_type: ComponentType,
+    pub component_count: usize,
+    pub frac: u8,
+}
+
+impl AttributeFormat {
+    const fn new(component_type: ComponentType, component_count: usize, frac: u8) -> Self {
+        Self {
+            component_type,
+            component_count,
+            frac,
         }
+    }
+
+    /// The size of one value in bytes, which is also the stride of its array.
+    pub fn size(&self) -> usize {
+        let component_size = match self.component_type {
+            ComponentType::U8 | ComponentType::S8 => 1,
+            ComponentType::U16 | ComponentType::S16 => 2,
+            ComponentType::F32 => 4,
+        };
+        component_size * self.component_count
+    }
+
+    /// Reads one value with however many components the format has.
+    fn read_components<R: Read>(&self, r: &mut R) -> Result<Vec<f32>> {
+        match self.component_count {
+            1 => Ok(self.read::<_, 1>(r)?.to_vec()),
+            2 => Ok(self.read::<_, 2>(r)?.to_vec()),
+            3 => Ok(self.read::<_, 3>(r)?.to_vec()),
+            4 => Ok(self.read::<_, 4>(r)?.to_vec()),
+            n => bail!("Attribute has {n} components"),
+        }
+    }
+
+    fn read<R: Read, const N: usize>(&self, r: &mut R) -> Result<[f32; N]> {
+        if self.component_count != N {
+            bail!(
+                "Attribute has {} components, want {N}",
+                self.component_count,
+            );
+        }
+        let scale = (1u32 << self.frac) as f32;
+        let mut value = [0.0; N];
+        for x in &mut value {
+            *x = match self.component_type {
+                ComponentType::U8 => r.read_u8()? as f32 / scale,
+                ComponentType::S8 => r.read_u8()? as i8 as f32 / scale,
+                ComponentType::U16 => r.read_u16()? as f32 / scale,
+                ComponentType::S16 => r.read_i16()? as f32 / scale,
+                ComponentType::F32 => f32::from_bits(r.read_u32()?),
+            };
+        }
+        Ok(value)
+    }
+}
+
+/// One entry of the vertex attribute table: the formats of every attribute, selected by the low
+/// three bits of each primitive's opcode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct VertexFormat {
+    pub position: AttributeFormat,
+    pub normal: AttributeFormat,
+    pub texcoords: [AttributeFormat; 8],
+}
+
+impl VertexFormat {
+    /// Colors are always RGBA8.
+    const COLOR: AttributeFormat = AttributeFormat::new(ComponentType::U8, 4, 0);
+
+    /// The formats Metroid Prime loads into the vertex attribute table. Format 0 is all floats,
+    /// format 1 has compressed normals, and format 2 also has compressed first texture coordinates.
+    pub fn prime(index: u8) -> Result<Self> {
+        const POSITION: AttributeFormat = AttributeFormat::new(ComponentType::F32, 3, 0);
+        const FLOAT_NORMAL: AttributeFormat = AttributeFormat::new(ComponentType::F32, 3, 0);
+        const SHORT_NORMAL: AttributeFormat = AttributeFormat::new(ComponentType::S16, 3, 14);
+        const FLOAT_TEXCOORD: AttributeFormat = AttributeFormat::new(ComponentType::F32, 2, 0);
+        const SHORT_TEXCOORD: AttributeFormat = AttributeFormat::new(ComponentType::S16, 2, 15);
 
-        Ok(vertex_handler.finish())
+        let (normal, first_texcoord) = match index {
+            0 => (FLOAT_NORMAL, FLOAT_TEXCOORD),
+            1 => (SHORT_NORMAL, FLOAT_TEXCOORD),
+            2 => (SHORT_NORMAL, SHORT_TEXCOORD),
+            _ => bail!("unexpected GX vertex format: {index}"),
+        };
+        let mut texcoords = [FLOAT_TEXCOORD; 8];
+        texcoords[0] = first_texcoord;
+        Ok(Self {
+            position: POSITION,
+            normal,
+            texcoords,
+        })
     }
 }
 
+/// The attribute arrays that indexed vertices read from.
+#[derive(Clone, Copy, Debug)]
+pub struct VertexArrays<'a> {
+    pub positions: &'a [u8],
+    pub normals: &'a [u8],
+    pub colors: &'a [u8],
+    pub texcoords: &'a [u8],
+    /// Texture coordinates as signed 16-bit fixed point with 15 fractional bits, read by vertex
+    /// format 2's first texture coordinates.
+    pub short_texcoords: &'a [u8],
+}
+
 impl ReadFrom for DisplayList {
     fn read_from<R: Read>(r: &mut R) -> Result<Self> {
         let mut data = Vec::new();
@@ -248,11 +503,12 @@ where
         &mut self,
         position: [f32; 3],
         normal: [f32; 3],
-        texcoord: Option<[f32; 2]>,
+        texcoords: [Option<[f32; 2]>; 8],
+        colors: [Option<[f32; 4]>; 2],
         bone_id: BoneId,
         weight: Weight,
     );
-    fn finish(self) -> Batch<BoneId, Weight>;
+    fn finish(self) -> Result<Batch<BoneId, Weight>>;
 }
 
 struct Triangles<BoneId, Weight>
@@ -262,15 +518,18 @@ where
 {
     positions: Vec<[f32; 3]>,
     normals: Vec<[f32; 3]>,
-    texcoords: Vec<[f32; 2]>,
+    texcoords: [Vec<[f32; 2]>; 8],
+    colors: [Vec<[f32; 4]>; 2],
     bone_ids: Vec<BoneId>,
     weights: Vec<Weight>,
     position_a: [f32; 3],
     position_b: [f32; 3],
     normal_a: [f32; 3],
     normal_b: [f32; 3],
-    texcoord_a: [f32; 2],
-    texcoord_b: [f32; 2],
+    texcoord_a: [[f32; 2]; 8],
+    color_a: [[f32; 4]; 2],
+    texcoord_b: [[f32; 2]; 8],
+    color_b: [[f32; 4]; 2],
     bone_id_a: BoneId,
     bone_id_b: BoneId,
     weight_a: Weight,
@@ -290,15 +549,18 @@ where
         Self {
             positions: Vec::new(),
             normals: Vec::new(),
-            texcoords: Vec::new(),
+            texcoords: Default::default(),
+            colors: Default::default(),
             bone_ids: Vec::new(),
             weights: Vec::new(),
             position_a: [0.0; 3],
             position_b: [0.0; 3],
             normal_a: [0.0; 3],
             normal_b: [0.0; 3],
-            texcoord_a: [0.0; 2],
-            texcoord_b: [0.0; 2],
+            texcoord_a: [[0.0; 2]; 8],
+            color_a: [[0.0; 4]; 2],
+            texcoord_b: [[0.0; 2]; 8],
+            color_b: [[0.0; 4]; 2],
             bone_id_a: Default::default(),
             bone_id_b: Default::default(),
             weight_a: Default::default(),
@@ -317,7 +579,8 @@ where
         &mut self,
         position: [f32; 3],
         normal: [f32; 3],
-        texcoord: Option<[f32; 2]>,
+        texcoords: [Option<[f32; 2]>; 8],
+        colors: [Option<[f32; 4]>; 2],
         bone_id: BoneId,
         weight: Weight,
     ) {
@@ -325,8 +588,15 @@ where
             0 => {
                 self.position_a = position;
                 self.normal_a = normal;
-                if let Some(texcoord) = texcoord {
-                    self.texcoord_a = texcoord;
+                for (slot, texcoord) in self.texcoord_a.iter_mut().zip(texcoords) {
+                    if let Some(texcoord) = texcoord {
+                        *slot = texcoord;
+                    }
+                }
+                for (slot, color) in self.color_a.iter_mut().zip(colors) {
+                    if let Some(color) = color {
+                        *slot = color;
+                    }
                 }
                 self.bone_id_a = bone_id;
                 self.weight_a = weight;
@@ -335,8 +605,15 @@ where
             1 => {
                 self.position_b = position;
                 self.normal_b = normal;
-                if let Some(texcoord) = texcoord {
-                    self.texcoord_b = texcoord;
+                for (slot, texcoord) in self.texcoord_b.iter_mut().zip(texcoords) {
+                    if let Some(texcoord) = texcoord {
+                        *slot = texcoord;
+                    }
+                }
+                for (slot, color) in self.color_b.iter_mut().zip(colors) {
+                    if let Some(color) = color {
+                        *slot = color;
+                    }
                 }
                 self.bone_id_b = bone_id;
                 self.weight_b = weight;
@@ -349,10 +626,19 @@ where
                 self.normals.push(self.normal_a);
                 self.normals.push(self.normal_b);
                 self.normals.push(normal);
-                if let Some(texcoord) = texcoord {
-                    self.texcoords.push(self.texcoord_a);
-                    self.texcoords.push(self.texcoord_b);
-                    self.texcoords.push(texcoord);
+                for (i, texcoord) in texcoords.into_iter().enumerate() {
+                    if let Some(texcoord) = texcoord {
+                        self.texcoords[i].extend([
+                            self.texcoord_a[i],
+                            self.texcoord_b[i],
+                            texcoord,
+                        ]);
+                    }
+                }
+                for (i, color) in colors.into_iter().enumerate() {
+                    if let Some(color) = color {
+                        self.colors[i].extend([self.color_a[i], self.color_b[i], color]);
+                    }
                 }
                 self.bone_ids.push(self.bone_id_a);
                 self.bone_ids.push(self.bone_id_b);
@@ -366,15 +652,18 @@ where
         }
     }
 
-    fn finish(self) -> Batch<BoneId, Weight> {
-        assert_eq!(self.state, 0);
-        Batch {
+    fn finish(self) -> Result<Batch<BoneId, Weight>> {
+        if self.state != 0 {
+            bail!("Triangle list ends with {} extra vertices", self.state);
+        }
+        Ok(Batch {
             positions: self.positions,
             normals: self.normals,
             texcoords: self.texcoords,
+            colors: self.colors,
             bone_ids: self.bone_ids,
             weights: self.weights,
-        }
+        })
     }
 }
 
@@ -385,15 +674,18 @@ where
 {
     positions: Vec<[f32; 3]>,
     normals: Vec<[f32; 3]>,
-    texcoords: Vec<[f32; 2]>,
+    texcoords: [Vec<[f32; 2]>; 8],
+    colors: [Vec<[f32; 4]>; 2],
     bone_ids: Vec<BoneId>,
     weights: Vec<Weight>,
     position_a: [f32; 3],
     position_b: [f32; 3],
     normal_a: [f32; 3],
     normal_b: [f32; 3],
-    texcoord_a: [f32; 2],
-    texcoord_b: [f32; 2],
+    texcoord_a: [[f32; 2]; 8],
+    color_a: [[f32; 4]; 2],
+    texcoord_b: [[f32; 2]; 8],
+    color_b: [[f32; 4]; 2],
     bone_id_a: BoneId,
     bone_id_b: BoneId,
     weight_a: Weight,
@@ -414,15 +706,18 @@ where
         Self {
             positions: Vec::new(),
             normals: Vec::new(),
-            texcoords: Vec::new(),
+            texcoords: Default::default(),
+            colors: Default::default(),
             bone_ids: Vec::new(),
             weights: Vec::new(),
             position_a: [0.0; 3],
             position_b: [0.0; 3],
             normal_a: [0.0; 3],
             normal_b: [0.0; 3],
-            texcoord_a: [0.0; 2],
-            texcoord_b: [0.0; 2],
+            texcoord_a: [[0.0; 2]; 8],
+            color_a: [[0.0; 4]; 2],
+            texcoord_b: [[0.0; 2]; 8],
+            color_b: [[0.0; 4]; 2],
             bone_id_a: Default::default(),
             bone_id_b: Default::default(),
             weight_a: Default::default(),
@@ -435,20 +730,29 @@ where
         &mut self,
         position: [f32; 3],
         normal: [f32; 3],
-        texcoord: Option<[f32; 2]>,
+        texcoords: [Option<[f32; 2]>; 8],
+        colors: [Option<[f32; 4]>; 2],
         bone_id: BoneId,
         weight: Weight,
     ) {
         self.position_a = self.position_b;
         self.normal_a = self.normal_b;
         self.texcoord_a = self.texcoord_b;
+        self.color_a = self.color_b;
         self.bone_id_a = self.bone_id_b;
         self.weight_a = self.weight_b;
 
         self.position_b = position;
         self.normal_b = normal;
-        if let Some(texcoord) = texcoord {
-            self.texcoord_b = texcoord;
+        for (slot, texcoord) in self.texcoord_b.iter_mut().zip(texcoords) {
+            if let Some(texcoord) = texcoord {
+                *slot = texcoord;
+            }
+        }
+        for (slot, color) in self.color_b.iter_mut().zip(colors) {
+            if let Some(color) = color {
+                *slot = color;
+            }
         }
         self.bone_id_b = bone_id;
         self.weight_b = weight;
@@ -464,7 +768,8 @@ where
         &mut self,
         position: [f32; 3],
         normal: [f32; 3],
-        texcoord: Option<[f32; 2]>,
+        texcoords: [Option<[f32; 2]>; 8],
+        colors: [Option<[f32; 4]>; 2],
         bone_id: BoneId,
         weight: Weight,
     ) {
@@ -472,8 +777,15 @@ where
             0 => {
                 self.position_a = position;
                 self.normal_a = normal;
-                if let Some(texcoord) = texcoord {
-                    self.texcoord_a = texcoord;
+                for (slot, texcoord) in self.texcoord_a.iter_mut().zip(texcoords) {
+                    if let Some(texcoord) = texcoord {
+                        *slot = texcoord;
+                    }
+                }
+                for (slot, color) in self.color_a.iter_mut().zip(colors) {
+                    if let Some(color) = color {
+                        *slot = color;
+                    }
                 }
                 self.bone_id_a = bone_id;
                 self.weight_a = weight;
@@ -482,8 +794,15 @@ where
             1 => {
                 self.position_b = position;
                 self.normal_b = normal;
-                if let Some(texcoord) = texcoord {
-                    self.texcoord_b = texcoord;
+                for (slot, texcoord) in self.texcoord_b.iter_mut().zip(texcoords) {
+                    if let Some(texcoord) = texcoord {
+                        *slot = texcoord;
+                    }
+                }
+                for (slot, color) in self.color_b.iter_mut().zip(colors) {
+                    if let Some(color) = color {
+                        *slot = color;
+                    }
                 }
                 self.bone_id_b = bone_id;
                 self.weight_b = weight;
@@ -496,10 +815,19 @@ where
                 self.normals.push(self.normal_a);
                 self.normals.push(self.normal_b);
                 self.normals.push(normal);
-                if let Some(texcoord) = texcoord {
-                    self.texcoords.push(self.texcoord_a);
-                    self.texcoords.push(self.texcoord_b);
-                    self.texcoords.push(texcoord);
+                for (i, texcoord) in texcoords.into_iter().enumerate() {
+                    if let Some(texcoord) = texcoord {
+                        self.texcoords[i].extend([
+                            self.texcoord_a[i],
+                            self.texcoord_b[i],
+                            texcoord,
+                        ]);
+                    }
+                }
+                for (i, color) in colors.into_iter().enumerate() {
+                    if let Some(color) = color {
+                        self.colors[i].extend([self.color_a[i], self.color_b[i], color]);
+                    }
                 }
                 self.bone_ids.push(self.bone_id_a);
                 self.bone_ids.push(self.bone_id_b);
@@ -507,7 +835,7 @@ where
                 self.weights.push(self.weight_a);
                 self.weights.push(self.weight_b);
                 self.weights.push(weight);
-                self.shift(position, normal, texcoord, bone_id, weight);
+                self.shift(position, normal, texcoords, colors, bone_id, weight);
                 self.state = 3;
             }
             3 => {
@@ -517,10 +845,19 @@ where
                 self.normals.push(self.normal_b);
                 self.normals.push(self.normal_a);
                 self.normals.push(normal);
-                if let Some(texcoord) = texcoord {
-                    self.texcoords.push(self.texcoord_b);
-                    self.texcoords.push(self.texcoord_a);
-                    self.texcoords.push(texcoord);
+                for (i, texcoord) in texcoords.into_iter().enumerate() {
+                    if let Some(texcoord) = texcoord {
+                        self.texcoords[i].extend([
+                            self.texcoord_b[i],
+                            self.texcoord_a[i],
+                            texcoord,
+                        ]);
+                    }
+                }
+                for (i, color) in colors.into_iter().enumerate() {
+                    if let Some(color) = color {
+                        self.colors[i].extend([self.color_b[i], self.color_a[i], color]);
+                    }
                 }
                 self.bone_ids.push(self.bone_id_b);
                 self.bone_ids.push(self.bone_id_a);
@@ -528,21 +865,22 @@ where
                 self.weights.push(self.weight_b);
                 self.weights.push(self.weight_a);
                 self.weights.push(weight);
-                self.shift(position, normal, texcoord, bone_id, weight);
+                self.shift(position, normal, texcoords, colors, bone_id, weight);
                 self.state = 2;
             }
             _ => unreachable!(),
         }
     }
 
-    fn finish(self) -> Batch<BoneId, Weight> {
-        Batch {
+    fn finish(self) -> Result<Batch<BoneId, Weight>> {
+        Ok(Batch {
             positions: self.positions,
             normals: self.normals,
             texcoords: self.texcoords,
+            colors: self.colors,
             bone_ids: self.bone_ids,
             weights: self.weights,
-        }
+        })
     }
 }
 
@@ -553,15 +891,18 @@ where
 {
     positions: Vec<[f32; 3]>,
     normals: Vec<[f32; 3]>,
-    texcoords: Vec<[f32; 2]>,
+    texcoords: [Vec<[f32; 2]>; 8],
+    colors: [Vec<[f32; 4]>; 2],
     bone_ids: Vec<BoneId>,
     weights: Vec<Weight>,
     position_a: [f32; 3],
     position_b: [f32; 3],
     normal_a: [f32; 3],
     normal_b: [f32; 3],
-    texcoord_a: [f32; 2],
-    texcoord_b: [f32; 2],
+    texcoord_a: [[f32; 2]; 8],
+    color_a: [[f32; 4]; 2],
+    texcoord_b: [[f32; 2]; 8],
+    color_b: [[f32; 4]; 2],
     bone_id_a: BoneId,
     bone_id_b: BoneId,
     weight_a: Weight,
@@ -581,15 +922,18 @@ where
         Self {
             positions: Vec::new(),
             normals: Vec::new(),
-            texcoords: Vec::new(),
+            texcoords: Default::default(),
+            colors: Default::default(),
             bone_ids: Vec::new(),
             weights: Vec::new(),
             position_a: [0.0; 3],
             position_b: [0.0; 3],
             normal_a: [0.0; 3],
             normal_b: [0.0; 3],
-            texcoord_a: [0.0; 2],
-            texcoord_b: [0.0; 2],
+            texcoord_a: [[0.0; 2]; 8],
+            color_a: [[0.0; 4]; 2],
+            texcoord_b: [[0.0; 2]; 8],
+            color_b: [[0.0; 4]; 2],
             bone_id_a: Default::default(),
             bone_id_b: Default::default(),
             weight_a: Default::default(),
@@ -602,14 +946,22 @@ where
         &mut self,
         position: [f32; 3],
         normal: [f32; 3],
-        texcoord: Option<[f32; 2]>,
+        texcoords: [Option<[f32; 2]>; 8],
+        colors: [Option<[f32; 4]>; 2],
         bone_id: BoneId,
         weight: Weight,
     ) {
         self.position_b = position;
         self.normal_b = normal;
-        if let Some(texcoord) = texcoord {
-            self.texcoord_b = texcoord;
+        for (slot, texcoord) in self.texcoord_b.iter_mut().zip(texcoords) {
+            if let Some(texcoord) = texcoord {
+                *slot = texcoord;
+            }
+        }
+        for (slot, color) in self.color_b.iter_mut().zip(colors) {
+            if let Some(color) = color {
+                *slot = color;
+            }
         }
         self.bone_id_b = bone_id;
         self.weight_b = weight;
@@ -625,7 +977,8 @@ where
         &mut self,
         position: [f32; 3],
         normal: [f32; 3],
-        texcoord: Option<[f32; 2]>,
+        texcoords: [Option<[f32; 2]>; 8],
+        colors: [Option<[f32; 4]>; 2],
         bone_id: BoneId,
         weight: Weight,
     ) {
@@ -633,15 +986,22 @@ where
             0 => {
                 self.position_a = position;
                 self.normal_a = normal;
-                if let Some(texcoord) = texcoord {
-                    self.texcoord_a = texcoord;
+                for (slot, texcoord) in self.texcoord_a.iter_mut().zip(texcoords) {
+                    if let Some(texcoord) = texcoord {
+                        *slot = texcoord;
+                    }
+                }
+                for (slot, color) in self.color_a.iter_mut().zip(colors) {
+                    if let Some(color) = color {
+                        *slot = color;
+                    }
                 }
                 self.bone_id_a = bone_id;
                 self.weight_a = weight;
                 self.state = 1;
             }
             1 => {
-                self.shift(position, normal, texcoord, bone_id, weight);
+                self.shift(position, normal, texcoords, colors, bone_id, weight);
                 self.state = 2;
             }
             2 => {
@@ -651,10 +1011,19 @@ where
                 self.normals.push(self.normal_a);
                 self.normals.push(self.normal_b);
                 self.normals.push(normal);
-                if let Some(texcoord) = texcoord {
-                    self.texcoords.push(self.texcoord_a);
-                    self.texcoords.push(self.texcoord_b);
-                    self.texcoords.push(texcoord);
+                for (i, texcoord) in texcoords.into_iter().enumerate() {
+                    if let Some(texcoord) = texcoord {
+                        self.texcoords[i].extend([
+                            self.texcoord_a[i],
+                            self.texcoord_b[i],
+                            texcoord,
+                        ]);
+                    }
+                }
+                for (i, color) in colors.into_iter().enumerate() {
+                    if let Some(color) = color {
+                        self.colors[i].extend([self.color_a[i], self.color_b[i], color]);
+                    }
                 }
                 self.bone_ids.push(self.bone_id_a);
                 self.bone_ids.push(self.bone_id_b);
@@ -662,20 +1031,21 @@ where
                 self.weights.push(self.weight_a);
                 self.weights.push(self.weight_b);
                 self.weights.push(weight);
-                self.shift(position, normal, texcoord, bone_id, weight);
+                self.shift(position, normal, texcoords, colors, bone_id, weight);
             }
             _ => unreachable!(),
         }
     }
 
-    fn finish(self) -> Batch<BoneId, Weight> {
-        Batch {
+    fn finish(self) -> Result<Batch<BoneId, Weight>> {
+        Ok(Batch {
             positions: self.positions,
             normals: self.normals,
             texcoords: self.texcoords,
+            colors: self.colors,
             bone_ids: self.bone_ids,
             weights: self.weights,
-        }
+        })
     }
 }
 
@@ -687,7 +1057,204 @@ where
 {
     pub positions: Vec<[f32; 3]>,
     pub normals: Vec<[f32; 3]>,
-    pub texcoords: Vec<[f32; 2]>,
+    /// Coordinates for each of the eight texture coordinate sets, each empty if the vertices don't
+    /// have it.
+    pub texcoords: [Vec<[f32; 2]>; 8],
+    /// Colors from 0 to 1 for each of the two color channels, each empty if the vertices don't
+    /// have it.
+    pub colors: [Vec<[f32; 4]>; 2],
     pub bone_ids: Vec<BoneId>,
     pub weights: Vec<Weight>,
 }
+
+/// A TEV color combiner input, `GXTevColorArg`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TevColorArg {
+    PrevColor,
+    PrevAlpha,
+    Color0,
+    Alpha0,
+    Color1,
+    Alpha1,
+    Color2,
+    Alpha2,
+    TextureColor,
+    TextureAlpha,
+    RasterizedColor,
+    RasterizedAlpha,
+    One,
+    Half,
+    Konst,
+    Zero,
+}
+
+impl TevColorArg {
+    fn from_bits(bits: u32) -> Self {
+        match bits & 0xf {
+            0 => Self::PrevColor,
+            1 => Self::PrevAlpha,
+            2 => Self::Color0,
+            3 => Self::Alpha0,
+            4 => Self::Color1,
+            5 => Self::Alpha1,
+            6 => Self::Color2,
+            7 => Self::Alpha2,
+            8 => Self::TextureColor,
+            9 => Self::TextureAlpha,
+            10 => Self::RasterizedColor,
+            11 => Self::RasterizedAlpha,
+            12 => Self::One,
+            13 => Self::Half,
+            14 => Self::Konst,
+            _ => Self::Zero,
+        }
+    }
+
+    /// Decodes the four inputs `[a, b, c, d]` packed five bits apart.
+    pub fn unpack(packed: u32) -> [Self; 4] {
+        [0, 5, 10, 15].map(|shift| Self::from_bits(packed >> shift))
+    }
+}
+
+/// A TEV alpha combiner input, `GXTevAlphaArg`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TevAlphaArg {
+    PrevAlpha,
+    Alpha0,
+    Alpha1,
+    Alpha2,
+    TextureAlpha,
+    RasterizedAlpha,
+    Konst,
+    Zero,
+}
+
+impl TevAlphaArg {
+    fn from_bits(bits: u32) -> Self {
+        match bits & 0x7 {
+            0 => Self::PrevAlpha,
+            1 => Self::Alpha0,
+            2 => Self::Alpha1,
+            3 => Self::Alpha2,
+            4 => Self::TextureAlpha,
+            5 => Self::RasterizedAlpha,
+            6 => Self::Konst,
+            _ => Self::Zero,
+        }
+    }
+
+    /// Decodes the four inputs `[a, b, c, d]` packed five bits apart.
+    pub fn unpack(packed: u32) -> [Self; 4] {
+        [0, 5, 10, 15].map(|shift| Self::from_bits(packed >> shift))
+    }
+}
+
+/// A TEV combiner operation, `GXTevOp`. The comparison operations select `c` where the comparison
+/// of `a` and `b` holds and zero elsewhere, then add `d`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TevOp {
+    Add,
+    Subtract,
+    CompareR8Greater,
+    CompareR8Equal,
+    CompareGr16Greater,
+    CompareGr16Equal,
+    CompareBgr24Greater,
+    CompareBgr24Equal,
+    /// `CompareRgb8*` for color combiners and `CompareA8*` for alpha combiners.
+    CompareComponentGreater,
+    CompareComponentEqual,
+}
+
+/// A TEV combiner bias, `GXTevBias`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TevBias {
+    Zero,
+    AddHalf,
+    SubtractHalf,
+}
+
+/// A TEV combiner scale, `GXTevScale`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TevScale {
+    One,
+    Two,
+    Four,
+    Half,
+}
+
+impl TevScale {
+    pub fn factor(self) -> f32 {
+        match self {
+            Self::One => 1.0,
+            Self::Two => 2.0,
+            Self::Four => 4.0,
+            Self::Half => 0.5,
+        }
+    }
+}
+
+/// A TEV output register, `GXTevRegID`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TevRegister {
+    Prev,
+    Register0,
+    Register1,
+    Register2,
+}
+
+/// The operation of one TEV color or alpha combiner. The result is
+/// `clamp(scale * (d op ((1 - c) * a + c * b) + bias))`, written to `output`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct TevCombiner {
+    pub op: TevOp,
+    pub bias: TevBias,
+    pub scale: TevScale,
+    pub clamp: bool,
+    pub output: TevRegister,
+}
+
+impl TevCombiner {
+    pub fn unpack(packed: u32) -> Result<Self> {
+        let op = match packed & 0xf {
+            0 => TevOp::Add,
+            1 => TevOp::Subtract,
+            8 => TevOp::CompareR8Greater,
+            9 => TevOp::CompareR8Equal,
+            10 => TevOp::CompareGr16Greater,
+            11 => TevOp::CompareGr16Equal,
+            12 => TevOp::CompareBgr24Greater,
+            13 => TevOp::CompareBgr24Equal,
+            14 => TevOp::CompareComponentGreater,
+            15 => TevOp::CompareComponentEqual,
+            x => bail!("Unexpected TEV op: {x}"),
+        };
+        let bias = match (packed >> 4) & 0x3 {
+            0 => TevBias::Zero,
+            1 => TevBias::AddHalf,
+            2 => TevBias::SubtractHalf,
+            // Comparison ops encode their bias field as 3.
+            _ => TevBias::Zero,
+        };
+        let scale = match (packed >> 6) & 0x3 {
+            0 => TevScale::One,
+            1 => TevScale::Two,
+            2 => TevScale::Four,
+            _ => TevScale::Half,
+        };
+        let clamp = (packed >> 8) & 0x1 != 0;
+        let output = match (packed >> 9) & 0x3 {
+            0 => TevRegister::Prev,
+            1 => TevRegister::Register0,
+            2 => TevRegister::Register1,
+            _ => TevRegister::Register2,
+        };
+        Ok(Self {
+            op,
+            bias,
+            scale,
+            clamp,
+            output,
+        })
+    }
+}
@@ -0,0 +1,198 @@
+//! Checking a model from a 3D editor against the model it will replace. A material's vertex
+//! attribute flags fix which attributes its surfaces' vertices carry, so each replacement surface
+//! has to supply exactly those. Missing attributes with a harmless stand-in get a placeholder; the
+//! rest are reported as what must be added, so an import fails with guidance instead of garbage.
+
+use std::fmt;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::cmdl::Cmdl;
+use crate::gltf_input;
+use crate::gx::{AttributeInput, VertexInputs};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AttributeProblem {
+    SurfaceCount {
+        primitives: usize,
+        surfaces: usize,
+    },
+    /// An attribute the surface's material reads that the primitive doesn't have.
+    Missing {
+        primitive: String,
+        material: usize,
+        attribute: String,
+        placeholder: Option<&'static str>,
+    },
+    /// An attribute the primitive has that the surface's material doesn't read. Only a warning.
+    Unused {
+        primitive: String,
+        material: usize,
+        attribute: String,
+    },
+}
+
+impl AttributeProblem {
+    /// Whether the import can't go ahead, rather than just filling in or dropping an attribute.
+    pub fn is_error(&self) -> bool {
+        match self {
+            Self::SurfaceCount { .. } => true,
+            Self::Missing { placeholder, .. } => placeholder.is_none(),
+            Self::Unused { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for AttributeProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SurfaceCount {
+                primitives,
+                surfaces,
+            } => write!(
+                f,
+                "The model has {primitives} primitives, but the one it replaces has {surfaces} \
+                 surfaces",
+            ),
+            Self::Missing {
+                primitive,
+                material,
+                attribute,
+                placeholder: Some(placeholder),
+            } => write!(
+                f,
+                "Warning: {primitive} has no {attribute}, which material {material} reads; it \
+                 will be filled with {placeholder}",
+            ),
+            Self::Missing {
+                primitive,
+                material,
+                attribute,
+                placeholder: None,
+            } => write!(
+                f,
+                "{primitive} has no {attribute}, which material {material} reads; add it before \
+                 importing",
+            ),
+            Self::Unused {
+                primitive,
+                material,
+                attribute,
+            } => write!(
+                f,
+                "Warning: {primitive} has {attribute}, which material {material} doesn't read; \
+                 it will be dropped",
+            ),
+        }
+    }
+}
+
+/// Checks each primitive of the glTF model at `path` against the material of the surface it
+/// replaces in `cmdl`, pairing primitives with surfaces in order. Returns every problem found.
+pub fn check(path: &Path, cmdl: &Cmdl, material_set_index: usize) -> Result<Vec<AttributeProblem>> {
+    let (document, _) = gltf_input::load(path)?;
+    let material_set = &cmdl.materials[material_set_index];
+    let primitives: Vec<(String, &gltf_input::Primitive)> = document
+        .meshes
+        .iter()
+        .enumerate()
+        .flat_map(|(mesh_index, mesh)| {
+            let mesh_name = mesh
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("Mesh {mesh_index}"));
+            mesh.primitives
+                .iter()
+                .enumerate()
+                .map(move |(index, primitive)| {
+                    (format!("{mesh_name} primitive {index}"), primitive)
+                })
+        })
+        .collect();
+
+    let mut problems = Vec::new();
+    if primitives.len() != cmdl.surfaces.len() {
+        problems.push(AttributeProblem::SurfaceCount {
+            primitives: primitives.len(),
+            surfaces: cmdl.surfaces.len(),
+        });
+    }
+    for ((name, primitive), surface) in primitives.iter().zip(&cmdl.surfaces) {
+        let material = surface.material_index as usize;
+        let Some(inputs) = material_set
+            .materials
+            .get(material)
+            .map(|material| material.vertex_inputs())
+        else {
+            continue;
+        };
+        let required = required_attributes(&inputs);
+        for attribute in &required {
+            if !primitive.attributes.contains_key(attribute) {
+                problems.push(AttributeProblem::Missing {
+                    primitive: name.clone(),
+                    material,
+                    placeholder: placeholder(attribute),
+                    attribute: attribute.clone(),
+                });
+            }
+        }
+        let mut present: Vec<&String> = primitive
+            .attributes
+            .keys()
+            .filter(|attribute| is_gx_attribute(attribute) && !required.contains(attribute))
+            .collect();
+        present.sort();
+        for attribute in present {
+            problems.push(AttributeProblem::Unused {
+                primitive: name.clone(),
+                material,
+                attribute: attribute.clone(),
+            });
+        }
+    }
+    Ok(problems)
+}
+
+/// The glTF attributes matching the GX attributes a material reads.
+fn required_attributes(inputs: &VertexInputs) -> Vec<String> {
+    [
+        ("POSITION".to_string(), inputs.position),
+        ("NORMAL".to_string(), inputs.normal),
+    ]
+    .into_iter()
+    .chain(
+        inputs
+            .colors
+            .iter()
+            .enumerate()
+            .map(|(i, &input)| (format!("COLOR_{i}"), input)),
+    )
+    .chain(
+        inputs
+            .texcoords
+            .iter()
+            .enumerate()
+            .map(|(i, &input)| (format!("TEXCOORD_{i}"), input)),
+    )
+    .filter(|&(_, input)| input != AttributeInput::None)
+    .map(|(attribute, _)| attribute)
+    .collect()
+}
+
+/// Whether a glTF attribute has a GX counterpart that vertex attribute flags control. Skinning
+/// attributes and tangents are handled apart from materials.
+fn is_gx_attribute(attribute: &str) -> bool {
+    attribute == "NORMAL" || attribute.starts_with("COLOR_") || attribute.starts_with("TEXCOORD_")
+}
+
+/// What stands in for a missing attribute, or `None` if it has to be authored.
+fn placeholder(attribute: &str) -> Option<&'static str> {
+    match attribute {
+        "POSITION" | "TEXCOORD_0" => None,
+        "NORMAL" => Some("flat normals computed from the triangles"),
+        _ if attribute.starts_with("COLOR_") => Some("opaque white"),
+        _ => Some("a copy of TEXCOORD_0"),
+    }
+}
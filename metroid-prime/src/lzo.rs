@@ -0,0 +1,130 @@
+//! LZO1X decompression, which Metroid Prime 2 uses for pak resources and MREA blocks in place of
+//! zlib.
+
+use anyhow::{anyhow, Result};
+
+/// Decompresses one LZO1X stream, up to its end marker.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut r = Input { data, pos: 0 };
+    let mut out = Vec::new();
+
+    // The number of literals copied after the previous instruction, which changes what short match
+    // instructions mean. 4 stands for a run of four or more.
+    let mut state;
+
+    let first = r.peek()?;
+    if first > 17 {
+        r.pos += 1;
+        let count = (first - 17) as usize;
+        out.extend_from_slice(r.take(count)?);
+        state = count.min(4);
+    } else {
+        state = 0;
+    }
+
+    loop {
+        let t = r.byte()? as usize;
+        let (distance, len, next);
+        if t < 16 {
+            if state == 0 {
+                // A run of literals.
+                let count = if t == 0 { r.extended_length(15)? } else { t } + 3;
+                out.extend_from_slice(r.take(count)?);
+                state = 4;
+                continue;
+            }
+            next = t & 3;
+            let low = (t >> 2) + ((r.byte()? as usize) << 2);
+            if state < 4 {
+                (distance, len) = (1 + low, 2);
+            } else {
+                (distance, len) = (1 + 0x800 + low, 3);
+            }
+        } else if t >= 64 {
+            next = t & 3;
+            distance = 1 + ((t >> 2) & 7) + ((r.byte()? as usize) << 3);
+            len = (t >> 5) + 1;
+        } else if t >= 32 {
+            len = if t & 31 == 0 {
+                r.extended_length(31)?
+            } else {
+                t & 31
+            } + 2;
+            let bits = r.le16()?;
+            next = bits & 3;
+            distance = 1 + (bits >> 2);
+        } else {
+            len = if t & 7 == 0 {
+                r.extended_length(7)?
+            } else {
+                t & 7
+            } + 2;
+            let bits = r.le16()?;
+            next = bits & 3;
+            let far = ((t & 8) << 11) + (bits >> 2);
+            if far == 0 {
+                break;
+            }
+            distance = far + 0x4000;
+        }
+
+        let start = out.len().checked_sub(distance).ok_or_else(|| {
+            anyhow!(
+                "LZO match {distance} bytes back, but only {} written",
+                out.len()
+            )
+        })?;
+        for i in start..start + len {
+            out.push(out[i]);
+        }
+
+        out.extend_from_slice(r.take(next)?);
+        state = next;
+    }
+    Ok(out)
+}
+
+struct Input<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Input<'a> {
+    fn peek(&self) -> Result<u8> {
+        self.data
+            .get(self.pos)
+            .copied()
+            .ok_or_else(|| anyhow!("LZO stream ended early"))
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let b = self.peek()?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn le16(&mut self) -> Result<usize> {
+        Ok(self.byte()? as usize | (self.byte()? as usize) << 8)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let slice = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| anyhow!("LZO stream ended early"))?;
+        self.pos += len;
+        Ok(slice)
+    }
+
+    /// Reads a length that didn't fit in its instruction: each zero byte adds 255, and the first
+    /// non-zero byte is added to `base`.
+    fn extended_length(&mut self, base: usize) -> Result<usize> {
+        let mut len = base;
+        loop {
+            match self.byte()? {
+                0 => len += 255,
+                b => return Ok(len + b as usize),
+            }
+        }
+    }
+}
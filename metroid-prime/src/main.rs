@@ -1,223 +1,3532 @@
 #![allow(dead_code)]
 
-use std::collections::HashMap;
+use std::cell::OnceCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::f32::consts::FRAC_PI_2;
 use std::ffi::OsStr;
 use std::fs::File;
 use std::hash::{Hash, Hasher};
 use std::io::{BufWriter, Write};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 use anyhow::{anyhow, bail, Result};
 use byteorder::{LittleEndian, WriteBytesExt};
-use clap::{Parser, Subcommand};
-use gamecube::bytes::ReadFrom;
+use clap::{Parser, Subcommand, ValueEnum};
+use gamecube::bytes::WriteTo;
 use gamecube::disc::Header;
-use gamecube::{Disc, ReadTypedExt};
+use gamecube::{Banner, Disc, DspAudio, ThpHeader};
 use gltf::Gltf;
 use memmap::Mmap;
-use nalgebra::{Isometry3, UnitQuaternion, Vector3};
+use nalgebra::{Isometry3, Matrix4, Translation3, UnitQuaternion, Vector3};
+use serde::{Deserialize, Serialize};
 
-use crate::ancs::Ancs;
-use crate::cmdl::Cmdl;
-use crate::mesh::CanonicalMesh;
-use crate::pak::{Pak, PakCache};
+use crate::ancs::{Ancs, AncsSummary, AnimationAabb, Character, Effect};
+use crate::anim::Anim;
+use crate::area_stats::AreaStats;
+use crate::audio::Agsc;
+use crate::cache::ResourceCache;
+use crate::catalog::CatalogFormat;
+use crate::cinf::Cinf;
+use crate::cmdl::{Cmdl, CmdlSections};
+use crate::detect::Revision;
+use crate::disc_resources::DiscResources;
+use crate::font::{Font, FontMetrics};
+use crate::frme::Frme;
+use crate::memdump::{MemDump, ResourceTableLayout};
+use crate::mesh::{
+    transform_from_rows, CanonicalMesh, CanonicalMeshSurface, ScriptMarker, WorldContext,
+};
+use crate::mlvl::Mlvl;
+use crate::mrea::{MreaSections, OctreeCell};
+use crate::names::AreaNames;
+use crate::output_image::OutputImage;
+use crate::output_names::OutputNames;
+use crate::pak::{Pak, PakCache, PakFile, PakFiles};
+use crate::parse::{ParseOptions, Parsed, Trace, Warning};
+use crate::part::AnimatedTexture;
+use crate::patch::PatchFormat;
+use crate::png_pool::{PngCompression, PngPool, PreviewFormat};
+use crate::presets::Preset;
+use crate::project::{Export, Project};
+use crate::quantize::VertexQuantization;
+use crate::resource::Resource;
+use crate::roundtrip::RoundTrip;
+use crate::scan::Scan;
+use crate::strg::Strg;
+use crate::template::{OutputTemplate, TemplateValues};
+use crate::tweaks::PlayerGunTweaks;
+use crate::txtr::{DecodedTexture, TextureFormat};
+use crate::workspace::Workspace;
 
 mod ancs;
+mod anim;
+mod area_stats;
+mod audio;
+mod cache;
+mod catalog;
 mod cinf;
 mod cmdl;
+mod compression;
+mod compression_stats;
 mod cskr;
+mod detect;
+mod disc_resources;
+mod dolphin;
+mod font;
+mod frme;
+mod gltf_input;
 mod gx;
+mod import_check;
+mod lzo;
+mod memdump;
 mod mesh;
+mod mlvl;
+mod modding;
+mod mrea;
+mod names;
+mod output_image;
+mod output_names;
 mod pak;
+mod parse;
+mod part;
+mod patch;
+mod png_pool;
+mod preload;
+mod presets;
+mod project;
+mod quantize;
+mod resource;
+mod rig;
+mod roundtrip;
+mod scan;
+mod scly;
+mod simplify;
+mod sound_emitters;
+mod stdio;
+mod strg;
+#[cfg(feature = "synthetic-disc")]
+mod synthetic;
+mod template;
+mod tev;
+mod texture_groups;
+mod tweaks;
 mod txtr;
+mod wav;
+mod workspace;
 
 #[derive(Parser)]
 struct Args {
-    /// Path to a Metroid Prime disc image, USA version 1.0.
-    image_path: String,
+    /// Path to a Metroid Prime disc image, USA version 1.00, 1.01, or 1.02. Commands that only read
+    /// paks can go without one when given loose pak files.
+    image_path: Option<String>,
+
+    /// Registers an additional disc image under a name. May be repeated. Example: pal=prime_pal.iso
+    #[arg(long = "disc", value_name = "NAME=PATH", global = true)]
+    discs: Vec<String>,
+
+    /// Adds a loose pak file on the host to the paks that commands like ExtractAll go through. May
+    /// be repeated, and works without a disc image. Example: Metroid1.pak
+    #[arg(long = "pak", value_name = "PATH", global = true)]
+    paks: Vec<String>,
+
+    /// Name of the registered disc image to operate on. Example: pal
+    #[arg(long, global = true)]
+    game: Option<String>,
+
+    /// Directory for caching decompressed resources between runs.
+    #[arg(long, global = true)]
+    cache_dir: Option<String>,
+
+    /// Read disc images into memory instead of mapping them. This is chosen automatically for
+    /// images on network filesystems.
+    #[arg(long, global = true)]
+    no_mmap: bool,
+
+    /// Fail on data the parsers don't understand, such as unread trailing bytes, instead of
+    /// skipping it with a warning. Useful for format research.
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Go on with a disc image other than a known revision of the USA release, like a PAL or
+    /// Japanese disc, warning about it rather than failing.
+    #[arg(long, global = true)]
+    allow_any_version: bool,
+
+    /// What exports do when a model refers to a texture its pak doesn't have.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    missing_texture: MissingTexture,
 
     #[command(subcommand)]
     command: Command,
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum SplitBy {
+    /// One mesh and node for the whole model.
+    #[default]
+    None,
+    /// One mesh and node per material.
+    Material,
+    /// One mesh and node per surface.
+    Surface,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Recenter {
+    /// Keep the model where it was authored.
+    #[default]
+    Origin,
+    /// Move the center of the model's bounds to the origin.
+    BoundsCenter,
+    /// Move the center of the bottom face of the model's bounds to the origin.
+    Feet,
+}
+
+impl Recenter {
+    fn apply(self, mesh: &mut CanonicalMesh) {
+        let Some((min, max)) = mesh.bounds() else {
+            return;
+        };
+        let center = [0, 1, 2].map(|axis| (min[axis] + max[axis]) / 2.0);
+        // Prime is Z-up.
+        let offset = match self {
+            Self::Origin => return,
+            Self::BoundsCenter => center.map(|c| -c),
+            Self::Feet => [-center[0], -center[1], -min[2]],
+        };
+        mesh.translate(offset);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum MipOutput {
+    /// One PNG per level, with a suffix giving the level. Example: TXTR_SamusFace_mip1.png
+    Pngs,
+    /// One KTX2 container holding every level.
+    Ktx2,
+}
+
+/// How ExtractAll arranges the files it writes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum ExtractLayout {
+    /// OUT_DIR/PAK/FOURCC/NAME, for browsing by pak and type.
+    #[default]
+    ByType,
+    /// A copy of the disc's filesystem in OUT_DIR/files, with its system files in OUT_DIR/sys as
+    /// Dolphin extracts them, and each pak's resources in OUT_DIR/files/PAK/FOURCC/NAME beside it.
+    Disc,
+    /// OUT_DIR/NAME, with each resource written once however many paks share it.
+    Flat,
+}
+
+/// The directories of the disc layout holding the system files and the filesystem.
+const DISC_SYSTEM_DIR: &str = "sys";
+const DISC_FILES_DIR: &str = "files";
+
+impl ExtractLayout {
+    /// The directory a resource of type `fourcc` from `pak` is dumped into.
+    fn dump_dir(self, out_dir: &Path, pak: &PakFile, fourcc: &str) -> PathBuf {
+        match self {
+            Self::ByType => out_dir
+                .join(Path::new(pak.file_name()).file_stem().unwrap_or_default())
+                .join(fourcc),
+            Self::Disc => {
+                let pak_path = pak.disc_path().unwrap_or(Path::new(pak.file_name()));
+                out_dir
+                    .join(DISC_FILES_DIR)
+                    .join(pak_path.with_extension(""))
+                    .join(fourcc)
+            }
+            Self::Flat => out_dir.to_path_buf(),
+        }
+    }
+}
+
+/// What to do when a model refers to a texture its pak doesn't have.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum MissingTexture {
+    /// Substitute a magenta and black checkerboard, with a warning.
+    #[default]
+    Placeholder,
+    /// Leave the texture out so its materials are untextured, with a warning.
+    Skip,
+    /// Fail the export.
+    Error,
+}
+
+/// How area and world exports mark the script objects placed in each area.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ScriptMarkers {
+    /// An empty node per object.
+    Empties,
+    /// An empty node per object with a small wireframe box, and a translucent box filling each
+    /// trigger or other volume.
+    Gizmos,
+}
+
+/// Options shared by the glTF exporters.
+#[derive(Clone, Copy, Debug, Default)]
+struct ExportOptions {
+    split_by: SplitBy,
+    bake_materials: bool,
+    approximate_reflections: bool,
+    animation_bounds: bool,
+    quantize: bool,
+    /// Whether to export vertex colors as `COLOR_0`, which glTF multiplies with the base color, and
+    /// the second color channel as `COLOR_1`.
+    vertex_colors: bool,
+    /// Whether to add a scene for each of the mesh's parts, after the default scene holding all of
+    /// them.
+    scene_per_part: bool,
+    /// A translation for a root node above the rest of the default scene, to place the model
+    /// relative to something other than its own origin.
+    root_offset: Option<[f32; 3]>,
+    /// How to mark the mesh's script objects, if at all.
+    script_objects: Option<ScriptMarkers>,
+    /// Whether to add boxes for the volumes of the mesh's script objects.
+    trigger_volumes: bool,
+    /// Whether to add cameras, and polylines through their waypoints, for the mesh's camera script
+    /// objects.
+    cameras: bool,
+    missing_texture: MissingTexture,
+}
+
+/// The base name of exported files unless another is given.
+const DEFAULT_EXPORT_NAME: &str = "gltf_export";
+
+/// Where an export's files go. They share a directory and a base name, so the glTF file can refer
+/// to the others by relative URI.
+struct ExportFiles<'a> {
+    dir: &'a Path,
+    name: &'a str,
+}
+
+impl ExportFiles<'_> {
+    /// The name of the file with `suffix` after the base name. Example: `_00.png`
+    fn file_name(&self, suffix: &str) -> String {
+        format!("{}{suffix}", self.name)
+    }
+
+    fn path(&self, suffix: &str) -> PathBuf {
+        self.dir.join(self.file_name(suffix))
+    }
+}
+
+/// Resolves an export's `--out-dir` against the current directory, so a recorded export writes to
+/// the same place wherever it's replayed from.
+fn resolve_out_dir(out_dir: Option<PathBuf>) -> Result<PathBuf> {
+    let current_dir = std::env::current_dir()?;
+    Ok(match out_dir {
+        Some(out_dir) => current_dir.join(out_dir),
+        None => current_dir,
+    })
+}
+
 #[derive(Subcommand)]
 enum Command {
     ExtractCmdl {
-        /// Disc path of the pak file. Example: NoARAM.pak
+        /// Disc path of the pak file, or the path of a loose pak file on the host.
+        /// Example: NoARAM.pak
         pak_path: String,
 
-        /// Name of the CMDL entry within the pak file. Example: CMDL_InvWaveBeam
+        /// Name of the CMDL entry within the pak file, or its file ID in hex.
+        /// Example: CMDL_InvWaveBeam
         name: String,
 
         /// Index of the material set. Defaults to zero.
         material_set_index: Option<usize>,
+
+        /// How surfaces are grouped into glTF meshes and nodes.
+        #[arg(long, value_enum, default_value_t)]
+        split_by: SplitBy,
+
+        /// Composite each material's texture stages into one baked base color texture where
+        /// possible.
+        #[arg(long)]
+        bake_materials: bool,
+
+        /// Render reflective surfaces as metallic, using the reflection texture as their
+        /// metallic-roughness texture.
+        #[arg(long)]
+        approximate_reflections: bool,
+
+        /// Move the model before export so it sits at a predictable spot relative to the origin.
+        #[arg(long, value_enum, default_value_t)]
+        recenter: Recenter,
+
+        /// Reduce each surface to this fraction of its triangles, for preview-quality exports.
+        /// Example: 0.5
+        #[arg(long, value_name = "RATIO")]
+        simplify: Option<f32>,
+
+        /// Store positions, normals, and texture coordinates as normalized integers
+        /// (KHR_mesh_quantization), with the node transform restoring the original scale.
+        #[arg(long)]
+        quantize: bool,
+
+        /// Leave out vertex colors. By default they're exported as COLOR_0, which glTF viewers
+        /// multiply with the base color, so baked lighting darkens the texture as it does in game.
+        #[arg(long)]
+        no_vertex_colors: bool,
+
+        /// Print a disassembly of each surface's display list before exporting, for debugging
+        /// models that parse but render garbled.
+        #[arg(long = "dump-displaylist")]
+        dump_display_list: bool,
+
+        /// Directory to write the export into, created if needed. Defaults to the current
+        /// directory.
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+
+        /// Base name of the exported files: NAME.gltf, NAME.bin, NAME_00.png, and so on.
+        #[arg(long = "name", value_name = "NAME", default_value = DEFAULT_EXPORT_NAME)]
+        file_name: String,
+
+        /// Project file to record this export in, for later use with Reexport.
+        #[arg(long)]
+        project: Option<String>,
+    },
+    /// Exports every area of a world as one glTF scene, with a node per area placing it in the
+    /// world. Statistics for each area are written to NAME_area_stats.json alongside.
+    ExtractWorld {
+        /// Disc path of the world's pak file, or the path of a loose pak file on the host.
+        /// Example: Metroid2.pak
+        pak_path: String,
+
+        /// Name of the MLVL entry within the pak file, or its file ID in hex. Defaults to the pak's
+        /// only MLVL. Example: 0x83f6ff6f
+        world: Option<String>,
+
+        /// How each area's surfaces are grouped into glTF meshes and nodes.
+        #[arg(long, value_enum, default_value_t)]
+        split_by: SplitBy,
+
+        /// Composite each material's texture stages into one baked base color texture where
+        /// possible.
+        #[arg(long)]
+        bake_materials: bool,
+
+        /// Render reflective surfaces as metallic, using the reflection texture as their
+        /// metallic-roughness texture.
+        #[arg(long)]
+        approximate_reflections: bool,
+
+        /// Reduce each surface to this fraction of its triangles, for preview-quality exports.
+        /// Example: 0.5
+        #[arg(long, value_name = "RATIO")]
+        simplify: Option<f32>,
+
+        /// Store positions, normals, and texture coordinates as normalized integers
+        /// (KHR_mesh_quantization), with the node transform restoring the original scale.
+        #[arg(long)]
+        quantize: bool,
+
+        /// Leave out vertex colors. By default they're exported as COLOR_0, which glTF viewers
+        /// multiply with the base color, so baked lighting darkens the texture as it does in game.
+        #[arg(long)]
+        no_vertex_colors: bool,
+
+        /// Add a scene for each area after the default scene holding the whole world, so viewers
+        /// that switch between scenes can show one area at a time.
+        #[arg(long)]
+        scene_per_area: bool,
+
+        /// Add each area's render octree as translucent boxes under an area_octree node, one per
+        /// leaf cell with the world models it holds in its extras, for studying how the game culls
+        /// geometry.
+        #[arg(long)]
+        debug_geometry: bool,
+
+        /// Add a node for each script object placed in the area, like actors, triggers, and
+        /// pickups, with its type and name in extras. Doors and elevators also record the area
+        /// they lead to.
+        #[arg(long, value_enum, value_name = "STYLE")]
+        script_objects: Option<ScriptMarkers>,
+
+        /// Add a translucent box for each trigger, water, and other script object covering a
+        /// volume, with its exact extents in extras, under a trigger_volumes node that is also the
+        /// only node of a scene named volumes.
+        #[arg(long)]
+        trigger_volumes: bool,
+
+        /// Add a glTF camera for each Camera, CameraWaypoint, CameraHint, and SpindleCamera script
+        /// object under a cameras node, and a polyline through the waypoints each cinematic camera
+        /// follows. The game moves the camera along a spline through them, which isn't sampled.
+        #[arg(long)]
+        cameras: bool,
+
+        /// Directory to write the export into, created if needed. Defaults to the current
+        /// directory.
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+
+        /// Base name of the exported files: NAME.gltf, NAME.bin, NAME_00.png, and so on.
+        #[arg(long = "name", value_name = "NAME", default_value = DEFAULT_EXPORT_NAME)]
+        file_name: String,
+
+        /// Project file to record this export in, for later use with Reexport.
+        #[arg(long)]
+        project: Option<String>,
+    },
+    /// Exports an area's world geometry in area space.
+    ExtractMrea {
+        /// Disc path of the pak file, or the path of a loose pak file on the host.
+        /// Example: Metroid4.pak
+        pak_path: String,
+
+        /// Name of the MREA entry within the pak file, or its file ID in hex. Example: 0x2b3f1184
+        mrea: String,
+
+        /// How surfaces are grouped into glTF meshes and nodes.
+        #[arg(long, value_enum, default_value_t)]
+        split_by: SplitBy,
+
+        /// Composite each material's texture stages into one baked base color texture where
+        /// possible.
+        #[arg(long)]
+        bake_materials: bool,
+
+        /// Render reflective surfaces as metallic, using the reflection texture as their
+        /// metallic-roughness texture.
+        #[arg(long)]
+        approximate_reflections: bool,
+
+        /// Move the area before export so it sits at a predictable spot relative to the origin.
+        #[arg(long, value_enum, default_value_t)]
+        recenter: Recenter,
+
+        /// Reduce each surface to this fraction of its triangles, for preview-quality exports.
+        /// Example: 0.5
+        #[arg(long, value_name = "RATIO")]
+        simplify: Option<f32>,
+
+        /// Store positions, normals, and texture coordinates as normalized integers
+        /// (KHR_mesh_quantization), with the node transform restoring the original scale.
+        #[arg(long)]
+        quantize: bool,
+
+        /// Leave out vertex colors. By default they're exported as COLOR_0, which glTF viewers
+        /// multiply with the base color, so baked lighting darkens the texture as it does in game.
+        #[arg(long)]
+        no_vertex_colors: bool,
+
+        /// Add the area's render octree as translucent boxes under an area_octree node, one per
+        /// leaf cell with the world models it holds in its extras, for studying how the game culls
+        /// geometry.
+        #[arg(long)]
+        debug_geometry: bool,
+
+        /// Add a node for each script object placed in the area, like actors, triggers, and
+        /// pickups, with its type and name in extras. Doors and elevators also record the area
+        /// they lead to.
+        #[arg(long, value_enum, value_name = "STYLE")]
+        script_objects: Option<ScriptMarkers>,
+
+        /// Add a translucent box for each trigger, water, and other script object covering a
+        /// volume, with its exact extents in extras, under a trigger_volumes node that is also the
+        /// only node of a scene named volumes.
+        #[arg(long)]
+        trigger_volumes: bool,
+
+        /// Add a glTF camera for each Camera, CameraWaypoint, CameraHint, and SpindleCamera script
+        /// object under a cameras node, and a polyline through the waypoints each cinematic camera
+        /// follows. The game moves the camera along a spline through them, which isn't sampled.
+        #[arg(long)]
+        cameras: bool,
+
+        /// Directory to write the export into, created if needed. Defaults to the current
+        /// directory.
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+
+        /// Base name of the exported files: NAME.gltf, NAME.bin, NAME_00.png, and so on.
+        #[arg(long = "name", value_name = "NAME", default_value = DEFAULT_EXPORT_NAME)]
+        file_name: String,
+
+        /// Project file to record this export in, for later use with Reexport.
+        #[arg(long)]
+        project: Option<String>,
     },
     ExtractAncs {
-        /// Disc path of the pak file. Example: SamusGun.pak
+        /// Disc path of the pak file, or the path of a loose pak file on the host.
+        /// Example: SamusGun.pak
+        pak_path: String,
+
+        /// Name of the ANCS entry within the pak file, or its file ID in hex. Example: Wave
+        ancs_name: String,
+
+        /// Name of the character within the ANCS resource. Omit it to export every character into
+        /// one glTF, with a scene for each after the default scene holding all of them at the
+        /// origin. Example: Wave
+        character_name: Option<String>,
+
+        /// Index of the material set. Defaults to zero.
+        material_set_index: Option<usize>,
+
+        /// How surfaces are grouped into glTF meshes and nodes.
+        #[arg(long, value_enum, default_value_t)]
+        split_by: SplitBy,
+
+        /// Composite each material's texture stages into one baked base color texture where
+        /// possible.
+        #[arg(long)]
+        bake_materials: bool,
+
+        /// Render reflective surfaces as metallic, using the reflection texture as their
+        /// metallic-roughness texture.
+        #[arg(long)]
+        approximate_reflections: bool,
+
+        /// Add a box for each animation's culling bounds, and one for their union.
+        #[arg(long)]
+        animation_bounds: bool,
+
+        /// Skin the character to its skeleton and include the animations its ANCS plays, instead
+        /// of exporting it static in its bind pose. Needs CHARACTER_NAME.
+        #[arg(long)]
+        rigged: bool,
+
+        /// Write the character's and its animation set's metadata to this JSON file instead of
+        /// exporting a model, covering every character if none is named. Example: wave_summary.json
+        #[arg(long)]
+        summary: Option<String>,
+
+        /// Move the model before export so it sits at a predictable spot relative to the origin.
+        #[arg(long, value_enum, default_value_t)]
+        recenter: Recenter,
+
+        /// Reduce each surface to this fraction of its triangles, for preview-quality exports.
+        /// Example: 0.5
+        #[arg(long, value_name = "RATIO")]
+        simplify: Option<f32>,
+
+        /// Directory to write the export into, created if needed. Defaults to the current
+        /// directory.
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+
+        /// Base name of the exported files: NAME.gltf, NAME.bin, NAME_00.png, and so on.
+        #[arg(long = "name", value_name = "NAME", default_value = DEFAULT_EXPORT_NAME)]
+        file_name: String,
+
+        /// Project file to record this export in, for later use with Reexport.
+        #[arg(long)]
+        project: Option<String>,
+    },
+    /// Checks a rigged glTF model against a character's skeleton before it's imported, reporting
+    /// joints the skeleton doesn't have, joints parented differently from their bones, and
+    /// vertices weighted to too many bones or with weights that don't sum to one.
+    CheckRig {
+        /// Disc path of the pak file, or the path of a loose pak file on the host.
+        /// Example: SamusGun.pak
         pak_path: String,
 
-        /// Name of the ANCS entry within the pak file. Example: Wave
+        /// Name of the ANCS entry within the pak file, or its file ID in hex. Example: Wave
         ancs_name: String,
 
-        /// Name of the character within the ANCS resource. Example: Wave
+        /// Name of the character whose skeleton the model is rigged to. Example: Wave
         character_name: String,
 
+        /// Path to the rigged model, as .gltf or .glb. Example: wave_edited.glb
+        gltf_path: String,
+
+        /// Most bones one vertex may be weighted to.
+        #[arg(long, default_value_t = 4)]
+        max_influences: usize,
+    },
+    /// Checks a glTF model's vertex attributes against the materials of the model it's to replace,
+    /// reporting which missing attributes get placeholders and which must be added. Primitives
+    /// are paired with surfaces in order, as ExtractCmdl writes them unless split by material.
+    CheckImport {
+        /// Disc path of the pak file, or the path of a loose pak file on the host.
+        /// Example: NoARAM.pak
+        pak_path: String,
+
+        /// Name of the CMDL entry to be replaced, or its file ID in hex. Example: CMDL_InvWaveBeam
+        name: String,
+
+        /// Path to the replacement model, as .gltf or .glb. Example: wave_beam_edited.gltf
+        gltf_path: String,
+
         /// Index of the material set. Defaults to zero.
         material_set_index: Option<usize>,
     },
+    DumpMaterials {
+        /// Disc path of the pak file, or the path of a loose pak file on the host.
+        /// Example: NoARAM.pak
+        pak_path: String,
+
+        /// Name of the CMDL entry within the pak file, or its file ID in hex.
+        /// Example: CMDL_InvWaveBeam
+        name: String,
+    },
+    ExtractTxtr {
+        /// Disc path of the pak file, or the path of a loose pak file on the host.
+        /// Example: Metroid1.pak
+        pak_path: String,
+
+        /// Name of the TXTR entry within the pak file, or its file ID in hex.
+        /// Example: TXTR_SamusFace
+        name: String,
+
+        /// Path to write the PNG to, or - for standard output. Defaults to the entry name with a
+        /// .png extension, or .ktx2 when writing a KTX2 container.
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Write every mip level rather than only the largest.
+        #[arg(long, value_enum)]
+        mips: Option<MipOutput>,
+    },
+    /// Encodes a PNG as TXTR data with mip levels, ready to replace a texture with BuildMod or a
+    /// pak editor.
+    EncodeTxtr {
+        /// Path of the PNG to encode.
+        input: String,
+
+        /// Texture format to encode in. Example: cmpr
+        #[arg(long, value_enum)]
+        format: TextureFormat,
+
+        /// Number of mip levels, including the full size one. Defaults to halving until a level
+        /// would be smaller than one of the format's blocks.
+        #[arg(long)]
+        mip_count: Option<u32>,
+
+        /// Path to write the TXTR data to, or - for standard output. Defaults to the input path
+        /// with a .txtr extension.
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Finds textures that particle systems play as flipbooks and writes each as an animated PNG
+    /// of its tiles, with texture_animations.json giving the timing and referencing particles.
+    ExtractTextureAnimations {
+        /// Directory to write the animations into.
+        output_dir: String,
+    },
+    /// Writes every scan's text, in each language, and images as a browsable copy of the logbook:
+    /// logbook.json listing the scans by logbook category, and their images as PNGs in images/.
+    /// Scans outside the logbook are included under the category "none".
+    ExportLogbook {
+        /// Directory to write the logbook into.
+        output_dir: String,
+    },
+    /// Writes a font's glyph texture as a PNG, with its metrics beside it: glyph rectangles,
+    /// spacing, and kerning.
+    ExtractFont {
+        /// Disc path of the pak file, or the path of a loose pak file on the host.
+        /// Example: NoARAM.pak
+        pak_path: String,
+
+        /// Name of the FONT entry within the pak file, or its file ID in hex.
+        /// Example: FONT_Deface14B
+        font_name: String,
+
+        /// Format of the metrics file.
+        #[arg(long, value_enum, default_value_t)]
+        metrics: FontMetrics,
+
+        /// Directory to write the files into, named for the entry. Defaults to the current
+        /// directory.
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+    /// Draws a string in one of the game's fonts, for checking glyph metrics and mocking up mods.
+    RenderText {
+        /// Disc path of the pak file, or the path of a loose pak file on the host.
+        /// Example: NoARAM.pak
+        pak_path: String,
+
+        /// Name of the FONT entry within the pak file, or its file ID in hex.
+        /// Example: FONT_Deface14B
+        font_name: String,
+
+        /// Text to draw. Line breaks start new lines. Example: "Hello, Samus"
+        text: String,
+
+        /// Path to write the PNG to, or - for standard output. Example: hello.png
+        output_path: String,
+    },
+    /// Replaces strings in a STRG resource, for translations and text mods, and writes the
+    /// repacked pak either as a loose file or into a copy of the disc image.
+    ReplaceStrings {
+        /// Disc path of the pak file, or the path of a loose pak file on the host.
+        /// Example: Metroid1.pak
+        pak_path: String,
+
+        /// Name or file ID of the STRG resource. Example: 0x2d9aa4cd
+        strg: String,
+
+        /// Path to a JSON object mapping language IDs to objects mapping string indices to
+        /// replacement text, or - for standard input. Example: {"ENGL": {"3": "Hello"}}
+        edits_path: String,
+
+        /// Path to write the repacked pak to if it ends in .pak, or else a copy of the disc image
+        /// with the pak replaced in place.
+        #[arg(required_unless_present = "in_place")]
+        output_path: Option<String>,
+
+        /// Patch the input disc image directly instead of writing a copy.
+        #[arg(long, conflicts_with = "output_path")]
+        in_place: bool,
+
+        /// Launch Dolphin on the written image. Takes an optional path to the Dolphin executable;
+        /// otherwise it is located automatically. Not for loose pak output.
+        #[arg(long, value_name = "DOLPHIN_PATH")]
+        run_dolphin: Option<Option<String>>,
+    },
+    /// Extracts assets into a directory of editable files as the start of a mod: textures as PNGs,
+    /// strings as JSON, and anything else as its decompressed data, with a manifest recording
+    /// where each came from. BuildMod turns the edited directory back into a disc image.
+    InitMod {
+        /// Directory to create the project in. Example: my_mod
+        dir: PathBuf,
+
+        /// Disc path of a pak to take assets from. May be repeated. Defaults to every pak.
+        /// Example: Metroid1.pak
+        #[arg(long = "from", value_name = "PAK_PATH")]
+        from_paks: Vec<String>,
+
+        /// Resource types to extract, separated by commas. Example: TXTR,STRG
+        #[arg(long, value_delimiter = ',', default_values = ["STRG", "TXTR"])]
+        types: Vec<String>,
+    },
+    /// Encodes the assets edited in a project made by InitMod and writes a copy of the disc image
+    /// with the changed paks patched in. Textures keep their size and format, and strings their
+    /// count, so the paks still fit.
+    BuildMod {
+        /// Directory holding the project. Example: my_mod
+        dir: PathBuf,
+
+        /// Path to write the modded disc image to. Example: my_mod.iso
+        #[arg(required_unless_present = "in_place")]
+        output_path: Option<String>,
+
+        /// Patch the input disc image directly instead of writing a copy, which is much faster
+        /// when iterating on small edits.
+        #[arg(long, conflicts_with = "output_path")]
+        in_place: bool,
+
+        /// Launch Dolphin on the written image. Takes an optional path to the Dolphin executable;
+        /// otherwise it is located automatically.
+        #[arg(long, value_name = "DOLPHIN_PATH")]
+        run_dolphin: Option<Option<String>>,
+    },
+    /// Lists every pak with its size and how many resources it holds.
+    ListPaks,
+    /// Lists every resource in a pak with its fourcc, file ID, whether it's compressed, its size as
+    /// stored and decompressed, and its name if it has one.
+    ListEntries {
+        /// Disc path of the pak file, or the path of a loose pak file on the host.
+        /// Example: Metroid1.pak
+        pak_path: String,
+    },
+    /// Lists every area of every world with its MREA ID and the path exports of it are named by.
+    ListAreas,
+    /// Searches the text of every STRG resource in every language, ignoring case, and prints each
+    /// matching string with the resource and index to pass to ReplaceStrings.
+    SearchText {
+        /// Text to search for. Example: "Chozo Artifact"
+        pattern: String,
+
+        /// Only search strings in this language. Example: ENGL
+        #[arg(long)]
+        language: Option<String>,
+    },
+    /// Parses one decompressed resource, as written by ScanMemoryDump or other tools, and prints a
+    /// summary of it.
+    Inspect {
+        /// Path to the resource, or - for standard input. Example: 0x80a41e20.cmdl
+        input_path: String,
+
+        /// Type of the resource. Defaults to its magic number, then to the file extension.
+        /// Example: CMDL
+        #[arg(long)]
+        fourcc: Option<String>,
+
+        /// List the byte ranges the parser never read, for working out formats that aren't fully
+        /// understood yet.
+        #[arg(long)]
+        trace: bool,
+    },
+    /// Parses every resource in every pak with each layout known for its type, reporting which
+    /// layout fits each type best and any resource that no layout, or more than one, fits cleanly.
+    /// Among layouts that fit equally well, one the disc's release is known to use is favored, as
+    /// it is when a resource doesn't parse as the version it states. Useful for checking a new
+    /// revision of the game, like a PAL disc.
+    DetectLayouts,
+    /// Parses every resource in every pak and dumps it to OUT_DIR/PAK/FOURCC/NAME, or as arranged
+    /// by --layout or --template, reporting any that fail to parse. Textures are written as PNGs,
+    /// models as glTF, the characters of an ANCS as glTF in a directory named for it, and anything
+    /// else as its decompressed data. Resources without a name use their file ID.
+    ExtractAll {
+        /// Resource types to dump, separated by commas. Defaults to every type. Example: CMDL,TXTR
+        #[arg(long, value_delimiter = ',')]
+        types: Vec<String>,
+
+        /// How hard to compress the dumped textures.
+        #[arg(long, value_enum, default_value_t)]
+        png_compression: PngCompression,
+
+        /// Also write each texture in this format, for galleries and browsers.
+        #[arg(long, value_enum)]
+        preview_format: Option<PreviewFormat>,
+
+        /// How to arrange the dumped files. The disc layout also copies every file on the disc,
+        /// videos, audio, and the executable included, so it doubles as a full filesystem dump.
+        #[arg(long, value_enum, default_value_t)]
+        layout: ExtractLayout,
+
+        /// Directory to dump the resources into.
+        #[arg(long, default_value = "out")]
+        out_dir: PathBuf,
+
+        /// Where to put each resource, as a path template under OUT_DIR. The fields are {pak},
+        /// {fourcc}, {name}, {id}, and {ext}, and {a|b} takes b where a has no value. Overrides
+        /// --layout. Example: "{pak}/{fourcc}/{name|id}.{ext}"
+        #[arg(long, value_parser = OutputTemplate::parse)]
+        template: Option<OutputTemplate>,
+    },
+    /// Exports a commonly wanted set of assets. Characters are written as rigged glTF, with joints,
+    /// skin, and the animations their ANCS plays, along with their metadata in
+    /// OUT_DIR/FOLDER/CHARACTER. Each resource is exported once, however many paks share it.
+    ExportPreset {
+        /// The set of characters to export.
+        #[arg(long, value_enum)]
+        preset: Preset,
+
+        /// Directory to export the characters into. Defaults to one named for the preset.
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+
+        /// Where the hud preset, which writes a file per resource, puts each, as a path template
+        /// under OUT_DIR. The fields are as for ExtractAll's --template. Other presets don't
+        /// take one.
+        #[arg(long, value_parser = OutputTemplate::parse)]
+        template: Option<OutputTemplate>,
+    },
+    /// Measures every area of every world: surfaces, triangles, materials, texture data, and
+    /// lights.
+    AreaStats {
+        /// Path to write the statistics to, as JSON. Example: area_stats.json
+        output_path: String,
+    },
+    /// Lists the resources the game preloads for every area of every world, in the order it loads
+    /// them, and compares them with the references the parsers can follow: listed textures,
+    /// skins, skeletons, and animations nothing refers to, and referenced resources that aren't
+    /// listed.
+    PreloadReport {
+        /// Path to write the report to, as JSON. Example: preload.json
+        output_path: String,
+    },
+    /// Lists the sound objects placed in every area of every world: where each plays from, its
+    /// sound ID and the sound effect the ATBL translates it to, and whether it loops.
+    SoundEmitters {
+        /// Path to write the report to, as JSON. Example: sound_emitters.json
+        output_path: String,
+    },
+    /// Reports how much of each pak is compressed and how much recompressing every resource at
+    /// the highest level would save, per pak and in total.
+    CompressionStats {
+        /// Path to also write the statistics to, by pak and resource type, as JSON.
+        /// Example: compression_stats.json
+        #[arg(long)]
+        output_path: Option<String>,
+    },
+    Catalog {
+        /// Path to write the catalog to. Example: catalog.csv
+        output_path: String,
+
+        /// Format of the written catalog.
+        #[arg(long, value_enum, default_value_t)]
+        format: CatalogFormat,
+    },
+    /// Groups textures that look the same across every pak, so a texture pack can replace one
+    /// representative per group.
+    TextureGroups {
+        /// Path to write the groups to, as JSON. Example: texture_groups.json
+        output_path: String,
+
+        /// How many of the 64 perceptual hash bits may differ between textures in a group. Zero
+        /// only groups textures that look identical at a glance. Example: 4
+        #[arg(long, default_value_t = 2)]
+        max_distance: u32,
+    },
+    ListVideos,
+    ExtractVideos {
+        /// Directory to copy the THP files into, keeping their directories on the disc.
+        output_dir: String,
+    },
+    ExtractMusic {
+        /// Directory to write WAV files into, one per DSP stream, keeping their directories on the
+        /// disc.
+        output_dir: String,
+    },
+    /// Decodes the samples of every AGSC audio group to WAV files in OUTPUT_DIR/GROUP, named by
+    /// sample ID, with samples.json in each listing their rates, notes, and loops. Sound effects
+    /// live here; music is in DSP streams, for ExtractMusic.
+    ExtractSounds {
+        /// Directory to write the audio groups into.
+        output_dir: String,
+    },
+    /// Reads the paks the game had loaded from a MEM1 dump, following its resource tables from
+    /// --root, and writes their names and resource tables to OUTPUT_DIR/paks.json. Without --root,
+    /// or if the tables can't be followed, falls back to scanning for CMDLs by their magic number
+    /// at 32-byte aligned addresses, writing each one that parses to OUTPUT_DIR, named by address.
+    ScanMemoryDump {
+        /// Path to a raw MEM1 dump saved by Dolphin. Example: mem1.raw
+        dump_path: String,
+
+        /// Directory to write the pak tables or each recovered resource into.
+        output_dir: String,
+
+        /// Address of the game's global pointer to its resource loader, from the build's symbol
+        /// map, as 0x-prefixed hex.
+        #[arg(long, value_parser = memdump::parse_address)]
+        root: Option<u32>,
+    },
+    /// Writes a disc image built from made-up data, holding one pak with a textured triangle, for
+    /// tests that can't use game data.
+    #[cfg(feature = "synthetic-disc")]
+    WriteSyntheticDisc {
+        /// Path to write the disc image to. Example: synthetic.iso
+        image_path: String,
+
+        /// Directory to also write seed inputs for the fuzz targets into.
+        #[arg(long)]
+        corpus_dir: Option<String>,
+    },
+    /// Parses and rewrites every resource that has a writer, failing if any doesn't come back
+    /// byte for byte.
+    CheckRoundTrip,
+    CompareFiles {
+        /// Name of the registered disc image to compare against. Example: pal
+        other_game: String,
+    },
+    Reexport {
+        /// Path to a project file written by the --project option of the extraction commands.
+        project_path: String,
+    },
+    CreatePatch {
+        /// Path to a modified copy of the disc image.
+        modified_image_path: String,
+
+        /// Output path. A file for BPS patches, or a directory for Riivolution layouts.
+        output_path: String,
+
+        /// Format of the emitted patch.
+        #[arg(long, value_enum, default_value_t)]
+        format: PatchFormat,
+    },
+    Rebrand {
+        /// Path to write the rebranded copy of the disc image.
+        #[arg(required_unless_present = "in_place")]
+        output_path: Option<String>,
+
+        /// Rebrand the input disc image directly instead of writing a copy.
+        #[arg(long, conflicts_with = "output_path")]
+        in_place: bool,
+
+        /// Replacement game code, so the copy sorts separately from the original. Example: GM8X
+        #[arg(long)]
+        game_code: Option<String>,
+
+        /// Replacement title for the disc header and banner.
+        #[arg(long)]
+        title: Option<String>,
+
+        /// Replacement banner description.
+        #[arg(long)]
+        description: Option<String>,
+
+        /// Path to a 96x32 PNG to use as the banner image.
+        #[arg(long)]
+        banner_image: Option<String>,
+
+        /// Launch Dolphin on the written image. Takes an optional path to the Dolphin executable;
+        /// otherwise it is located automatically.
+        #[arg(long, value_name = "DOLPHIN_PATH")]
+        run_dolphin: Option<Option<String>>,
+    },
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    let workspace = Workspace::open(args.image_path.as_deref(), &args.discs, args.no_mmap)?;
+    let image = if workspace.is_empty() && args.game.is_none() {
+        None
+    } else {
+        Some(workspace.select(args.game.as_deref())?)
+    };
 
-    let disc_file = File::open(&args.image_path)?;
-    let disc_mmap = unsafe { Mmap::map(&disc_file) }?;
-    assert_eq!(disc_mmap.len(), gamecube::disc::SIZE as usize);
+    if matches!(
+        args.command,
+        Command::ExtractAll { .. }
+            | Command::DetectLayouts
+            | Command::AreaStats { .. }
+            | Command::SoundEmitters { .. }
+            | Command::PreloadReport { .. }
+            | Command::CompressionStats { .. }
+            | Command::Catalog { .. }
+            | Command::TextureGroups { .. }
+            | Command::CheckRoundTrip
+            | Command::CompareFiles { .. }
+            | Command::CreatePatch { .. }
+    ) {
+        // These commands read most of the image.
+        for image in workspace.iter() {
+            image.advise_sequential();
+        }
+    }
+
+    let disc = image.map(|image| Disc::new(image.data())).transpose()?;
+    if let Some(disc) = &disc {
+        verify_disc(disc.header(), args.allow_any_version)?;
+    }
+    ParseOptions {
+        strict: args.strict,
+        revision: disc
+            .as_ref()
+            .and_then(|disc| Revision::from_header(disc.header())),
+    }
+    .install();
+    let paks = PakFiles::new(disc.as_ref(), args.paks.iter().map(PathBuf::from).collect());
+    // Commands that read more of the disc than its paks can't run on loose paks alone.
+    let require_disc = || {
+        image.zip(disc.as_ref()).ok_or_else(|| {
+            anyhow!("This command needs a disc image; pass an image path or --disc NAME=PATH")
+        })
+    };
 
-    let disc = Disc::new(&*disc_mmap)?;
-    verify_disc(disc.header())?;
+    let cache = args.cache_dir.map(ResourceCache::new).transpose()?;
+    let missing_texture = args.missing_texture;
+    // Loaded once, on first use, for the commands that look up resources across every pak.
+    let pak_files_cell = OnceCell::new();
+    let pak_files = || pak_files_cell.get_or_init(|| paks.load_all()).as_slice();
+    let resources_cell = OnceCell::new();
+    let resources =
+        || resources_cell.get_or_init(|| DiscResources::new(pak_files(), cache.as_ref()));
 
     match args.command {
         Command::ExtractCmdl {
             pak_path,
             name,
             material_set_index,
+            split_by,
+            bake_materials,
+            approximate_reflections,
+            recenter,
+            simplify,
+            quantize,
+            no_vertex_colors,
+            dump_display_list,
+            out_dir,
+            file_name,
+            project,
+        } => {
+            if dump_display_list {
+                print_display_lists(
+                    &paks,
+                    cache.as_ref(),
+                    &pak_path,
+                    &name,
+                    material_set_index.unwrap_or(0),
+                )?;
+            }
+            let export = Export::Cmdl {
+                pak_path,
+                name,
+                material_set_index: material_set_index.unwrap_or(0),
+                split_by,
+                bake_materials,
+                approximate_reflections,
+                recenter,
+                simplify,
+                quantize,
+                no_vertex_colors,
+                file_name,
+                out_dir: resolve_out_dir(out_dir)?,
+            };
+            run_export(&paks, cache.as_ref(), resources(), &export, missing_texture)?;
+            if let Some(project) = project {
+                record_export(Path::new(&project), export)?;
+            }
+        }
+        Command::ExtractWorld {
+            pak_path,
+            world,
+            split_by,
+            bake_materials,
+            approximate_reflections,
+            simplify,
+            quantize,
+            no_vertex_colors,
+            scene_per_area,
+            debug_geometry,
+            script_objects,
+            trigger_volumes,
+            cameras,
+            out_dir,
+            file_name,
+            project,
+        } => {
+            let export = Export::World {
+                pak_path,
+                world,
+                split_by,
+                bake_materials,
+                approximate_reflections,
+                simplify,
+                quantize,
+                no_vertex_colors,
+                scene_per_area,
+                debug_geometry,
+                script_objects,
+                trigger_volumes,
+                cameras,
+                file_name,
+                out_dir: resolve_out_dir(out_dir)?,
+            };
+            run_export(&paks, cache.as_ref(), resources(), &export, missing_texture)?;
+            if let Some(project) = project {
+                record_export(Path::new(&project), export)?;
+            }
+        }
+        Command::ExtractMrea {
+            pak_path,
+            mrea,
+            split_by,
+            bake_materials,
+            approximate_reflections,
+            recenter,
+            simplify,
+            quantize,
+            no_vertex_colors,
+            debug_geometry,
+            script_objects,
+            trigger_volumes,
+            cameras,
+            out_dir,
+            file_name,
+            project,
         } => {
-            let mut pak = PakCache::new(Pak::new(
-                disc.find_file(Path::new(&pak_path))?
-                    .expect("Couldn't find the pak file")
-                    .data(),
-            )?);
-            let cmdl_pak_entry = pak.entry(&name).expect("Couldn't find the pak entry");
-            let cmdl: Cmdl = pak
-                .data_with_fourcc(cmdl_pak_entry.file_id(), "CMDL")?
-                .unwrap()
-                .as_slice()
-                .read_typed()?;
-            let mesh = CanonicalMesh::from_cmdl(&cmdl, material_set_index.unwrap_or(0))?;
-            export_static_gltf(&mut pak, &mesh)?;
+            let export = Export::Mrea {
+                pak_path,
+                mrea,
+                split_by,
+                bake_materials,
+                approximate_reflections,
+                recenter,
+                simplify,
+                quantize,
+                no_vertex_colors,
+                debug_geometry,
+                script_objects,
+                trigger_volumes,
+                cameras,
+                file_name,
+                out_dir: resolve_out_dir(out_dir)?,
+            };
+            run_export(&paks, cache.as_ref(), resources(), &export, missing_texture)?;
+            if let Some(project) = project {
+                record_export(Path::new(&project), export)?;
+            }
         }
         Command::ExtractAncs {
             pak_path,
             ancs_name,
             character_name,
             material_set_index,
+            split_by,
+            bake_materials,
+            approximate_reflections,
+            recenter,
+            simplify,
+            animation_bounds,
+            rigged,
+            summary,
+            out_dir,
+            file_name,
+            project,
         } => {
-            let mut pak = PakCache::new(Pak::new(
-                disc.find_file(Path::new(&pak_path))?
-                    .expect("Couldn't find the pak file")
-                    .data(),
-            )?);
-            let ancs_pak_entry = pak.entry(&ancs_name).expect("Couldn't find the pak entry");
-            let ancs: Ancs = pak
-                .data_with_fourcc(ancs_pak_entry.file_id(), "ANCS")?
-                .expect("Couldn't find the pak entry")
-                .as_slice()
-                .read_typed()?;
-            for (character_index, character) in ancs.character_set.characters.iter().enumerate() {
-                if character.name != character_name {
-                    continue;
-                }
-                let mesh = CanonicalMesh::from_ancs(
-                    &mut pak,
-                    &ancs,
-                    character_index,
-                    material_set_index.unwrap_or(0),
+            if let Some(summary) = summary {
+                write_ancs_summary(
+                    &paks,
+                    cache.as_ref(),
+                    &pak_path,
+                    &ancs_name,
+                    character_name.as_deref(),
+                    Path::new(&summary),
                 )?;
-                export_static_gltf(&mut pak, &mesh)?;
+                return Ok(());
+            }
+            let export = Export::Ancs {
+                pak_path,
+                ancs_name,
+                character_name,
+                material_set_index: material_set_index.unwrap_or(0),
+                split_by,
+                bake_materials,
+                approximate_reflections,
+                recenter,
+                simplify,
+                animation_bounds,
+                root_offset: None,
+                rigged,
+                file_name,
+                out_dir: resolve_out_dir(out_dir)?,
+            };
+            run_export(&paks, cache.as_ref(), resources(), &export, missing_texture)?;
+            if let Some(project) = project {
+                record_export(Path::new(&project), export)?;
+            }
+        }
+        Command::CheckRig {
+            pak_path,
+            ancs_name,
+            character_name,
+            gltf_path,
+            max_influences,
+        } => {
+            let pak_file = paks.load(&pak_path)?;
+            let pak = Pak::new(pak_file.data())?.with_cache(cache.clone());
+            let file_id = pak.file_id(&ancs_name)?;
+            let ancs_data = pak
+                .data_with_fourcc(file_id, "ANCS")?
+                .ok_or_else(|| anyhow!("ANCS 0x{file_id:08x} not found"))?;
+            let ancs: Ancs = parse::read_resource("ANCS", &ancs_data)?;
+            let character = ancs
+                .character_set
+                .characters
+                .iter()
+                .find(|character| character.name == character_name)
+                .ok_or_else(|| {
+                    anyhow!("Couldn't find the character {character_name} in {ancs_name}")
+                })?;
+            let skeleton_data = pak
+                .data_with_fourcc(character.skeleton_id, "CINF")?
+                .ok_or_else(|| anyhow!("Skeleton 0x{:08x} not found", character.skeleton_id))?;
+            let skeleton: Cinf = parse::read_resource("CINF", &skeleton_data)?;
+
+            let problems = rig::check(Path::new(&gltf_path), &skeleton, max_influences)?;
+            for problem in &problems {
+                println!("{problem}");
+            }
+            let error_count = problems.iter().filter(|p| p.is_error()).count();
+            if error_count > 0 {
+                bail!("The rig has {error_count} problems to fix before importing");
+            }
+            println!("The rig matches the skeleton of {character_name}");
+        }
+        Command::CheckImport {
+            pak_path,
+            name,
+            gltf_path,
+            material_set_index,
+        } => {
+            let pak_file = paks.load(&pak_path)?;
+            let pak = Pak::new(pak_file.data())?.with_cache(cache);
+            let file_id = pak.file_id(&name)?;
+            let cmdl_data = pak
+                .data_with_fourcc(file_id, "CMDL")?
+                .ok_or_else(|| anyhow!("CMDL 0x{file_id:08x} not found"))?;
+            let cmdl: Cmdl = parse::read_resource("CMDL", &cmdl_data)?;
+            let material_set_index = material_set_index.unwrap_or(0);
+            if material_set_index >= cmdl.materials.len() {
+                bail!(
+                    "Material set {material_set_index} is out of range; the model has {}",
+                    cmdl.materials.len(),
+                );
+            }
+
+            let problems = import_check::check(Path::new(&gltf_path), &cmdl, material_set_index)?;
+            for problem in &problems {
+                println!("{problem}");
+            }
+            let error_count = problems.iter().filter(|p| p.is_error()).count();
+            if error_count > 0 {
+                bail!("The model has {error_count} problems to fix before importing");
+            }
+            println!("The model's vertex attributes fit {name}");
+        }
+        Command::DumpMaterials { pak_path, name } => {
+            let pak_file = paks.load(&pak_path)?;
+            let pak = Pak::new(pak_file.data())?.with_cache(cache);
+            let file_id = pak.file_id(&name)?;
+            let cmdl_data = pak
+                .data_with_fourcc(file_id, "CMDL")?
+                .ok_or_else(|| anyhow!("CMDL 0x{file_id:08x} not found"))?;
+            let cmdl = CmdlSections::new(&cmdl_data)?;
+            let material_sets = (0..cmdl.material_set_count())
+                .map(|index| cmdl.material_set(index))
+                .collect::<Result<Vec<_>>>()?;
+            let mut stdout = std::io::stdout().lock();
+            serde_json::to_writer_pretty(&mut stdout, &material_sets)?;
+            writeln!(stdout)?;
+        }
+        Command::ExtractTxtr {
+            pak_path,
+            name,
+            output,
+            mips,
+        } => {
+            let pak_file = paks.load(&pak_path)?;
+            let pak = Pak::new(pak_file.data())?.with_cache(cache);
+            let file_id = pak.file_id(&name)?;
+            let data = pak
+                .data_with_fourcc(file_id, "TXTR")?
+                .ok_or_else(|| anyhow!("TXTR 0x{file_id:08x} not found"))?;
+            match mips {
+                None => {
+                    let output = output.unwrap_or_else(|| format!("{name}.png"));
+                    let mut w = stdio::create_output(&output)?;
+                    txtr::decode(&data)?.write_png(&mut w)?;
+                    w.flush()?;
+                    if output != stdio::STDIO {
+                        println!("Wrote {output}");
+                    }
+                }
+                Some(MipOutput::Pngs) => {
+                    let output = PathBuf::from(output.unwrap_or_else(|| format!("{name}.png")));
+                    if output == Path::new(stdio::STDIO) {
+                        bail!("Mip levels are written to separate files, not standard output");
+                    }
+                    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+                    for (level, texture) in txtr::decode_mips(&data)?.iter().enumerate() {
+                        let path = output.with_file_name(format!("{stem}_mip{level}.png"));
+                        let mut w = BufWriter::new(File::create(&path)?);
+                        texture.write_png(&mut w)?;
+                        w.flush()?;
+                        println!("Wrote {}", path.display());
+                    }
+                }
+                Some(MipOutput::Ktx2) => {
+                    let output = output.unwrap_or_else(|| format!("{name}.ktx2"));
+                    let mut w = stdio::create_output(&output)?;
+                    DecodedTexture::write_ktx2(&txtr::decode_mips(&data)?, &mut w)?;
+                    w.flush()?;
+                    if output != stdio::STDIO {
+                        println!("Wrote {output}");
+                    }
+                }
+            }
+        }
+        Command::EncodeTxtr {
+            input,
+            format,
+            mip_count,
+            output,
+        } => {
+            let texture = DecodedTexture::read_png(Path::new(&input))?;
+            let mip_count = match mip_count {
+                Some(0) => bail!("A texture needs at least one mip level"),
+                Some(mip_count) => mip_count,
+                None => txtr::full_mip_count(format.id(), texture.width, texture.height)?,
+            };
+            let output = output.unwrap_or_else(|| {
+                Path::new(&input)
+                    .with_extension("txtr")
+                    .to_string_lossy()
+                    .into_owned()
+            });
+            let mut w = stdio::create_output(&output)?;
+            w.write_all(&txtr::encode(&texture, format.id(), mip_count)?)?;
+            w.flush()?;
+            if output != stdio::STDIO {
+                println!("Wrote {output} with {mip_count} mip levels");
+            }
+        }
+        Command::ExportLogbook { output_dir } => {
+            let output_dir = Path::new(&output_dir);
+            std::fs::create_dir_all(output_dir)?;
+            export_logbook(&paks, cache.as_ref(), output_dir)?;
+        }
+        Command::ExtractTextureAnimations { output_dir } => {
+            let output_dir = Path::new(&output_dir);
+            std::fs::create_dir_all(output_dir)?;
+            extract_texture_animations(&paks, cache.as_ref(), output_dir)?;
+        }
+        Command::ExtractFont {
+            pak_path,
+            font_name,
+            metrics,
+            out_dir,
+        } => {
+            let pak_file = paks.load(&pak_path)?;
+            let pak = Pak::new(pak_file.data())?.with_cache(cache);
+            let file_id = pak.file_id(&font_name)?;
+            let font_data = pak
+                .data_with_fourcc(file_id, "FONT")?
+                .ok_or_else(|| anyhow!("FONT 0x{file_id:08x} not found"))?;
+            let font: Font = parse::read_resource("FONT", &font_data)?;
+            let out_dir = resolve_out_dir(out_dir)?;
+            std::fs::create_dir_all(&out_dir)?;
+            write_font(&pak, &font, &out_dir, &font_name, metrics)?;
+            println!(
+                "Wrote {font_name}.png and {font_name}.{} to {}",
+                metrics.extension(),
+                out_dir.display(),
+            );
+        }
+        Command::RenderText {
+            pak_path,
+            font_name,
+            text,
+            output_path,
+        } => {
+            let pak_file = paks.load(&pak_path)?;
+            let pak = Pak::new(pak_file.data())?.with_cache(cache);
+            let file_id = pak.file_id(&font_name)?;
+            let font_data = pak
+                .data_with_fourcc(file_id, "FONT")?
+                .ok_or_else(|| anyhow!("FONT 0x{file_id:08x} not found"))?;
+            let font: Font = parse::read_resource("FONT", &font_data)?;
+            let texture_data = pak
+                .data_with_fourcc(font.texture_id, "TXTR")?
+                .ok_or_else(|| anyhow!("Font texture 0x{:08x} not found", font.texture_id))?;
+            let rendered = font.render(&txtr::decode(&texture_data)?, &text)?;
+            let mut w = stdio::create_output(&output_path)?;
+            rendered.write_png(&mut w)?;
+            w.flush()?;
+            if output_path != stdio::STDIO {
+                println!("Wrote {}x{} {output_path}", rendered.width, rendered.height);
+            }
+        }
+        Command::ReplaceStrings {
+            pak_path,
+            strg,
+            edits_path,
+            output_path,
+            in_place,
+            run_dolphin,
+        } => {
+            let pak_file = paks.load(&pak_path)?;
+            let pak = Pak::new(pak_file.data())?.with_cache(cache);
+            let file_id = pak.file_id(&strg)?;
+            let mut strg: Strg = parse::read_resource(
+                "STRG",
+                &pak.data_with_fourcc(file_id, "STRG")?
+                    .ok_or_else(|| anyhow!("STRG 0x{file_id:08x} not found in {pak_path}"))?,
+            )?;
+
+            let edits: BTreeMap<String, BTreeMap<usize, String>> =
+                serde_json::from_slice(&stdio::read_input(&edits_path)?)?;
+            for (language_id, strings) in edits {
+                let language = strg
+                    .language_mut(&language_id)
+                    .ok_or_else(|| anyhow!("STRG 0x{file_id:08x} has no {language_id} strings"))?;
+                for (index, text) in strings {
+                    let len = language.strings.len();
+                    *language.strings.get_mut(index).ok_or_else(|| {
+                        anyhow!("String {index} is out of range; {language_id} has {len}")
+                    })? = text;
+                }
+            }
+            let mut strg_data = Vec::new();
+            strg.write_to(&mut strg_data)?;
+            let pak_data = pak.rebuild(&HashMap::from([(file_id, strg_data)]))?;
+
+            match output_path {
+                Some(output_path) if output_path.ends_with(".pak") => {
+                    if run_dolphin.is_some() {
+                        bail!("Dolphin runs disc images, not loose paks");
+                    }
+                    std::fs::write(&output_path, &pak_data)?;
+                    println!("Wrote {output_path}");
+                }
+                output_path => {
+                    let (image, _) = require_disc()?;
+                    let mut output = OutputImage::create(image, output_path.as_deref(), in_place)?;
+                    output.inject_file(Path::new(&pak_path), &pak_data)?;
+                    let output_path = output.finish()?;
+                    println!("Wrote {}", output_path.display());
+
+                    if let Some(dolphin_path) = run_dolphin {
+                        dolphin::launch(dolphin_path.as_deref().map(Path::new), &output_path)?;
+                    }
+                }
+            }
+        }
+        Command::InitMod {
+            dir,
+            from_paks,
+            types,
+        } => {
+            let (_, disc) = require_disc()?;
+            let types: Vec<String> = types.iter().map(|t| t.to_uppercase()).collect();
+            let manifest = modding::init(
+                &paks,
+                cache.as_ref(),
+                disc.header().game_code(),
+                &from_paks,
+                &types,
+                &dir,
+            )?;
+            println!(
+                "Extracted {} assets to {}",
+                manifest.assets.len(),
+                dir.display()
+            );
+        }
+        Command::BuildMod {
+            dir,
+            output_path,
+            in_place,
+            run_dolphin,
+        } => {
+            let (image, disc) = require_disc()?;
+            let rebuilt = modding::build(&paks, cache.as_ref(), disc.header().game_code(), &dir)?;
+            if rebuilt.is_empty() {
+                println!("No assets were edited");
+                return Ok(());
+            }
+            let mut output = OutputImage::create(image, output_path.as_deref(), in_place)?;
+            for (pak_path, pak_data) in &rebuilt {
+                output.inject_file(Path::new(pak_path), pak_data)?;
+            }
+            let output_path = output.finish()?;
+            println!(
+                "Wrote {} with {} paks replaced",
+                output_path.display(),
+                rebuilt.len(),
+            );
+
+            if let Some(dolphin_path) = run_dolphin {
+                dolphin::launch(dolphin_path.as_deref().map(Path::new), &output_path)?;
+            }
+        }
+        Command::Inspect {
+            input_path,
+            fourcc,
+            trace,
+        } => {
+            let data = stdio::read_input(&input_path)?;
+            let fourcc = fourcc
+                .map(|fourcc| fourcc.to_uppercase())
+                .or_else(|| Resource::sniff(&data).map(str::to_string))
+                .or_else(|| {
+                    Path::new(&input_path)
+                        .extension()
+                        .and_then(OsStr::to_str)
+                        .map(str::to_uppercase)
+                })
+                .ok_or_else(|| anyhow!("Couldn't tell the resource type; pass --fourcc"))?;
+            let (resource, read_trace) = Trace::capture(&data, || Resource::parse(&fourcc, &data));
+            let resource = resource?.ok_or_else(|| anyhow!("No parser for {fourcc} resources"))?;
+            println!("{fourcc}: {}", resource.summary());
+            if trace {
+                print_trace(&read_trace, &data);
+            }
+        }
+        Command::DetectLayouts => detect_layouts(&paks, cache.as_ref())?,
+        Command::ExtractAll {
+            types,
+            png_compression,
+            preview_format,
+            layout,
+            out_dir,
+            template,
+        } => {
+            if layout == ExtractLayout::Disc {
+                match &disc {
+                    Some(disc) => dump_disc_files(disc, &out_dir)?,
+                    None => println!("No disc image is given, so only the loose paks are dumped"),
+                }
+            }
+            let types: Vec<String> = types.iter().map(|t| t.to_uppercase()).collect();
+            let dump = DumpOptions {
+                types: &types,
+                png_compression,
+                preview_format,
+                layout,
+                template: template.as_ref(),
+            };
+            process_all_resources(
+                pak_files(),
+                cache.as_ref(),
+                resources(),
+                &dump,
+                &out_dir,
+                missing_texture,
+            )?;
+        }
+        Command::ExportPreset {
+            preset,
+            out_dir,
+            template,
+        } => {
+            if template.is_some() && preset != Preset::Hud {
+                bail!("Only the hud preset takes --template");
+            }
+            let out_dir = out_dir.unwrap_or_else(|| PathBuf::from(preset.default_out_dir()));
+            export_preset(
+                &paks,
+                cache.as_ref(),
+                resources(),
+                preset,
+                &out_dir,
+                missing_texture,
+                template.as_ref(),
+            )?;
+        }
+        Command::CompressionStats { output_path } => {
+            let stats = compression_stats::build(&paks, cache.as_ref())?;
+            compression_stats::print(&stats);
+            if let Some(output_path) = output_path {
+                compression_stats::write(&stats, BufWriter::new(File::create(&output_path)?))?;
+            }
+        }
+        Command::AreaStats { output_path } => {
+            let stats = area_stats::build(&paks, cache.as_ref())?;
+            area_stats::write(&stats, BufWriter::new(File::create(&output_path)?))?;
+            println!("Measured {} areas", stats.len());
+        }
+        Command::PreloadReport { output_path } => {
+            let preloads = preload::build(&paks, cache.as_ref())?;
+            preload::write(&preloads, BufWriter::new(File::create(&output_path)?))?;
+            println!(
+                "Expanded {} areas: {} resources preloaded, {} unreferenced, {} unlisted",
+                preloads.len(),
+                preloads
+                    .iter()
+                    .map(|area| area.resources.len())
+                    .sum::<usize>(),
+                preloads
+                    .iter()
+                    .map(|area| area.unreferenced.len())
+                    .sum::<usize>(),
+                preloads
+                    .iter()
+                    .map(|area| area.unlisted.len())
+                    .sum::<usize>(),
+            );
+        }
+        Command::SoundEmitters { output_path } => {
+            let sounds = sound_emitters::build(&paks, cache.as_ref())?;
+            sound_emitters::write(&sounds, BufWriter::new(File::create(&output_path)?))?;
+            println!(
+                "Found {} sound emitters in {} areas",
+                sounds.iter().map(|area| area.emitters.len()).sum::<usize>(),
+                sounds.len(),
+            );
+        }
+        Command::Catalog {
+            output_path,
+            format,
+        } => {
+            let entries = catalog::build(&paks, cache.as_ref())?;
+            catalog::write(
+                &entries,
+                format,
+                BufWriter::new(File::create(&output_path)?),
+            )?;
+            println!("Catalogued {} models", entries.len());
+        }
+        Command::TextureGroups {
+            output_path,
+            max_distance,
+        } => {
+            let groups = texture_groups::build(&paks, cache.as_ref(), max_distance)?;
+            texture_groups::write(&groups, BufWriter::new(File::create(&output_path)?))?;
+            println!(
+                "Found {} groups covering {} textures",
+                groups.len(),
+                groups.iter().map(|g| g.members.len()).sum::<usize>()
+            );
+        }
+        Command::ListVideos => list_videos(require_disc()?.1)?,
+        Command::ExtractVideos { output_dir } => {
+            let (_, disc) = require_disc()?;
+            let output_dir = Path::new(&output_dir);
+            std::fs::create_dir_all(output_dir)?;
+            let mut count = 0;
+            for file in disc.iter_files() {
+                let file = file?;
+                if file.path().extension().and_then(OsStr::to_str) != Some(gamecube::thp::EXTENSION)
+                {
+                    continue;
+                }
+                let Some(output_path) = disc_output_path(output_dir, file.path()) else {
+                    continue;
+                };
+                if let Some(parent) = output_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(output_path, file.data())?;
+                count += 1;
+            }
+            println!("Extracted {count} videos to {}", output_dir.display());
+        }
+        Command::ExtractMusic { output_dir } => {
+            let (_, disc) = require_disc()?;
+            let output_dir = Path::new(&output_dir);
+            std::fs::create_dir_all(output_dir)?;
+            let mut count = 0;
+            for file in disc.iter_files() {
+                let file = file?;
+                if file.path().extension().and_then(OsStr::to_str) != Some(gamecube::dsp::EXTENSION)
+                {
+                    continue;
+                }
+                let audio = match DspAudio::new(file.data()) {
+                    Ok(audio) => audio,
+                    Err(e) => {
+                        println!("{}: {e}", file.path().display());
+                        continue;
+                    }
+                };
+                let Some(output_path) = disc_output_path(output_dir, file.path()) else {
+                    continue;
+                };
+                let output_path = output_path.with_extension("wav");
+                if let Some(parent) = output_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                wav::write(
+                    BufWriter::new(File::create(output_path)?),
+                    audio.sample_rate(),
+                    audio.samples(),
+                    audio.loop_range(),
+                )?;
+                count += 1;
+            }
+            println!("Extracted {count} streams to {}", output_dir.display());
+        }
+        #[cfg(feature = "synthetic-disc")]
+        Command::WriteSyntheticDisc {
+            image_path,
+            corpus_dir,
+        } => {
+            let mut file = File::create(&image_path)?;
+            file.write_all(&synthetic::disc()?)?;
+            // Disc images are checked for their full size, which is mostly unused here.
+            file.set_len(gamecube::disc::SIZE as u64)?;
+            if let Some(corpus_dir) = corpus_dir {
+                let corpus_dir = Path::new(&corpus_dir).join("display_list");
+                std::fs::create_dir_all(&corpus_dir)?;
+                std::fs::write(
+                    corpus_dir.join("synthetic_triangle"),
+                    synthetic::display_list_fuzz_input()?,
+                )?;
+            }
+        }
+        Command::ExtractSounds { output_dir } => {
+            extract_sounds(&paks, cache.as_ref(), Path::new(&output_dir))?;
+        }
+        Command::ScanMemoryDump {
+            dump_path,
+            output_dir,
+            root,
+        } => {
+            let dump_data = std::fs::read(&dump_path)?;
+            let dump = MemDump::new(&dump_data)?;
+            let output_dir = Path::new(&output_dir);
+            std::fs::create_dir_all(output_dir)?;
+            if let Some(root) = root {
+                match dump.walk_paks(root, &ResourceTableLayout::default()) {
+                    Ok(paks) => {
+                        for pak in &paks {
+                            println!(
+                                "{} {} {} resources",
+                                pak.address,
+                                pak.name,
+                                pak.resources.len(),
+                            );
+                        }
+                        let mut w = BufWriter::new(File::create(output_dir.join("paks.json"))?);
+                        serde_json::to_writer_pretty(&mut w, &paks)?;
+                        writeln!(w)?;
+                        w.flush()?;
+                        println!("Found {} loaded paks", paks.len());
+                        return Ok(());
+                    }
+                    Err(e) => println!("Couldn't walk the resource tables, scanning instead: {e}"),
+                }
+            }
+            let resources = dump.find_resources();
+            for resource in &resources {
+                println!(
+                    "0x{:08x} {} {} bytes",
+                    resource.address,
+                    resource.fourcc,
+                    resource.data.len(),
+                );
+                std::fs::write(
+                    output_dir.join(format!(
+                        "0x{:08x}.{}",
+                        resource.address,
+                        resource.fourcc.to_lowercase(),
+                    )),
+                    resource.data,
+                )?;
+            }
+            println!("Recovered {} resources", resources.len());
+        }
+        Command::ListPaks => {
+            for file in paks.iter() {
+                let file = file?;
+                let pak = Pak::new(file.data())?;
+                println!(
+                    "{}: {} bytes, {} resources",
+                    file.path(),
+                    file.data().len(),
+                    pak.iter_resources().count(),
+                );
+            }
+        }
+        Command::ListEntries { pak_path } => {
+            let pak_file = paks.load(&pak_path)?;
+            let pak = Pak::new(pak_file.data())?;
+            let names: HashMap<u32, String> = pak
+                .iter_names()
+                .map(|e| (e.file_id(), e.name().to_string()))
+                .collect();
+            for resource in pak.iter_resources() {
+                let info = resource.info()?;
+                println!(
+                    "{} 0x{:08x} {:<10} {:>9} {:>9} {}",
+                    resource.fourcc(),
+                    resource.file_id(),
+                    if info.compressed {
+                        "compressed"
+                    } else {
+                        "stored"
+                    },
+                    info.compressed_size,
+                    info.decompressed_size,
+                    names
+                        .get(&resource.file_id())
+                        .map(String::as_str)
+                        .unwrap_or_default(),
+                );
+            }
+        }
+        Command::ListAreas => {
+            let names = AreaNames::load(&paks, cache.as_ref())?;
+            let mut areas: Vec<_> = names.iter().collect();
+            areas.sort_by_key(|&(mrea_id, name)| (name.path(), mrea_id));
+            for (mrea_id, name) in areas {
+                println!("0x{mrea_id:08x} {}", name.path().display());
+            }
+        }
+        Command::SearchText { pattern, language } => {
+            search_text(&paks, cache.as_ref(), &pattern, language.as_deref())?
+        }
+        Command::CheckRoundTrip => check_round_trip(&paks, cache.as_ref())?,
+        Command::CompareFiles { other_game } => {
+            let (_, disc) = require_disc()?;
+            let other_disc = Disc::new(workspace.get(&other_game)?.data())?;
+            verify_disc(other_disc.header(), args.allow_any_version)?;
+            compare_disc_files(disc, &other_disc)?;
+        }
+        Command::Reexport { project_path } => {
+            let project = Project::load(Path::new(&project_path))?;
+            if project.tool_version != env!("CARGO_PKG_VERSION") {
+                println!(
+                    "Project was written by version {}; re-exporting with version {}",
+                    project.tool_version,
+                    env!("CARGO_PKG_VERSION"),
+                );
+            }
+            for export in &project.exports {
+                run_export(&paks, cache.as_ref(), resources(), export, missing_texture)?;
+            }
+            println!("Re-exported {} assets", project.exports.len());
+        }
+        Command::CreatePatch {
+            modified_image_path,
+            output_path,
+            format,
+        } => {
+            let (image, disc) = require_disc()?;
+            let modified_file = File::open(&modified_image_path)?;
+            let modified_mmap = unsafe { Mmap::map(&modified_file) }?;
+
+            match format {
+                PatchFormat::Bps => {
+                    let file = BufWriter::new(File::create(&output_path)?);
+                    patch::write_bps(image.data(), &modified_mmap, file)?;
+                }
+                PatchFormat::Riivolution => {
+                    let modified_disc = Disc::new(&modified_mmap)?;
+                    let count =
+                        patch::write_riivolution(disc, &modified_disc, Path::new(&output_path))?;
+                    println!("Wrote {count} replacement files to {output_path}");
+                }
+            }
+        }
+        Command::Rebrand {
+            output_path,
+            in_place,
+            game_code,
+            title,
+            description,
+            banner_image,
+            run_dolphin,
+        } => {
+            let (image, disc) = require_disc()?;
+            let mut header = disc.header().clone();
+            if let Some(game_code) = &game_code {
+                header.set_game_code(game_code)?;
+            }
+            if let Some(title) = &title {
+                header.set_game_name(title)?;
+            }
+
+            let banner_file = disc
+                .find_file(Path::new(gamecube::banner::PATH))?
+                .ok_or_else(|| anyhow!("Couldn't find the banner file"))?;
+            let mut banner = Banner::new(banner_file.data())?;
+            for metadata in banner.metadata_mut() {
+                if let Some(title) = &title {
+                    metadata.short_title = title.clone();
+                    metadata.long_title = title.clone();
+                }
+                if let Some(description) = &description {
+                    metadata.description = description.clone();
+                }
+            }
+            if let Some(banner_image) = &banner_image {
+                banner.set_image_rgba(&DecodedTexture::read_png(Path::new(banner_image))?.rgba)?;
+            }
+
+            let mut output = OutputImage::create(image, output_path.as_deref(), in_place)?;
+            header.write_to(output.range_mut(0..Header::SIZE as usize))?;
+            let banner_offset = banner_file.offset() as usize;
+            banner.write_to(
+                output.range_mut(banner_offset..banner_offset + banner_file.data().len()),
+            )?;
+            let output_path = output.finish()?;
+
+            if let Some(dolphin_path) = run_dolphin {
+                dolphin::launch(dolphin_path.as_deref().map(Path::new), &output_path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the disassembled display list of every surface of a model, decoded with the vertex
+/// attributes of its material in the given material set.
+fn print_display_lists(
+    paks: &PakFiles,
+    cache: Option<&ResourceCache>,
+    pak_path: &str,
+    name: &str,
+    material_set_index: usize,
+) -> Result<()> {
+    let pak_file = paks.load(pak_path)?;
+    let pak = Pak::new(pak_file.data())?.with_cache(cache.cloned());
+    let file_id = pak.file_id(name)?;
+    let cmdl_data = pak
+        .data_with_fourcc(file_id, "CMDL")?
+        .ok_or_else(|| anyhow!("CMDL 0x{file_id:08x} not found"))?;
+    let cmdl: Cmdl = parse::read_resource("CMDL", &cmdl_data)?;
+    let material_set = cmdl
+        .materials
+        .get(material_set_index)
+        .ok_or_else(|| anyhow!("No material set {material_set_index}"))?;
+    for (i, surface) in cmdl.surfaces.iter().enumerate() {
+        let material = material_set
+            .materials
+            .get(surface.material_index as usize)
+            .ok_or_else(|| anyhow!("Surface {i} has no material {}", surface.material_index))?;
+        println!("Surface {i}, material {}:", surface.material_index);
+        print!(
+            "{}",
+            surface.display_list.disassemble(&material.vertex_inputs())
+        );
+    }
+    Ok(())
+}
+
+/// Performs one export, either freshly requested on the command line or replayed from a project.
+fn run_export(
+    paks: &PakFiles,
+    cache: Option<&ResourceCache>,
+    resources: &DiscResources,
+    export: &Export,
+    missing_texture: MissingTexture,
+) -> Result<()> {
+    std::fs::create_dir_all(export.out_dir())?;
+    match export {
+        Export::Cmdl {
+            pak_path,
+            name,
+            material_set_index,
+            split_by,
+            bake_materials,
+            approximate_reflections,
+            recenter,
+            simplify,
+            quantize,
+            no_vertex_colors,
+            file_name,
+            out_dir,
+        } => {
+            let files = ExportFiles {
+                dir: out_dir,
+                name: file_name,
+            };
+            let options = ExportOptions {
+                split_by: *split_by,
+                bake_materials: *bake_materials,
+                approximate_reflections: *approximate_reflections,
+                animation_bounds: false,
+                quantize: *quantize,
+                vertex_colors: !*no_vertex_colors,
+                scene_per_part: false,
+                root_offset: None,
+                script_objects: None,
+                trigger_volumes: false,
+                cameras: false,
+                missing_texture,
+            };
+            let pak_file = paks.load(pak_path)?;
+            let mut pak = PakCache::new(Pak::new(pak_file.data())?.with_cache(cache.cloned()))
+                .with_resources(resources);
+            let file_id = pak.file_id(name)?;
+            let cmdl_data = pak
+                .data_with_fourcc(file_id, "CMDL")?
+                .ok_or_else(|| anyhow!("CMDL 0x{file_id:08x} not found"))?;
+            let Parsed {
+                value: mut mesh,
+                warnings,
+            } = Parsed::capture(|| {
+                let cmdl: Cmdl = parse::read_resource("CMDL", &cmdl_data)?;
+                CanonicalMesh::from_cmdl(&cmdl, *material_set_index)
+            })?;
+            recenter.apply(&mut mesh);
+            if let Some(ratio) = simplify {
+                mesh.simplify(*ratio)?;
+            }
+            export_static_gltf(&mut pak, &mesh, options, warnings, &files)?;
+        }
+        Export::World {
+            pak_path,
+            world,
+            split_by,
+            bake_materials,
+            approximate_reflections,
+            simplify,
+            quantize,
+            no_vertex_colors,
+            scene_per_area,
+            debug_geometry,
+            script_objects,
+            trigger_volumes,
+            cameras,
+            file_name,
+            out_dir,
+        } => {
+            let files = ExportFiles {
+                dir: out_dir,
+                name: file_name,
+            };
+            let options = ExportOptions {
+                split_by: *split_by,
+                bake_materials: *bake_materials,
+                approximate_reflections: *approximate_reflections,
+                animation_bounds: false,
+                quantize: *quantize,
+                vertex_colors: !*no_vertex_colors,
+                scene_per_part: *scene_per_area,
+                root_offset: None,
+                script_objects: *script_objects,
+                trigger_volumes: *trigger_volumes,
+                cameras: *cameras,
+                missing_texture,
+            };
+            let names = AreaNames::load(paks, cache)?;
+            let pak_file = paks.load(pak_path)?;
+            let pak = Pak::new(pak_file.data())?.with_cache(cache.cloned());
+            let mlvl_id = match world {
+                Some(world) => pak.file_id(world)?,
+                None => {
+                    let ids: Vec<u32> = pak
+                        .iter_resources()
+                        .filter(|entry| entry.fourcc() == "MLVL")
+                        .map(|entry| entry.file_id())
+                        .collect();
+                    match ids[..] {
+                        [id] => id,
+                        _ => bail!("{pak_path} has {} MLVLs; pick one", ids.len()),
+                    }
+                }
+            };
+            let mlvl: Mlvl = parse::read_resource(
+                "MLVL",
+                &pak.data_with_fourcc(mlvl_id, "MLVL")?
+                    .ok_or_else(|| anyhow!("MLVL 0x{mlvl_id:08x} not found in {pak_path}"))?,
+            )?;
+
+            // Areas that fail to parse are reported and left out of the scene.
+            let mut parts = Vec::new();
+            let mut stats = Vec::new();
+            let mut warnings = Vec::new();
+            for (area_index, area) in mlvl.areas.iter().enumerate() {
+                let result = Parsed::capture(|| {
+                    let data = pak
+                        .data_with_fourcc(area.mrea_id, "MREA")?
+                        .ok_or_else(|| anyhow!("MREA not found"))?;
+                    let sections = MreaSections::new(&data)?;
+                    let mut mesh = CanonicalMesh::from_mrea(&sections)?;
+                    if *debug_geometry {
+                        mesh.octree_cells = octree_cells(&sections)?;
+                    }
+                    if script_objects.is_some() || *trigger_volumes || *cameras {
+                        let world = WorldContext {
+                            mlvl: &mlvl,
+                            area_index,
+                            names: &names,
+                        };
+                        mesh.script_markers = ScriptMarker::from_mrea(&sections, Some(&world))?;
+                    }
+                    if let Some(ratio) = simplify {
+                        mesh.simplify(*ratio)?;
+                    }
+                    let area_stats = AreaStats::new(pak_path, &pak, area.mrea_id, &names)?;
+                    anyhow::Ok((mesh, area_stats))
+                });
+                match result {
+                    Ok(Parsed {
+                        value: (mesh, area_stats),
+                        warnings: area_warnings,
+                    }) => {
+                        let name = names.get(area.mrea_id).map_or_else(
+                            || format!("0x{:08x}", area.mrea_id),
+                            |name| name.area.clone(),
+                        );
+                        parts.push((name, mesh, transform_from_rows(&area.transform)));
+                        stats.push(area_stats);
+                        warnings.extend(area_warnings);
+                    }
+                    Err(e) => println!(
+                        "Error in {pak_path} MREA {}: {e}",
+                        names.describe(area.mrea_id)
+                    ),
+                }
+            }
+            let mesh = CanonicalMesh::merge_parts(parts)?;
+            let mut pak = PakCache::new(pak).with_resources(resources);
+            export_static_gltf(&mut pak, &mesh, options, warnings, &files)?;
+            area_stats::write(
+                &stats,
+                BufWriter::new(File::create(files.path("_area_stats.json"))?),
+            )?;
+        }
+        Export::Mrea {
+            pak_path,
+            mrea,
+            split_by,
+            bake_materials,
+            approximate_reflections,
+            recenter,
+            simplify,
+            quantize,
+            no_vertex_colors,
+            debug_geometry,
+            script_objects,
+            trigger_volumes,
+            cameras,
+            file_name,
+            out_dir,
+        } => {
+            let files = ExportFiles {
+                dir: out_dir,
+                name: file_name,
+            };
+            let options = ExportOptions {
+                split_by: *split_by,
+                bake_materials: *bake_materials,
+                approximate_reflections: *approximate_reflections,
+                animation_bounds: false,
+                quantize: *quantize,
+                vertex_colors: !*no_vertex_colors,
+                scene_per_part: false,
+                root_offset: None,
+                script_objects: *script_objects,
+                trigger_volumes: *trigger_volumes,
+                cameras: *cameras,
+                missing_texture,
+            };
+            let pak_file = paks.load(pak_path)?;
+            let mut pak = PakCache::new(Pak::new(pak_file.data())?.with_cache(cache.cloned()))
+                .with_resources(resources);
+            let file_id = pak.file_id(mrea)?;
+            let mrea_data = pak
+                .data_with_fourcc(file_id, "MREA")?
+                .ok_or_else(|| anyhow!("MREA 0x{file_id:08x} not found in {pak_path}"))?;
+            let sections = MreaSections::new(&mrea_data)?;
+            // Doors and elevators are resolved through the world holding the area, if any does.
+            let world = match script_objects {
+                Some(_) => find_area_world(resources, file_id)?,
+                None => None,
+            };
+            let names = match world {
+                Some(_) => AreaNames::load(paks, cache)?,
+                None => AreaNames::default(),
+            };
+            let Parsed {
+                value: mut mesh,
+                warnings,
+            } = Parsed::capture(|| {
+                let mut mesh = CanonicalMesh::from_mrea(&sections)?;
+                if *debug_geometry {
+                    mesh.octree_cells = octree_cells(&sections)?;
+                }
+                if script_objects.is_some() || *trigger_volumes || *cameras {
+                    let world = world.as_ref().map(|(mlvl, area_index)| WorldContext {
+                        mlvl,
+                        area_index: *area_index,
+                        names: &names,
+                    });
+                    mesh.script_markers = ScriptMarker::from_mrea(&sections, world.as_ref())?;
+                }
+                Ok(mesh)
+            })?;
+            recenter.apply(&mut mesh);
+            if let Some(ratio) = simplify {
+                mesh.simplify(*ratio)?;
+            }
+            export_static_gltf(&mut pak, &mesh, options, warnings, &files)?;
+        }
+        Export::Ancs {
+            pak_path,
+            ancs_name,
+            character_name,
+            material_set_index,
+            split_by,
+            bake_materials,
+            approximate_reflections,
+            recenter,
+            simplify,
+            animation_bounds,
+            root_offset,
+            rigged,
+            file_name,
+            out_dir,
+        } => {
+            if *rigged && character_name.is_none() {
+                bail!("A rigged export needs a character name");
+            }
+            let files = ExportFiles {
+                dir: out_dir,
+                name: file_name,
+            };
+            let options = ExportOptions {
+                split_by: *split_by,
+                bake_materials: *bake_materials,
+                approximate_reflections: *approximate_reflections,
+                animation_bounds: *animation_bounds,
+                quantize: false,
+                vertex_colors: false,
+                scene_per_part: character_name.is_none(),
+                root_offset: *root_offset,
+                script_objects: None,
+                trigger_volumes: false,
+                cameras: false,
+                missing_texture,
+            };
+            let pak_file = paks.load(pak_path)?;
+            let mut pak = PakCache::new(Pak::new(pak_file.data())?.with_cache(cache.cloned()))
+                .with_resources(resources);
+            let file_id = pak.file_id(ancs_name)?;
+            let ancs_data = pak
+                .data_with_fourcc(file_id, "ANCS")?
+                .ok_or_else(|| anyhow!("ANCS 0x{file_id:08x} not found"))?;
+            let Parsed {
+                value: ancs,
+                warnings: ancs_warnings,
+            } = Parsed::capture(|| parse::read_resource::<Ancs>("ANCS", &ancs_data))?;
+            // Without a character name, every character becomes a part with its own scene. Parts
+            // are merged as static meshes, so the skins are dropped.
+            let mut parts = Vec::new();
+            let mut warnings = ancs_warnings;
+            for (character_index, character) in ancs.character_set.characters.iter().enumerate() {
+                if character_name
+                    .as_ref()
+                    .is_some_and(|name| &character.name != name)
+                {
+                    continue;
+                }
+                let Parsed {
+                    value: mut mesh,
+                    warnings: mesh_warnings,
+                } = Parsed::capture(|| {
+                    CanonicalMesh::from_ancs(&mut pak, &ancs, character_index, *material_set_index)
+                })?;
+                recenter.apply(&mut mesh);
+                if let Some(ratio) = simplify {
+                    mesh.simplify(*ratio)?;
+                }
+                warnings.extend(mesh_warnings);
+                if character_name.is_some() {
+                    match &mut mesh.skin {
+                        Some(skin) if *rigged => {
+                            skin.animations = load_animations(&mut pak, pak_path, &ancs);
+                            export_skinned_gltf(&mut pak, &mesh, options, warnings, &files)?;
+                        }
+                        _ => export_static_gltf(&mut pak, &mesh, options, warnings, &files)?,
+                    }
+                    return Ok(());
+                }
+                mesh.skin = None;
+                parts.push((character.name.clone(), mesh, Matrix4::identity()));
+            }
+            match character_name {
+                Some(name) => bail!("Couldn't find the character {name} in {ancs_name}"),
+                None => {
+                    let mesh = CanonicalMesh::merge_parts(parts)?;
+                    export_static_gltf(&mut pak, &mesh, options, warnings, &files)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses every animation an ANCS plays, named by its primitive. Animations that are missing or
+/// fail to parse are reported and left out.
+fn load_animations(pak: &mut PakCache, pak_path: &str, ancs: &Ancs) -> Vec<(String, Anim)> {
+    let mut animations = Vec::new();
+    for (name, animation_id) in presets::animation_ids(ancs) {
+        let result = pak.data_with_fourcc(animation_id, "ANIM").and_then(|data| {
+            let data = data.ok_or_else(|| anyhow!("ANIM not found"))?;
+            parse::read_resource::<Anim>("ANIM", &data)
+        });
+        match result {
+            Ok(anim) => animations.push((name, anim)),
+            Err(e) => println!("Error in {pak_path} ANIM 0x{animation_id:08x}: {e}"),
+        }
+    }
+    animations
+}
+
+/// Writes an [`AncsSummary`] of one character as JSON, or of every character if no name is given.
+fn write_ancs_summary(
+    paks: &PakFiles,
+    cache: Option<&ResourceCache>,
+    pak_path: &str,
+    ancs_name: &str,
+    character_name: Option<&str>,
+    output_path: &Path,
+) -> Result<()> {
+    let pak_file = paks.load(pak_path)?;
+    let pak = Pak::new(pak_file.data())?.with_cache(cache.cloned());
+    let file_id = pak.file_id(ancs_name)?;
+    let ancs_data = pak
+        .data_with_fourcc(file_id, "ANCS")?
+        .ok_or_else(|| anyhow!("ANCS 0x{file_id:08x} not found"))?;
+    let ancs: Ancs = parse::read_resource("ANCS", &ancs_data)?;
+    let characters: Vec<&Character> = match character_name {
+        Some(name) => vec![ancs
+            .character_set
+            .characters
+            .iter()
+            .find(|character| character.name == name)
+            .ok_or_else(|| anyhow!("Couldn't find the character {name} in {ancs_name}"))?],
+        None => ancs.character_set.characters.iter().collect(),
+    };
+
+    let mut file = BufWriter::new(File::create(output_path)?);
+    serde_json::to_writer_pretty(&mut file, &AncsSummary::new(&ancs, characters))?;
+    writeln!(file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Exports each character of a preset as rigged glTF, skinned to its skeleton with the animations
+/// of its ANCS. An ANCS shared by several paks is exported once, from the first pak that has it.
+fn export_preset(
+    paks: &PakFiles,
+    cache: Option<&ResourceCache>,
+    resources: &DiscResources,
+    preset: Preset,
+    out_dir: &Path,
+    missing_texture: MissingTexture,
+    template: Option<&OutputTemplate>,
+) -> Result<()> {
+    if preset == Preset::Hud {
+        return export_hud(paks, cache, out_dir, template);
+    }
+    let root_offset = if preset == Preset::Gun {
+        let tweaks = PlayerGunTweaks::find(paks, cache)?;
+        if tweaks.is_none() {
+            println!(
+                "Couldn't find the {} tweaks, so the gun is exported at its origin",
+                PlayerGunTweaks::NAME,
+            );
+        }
+        tweaks.map(|tweaks| tweaks.gun_position)
+    } else {
+        None
+    };
+    let mut file_names = OutputNames::new(out_dir);
+    let mut seen = HashSet::new();
+    let mut found = BTreeSet::new();
+    for file in paks.iter() {
+        let file = file?;
+        if !preset.includes_pak(file.file_name()) {
+            continue;
+        }
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        let names: HashMap<u32, String> = pak
+            .iter_names()
+            .filter(|entry| entry.fourcc() == "ANCS")
+            .map(|entry| (entry.file_id(), entry.name().to_string()))
+            .collect();
+        for entry in pak
+            .iter_resources()
+            .filter(|entry| entry.fourcc() == "ANCS")
+        {
+            let file_id = entry.file_id();
+            if !seen.insert(file_id) {
+                continue;
+            }
+            // Most creature ANCS have no name, so they go by their character's name instead.
+            let ancs_name = names
+                .get(&file_id)
+                .cloned()
+                .unwrap_or_else(|| format!("0x{file_id:08x}"));
+            let result = (|| {
+                let data = pak.data(file_id)?.unwrap();
+                let ancs: Ancs = parse::read_resource("ANCS", &data)?;
+                for character in &ancs.character_set.characters {
+                    let label = match names.get(&file_id) {
+                        Some(name) => name,
+                        None if !character.name.is_empty() => &character.name,
+                        None => &ancs_name,
+                    };
+                    let Some(folder) = preset.folder(label, character) else {
+                        continue;
+                    };
+                    let folder_dir = file_names.join(out_dir, &folder);
+                    let character_dir = file_names.join(&folder_dir, &character.name);
+                    let file_name = file_names.name(&character_dir, &character.name);
+                    run_export(
+                        paks,
+                        cache,
+                        resources,
+                        &Export::Ancs {
+                            pak_path: file.path().to_string(),
+                            ancs_name: ancs_name.clone(),
+                            character_name: Some(character.name.clone()),
+                            material_set_index: 0,
+                            split_by: SplitBy::default(),
+                            bake_materials: false,
+                            approximate_reflections: false,
+                            recenter: Recenter::default(),
+                            simplify: None,
+                            animation_bounds: false,
+                            root_offset,
+                            rigged: true,
+                            file_name,
+                            out_dir: character_dir.clone(),
+                        },
+                        missing_texture,
+                    )?;
+                    write_ancs_summary(
+                        paks,
+                        cache,
+                        file.path(),
+                        &ancs_name,
+                        Some(&character.name),
+                        &character_dir.join("summary.json"),
+                    )?;
+                    println!("Exported {folder}/{}", character.name);
+                    found.insert(folder);
+                }
+                Ok::<_, anyhow::Error>(())
+            })();
+            if let Err(e) = result {
+                println!("Error in {} ANCS 0x{file_id:08x}: {e}", file.path());
+            }
+        }
+    }
+
+    file_names.write_mapping()?;
+    if preset == Preset::Suits {
+        for (suit, _) in presets::SUITS {
+            if !found.contains(*suit) {
+                println!("No characters of the {suit} suit were found");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Exports the FRME, FONT, and TXTR resources of the GUI paks into a directory for each type, with
+/// each frame's widget tree as JSON. A resource shared by several paks is exported once.
+fn export_hud(
+    paks: &PakFiles,
+    cache: Option<&ResourceCache>,
+    out_dir: &Path,
+    template: Option<&OutputTemplate>,
+) -> Result<()> {
+    let frames_dir = out_dir.join("frames");
+    let fonts_dir = out_dir.join("fonts");
+    let textures_dir = out_dir.join("textures");
+    let write_png = |path: PathBuf, data: &[u8]| -> Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        txtr::decode(data)?.write_png(&mut w)?;
+        w.flush()?;
+        Ok(())
+    };
+
+    let mut file_names = OutputNames::new(out_dir);
+    let mut seen = HashSet::new();
+    let mut font_textures = HashSet::new();
+    let mut textures = Vec::new();
+    let mut counts = [0; 3];
+    for file in paks.iter() {
+        let file = file?;
+        if !Preset::Hud.includes_pak(file.file_name()) {
+            continue;
+        }
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        let pak_stem = pak_stem(&file);
+        for entry in pak.iter_resources() {
+            let fourcc = entry.fourcc();
+            if !["FRME", "FONT", "TXTR"].contains(&fourcc) || !seen.insert(entry.file_id()) {
+                continue;
+            }
+            let name = pak
+                .iter_names()
+                .find(|e| e.file_id() == entry.file_id())
+                .map(|e| e.name().to_string());
+            let values = |ext| TemplateValues {
+                pak: &pak_stem,
+                fourcc,
+                name: name.as_deref(),
+                file_id: entry.file_id(),
+                ext,
+            };
+            let result = (|| {
+                let data = pak.data(entry.file_id())?.unwrap();
+                match fourcc {
+                    "FRME" => {
+                        let frme: Frme = parse::read_resource("FRME", &data)?;
+                        let (dir, name) =
+                            file_names.resource(template, &frames_dir, &values("json"))?;
+                        let mut w = BufWriter::new(File::create(dir.join(format!("{name}.json")))?);
+                        serde_json::to_writer_pretty(&mut w, &frme.tree())?;
+                        writeln!(w)?;
+                        w.flush()?;
+                        counts[0] += 1;
+                    }
+                    "FONT" => {
+                        let font: Font = parse::read_resource("FONT", &data)?;
+                        let (dir, name) = file_names.resource(
+                            template,
+                            &fonts_dir,
+                            &values(FontMetrics::Json.extension()),
+                        )?;
+                        write_font(&pak, &font, &dir, &name, FontMetrics::Json)?;
+                        font_textures.insert(font.texture_id);
+                        counts[1] += 1;
+                    }
+                    // Textures wait until every font is known, so glyph textures aren't repeated.
+                    _ => textures.push((entry.file_id(), pak_stem.clone(), name.clone(), data)),
+                }
+                Ok::<_, anyhow::Error>(())
+            })();
+            if let Err(e) = result {
+                println!(
+                    "Error in {} {fourcc} 0x{:08x}: {e}",
+                    file.path(),
+                    entry.file_id()
+                );
+            }
+        }
+    }
+    for (file_id, pak_stem, name, data) in textures {
+        if font_textures.contains(&file_id) {
+            continue;
+        }
+        let values = TemplateValues {
+            pak: &pak_stem,
+            fourcc: "TXTR",
+            name: name.as_deref(),
+            file_id,
+            ext: "png",
+        };
+        let result = file_names
+            .resource(template, &textures_dir, &values)
+            .and_then(|(dir, name)| write_png(dir.join(format!("{name}.png")), &data));
+        match result {
+            Ok(()) => counts[2] += 1,
+            Err(e) => println!("Error in TXTR 0x{file_id:08x}: {e}"),
+        }
+    }
+    println!(
+        "Exported {} frames, {} fonts, and {} textures",
+        counts[0], counts[1], counts[2],
+    );
+    file_names.write_mapping()
+}
+
+/// Writes a font's glyph texture to DIR/NAME.png and its metrics beside it.
+fn write_font(pak: &Pak, font: &Font, dir: &Path, name: &str, metrics: FontMetrics) -> Result<()> {
+    let texture_data = pak
+        .data_with_fourcc(font.texture_id, "TXTR")?
+        .ok_or_else(|| anyhow!("Font texture 0x{:08x} not found", font.texture_id))?;
+    let texture = txtr::decode(&texture_data)?;
+    let texture_file = format!("{name}.png");
+    let mut w = BufWriter::new(File::create(dir.join(&texture_file))?);
+    texture.write_png(&mut w)?;
+    w.flush()?;
+
+    let mut w = BufWriter::new(File::create(
+        dir.join(format!("{name}.{}", metrics.extension())),
+    )?);
+    match metrics {
+        FontMetrics::Json => {
+            serde_json::to_writer_pretty(&mut w, font)?;
+            writeln!(w)?;
+        }
+        FontMetrics::Bmfont => {
+            font.write_bmfont(&mut w, texture.width, texture.height, &texture_file)?
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes every sample of every AGSC as a WAV file. A group shared by several paks is written once.
+fn extract_sounds(paks: &PakFiles, cache: Option<&ResourceCache>, output_dir: &Path) -> Result<()> {
+    let mut file_names = OutputNames::new(output_dir);
+    let mut seen = HashSet::new();
+    let (mut group_count, mut sample_count) = (0, 0);
+    for file in paks.iter() {
+        let file = file?;
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        for entry in pak.iter_resources().filter(|e| e.fourcc() == "AGSC") {
+            if !seen.insert(entry.file_id()) {
+                continue;
+            }
+            let result = (|| {
+                let agsc: Agsc = parse::read_resource("AGSC", &entry.data()?)?;
+                let group_dir = file_names.join(output_dir, &agsc.name);
+                std::fs::create_dir_all(&group_dir)?;
+                for sample in &agsc.samples {
+                    let samples = match sample.decode(&agsc.sample_data) {
+                        Ok(samples) => samples,
+                        Err(e) => {
+                            println!("Error in {} AGSC {}: {e}", file.path(), agsc.name);
+                            continue;
+                        }
+                    };
+                    let path = group_dir.join(format!("0x{:04x}.wav", sample.id));
+                    wav::write(
+                        BufWriter::new(File::create(path)?),
+                        sample.sample_rate as u32,
+                        &samples,
+                        sample.wav_loop_range(),
+                    )?;
+                    sample_count += 1;
+                }
+                let mut w = BufWriter::new(File::create(group_dir.join("samples.json"))?);
+                serde_json::to_writer_pretty(&mut w, &agsc.samples)?;
+                writeln!(w)?;
+                w.flush()?;
+                Ok::<_, anyhow::Error>(())
+            })();
+            match result {
+                Ok(()) => group_count += 1,
+                Err(e) => println!(
+                    "Error in {} AGSC 0x{:08x}: {e}",
+                    file.path(),
+                    entry.file_id()
+                ),
+            }
+        }
+    }
+    file_names.write_mapping()?;
+    println!("Extracted {sample_count} samples from {group_count} audio groups");
+    Ok(())
+}
+
+/// Prints every THP video on the disc with its resolution, length, and audio format.
+fn list_videos(disc: &Disc) -> Result<()> {
+    for file in disc.iter_files() {
+        let file = file?;
+        if file.path().extension().and_then(OsStr::to_str) != Some(gamecube::thp::EXTENSION) {
+            continue;
+        }
+        let header = match ThpHeader::new(file.data()) {
+            Ok(header) => header,
+            Err(e) => {
+                println!("{}: {e}", file.path().display());
+                continue;
+            }
+        };
+        let video = match header.video() {
+            Some(video) => format!("{}x{}", video.width, video.height),
+            None => "no video".to_string(),
+        };
+        let audio = match header.audio() {
+            Some(audio) => format!("{} ch {} Hz", audio.channel_count, audio.sample_rate),
+            None => "no audio".to_string(),
+        };
+        println!(
+            "{}: {video}, {} frames at {} fps, {audio}, {} bytes",
+            file.path().display(),
+            header.frame_count(),
+            header.frames_per_second(),
+            file.data().len(),
+        );
+    }
+    Ok(())
+}
+
+/// One animated texture as listed in texture_animations.json.
+#[derive(Serialize)]
+struct TextureAnimationEntry {
+    pak: String,
+    #[serde(flatten)]
+    animation: AnimatedTexture,
+    frame_count: usize,
+    frame_duration_ms: u32,
+    part_ids: Vec<String>,
+}
+
+/// Writes every animated texture referenced by a PART in each pak as an APNG.
+fn extract_texture_animations(
+    paks: &PakFiles,
+    cache: Option<&ResourceCache>,
+    output_dir: &Path,
+) -> Result<()> {
+    let mut entries = Vec::new();
+    for file in paks.iter() {
+        let file = file?;
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+
+        let mut part_ids_by_animation = BTreeMap::<AnimatedTexture, Vec<String>>::new();
+        for entry in pak.iter_resources().filter(|e| e.fourcc() == "PART") {
+            match entry.data() {
+                Ok(data) => {
+                    for animation in part::find_animated_textures(&data) {
+                        part_ids_by_animation
+                            .entry(animation)
+                            .or_default()
+                            .push(format!("0x{:08x}", entry.file_id()));
+                    }
+                }
+                Err(e) => println!(
+                    "Error in {} PART 0x{:08x}: {e}",
+                    file.path(),
+                    entry.file_id(),
+                ),
+            }
+        }
+
+        for (animation, part_ids) in part_ids_by_animation {
+            let result = (|| {
+                let data = pak
+                    .data_with_fourcc(animation.texture_id, "TXTR")?
+                    .ok_or_else(|| anyhow!("Texture not found"))?;
+                let frames = animation.frames(&txtr::decode(&data)?)?;
+                let frame_duration_ms = animation.frame_duration_ms(frames.len());
+                let path = output_dir.join(format!(
+                    "{} 0x{:08x} {}x{}.png",
+                    file.file_name(),
+                    animation.texture_id,
+                    animation.tile_width,
+                    animation.tile_height,
+                ));
+                DecodedTexture::write_apng(
+                    &frames,
+                    (frame_duration_ms.min(u16::MAX as u32) as u16, 1000),
+                    BufWriter::new(File::create(path)?),
+                )?;
+                Ok::<_, anyhow::Error>((frames.len(), frame_duration_ms))
+            })();
+            match result {
+                Ok((frame_count, frame_duration_ms)) => entries.push(TextureAnimationEntry {
+                    pak: file.path().to_string(),
+                    animation,
+                    frame_count,
+                    frame_duration_ms,
+                    part_ids,
+                }),
+                Err(e) => println!(
+                    "Error in {} TXTR 0x{:08x}: {e}",
+                    file.path(),
+                    animation.texture_id,
+                ),
+            }
+        }
+    }
+
+    let mut w = BufWriter::new(File::create(output_dir.join("texture_animations.json"))?);
+    serde_json::to_writer_pretty(&mut w, &entries)?;
+    writeln!(w)?;
+    w.flush()?;
+    println!("Extracted {} texture animations", entries.len());
+    Ok(())
+}
+
+/// One scan as listed in logbook.json.
+#[derive(Serialize)]
+struct LogbookEntry {
+    name: Option<String>,
+    file_id: String,
+    pak: String,
+    #[serde(flatten)]
+    scan: Scan,
+    /// The title of the scan's logbook category, as the logbook shows it.
+    category_name: &'static str,
+    /// The strings of the scan's STRG, by language.
+    text: BTreeMap<String, Vec<String>>,
+    /// The file names in images/ of the scan's images, in the order of `images`.
+    image_files: Vec<String>,
+}
+
+/// Writes every SCAN with its text and images. A scan shared by several paks is written once.
+fn export_logbook(paks: &PakFiles, cache: Option<&ResourceCache>, output_dir: &Path) -> Result<()> {
+    let images_dir = output_dir.join("images");
+    std::fs::create_dir_all(&images_dir)?;
+    let mut file_names = OutputNames::new(output_dir);
+    let mut seen = HashSet::new();
+    let mut image_files = HashMap::<u32, String>::new();
+    let mut entries = Vec::new();
+    for file in paks.iter() {
+        let file = file?;
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        let name_of = |file_id: u32| {
+            pak.iter_names()
+                .find(|e| e.file_id() == file_id)
+                .map(|e| e.name().to_string())
+        };
+        for entry in pak.iter_resources().filter(|e| e.fourcc() == "SCAN") {
+            if !seen.insert(entry.file_id()) {
+                continue;
+            }
+            let result = (|| {
+                let scan: Scan = parse::read_resource("SCAN", &entry.data()?)?;
+                let strg = pak
+                    .data_with_fourcc(scan.string_id, "STRG")?
+                    .ok_or_else(|| anyhow!("STRG 0x{:08x} not found", scan.string_id))?;
+                let strg: Strg = parse::read_resource("STRG", &strg)?;
+                let text = strg
+                    .languages
+                    .into_iter()
+                    .map(|language| (language.id, language.strings))
+                    .collect();
+
+                let mut files = Vec::new();
+                for image in &scan.images {
+                    if let Some(file_name) = image_files.get(&image.texture_id) {
+                        files.push(file_name.clone());
+                        continue;
+                    }
+                    let texture = pak
+                        .data_with_fourcc(image.texture_id, "TXTR")?
+                        .ok_or_else(|| anyhow!("TXTR 0x{:08x} not found", image.texture_id))?;
+                    let name = file_names.name(
+                        &images_dir,
+                        &name_of(image.texture_id)
+                            .unwrap_or_else(|| format!("0x{:08x}", image.texture_id)),
+                    );
+                    let file_name = format!("{name}.png");
+                    let mut w = BufWriter::new(File::create(images_dir.join(&file_name))?);
+                    txtr::decode(&texture)?.write_png(&mut w)?;
+                    w.flush()?;
+                    image_files.insert(image.texture_id, file_name.clone());
+                    files.push(file_name);
+                }
+
+                Ok::<_, anyhow::Error>(LogbookEntry {
+                    name: name_of(entry.file_id()),
+                    file_id: format!("0x{:08x}", entry.file_id()),
+                    pak: file.path().to_string(),
+                    category_name: scan.category.name(),
+                    scan,
+                    text,
+                    image_files: files,
+                })
+            })();
+            match result {
+                Ok(entry) => entries.push(entry),
+                Err(e) => println!(
+                    "Error in {} SCAN 0x{:08x}: {e}",
+                    file.path(),
+                    entry.file_id()
+                ),
+            }
+        }
+    }
+    entries.sort_by(|a, b| {
+        (a.scan.category, &a.name, &a.file_id).cmp(&(b.scan.category, &b.name, &b.file_id))
+    });
+
+    let mut w = BufWriter::new(File::create(output_dir.join("logbook.json"))?);
+    serde_json::to_writer_pretty(&mut w, &entries)?;
+    writeln!(w)?;
+    w.flush()?;
+    file_names.write_mapping()?;
+    println!(
+        "Exported {} scans with {} images",
+        entries.len(),
+        image_files.len(),
+    );
+    Ok(())
+}
+
+/// Rewrites every resource with a writer and reports those that differ from the original.
+fn check_round_trip(paks: &PakFiles, cache: Option<&ResourceCache>) -> Result<()> {
+    let (mut identical, mut padding_differs, mut failed) = (0, 0, 0);
+    for file in paks.iter() {
+        let file = file?;
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        for entry in pak.iter_resources() {
+            if !roundtrip::FOURCCS.contains(&entry.fourcc()) {
+                continue;
+            }
+            let result = entry
+                .data()
+                .and_then(|data| roundtrip::check(entry.fourcc(), &data));
+            let description = format!(
+                "{} {:>4} 0x{:08x}",
+                file.path(),
+                entry.fourcc(),
+                entry.file_id(),
+            );
+            match result {
+                Ok(Some(RoundTrip::Identical)) => identical += 1,
+                Ok(Some(RoundTrip::PaddingDiffers)) => padding_differs += 1,
+                Ok(Some(RoundTrip::Differs { offset })) => {
+                    println!("Mismatch in {description} at 0x{offset:x}");
+                    failed += 1;
+                }
+                Ok(None) => (),
+                Err(e) => {
+                    println!("Error in {description}: {e}");
+                    failed += 1;
+                }
+            }
+        }
+    }
+    println!("{identical} identical, {padding_differs} identical up to padding, {failed} failed");
+    if failed > 0 {
+        bail!("{failed} resources didn't round-trip");
+    }
+    Ok(())
+}
+
+fn search_text(
+    paks: &PakFiles,
+    cache: Option<&ResourceCache>,
+    pattern: &str,
+    language: Option<&str>,
+) -> Result<()> {
+    let mut match_count = 0;
+    for file in paks.iter() {
+        let file = file?;
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        for entry in pak.iter_resources() {
+            if entry.fourcc() != "STRG" {
+                continue;
+            }
+            let file_id = entry.file_id();
+            let strg: Strg = match entry
+                .data()
+                .and_then(|data| parse::read_resource("STRG", &data))
+            {
+                Ok(strg) => strg,
+                Err(e) => {
+                    println!("Error in {} STRG 0x{file_id:08x}: {e}", file.path());
+                    continue;
+                }
+            };
+            let name = pak
+                .iter_names()
+                .find(|e| e.file_id() == file_id)
+                .map(|e| format!(" {}", e.name()))
+                .unwrap_or_default();
+            for (language_id, index, text) in strg.search(pattern) {
+                if language.is_some_and(|language| language != language_id) {
+                    continue;
+                }
+                println!(
+                    "{} STRG 0x{file_id:08x}{name} {language_id} {index}: {text:?}",
+                    file.path(),
+                );
+                match_count += 1;
+            }
+        }
+    }
+    println!("{match_count} matches");
+    Ok(())
+}
+
+/// Prints files that were added, removed, or changed going from one disc image to another.
+fn compare_disc_files(disc: &Disc, other_disc: &Disc) -> Result<()> {
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for file in disc.iter_files() {
+        let file = file?;
+        match other_disc.find_file(file.path())? {
+            Some(other_file) if other_file.data() == file.data() => (),
+            Some(other_file) => {
+                println!(
+                    "~ {} ({} -> {} bytes)",
+                    file.path().display(),
+                    file.data().len(),
+                    other_file.data().len(),
+                );
+                changed += 1;
+            }
+            None => {
+                println!("- {}", file.path().display());
+                removed += 1;
+            }
+        }
+    }
+    for other_file in other_disc.iter_files() {
+        let other_file = other_file?;
+        if disc.find_file(other_file.path())?.is_none() {
+            println!(
+                "+ {} ({} bytes)",
+                other_file.path().display(),
+                other_file.data().len(),
+            );
+            added += 1;
+        }
+    }
+    println!("{added} added, {removed} removed, {changed} changed");
+    Ok(())
+}
+
+fn record_export(project_path: &Path, export: Export) -> Result<()> {
+    let mut project = Project::load_or_default(project_path)?;
+    project.record(export);
+    project.save(project_path)
+}
+
+fn detect_layouts(paks: &PakFiles, cache: Option<&ResourceCache>) -> Result<()> {
+    match ParseOptions::current().revision {
+        Some(revision) => println!("Favoring the layouts of release {revision}"),
+        None => println!("Unknown release, so no layout is favored"),
+    }
+    // How many resources of each type each layout fit best, with `None` for no layout at all.
+    let mut counts: BTreeMap<(String, Option<&'static str>), usize> = BTreeMap::new();
+    for file in paks.iter() {
+        let file = file?;
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        for entry in pak.iter_resources() {
+            let data = entry.data()?;
+            let Some(detection) = detect::detect(entry.fourcc(), &data) else {
+                continue;
+            };
+            let best = detection.best();
+            let describe = || {
+                format!(
+                    "{} {:>4} 0x{:08x}",
+                    file.path(),
+                    entry.fourcc(),
+                    entry.file_id()
+                )
+            };
+            match best {
+                None => {
+                    println!("No layout fits {}:", describe());
+                    for probe in &detection.probes {
+                        if let detect::Outcome::Failed(e) = &probe.outcome {
+                            println!("  {}: {e}", probe.layout.name);
+                        }
+                    }
+                }
+                Some(best) if detection.is_ambiguous() => println!(
+                    "Ambiguous layout for {}: {}",
+                    describe(),
+                    detection
+                        .probes
+                        .iter()
+                        .filter(|probe| {
+                            probe.outcome == best.outcome && probe.expected == best.expected
+                        })
+                        .map(|probe| probe.layout.name)
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ),
+                Some(_) => (),
             }
+            *counts
+                .entry((
+                    entry.fourcc().to_string(),
+                    best.map(|probe| probe.layout.name),
+                ))
+                .or_default() += 1;
         }
     }
-
+    for ((fourcc, layout), count) in counts {
+        println!("{fourcc} {}: {count}", layout.unwrap_or("no layout"));
+    }
     Ok(())
 }
 
-fn process_all_resources(disc: &Disc) -> Result<()> {
-    // Attempt to parse every file with a known type.
-    for file in disc.iter_files() {
-        let file = file?;
-        if file.path().extension().and_then(OsStr::to_str) == Some("pak") {
-            let pak = Pak::new(file.data())?;
-            for entry in pak.iter_resources() {
-                let name = pak
-                    .iter_names()
-                    .find(|e| e.file_id() == entry.file_id())
-                    .map(|e| e.name().to_string());
-                let data = pak.data(entry.file_id())?.unwrap();
-                let result = match entry.fourcc() {
-                    "ANCS" => Ancs::read_from(&mut data.as_slice()).map(drop),
-                    "CMDL" => Cmdl::read_from(&mut data.as_slice()).map(drop),
-                    "TXTR" => {
-                        let mut dump_path = PathBuf::new();
-                        dump_path.push("out");
-                        match &name {
-                            Some(name) => dump_path.push(format!(
-                                "{} {}.png",
-                                file.path().file_name().unwrap().to_str().unwrap(),
-                                name,
-                            )),
-                            None => dump_path.push(format!(
-                                "{} 0x{:08x}.png",
-                                file.path().file_name().unwrap().to_str().unwrap(),
-                                entry.file_id(),
-                            )),
-                        }
+/// What ExtractAll dumps and how.
+struct DumpOptions<'a> {
+    /// Resource types to dump, or every type if empty.
+    types: &'a [String],
+    png_compression: PngCompression,
+    preview_format: Option<PreviewFormat>,
+    layout: ExtractLayout,
+    /// Where to put each resource's files instead of where `layout` does.
+    template: Option<&'a OutputTemplate>,
+}
 
-                        if !dump_path.exists() {
-                            let mut buf = Vec::<u8>::new();
-                            let result = txtr::dump(&data, &mut buf);
-                            if result.is_ok() {
-                                let mut w = BufWriter::new(File::create(dump_path)?);
-                                w.write_all(&buf)?;
-                                w.flush().unwrap();
-                            }
-                            result
-                        } else {
-                            Ok(())
-                        }
+fn process_all_resources(
+    pak_files: &[PakFile],
+    cache: Option<&ResourceCache>,
+    resources: &DiscResources,
+    dump: &DumpOptions,
+    out_dir: &Path,
+    missing_texture: MissingTexture,
+) -> Result<()> {
+    let png_pool = PngPool::new();
+    let options = ExportOptions {
+        missing_texture,
+        ..Default::default()
+    };
+    let mut file_names = OutputNames::new(out_dir);
+    let mut seen = HashSet::new();
+
+    // Attempt to parse every file with a known type.
+    for file in pak_files {
+        let pak = match Pak::new(file.data()) {
+            Ok(pak) => pak.with_cache(cache.cloned()),
+            Err(e) => {
+                println!("Skipping {}, which doesn't parse: {e}", file.path());
+                continue;
+            }
+        };
+        let mut pak_cache = PakCache::new(Pak::new(file.data())?.with_cache(cache.cloned()))
+            .with_resources(resources);
+        for entry in pak.iter_resources() {
+            if !dump.types.is_empty() && !dump.types.iter().any(|t| t == entry.fourcc()) {
+                continue;
+            }
+            let write_once = match dump.template {
+                Some(template) => !template.uses_pak(),
+                None => dump.layout == ExtractLayout::Flat,
+            };
+            if write_once && !seen.insert(entry.file_id()) {
+                continue;
+            }
+            let name = pak
+                .iter_names()
+                .find(|e| e.file_id() == entry.file_id())
+                .map(|e| e.name().to_string());
+            let data = pak.data(entry.file_id())?.unwrap();
+            let fourcc = match Resource::sniff(&data) {
+                Some(sniffed) if sniffed != entry.fourcc() => {
+                    println!(
+                        "Reclassified {} {:>4} 0x{:08x} {:?} as {}",
+                        file.path(),
+                        entry.fourcc(),
+                        entry.file_id(),
+                        name,
+                        sniffed,
+                    );
+                    sniffed
+                }
+                _ => entry.fourcc(),
+            };
+            let extension = dump_extension(fourcc);
+            let (dump_dir, dump_name) = file_names.resource(
+                dump.template,
+                &dump.layout.dump_dir(out_dir, file, fourcc),
+                &TemplateValues {
+                    pak: &pak_stem(file),
+                    fourcc,
+                    name: name.as_deref(),
+                    file_id: entry.file_id(),
+                    ext: &extension,
+                },
+            )?;
+            let result = match fourcc {
+                "TXTR" => {
+                    let dump_path = dump_dir.join(format!("{dump_name}.png"));
+                    if !dump_path.exists() {
+                        txtr::decode(&data).and_then(|texture| {
+                            png_pool.submit(
+                                dump_path,
+                                texture,
+                                dump.png_compression,
+                                dump.preview_format,
+                            )
+                        })
+                    } else {
+                        Ok(())
                     }
-                    _ => Ok(()),
-                };
-                match result {
-                    Ok(()) => (),
-                    Err(e) => {
+                }
+                "CMDL" => Parsed::capture(|| {
+                    let cmdl: Cmdl = parse::read_resource("CMDL", &data)?;
+                    CanonicalMesh::from_cmdl(&cmdl, 0)
+                })
+                .and_then(|parsed| {
+                    let files = ExportFiles {
+                        dir: &dump_dir,
+                        name: &dump_name,
+                    };
+                    let options = ExportOptions {
+                        vertex_colors: true,
+                        ..options
+                    };
+                    export_static_gltf(
+                        &mut pak_cache,
+                        &parsed.value,
+                        options,
+                        parsed.warnings,
+                        &files,
+                    )
+                }),
+                "ANCS" => (|| {
+                    let Parsed {
+                        value: ancs,
+                        warnings: ancs_warnings,
+                    } = Parsed::capture(|| parse::read_resource::<Ancs>("ANCS", &data))?;
+                    let character_dir = dump_dir.join(&dump_name);
+                    std::fs::create_dir_all(&character_dir)?;
+                    for (index, character) in ancs.character_set.characters.iter().enumerate() {
+                        let Parsed {
+                            value: mesh,
+                            warnings,
+                        } = Parsed::capture(|| {
+                            CanonicalMesh::from_ancs(&mut pak_cache, &ancs, index, 0)
+                        })?;
+                        let files = ExportFiles {
+                            dir: &character_dir,
+                            name: &file_names.name(&character_dir, &character.name),
+                        };
+                        let warnings = [ancs_warnings.clone(), warnings].concat();
+                        export_static_gltf(&mut pak_cache, &mesh, options, warnings, &files)?;
+                    }
+                    Ok(())
+                })(),
+                fourcc => Parsed::capture(|| Resource::parse(fourcc, &data)).and_then(|parsed| {
+                    for warning in parsed.warnings {
                         println!(
-                            "Error in {} {:>4} 0x{:08x} {:?}: {}",
-                            file.path().display(),
-                            entry.fourcc(),
+                            "Warning in {} {:>4} 0x{:08x} {:?}: {}",
+                            file.path(),
+                            fourcc,
                             entry.file_id(),
                             name,
-                            e,
+                            warning.message,
                         );
                     }
+                    std::fs::write(dump_dir.join(format!("{dump_name}.{extension}")), &data)?;
+                    Ok(())
+                }),
+            };
+            match result {
+                Ok(()) => (),
+                Err(e) => {
+                    println!(
+                        "Error in {} {:>4} 0x{:08x} {:?}: {}",
+                        file.path(),
+                        fourcc,
+                        entry.file_id(),
+                        name,
+                        e,
+                    );
                 }
             }
         }
     }
+    file_names.write_mapping()?;
+    png_pool.finish()
+}
+
+/// The extension of the main file ExtractAll writes for a resource type.
+fn dump_extension(fourcc: &str) -> String {
+    match fourcc {
+        "TXTR" => "png".to_string(),
+        "CMDL" | "ANCS" => "gltf".to_string(),
+        _ => fourcc.to_lowercase(),
+    }
+}
+
+/// The pak's file name without its extension, for output templates.
+fn pak_stem(file: &PakFile) -> String {
+    let stem = Path::new(file.file_name()).file_stem().unwrap_or_default();
+    stem.to_string_lossy().into_owned()
+}
+
+/// Where a disc file goes under `out_dir`, keeping its directories. Names come from the disc, so
+/// one that would lead outside `out_dir` is reported and gets `None`.
+fn disc_output_path(out_dir: &Path, disc_path: &Path) -> Option<PathBuf> {
+    if !disc_path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        println!(
+            "Skipping {}, which leads outside the disc's filesystem",
+            disc_path.display()
+        );
+        return None;
+    }
+    Some(out_dir.join(disc_path))
+}
+
+/// Copies the disc's system files to OUT_DIR/sys and its filesystem to OUT_DIR/files.
+fn dump_disc_files(disc: &Disc, out_dir: &Path) -> Result<()> {
+    let system_dir = out_dir.join(DISC_SYSTEM_DIR);
+    match disc.system_files() {
+        Ok(files) => {
+            std::fs::create_dir_all(&system_dir)?;
+            for (name, data) in files {
+                std::fs::write(system_dir.join(name), data)?;
+            }
+        }
+        // The filesystem is still worth copying.
+        Err(e) => println!("Couldn't copy the system files: {e}"),
+    }
+
+    let files_dir = out_dir.join(DISC_FILES_DIR);
+    let mut count = 0;
+    for file in disc.iter_files() {
+        let file = match file {
+            Ok(file) => file,
+            Err(e) => {
+                println!("Error in the disc filesystem: {e}");
+                continue;
+            }
+        };
+        let Some(path) = disc_output_path(&files_dir, file.path()) else {
+            continue;
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, file.data())?;
+        count += 1;
+    }
+    println!(
+        "Copied {count} files from the disc to {}",
+        files_dir.display()
+    );
     Ok(())
 }
 
-fn export_static_gltf(pak: &mut PakCache, mesh: &CanonicalMesh) -> Result<()> {
-    let mut file = BufWriter::new(File::create("gltf_export.gltf")?);
-    make_static_gltf_document(pak, mesh)?.to_writer_pretty(&mut file)?;
+/// Writes a mesh as glTF with its vertices in their bind pose and no skin, along with any parts,
+/// debug geometry, and script objects the options ask for. `warnings` are those recorded while
+/// parsing the mesh, and are reported in the export stats along with any recorded while building
+/// the document.
+fn export_static_gltf(
+    pak: &mut PakCache,
+    mesh: &CanonicalMesh,
+    options: ExportOptions,
+    warnings: Vec<Warning>,
+    files: &ExportFiles,
+) -> Result<()> {
+    let mut stats = ExportStats {
+        warnings,
+        ..Default::default()
+    };
+    let mut file = BufWriter::new(File::create(files.path(".gltf"))?);
+    let document =
+        Parsed::capture(|| make_static_gltf_document(pak, mesh, options, files, &mut stats))?;
+    stats.warnings.extend(document.warnings);
+    document.value.to_writer_pretty(&mut file)?;
     file.flush()?;
+    stats.report(files)?;
 
     Ok(())
 }
 
-fn export_skinned_gltf(pak: &mut PakCache, mesh: &CanonicalMesh) -> Result<()> {
-    let mut file = BufWriter::new(File::create("gltf_export.gltf")?);
-    make_skinned_gltf_document(pak, mesh)?.to_writer_pretty(&mut file)?;
+/// Writes a skinned mesh as glTF, with a joint node for each bone of its skeleton and every
+/// vertex weighted to its bones. `warnings` are as for [`export_static_gltf`].
+fn export_skinned_gltf(
+    pak: &mut PakCache,
+    mesh: &CanonicalMesh,
+    options: ExportOptions,
+    warnings: Vec<Warning>,
+    files: &ExportFiles,
+) -> Result<()> {
+    let mut stats = ExportStats {
+        warnings,
+        ..Default::default()
+    };
+    let mut file = BufWriter::new(File::create(files.path(".gltf"))?);
+    let document =
+        Parsed::capture(|| make_skinned_gltf_document(pak, mesh, options, files, &mut stats))?;
+    stats.warnings.extend(document.warnings);
+    document.value.to_writer_pretty(&mut file)?;
     file.flush()?;
+    stats.report(files)?;
 
     Ok(())
 }
 
+/// Sizes of one glTF export and the parse warnings behind it, printed and written alongside it so
+/// pathological meshes stand out.
+#[derive(Debug, Default, Serialize)]
+struct ExportStats {
+    surfaces: Vec<SurfaceStats>,
+    triangle_count: usize,
+    vertex_count: usize,
+    index_buffer_size: usize,
+    attribute_buffer_size: usize,
+    warnings: Vec<Warning>,
+}
+
+#[derive(Debug, Serialize)]
+struct SurfaceStats {
+    material_index: usize,
+    triangle_count: usize,
+    /// Vertices remaining after identical ones are merged.
+    vertex_count: usize,
+}
+
+impl ExportStats {
+    /// Totals the per-surface counts, prints them, and writes them to `NAME_stats.json`.
+    fn report(&mut self, files: &ExportFiles) -> Result<()> {
+        self.triangle_count = self.surfaces.iter().map(|s| s.triangle_count).sum();
+        self.vertex_count = self.surfaces.iter().map(|s| s.vertex_count).sum();
+
+        for (index, surface) in self.surfaces.iter().enumerate() {
+            println!(
+                "Surface {index:3} (material {:2}): {:6} triangles, {:6} vertices",
+                surface.material_index, surface.triangle_count, surface.vertex_count,
+            );
+        }
+        println!(
+            "Total: {} triangles, {} vertices, {} index bytes, {} attribute bytes",
+            self.triangle_count,
+            self.vertex_count,
+            self.index_buffer_size,
+            self.attribute_buffer_size,
+        );
+        for warning in &self.warnings {
+            println!("Warning: {}", warning.message);
+        }
+
+        let mut file = BufWriter::new(File::create(files.path("_stats.json"))?);
+        serde_json::to_writer_pretty(&mut file, self)?;
+        writeln!(file)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 struct StaticVertex {
     position: [f32; 3],
     normal: [f32; 3],
     texcoord: [f32; 2],
+    /// Texture coordinate sets past the first, each written to the optional buffer if the surface
+    /// has it.
+    extra_texcoords: [[f32; 2]; 7],
+    /// One color per color channel, each written to the color buffer if the surface has it.
+    colors: [[u8; 4]; 2],
 }
 
 impl StaticVertex {
@@ -244,6 +3553,17 @@ impl PartialEq for StaticVertex {
             && self.normal[2].to_bits() == other.normal[2].to_bits()
             && self.texcoord[0].to_bits() == other.texcoord[0].to_bits()
             && self.texcoord[1].to_bits() == other.texcoord[1].to_bits()
+            && self
+                .extra_texcoords
+                .as_flattened()
+                .iter()
+                .map(|x| x.to_bits())
+                .eq(other
+                    .extra_texcoords
+                    .as_flattened()
+                    .iter()
+                    .map(|x| x.to_bits()))
+            && self.colors == other.colors
     }
 }
 
@@ -259,6 +3579,10 @@ impl Hash for StaticVertex {
         self.normal[2].to_bits().hash(state);
         self.texcoord[0].to_bits().hash(state);
         self.texcoord[1].to_bits().hash(state);
+        for x in self.extra_texcoords.as_flattened() {
+            x.to_bits().hash(state);
+        }
+        self.colors.hash(state);
     }
 }
 
@@ -267,8 +3591,8 @@ struct SkinnedVertex {
     position: [f32; 3],
     normal: [f32; 3],
     texcoord: [f32; 2],
-    joint: u8,
-    weight: f32,
+    joints: [u8; 4],
+    weights: [f32; 4],
 }
 
 impl SkinnedVertex {
@@ -281,14 +3605,10 @@ impl SkinnedVertex {
         data.write_f32::<LittleEndian>(self.normal[2])?;
         data.write_f32::<LittleEndian>(self.texcoord[0])?;
         data.write_f32::<LittleEndian>(self.texcoord[1])?;
-        data.write_u8(self.joint)?;
-        data.write_u8(0)?;
-        data.write_u8(0)?;
-        data.write_u8(0)?;
-        data.write_f32::<LittleEndian>(self.weight)?;
-        data.write_f32::<LittleEndian>(0.0)?;
-        data.write_f32::<LittleEndian>(0.0)?;
-        data.write_f32::<LittleEndian>(0.0)?;
+        data.write_all(&self.joints)?;
+        for weight in self.weights {
+            data.write_f32::<LittleEndian>(weight)?;
+        }
         Ok(())
     }
 }
@@ -303,8 +3623,8 @@ impl PartialEq for SkinnedVertex {
             && self.normal[2].to_bits() == other.normal[2].to_bits()
             && self.texcoord[0].to_bits() == other.texcoord[0].to_bits()
             && self.texcoord[1].to_bits() == other.texcoord[1].to_bits()
-            && self.joint == other.joint
-            && self.weight.to_bits() == other.weight.to_bits()
+            && self.joints == other.joints
+            && self.weights.map(f32::to_bits) == other.weights.map(f32::to_bits)
     }
 }
 
@@ -320,12 +3640,18 @@ impl Hash for SkinnedVertex {
         self.normal[2].to_bits().hash(state);
         self.texcoord[0].to_bits().hash(state);
         self.texcoord[1].to_bits().hash(state);
-        self.joint.hash(state);
-        self.weight.to_bits().hash(state);
+        self.joints.hash(state);
+        self.weights.map(f32::to_bits).hash(state);
     }
 }
 
-fn make_static_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Result<Gltf> {
+fn make_static_gltf_document(
+    pak: &mut PakCache,
+    mesh: &CanonicalMesh,
+    options: ExportOptions,
+    files: &ExportFiles,
+    stats: &mut ExportStats,
+) -> Result<Gltf> {
     const ATTRIBUTE_STRIDE: usize = 32;
     const POSITION_OFFSET: usize = 0;
     const NORMAL_OFFSET: usize = 12;
@@ -336,80 +3662,144 @@ fn make_static_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Result
     let mut textures = Vec::new();
     let mut materials = Vec::new();
     for (index, texture_id) in mesh.texture_ids.iter().copied().enumerate() {
-        let filename = format!("gltf_export_{index:02}.png");
+        let filename = files.file_name(&format!("_{index:02}.png"));
 
         // Export the texture to a file.
-        let texture_data = pak
-            .data_with_fourcc(texture_id, "TXTR")?
-            .ok_or_else(|| anyhow!("Texture 0x{texture_id:08x} not found"))?;
-        let mut file = BufWriter::new(File::create(&filename)?);
-        txtr::dump(texture_data.as_slice(), &mut file)?;
-        file.flush()?;
-        drop(file);
-
-        images.push(gltf::Image {
-            uri: Some(filename),
-            mime_type: None,
-            buffer_view: None,
+        let path = files.dir.join(&filename);
+        let written = write_export_texture(pak, texture_id, options.missing_texture, &path)?;
+        let source = written.then(|| {
+            images.push(gltf::Image {
+                uri: Some(filename),
+                mime_type: None,
+                buffer_view: None,
+            });
+            gltf::ImageIndex(images.len() - 1)
         });
 
         textures.push(gltf::Texture {
             sampler: Some(gltf::SamplerIndex(0)),
-            source: Some(gltf::ImageIndex(index)),
+            source,
         });
 
-        materials.push(gltf::Material {
-            pbr_metallic_roughness: Some(gltf::PbrMetallicRoughness {
-                base_color_factor: None,
-                base_color_texture: Some(gltf::TextureInfo {
-                    index: gltf::TextureIndex(index),
-                    tex_coord: Some(0),
-                }),
-                metallic_factor: Some(1.0),
-                roughness_factor: Some(0.25),
-                metallic_roughness_texture: None,
-            }),
-        });
+        let mut material = base_color_material(gltf::TextureIndex(index));
+        if source.is_none() {
+            if let Some(pbr) = &mut material.pbr_metallic_roughness {
+                pbr.base_color_texture = None;
+            }
+        }
+        materials.push(material);
     }
+    let baked_materials = if options.bake_materials {
+        bake_surface_materials(pak, mesh, files, &mut images, &mut textures, &mut materials)?
+    } else {
+        HashMap::new()
+    };
 
     // Process all surfaces into index and attribute buffers, generating glTF accessors and mesh
     // primitives that refer to them.
     let mut index_buffer = Vec::new();
     let mut attribute_buffer = Vec::new();
+    // Quantized vertices get their own buffer view, since its stride differs from the float
+    // attributes that helper geometry still uses.
+    let quantization = options.quantize.then(|| VertexQuantization::new(mesh));
+    let mut quantized_buffer = Vec::new();
+    // Vertex colors and texture coordinate sets past the first are kept apart too, since most
+    // surfaces don't have them. The view isn't strided, so accessors of any type can share it.
+    let mut optional_buffer = Vec::new();
+    let optional_view = gltf::BufferViewIndex(if quantization.is_some() { 3 } else { 2 });
     let mut nodes = Vec::new();
     let mut accessors = vec![];
     let mut mesh_primitives = Vec::new();
-    for surface in &mesh.surfaces {
-        assert_eq!(surface.positions.len(), surface.normals.len());
-        assert_eq!(surface.positions.len(), surface.texcoords.len());
+    let mut material_variants = HashMap::new();
+    for (surface_index, surface) in mesh.surfaces.iter().enumerate() {
+        check_surface_attributes(surface_index, surface)?;
+        // A surface without texture coordinates is still drawn, just without TEXCOORD_0.
+        let texcoords = surface.texcoords.first();
 
-        let first_texture_index = surface.texture_indices[0];
+        let material_index = surface_material(
+            &mut materials,
+            &mut material_variants,
+            MaterialVariant {
+                base: baked_materials
+                    .get(&surface.material_index)
+                    .copied()
+                    .unwrap_or(surface.texture_indices[0]),
+                unlit: surface.unlit,
+                reflection_texture: if options.approximate_reflections {
+                    reflection_texture(mesh, surface)
+                } else {
+                    None
+                },
+            },
+        );
 
         let index_byte_offset = index_buffer.len();
-        let attribute_byte_offset = attribute_buffer.len();
+        let attribute_byte_offset = match quantization {
+            Some(_) => quantized_buffer.len(),
+            None => attribute_buffer.len(),
+        };
+
+        let has_colors =
+            [0, 1].map(|channel| options.vertex_colors && !surface.colors[channel].is_empty());
+        let mut surface_colors: [Vec<u8>; 2] = Default::default();
+        let mut surface_texcoords = vec![Vec::new(); surface.texcoords.len().saturating_sub(1)];
 
         let mut index_count = 0;
         let mut vertex_count = 0;
         let mut indices_by_vertex = HashMap::new();
         let mut min_position = Vector3::repeat(f32::INFINITY);
         let mut max_position = Vector3::repeat(f32::NEG_INFINITY);
-        for ((&position, &normal), &texcoord) in surface
+        for (i, (&position, &normal)) in surface
             .positions
             .iter()
             .zip(surface.normals.iter())
-            .zip(surface.texcoords.iter())
+            .enumerate()
         {
+            let texcoord = texcoords.map_or([0.0; 2], |texcoords| texcoords[i]);
             let v = StaticVertex {
                 position,
                 normal,
                 texcoord,
+                extra_texcoords: std::array::from_fn(|set| {
+                    surface
+                        .texcoords
+                        .get(set + 1)
+                        .map_or([0.0; 2], |texcoords| texcoords[i])
+                }),
+                colors: [0, 1].map(|channel| {
+                    if has_colors[channel] {
+                        surface.colors[channel][i]
+                            .map(|x| (x.clamp(0.0, 1.0) * 255.0).round() as u8)
+                    } else {
+                        [0; 4]
+                    }
+                }),
             };
             let index = match indices_by_vertex.get(&v) {
                 Some(&index) => index,
                 None => {
                     let index = vertex_count.try_into().unwrap();
                     vertex_count += 1;
-                    v.write_to(&mut attribute_buffer)?;
+                    match &quantization {
+                        Some(quantization) => quantization.write_vertex(
+                            &mut quantized_buffer,
+                            position,
+                            normal,
+                            texcoord,
+                        )?,
+                        None => v.write_to(&mut attribute_buffer)?,
+                    }
+                    for ((data, &has_colors), color) in
+                        surface_colors.iter_mut().zip(&has_colors).zip(v.colors)
+                    {
+                        if has_colors {
+                            data.write_all(&color)?;
+                        }
+                    }
+                    for (data, texcoord) in surface_texcoords.iter_mut().zip(v.extra_texcoords) {
+                        data.write_f32::<LittleEndian>(texcoord[0])?;
+                        data.write_f32::<LittleEndian>(texcoord[1])?;
+                    }
                     indices_by_vertex.insert(v, index);
                     index
                 }
@@ -419,6 +3809,24 @@ fn make_static_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Result
             min_position = min_position.inf(&position.into());
             max_position = max_position.sup(&position.into());
         }
+        let color_byte_offsets = surface_colors.map(|data| {
+            let offset = optional_buffer.len();
+            optional_buffer.extend_from_slice(&data);
+            offset
+        });
+        let texcoord_byte_offsets: Vec<usize> = surface_texcoords
+            .iter()
+            .map(|data| {
+                let offset = optional_buffer.len();
+                optional_buffer.extend_from_slice(data);
+                offset
+            })
+            .collect();
+        stats.surfaces.push(SurfaceStats {
+            material_index: surface.material_index,
+            triangle_count: index_count / 3,
+            vertex_count,
+        });
 
         let accessor_base_index = accessors.len();
         accessors.push(gltf::Accessor {
@@ -426,103 +3834,426 @@ fn make_static_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Result
             byte_offset: index_byte_offset,
             type_: gltf::AccessorType::Scalar,
             component_type: gltf::AccessorComponentType::UnsignedShort,
+            normalized: false,
             count: index_count,
             min: None,
             max: None,
         });
-        accessors.push(gltf::Accessor {
-            buffer_view: Some(gltf::BufferViewIndex(1)),
-            byte_offset: attribute_byte_offset + POSITION_OFFSET,
-            type_: gltf::AccessorType::Vec3,
-            component_type: gltf::AccessorComponentType::Float,
-            count: vertex_count,
-            min: Some(min_position.iter().copied().collect()),
-            max: Some(max_position.iter().copied().collect()),
-        });
-        accessors.push(gltf::Accessor {
-            buffer_view: Some(gltf::BufferViewIndex(1)),
-            byte_offset: attribute_byte_offset + NORMAL_OFFSET,
-            type_: gltf::AccessorType::Vec3,
-            component_type: gltf::AccessorComponentType::Float,
-            count: vertex_count,
-            min: None,
-            max: None,
-        });
-        accessors.push(gltf::Accessor {
-            buffer_view: Some(gltf::BufferViewIndex(1)),
-            byte_offset: attribute_byte_offset + TEXCOORD0_OFFSET,
-            type_: gltf::AccessorType::Vec2,
-            component_type: gltf::AccessorComponentType::Float,
-            count: vertex_count,
-            min: None,
-            max: None,
-        });
+        match &quantization {
+            Some(quantization) => push_quantized_accessors(
+                &mut accessors,
+                quantization,
+                attribute_byte_offset,
+                vertex_count,
+                min_position,
+                max_position,
+            ),
+            None => {
+                accessors.push(gltf::Accessor {
+                    buffer_view: Some(gltf::BufferViewIndex(1)),
+                    byte_offset: attribute_byte_offset + POSITION_OFFSET,
+                    type_: gltf::AccessorType::Vec3,
+                    component_type: gltf::AccessorComponentType::Float,
+                    normalized: false,
+                    count: vertex_count,
+                    min: Some(min_position.iter().copied().collect()),
+                    max: Some(max_position.iter().copied().collect()),
+                });
+                accessors.push(gltf::Accessor {
+                    buffer_view: Some(gltf::BufferViewIndex(1)),
+                    byte_offset: attribute_byte_offset + NORMAL_OFFSET,
+                    type_: gltf::AccessorType::Vec3,
+                    component_type: gltf::AccessorComponentType::Float,
+                    normalized: false,
+                    count: vertex_count,
+                    min: None,
+                    max: None,
+                });
+                accessors.push(gltf::Accessor {
+                    buffer_view: Some(gltf::BufferViewIndex(1)),
+                    byte_offset: attribute_byte_offset + TEXCOORD0_OFFSET,
+                    type_: gltf::AccessorType::Vec2,
+                    component_type: gltf::AccessorComponentType::Float,
+                    normalized: false,
+                    count: vertex_count,
+                    min: None,
+                    max: None,
+                });
+            }
+        }
+
+        let mut attributes = HashMap::from([
+            (
+                gltf::MeshAttribute::Position,
+                gltf::AccessorIndex(accessor_base_index + 1),
+            ),
+            (
+                gltf::MeshAttribute::Normal,
+                gltf::AccessorIndex(accessor_base_index + 2),
+            ),
+        ]);
+        if texcoords.is_some() {
+            attributes.insert(
+                gltf::MeshAttribute::Texcoord(0),
+                gltf::AccessorIndex(accessor_base_index + 3),
+            );
+        }
+        for channel in (0..2).filter(|&channel| has_colors[channel]) {
+            attributes.insert(
+                gltf::MeshAttribute::Color(channel),
+                gltf::AccessorIndex(accessors.len()),
+            );
+            accessors.push(gltf::Accessor {
+                buffer_view: Some(optional_view),
+                byte_offset: color_byte_offsets[channel],
+                type_: gltf::AccessorType::Vec4,
+                component_type: gltf::AccessorComponentType::UnsignedByte,
+                normalized: true,
+                count: vertex_count,
+                min: None,
+                max: None,
+            });
+        }
+        for (i, &byte_offset) in texcoord_byte_offsets.iter().enumerate() {
+            attributes.insert(
+                gltf::MeshAttribute::Texcoord(i + 1),
+                gltf::AccessorIndex(accessors.len()),
+            );
+            accessors.push(gltf::Accessor {
+                buffer_view: Some(optional_view),
+                byte_offset,
+                type_: gltf::AccessorType::Vec2,
+                component_type: gltf::AccessorComponentType::Float,
+                normalized: false,
+                count: vertex_count,
+                min: None,
+                max: None,
+            });
+        }
 
         mesh_primitives.push(gltf::MeshPrimitive {
             mode: gltf::MeshPrimitiveMode::Triangles,
-            indices: gltf::AccessorIndex(accessor_base_index + 0),
-            attributes: [
-                (
-                    gltf::MeshAttribute::Position,
-                    gltf::AccessorIndex(accessor_base_index + 1),
-                ),
-                (
-                    gltf::MeshAttribute::Normal,
-                    gltf::AccessorIndex(accessor_base_index + 2),
-                ),
-                (
-                    gltf::MeshAttribute::Texcoord(0),
-                    gltf::AccessorIndex(accessor_base_index + 3),
-                ),
-            ]
-            .into_iter()
-            .collect(),
-            material: Some(gltf::MaterialIndex(first_texture_index)),
+            indices: gltf::AccessorIndex(accessor_base_index),
+            attributes,
+            material: Some(material_index),
+        });
+    }
+    if let Some(quantization) = &quantization {
+        quantization.apply_to_materials(&mut materials);
+    }
+    let mut meshes = Vec::new();
+    let mut scene_nodes = Vec::new();
+    let mut add_mesh_nodes = |nodes: &mut Vec<gltf::Node>, primitives| {
+        let mut mesh_nodes = Vec::new();
+        for (name, primitives) in split_mesh_primitives(primitives, options.split_by) {
+            mesh_nodes.push(gltf::NodeIndex(nodes.len()));
+            nodes.push(gltf::Node {
+                name: name.clone(),
+                transform: quantization
+                    .as_ref()
+                    .map(VertexQuantization::node_transform)
+                    .unwrap_or_default(),
+                mesh: Some(gltf::MeshIndex(meshes.len())),
+                ..Default::default()
+            });
+            meshes.push(gltf::Mesh { name, primitives });
+        }
+        mesh_nodes
+    };
+    let mut part_scenes = Vec::new();
+    let mut part_nodes = Vec::new();
+    if mesh.parts.is_empty() {
+        scene_nodes.extend(add_mesh_nodes(&mut nodes, mesh_primitives));
+    } else {
+        // Each part gets a node placing it, with its meshes below.
+        for part in &mesh.parts {
+            let children =
+                add_mesh_nodes(&mut nodes, mesh_primitives[part.surfaces.clone()].to_vec());
+            let part_node = gltf::NodeIndex(nodes.len());
+            nodes.push(gltf::Node {
+                name: part.name.clone(),
+                children,
+                transform: gltf::Transform::Matrix(part.transform),
+                ..Default::default()
+            });
+            scene_nodes.push(part_node);
+            part_nodes.push(part_node);
+            if options.scene_per_part {
+                // A node may be a root of any number of scenes, so the part's scene shares it
+                // with the default scene.
+                part_scenes.push(gltf::Scene {
+                    name: part.name.clone(),
+                    nodes: vec![part_node],
+                });
+            }
+        }
+    }
+    if options.animation_bounds && !mesh.animation_aabbs.is_empty() {
+        let box_mesh = gltf::MeshIndex(meshes.len());
+        meshes.push(gltf::Mesh {
+            name: "animation_bounds".to_string(),
+            primitives: vec![write_unit_box(
+                &mut index_buffer,
+                &mut attribute_buffer,
+                &mut accessors,
+                false,
+            )?],
+        });
+        scene_nodes.push(add_animation_bounds_nodes(
+            &mut nodes,
+            box_mesh,
+            &mesh.animation_aabbs,
+        ));
+    }
+    if !mesh.octree_cells.is_empty() {
+        let mut faces = write_unit_box(
+            &mut index_buffer,
+            &mut attribute_buffer,
+            &mut accessors,
+            true,
+        )?;
+        faces.material = Some(gltf::MaterialIndex(materials.len()));
+        materials.push(debug_box_material([0.2, 0.6, 1.0, 0.15]));
+        let box_mesh = gltf::MeshIndex(meshes.len());
+        meshes.push(gltf::Mesh {
+            name: "octree_cell".to_string(),
+            primitives: vec![
+                faces,
+                write_unit_box(
+                    &mut index_buffer,
+                    &mut attribute_buffer,
+                    &mut accessors,
+                    false,
+                )?,
+            ],
+        });
+        if mesh.parts.is_empty() {
+            scene_nodes.push(add_octree_nodes(&mut nodes, box_mesh, &mesh.octree_cells));
+        } else {
+            // Each part's cells are in its own space, so they go under its node.
+            for (part, &part_node) in mesh.parts.iter().zip(&part_nodes) {
+                let cells = &mesh.octree_cells[part.octree_cells.clone()];
+                if !cells.is_empty() {
+                    let octree_node = add_octree_nodes(&mut nodes, box_mesh, cells);
+                    nodes[part_node.0].children.push(octree_node);
+                }
+            }
+        }
+    }
+    // Script object gizmos and trigger volumes draw volumes with the same mesh, made once.
+    let mut volume_mesh = None;
+    if options.script_objects.is_some() && !mesh.script_markers.is_empty() {
+        let gizmos = if options.script_objects == Some(ScriptMarkers::Gizmos) {
+            let point = gltf::MeshIndex(meshes.len());
+            meshes.push(gltf::Mesh {
+                name: "script_object".to_string(),
+                primitives: vec![write_unit_box(
+                    &mut index_buffer,
+                    &mut attribute_buffer,
+                    &mut accessors,
+                    false,
+                )?],
+            });
+            let volume = add_volume_mesh(
+                &mut volume_mesh,
+                &mut index_buffer,
+                &mut attribute_buffer,
+                &mut accessors,
+                &mut materials,
+                &mut meshes,
+            )?;
+            Some(ScriptGizmos { point, volume })
+        } else {
+            None
+        };
+        if mesh.parts.is_empty() {
+            scene_nodes.push(add_script_marker_nodes(
+                &mut nodes,
+                &mesh.script_markers,
+                gizmos,
+            ));
+        } else {
+            for (part, &part_node) in mesh.parts.iter().zip(&part_nodes) {
+                let markers = &mesh.script_markers[part.script_markers.clone()];
+                if !markers.is_empty() {
+                    let script_node = add_script_marker_nodes(&mut nodes, markers, gizmos);
+                    nodes[part_node.0].children.push(script_node);
+                }
+            }
+        }
+    }
+    if let Some(skin) = mesh.skin.as_ref().filter(|_| !mesh.effects.is_empty()) {
+        // The mesh itself isn't skinned here, but effects still need bones to hang from.
+        let mut joints = Vec::new();
+        scene_nodes.push(extract_nodes_from_bone(
+            &mut nodes,
+            &mut joints,
+            &mut HashMap::new(),
+            Vector3::zeros(),
+            &skin.skeleton,
+        ));
+        scene_nodes.extend(add_effect_nodes(&mut nodes, &joints, &mesh.effects));
+    }
+    if let Some(offset) = options.root_offset {
+        let root = gltf::NodeIndex(nodes.len());
+        nodes.push(gltf::Node {
+            name: "root".to_string(),
+            children: std::mem::take(&mut scene_nodes),
+            transform: gltf::Transform::Decomposed {
+                translation: Some(Translation3::from(Vector3::from(offset))),
+                rotation: None,
+                scale: None,
+            },
+            ..Default::default()
+        });
+        scene_nodes.push(root);
+    }
+    let mut cameras = Vec::new();
+    if options.cameras {
+        if mesh.parts.is_empty() {
+            if let Some(camera_node) = add_camera_nodes(
+                &mut index_buffer,
+                &mut attribute_buffer,
+                &mut accessors,
+                &mut meshes,
+                &mut cameras,
+                &mut nodes,
+                &mesh.script_markers,
+            )? {
+                scene_nodes.push(camera_node);
+            }
+        } else {
+            for (part, &part_node) in mesh.parts.iter().zip(&part_nodes) {
+                if let Some(camera_node) = add_camera_nodes(
+                    &mut index_buffer,
+                    &mut attribute_buffer,
+                    &mut accessors,
+                    &mut meshes,
+                    &mut cameras,
+                    &mut nodes,
+                    &mesh.script_markers[part.script_markers.clone()],
+                )? {
+                    nodes[part_node.0].children.push(camera_node);
+                }
+            }
+        }
+    }
+    let mut volume_scenes = Vec::new();
+    if options.trigger_volumes && mesh.script_markers.iter().any(|m| m.volume.is_some()) {
+        let box_mesh = add_volume_mesh(
+            &mut volume_mesh,
+            &mut index_buffer,
+            &mut attribute_buffer,
+            &mut accessors,
+            &mut materials,
+            &mut meshes,
+        )?;
+        let children = if mesh.parts.is_empty() {
+            add_trigger_volume_nodes(&mut nodes, box_mesh, &mesh.script_markers)
+        } else {
+            // A scene's nodes can't have parents, so rather than going under the part nodes, each
+            // part's volumes get a node of their own placing them the same way.
+            let mut children = Vec::new();
+            for part in &mesh.parts {
+                let volume_nodes = add_trigger_volume_nodes(
+                    &mut nodes,
+                    box_mesh,
+                    &mesh.script_markers[part.script_markers.clone()],
+                );
+                if !volume_nodes.is_empty() {
+                    children.push(gltf::NodeIndex(nodes.len()));
+                    nodes.push(gltf::Node {
+                        name: part.name.clone(),
+                        children: volume_nodes,
+                        transform: gltf::Transform::Matrix(part.transform),
+                        ..Default::default()
+                    });
+                }
+            }
+            children
+        };
+        // Added after any root offset, which only models use, so the volumes scene can share it.
+        let volumes_node = gltf::NodeIndex(nodes.len());
+        nodes.push(gltf::Node {
+            name: "trigger_volumes".to_string(),
+            children,
+            ..Default::default()
+        });
+        scene_nodes.push(volumes_node);
+        volume_scenes.push(gltf::Scene {
+            name: "volumes".to_string(),
+            nodes: vec![volumes_node],
         });
     }
-    let mesh_node_index = gltf::NodeIndex(nodes.len());
-    nodes.push(gltf::Node {
-        name: "mesh".to_string(),
-        mesh: Some(gltf::MeshIndex(0)),
-        ..Default::default()
-    });
 
     // Write out the index and attribute buffers to a single externally referenced file.
-    let mut buffer_file = BufWriter::new(File::create("gltf_export.bin")?);
+    let mut buffer_file = BufWriter::new(File::create(files.path(".bin"))?);
     buffer_file.write_all(&index_buffer)?;
     buffer_file.write_all(&attribute_buffer)?;
+    buffer_file.write_all(&quantized_buffer)?;
+    buffer_file.write_all(&optional_buffer)?;
+    stats.index_buffer_size = index_buffer.len();
+    stats.attribute_buffer_size =
+        attribute_buffer.len() + quantized_buffer.len() + optional_buffer.len();
     buffer_file.flush()?;
     drop(buffer_file);
 
+    let mut buffer_views = vec![
+        gltf::BufferView {
+            buffer: gltf::BufferIndex(0),
+            byte_offset: 0,
+            byte_length: index_buffer.len(),
+            byte_stride: None,
+        },
+        gltf::BufferView {
+            buffer: gltf::BufferIndex(0),
+            byte_offset: index_buffer.len(),
+            byte_length: attribute_buffer.len(),
+            byte_stride: Some(ATTRIBUTE_STRIDE),
+        },
+    ];
+    let mut extensions_used = extensions_used(&materials);
+    let mut extensions_required = Vec::new();
+    if quantization.is_some() {
+        buffer_views.push(gltf::BufferView {
+            buffer: gltf::BufferIndex(0),
+            byte_offset: index_buffer.len() + attribute_buffer.len(),
+            byte_length: quantized_buffer.len(),
+            byte_stride: Some(VertexQuantization::STRIDE),
+        });
+        extensions_used.extend(VertexQuantization::extensions());
+        extensions_required.extend(VertexQuantization::extensions());
+    }
+    if !optional_buffer.is_empty() {
+        buffer_views.push(gltf::BufferView {
+            buffer: gltf::BufferIndex(0),
+            byte_offset: index_buffer.len() + attribute_buffer.len() + quantized_buffer.len(),
+            byte_length: optional_buffer.len(),
+            byte_stride: None,
+        });
+    }
+
     // Build the rest of the glTF file.
     Ok(Gltf {
+        extensions_used,
+        extensions_required,
         accessors,
+        animations: vec![],
         asset: gltf::Asset {
             version: gltf::Version,
         },
         buffers: vec![gltf::Buffer {
-            byte_length: index_buffer.len() + attribute_buffer.len(),
-            uri: "gltf_export.bin".to_string(),
+            byte_length: index_buffer.len()
+                + attribute_buffer.len()
+                + quantized_buffer.len()
+                + optional_buffer.len(),
+            uri: files.file_name(".bin"),
         }],
-        buffer_views: vec![
-            gltf::BufferView {
-                buffer: gltf::BufferIndex(0),
-                byte_offset: 0,
-                byte_length: index_buffer.len(),
-                byte_stride: None,
-            },
-            gltf::BufferView {
-                buffer: gltf::BufferIndex(0),
-                byte_offset: index_buffer.len(),
-                byte_length: attribute_buffer.len(),
-                byte_stride: Some(ATTRIBUTE_STRIDE),
-            },
-        ],
+        buffer_views,
+        cameras,
         images,
         materials,
-        meshes: vec![gltf::Mesh {
-            primitives: mesh_primitives,
-        }],
+        meshes,
         nodes,
         samplers: vec![gltf::Sampler {
             mag_filter: gltf::SamplerMagFilter::Linear,
@@ -531,17 +4262,74 @@ fn make_static_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Result
             wrap_t: gltf::SamplerWrap::Repeat,
         }],
         scene: Some(gltf::SceneIndex(0)),
-        scenes: vec![gltf::Scene {
+        scenes: [gltf::Scene {
             name: "scene".to_string(),
-            nodes: vec![mesh_node_index],
-            ..Default::default()
-        }],
+            nodes: scene_nodes,
+        }]
+        .into_iter()
+        .chain(part_scenes)
+        .chain(volume_scenes)
+        .collect(),
         skins: vec![],
         textures,
     })
 }
 
-fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Result<Gltf> {
+/// Pushes position, normal, and texture coordinate accessors for a surface's vertices in the
+/// quantized buffer view.
+fn push_quantized_accessors(
+    accessors: &mut Vec<gltf::Accessor>,
+    quantization: &VertexQuantization,
+    byte_offset: usize,
+    vertex_count: usize,
+    min_position: Vector3<f32>,
+    max_position: Vector3<f32>,
+) {
+    let view = Some(gltf::BufferViewIndex(2));
+    // Quantization preserves order, so the quantized bounds are the bounds' quantized values.
+    let bounds = |position: Vector3<f32>| {
+        let quantized = quantization.position(position.into());
+        Some(quantized.iter().map(|&x| x as f32).collect())
+    };
+    accessors.push(gltf::Accessor {
+        buffer_view: view,
+        byte_offset: byte_offset + VertexQuantization::POSITION_OFFSET,
+        type_: gltf::AccessorType::Vec3,
+        component_type: gltf::AccessorComponentType::Short,
+        normalized: true,
+        count: vertex_count,
+        min: bounds(min_position),
+        max: bounds(max_position),
+    });
+    accessors.push(gltf::Accessor {
+        buffer_view: view,
+        byte_offset: byte_offset + VertexQuantization::NORMAL_OFFSET,
+        type_: gltf::AccessorType::Vec3,
+        component_type: gltf::AccessorComponentType::Byte,
+        normalized: true,
+        count: vertex_count,
+        min: None,
+        max: None,
+    });
+    accessors.push(gltf::Accessor {
+        buffer_view: view,
+        byte_offset: byte_offset + VertexQuantization::TEXCOORD0_OFFSET,
+        type_: gltf::AccessorType::Vec2,
+        component_type: gltf::AccessorComponentType::UnsignedShort,
+        normalized: true,
+        count: vertex_count,
+        min: None,
+        max: None,
+    });
+}
+
+fn make_skinned_gltf_document(
+    pak: &mut PakCache,
+    mesh: &CanonicalMesh,
+    options: ExportOptions,
+    files: &ExportFiles,
+    stats: &mut ExportStats,
+) -> Result<Gltf> {
     const ATTRIBUTE_STRIDE: usize = 52;
     const POSITION_OFFSET: usize = 0;
     const NORMAL_OFFSET: usize = 12;
@@ -554,41 +4342,38 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
     let mut textures = Vec::new();
     let mut materials = Vec::new();
     for (index, texture_id) in mesh.texture_ids.iter().copied().enumerate() {
-        let filename = format!("gltf_export_{index:02}.png");
+        let filename = files.file_name(&format!("_{index:02}.png"));
 
         // Export the texture to a file.
-        let texture_data = pak
-            .data_with_fourcc(texture_id, "TXTR")?
-            .ok_or_else(|| anyhow!("Texture 0x{texture_id:08x} not found"))?;
-        let mut file = BufWriter::new(File::create(&filename)?);
-        txtr::dump(texture_data.as_slice(), &mut file)?;
-        file.flush()?;
-        drop(file);
-
-        images.push(gltf::Image {
-            uri: Some(filename),
-            mime_type: None,
-            buffer_view: None,
+        let path = files.dir.join(&filename);
+        let written = write_export_texture(pak, texture_id, options.missing_texture, &path)?;
+        let source = written.then(|| {
+            images.push(gltf::Image {
+                uri: Some(filename),
+                mime_type: None,
+                buffer_view: None,
+            });
+            gltf::ImageIndex(images.len() - 1)
         });
 
         textures.push(gltf::Texture {
             sampler: Some(gltf::SamplerIndex(0)),
-            source: Some(gltf::ImageIndex(index)),
+            source,
         });
 
-        materials.push(gltf::Material {
-            pbr_metallic_roughness: Some(gltf::PbrMetallicRoughness {
-                base_color_factor: None,
-                base_color_texture: Some(gltf::TextureInfo {
-                    index: gltf::TextureIndex(index),
-                    tex_coord: Some(0),
-                }),
-                metallic_factor: Some(1.0),
-                roughness_factor: Some(0.25),
-                metallic_roughness_texture: None,
-            }),
-        });
+        let mut material = base_color_material(gltf::TextureIndex(index));
+        if source.is_none() {
+            if let Some(pbr) = &mut material.pbr_metallic_roughness {
+                pbr.base_color_texture = None;
+            }
+        }
+        materials.push(material);
     }
+    let baked_materials = if options.bake_materials {
+        bake_surface_materials(pak, mesh, files, &mut images, &mut textures, &mut materials)?
+    } else {
+        HashMap::new()
+    };
 
     let mut nodes = Vec::new();
     let mut joints = Vec::new();
@@ -600,6 +4385,7 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
         Vector3::zeros(),
         &mesh.skin.as_ref().unwrap().skeleton,
     );
+    let detached_effect_nodes = add_effect_nodes(&mut nodes, &joints, &mesh.effects);
     let mut inverse_bind_pose_buffer = Vec::new();
     for node_index in &joints {
         let matrix = match nodes[node_index.0].transform {
@@ -630,18 +4416,43 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
         byte_offset: 0,
         type_: gltf::AccessorType::Mat4,
         component_type: gltf::AccessorComponentType::Float,
+        normalized: false,
         count: inverse_bind_pose_buffer.len() / 64,
         min: None,
         max: None,
     }];
     let mut mesh_primitives = Vec::new();
-    for surface in &mesh.surfaces {
-        assert_eq!(surface.positions.len(), surface.normals.len());
-        assert_eq!(surface.positions.len(), surface.texcoords.len());
-        assert_eq!(surface.positions.len(), surface.bone_ids.len());
-        assert_eq!(surface.positions.len(), surface.weights.len());
+    let mut material_variants = HashMap::new();
+    for (surface_index, surface) in mesh.surfaces.iter().enumerate() {
+        check_surface_attributes(surface_index, surface)?;
+        if surface.bone_ids.len() != surface.positions.len()
+            || surface.weights.len() != surface.positions.len()
+        {
+            bail!(
+                "Surface {surface_index} has {} positions but {} bone IDs and {} weights",
+                surface.positions.len(),
+                surface.bone_ids.len(),
+                surface.weights.len(),
+            );
+        }
+        let texcoords = surface.texcoords.first();
 
-        let first_texture_index = surface.texture_indices[0];
+        let material_index = surface_material(
+            &mut materials,
+            &mut material_variants,
+            MaterialVariant {
+                base: baked_materials
+                    .get(&surface.material_index)
+                    .copied()
+                    .unwrap_or(surface.texture_indices[0]),
+                unlit: surface.unlit,
+                reflection_texture: if options.approximate_reflections {
+                    reflection_texture(mesh, surface)
+                } else {
+                    None
+                },
+            },
+        );
 
         let index_byte_offset = index_buffer.len();
         let attribute_byte_offset = attribute_buffer.len();
@@ -651,20 +4462,20 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
         let mut indices_by_vertex = HashMap::new();
         let mut min_position = Vector3::repeat(f32::INFINITY);
         let mut max_position = Vector3::repeat(f32::NEG_INFINITY);
-        for ((((&position, &normal), &texcoord), &bone_id), &weight) in surface
+        for (i, (((&position, &normal), bone_ids), &weights)) in surface
             .positions
             .iter()
             .zip(surface.normals.iter())
-            .zip(surface.texcoords.iter())
             .zip(surface.bone_ids.iter())
             .zip(surface.weights.iter())
+            .enumerate()
         {
             let v = SkinnedVertex {
                 position,
                 normal,
-                texcoord,
-                joint: joints_by_bone_id[&bone_id],
-                weight,
+                texcoord: texcoords.map_or([0.0; 2], |texcoords| texcoords[i]),
+                joints: bone_ids.map(|bone_id| joints_by_bone_id[&bone_id]),
+                weights,
             };
             let index = match indices_by_vertex.get(&v) {
                 Some(&index) => index,
@@ -681,6 +4492,11 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
             min_position = min_position.inf(&position.into());
             max_position = max_position.sup(&position.into());
         }
+        stats.surfaces.push(SurfaceStats {
+            material_index: surface.material_index,
+            triangle_count: index_count / 3,
+            vertex_count,
+        });
 
         let accessor_base_index = accessors.len();
         accessors.push(gltf::Accessor {
@@ -688,6 +4504,7 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
             byte_offset: index_byte_offset,
             type_: gltf::AccessorType::Scalar,
             component_type: gltf::AccessorComponentType::UnsignedShort,
+            normalized: false,
             count: index_count,
             min: None,
             max: None,
@@ -697,6 +4514,7 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
             byte_offset: attribute_byte_offset + POSITION_OFFSET,
             type_: gltf::AccessorType::Vec3,
             component_type: gltf::AccessorComponentType::Float,
+            normalized: false,
             count: vertex_count,
             min: Some(min_position.iter().copied().collect()),
             max: Some(max_position.iter().copied().collect()),
@@ -706,6 +4524,7 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
             byte_offset: attribute_byte_offset + NORMAL_OFFSET,
             type_: gltf::AccessorType::Vec3,
             component_type: gltf::AccessorComponentType::Float,
+            normalized: false,
             count: vertex_count,
             min: None,
             max: None,
@@ -715,6 +4534,7 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
             byte_offset: attribute_byte_offset + TEXCOORD0_OFFSET,
             type_: gltf::AccessorType::Vec2,
             component_type: gltf::AccessorComponentType::Float,
+            normalized: false,
             count: vertex_count,
             min: None,
             max: None,
@@ -724,6 +4544,7 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
             byte_offset: attribute_byte_offset + JOINTS0_OFFSET,
             type_: gltf::AccessorType::Vec4,
             component_type: gltf::AccessorComponentType::UnsignedByte,
+            normalized: false,
             count: vertex_count,
             min: None,
             max: None,
@@ -733,6 +4554,7 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
             byte_offset: attribute_byte_offset + WEIGHTS0_OFFSET,
             type_: gltf::AccessorType::Vec4,
             component_type: gltf::AccessorComponentType::Float,
+            normalized: false,
             count: vertex_count,
             min: None,
             max: None,
@@ -740,7 +4562,7 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
 
         mesh_primitives.push(gltf::MeshPrimitive {
             mode: gltf::MeshPrimitiveMode::Triangles,
-            indices: gltf::AccessorIndex(accessor_base_index + 0),
+            indices: gltf::AccessorIndex(accessor_base_index),
             attributes: [
                 (
                     gltf::MeshAttribute::Position,
@@ -755,72 +4577,189 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
                     gltf::AccessorIndex(accessor_base_index + 3),
                 ),
                 (
-                    gltf::MeshAttribute::Joints(0),
-                    gltf::AccessorIndex(accessor_base_index + 4),
+                    gltf::MeshAttribute::Joints(0),
+                    gltf::AccessorIndex(accessor_base_index + 4),
+                ),
+                (
+                    gltf::MeshAttribute::Weights(0),
+                    gltf::AccessorIndex(accessor_base_index + 5),
+                ),
+            ]
+            .into_iter()
+            .filter(|(attribute, _)| {
+                texcoords.is_some() || *attribute != gltf::MeshAttribute::Texcoord(0)
+            })
+            .collect(),
+            material: Some(material_index),
+        });
+    }
+    let mut meshes = Vec::new();
+    let mut scene_nodes = Vec::new();
+    for (name, primitives) in split_mesh_primitives(mesh_primitives, options.split_by) {
+        scene_nodes.push(gltf::NodeIndex(nodes.len()));
+        nodes.push(gltf::Node {
+            name: name.clone(),
+            mesh: Some(gltf::MeshIndex(meshes.len())),
+            skin: Some(gltf::SkinIndex(0)),
+            ..Default::default()
+        });
+        meshes.push(gltf::Mesh { name, primitives });
+    }
+    scene_nodes.push(skeleton_root_node_index);
+    scene_nodes.extend(detached_effect_nodes);
+    if let Some(offset) = options.root_offset {
+        let root = gltf::NodeIndex(nodes.len());
+        nodes.push(gltf::Node {
+            name: "root".to_string(),
+            children: std::mem::take(&mut scene_nodes),
+            transform: gltf::Transform::Decomposed {
+                translation: Some(Translation3::from(Vector3::from(offset))),
+                rotation: None,
+                scale: None,
+            },
+            ..Default::default()
+        });
+        scene_nodes.push(root);
+    }
+
+    // Animate the joints. The keys of each animation share one time accessor, and channels for
+    // bones the skeleton doesn't have are dropped.
+    let mut animation_buffer = Vec::new();
+    let mut animations = Vec::new();
+    for (name, anim) in &mesh.skin.as_ref().unwrap().animations {
+        let times_accessor = gltf::AccessorIndex(accessors.len());
+        accessors.push(gltf::Accessor {
+            buffer_view: Some(gltf::BufferViewIndex(3)),
+            byte_offset: animation_buffer.len(),
+            type_: gltf::AccessorType::Scalar,
+            component_type: gltf::AccessorComponentType::Float,
+            normalized: false,
+            count: anim.times.len(),
+            min: anim.times.first().map(|&time| vec![time]),
+            max: anim.times.last().map(|&time| vec![time]),
+        });
+        for &time in &anim.times {
+            animation_buffer.write_f32::<LittleEndian>(time)?;
+        }
+        let mut animation = gltf::Animation {
+            name: name.clone(),
+            ..Default::default()
+        };
+        for channel in &anim.channels {
+            let Some(&joint) = joints_by_bone_id.get(&channel.bone_id) else {
+                continue;
+            };
+            let rotations: Vec<&[f32]> = channel.rotations.iter().map(|r| &r[..]).collect();
+            let translations: Vec<&[f32]> = channel.translations.iter().map(|t| &t[..]).collect();
+            for (path, type_, values) in [
+                (
+                    gltf::AnimationTargetPath::Rotation,
+                    gltf::AccessorType::Vec4,
+                    rotations,
                 ),
                 (
-                    gltf::MeshAttribute::Weights(0),
-                    gltf::AccessorIndex(accessor_base_index + 5),
+                    gltf::AnimationTargetPath::Translation,
+                    gltf::AccessorType::Vec3,
+                    translations,
                 ),
-            ]
-            .into_iter()
-            .collect(),
-            material: Some(gltf::MaterialIndex(first_texture_index)),
-        });
+            ] {
+                if values.is_empty() {
+                    continue;
+                }
+                accessors.push(gltf::Accessor {
+                    buffer_view: Some(gltf::BufferViewIndex(3)),
+                    byte_offset: animation_buffer.len(),
+                    type_,
+                    component_type: gltf::AccessorComponentType::Float,
+                    normalized: false,
+                    count: values.len(),
+                    min: None,
+                    max: None,
+                });
+                for &x in values.iter().copied().flatten() {
+                    animation_buffer.write_f32::<LittleEndian>(x)?;
+                }
+                animation.samplers.push(gltf::AnimationSampler {
+                    input: times_accessor,
+                    interpolation: gltf::AnimationInterpolation::Linear,
+                    output: gltf::AccessorIndex(accessors.len() - 1),
+                });
+                animation.channels.push(gltf::AnimationChannel {
+                    sampler: gltf::AnimationSamplerIndex(animation.samplers.len() - 1),
+                    target: gltf::AnimationChannelTarget {
+                        node: skin.joints[joint as usize],
+                        path,
+                    },
+                });
+            }
+        }
+        animations.push(animation);
     }
-    let mesh_node_index = gltf::NodeIndex(nodes.len());
-    nodes.push(gltf::Node {
-        name: "mesh".to_string(),
-        mesh: Some(gltf::MeshIndex(0)),
-        skin: Some(gltf::SkinIndex(0)),
-        ..Default::default()
-    });
 
     // Write out the index and attribute buffers to a single externally referenced file.
-    let mut buffer_file = BufWriter::new(File::create("gltf_export.bin")?);
+    let mut buffer_file = BufWriter::new(File::create(files.path(".bin"))?);
     buffer_file.write_all(&index_buffer)?;
     buffer_file.write_all(&attribute_buffer)?;
+    stats.index_buffer_size = index_buffer.len();
+    stats.attribute_buffer_size = attribute_buffer.len();
     buffer_file.write_all(&inverse_bind_pose_buffer)?;
+    buffer_file.write_all(&animation_buffer)?;
     buffer_file.flush()?;
     drop(buffer_file);
 
+    let mut buffer_views = vec![
+        gltf::BufferView {
+            buffer: gltf::BufferIndex(0),
+            byte_offset: 0,
+            byte_length: index_buffer.len(),
+            byte_stride: None,
+        },
+        gltf::BufferView {
+            buffer: gltf::BufferIndex(0),
+            byte_offset: index_buffer.len(),
+            byte_length: attribute_buffer.len(),
+            byte_stride: Some(ATTRIBUTE_STRIDE),
+        },
+        gltf::BufferView {
+            buffer: gltf::BufferIndex(0),
+            byte_offset: index_buffer.len() + attribute_buffer.len(),
+            byte_length: inverse_bind_pose_buffer.len(),
+            byte_stride: None,
+        },
+    ];
+    // glTF forbids empty buffer views.
+    if !animation_buffer.is_empty() {
+        buffer_views.push(gltf::BufferView {
+            buffer: gltf::BufferIndex(0),
+            byte_offset: index_buffer.len()
+                + attribute_buffer.len()
+                + inverse_bind_pose_buffer.len(),
+            byte_length: animation_buffer.len(),
+            byte_stride: None,
+        });
+    }
+
     // Build the rest of the glTF file.
     Ok(Gltf {
+        extensions_used: extensions_used(&materials),
+        extensions_required: Vec::new(),
         accessors,
+        animations,
         asset: gltf::Asset {
             version: gltf::Version,
         },
         buffers: vec![gltf::Buffer {
             byte_length: index_buffer.len()
                 + attribute_buffer.len()
-                + inverse_bind_pose_buffer.len(),
-            uri: "gltf_export.bin".to_string(),
+                + inverse_bind_pose_buffer.len()
+                + animation_buffer.len(),
+            uri: files.file_name(".bin"),
         }],
-        buffer_views: vec![
-            gltf::BufferView {
-                buffer: gltf::BufferIndex(0),
-                byte_offset: 0,
-                byte_length: index_buffer.len(),
-                byte_stride: None,
-            },
-            gltf::BufferView {
-                buffer: gltf::BufferIndex(0),
-                byte_offset: index_buffer.len(),
-                byte_length: attribute_buffer.len(),
-                byte_stride: Some(ATTRIBUTE_STRIDE),
-            },
-            gltf::BufferView {
-                buffer: gltf::BufferIndex(0),
-                byte_offset: index_buffer.len() + attribute_buffer.len(),
-                byte_length: inverse_bind_pose_buffer.len(),
-                byte_stride: None,
-            },
-        ],
+        buffer_views,
+        cameras: vec![],
         images,
         materials,
-        meshes: vec![gltf::Mesh {
-            primitives: mesh_primitives,
-        }],
+        meshes,
         nodes,
         samplers: vec![gltf::Sampler {
             mag_filter: gltf::SamplerMagFilter::Linear,
@@ -831,14 +4770,265 @@ fn make_skinned_gltf_document(pak: &mut PakCache, mesh: &CanonicalMesh) -> Resul
         scene: Some(gltf::SceneIndex(0)),
         scenes: vec![gltf::Scene {
             name: "scene".to_string(),
-            nodes: vec![mesh_node_index, skeleton_root_node_index],
-            ..Default::default()
+            nodes: scene_nodes,
         }],
         skins: vec![skin],
         textures,
     })
 }
 
+/// Fails if a surface's normals or texture coordinates don't line up with its positions, which
+/// would otherwise leave vertices reading past the end of an attribute.
+fn check_surface_attributes(surface_index: usize, surface: &CanonicalMeshSurface) -> Result<()> {
+    let count = surface.positions.len();
+    if surface.normals.len() != count {
+        bail!(
+            "Surface {surface_index} has {count} positions but {} normals",
+            surface.normals.len(),
+        );
+    }
+    if let Some(set) = surface.texcoords.iter().position(|set| set.len() != count) {
+        bail!(
+            "Surface {surface_index} has {count} positions but {} coordinates in texture \
+             coordinate set {set}",
+            surface.texcoords[set].len(),
+        );
+    }
+    Ok(())
+}
+
+/// Writes one of a model's textures as a PNG at `path`, following `policy` if the pak doesn't have
+/// it. Returns whether a texture was written.
+fn write_export_texture(
+    pak: &mut PakCache,
+    texture_id: u32,
+    policy: MissingTexture,
+    path: &Path,
+) -> Result<bool> {
+    let texture = match pak.data_with_fourcc(texture_id, "TXTR")? {
+        Some(texture_data) => txtr::decode(texture_data.as_slice())?,
+        None => match policy {
+            MissingTexture::Placeholder => {
+                parse::warn(format_args!(
+                    "Texture 0x{texture_id:08x} not found; substituted a placeholder",
+                ));
+                placeholder_texture()
+            }
+            MissingTexture::Skip => {
+                parse::warn(format_args!(
+                    "Texture 0x{texture_id:08x} not found; its materials are untextured",
+                ));
+                return Ok(false);
+            }
+            MissingTexture::Error => bail!("Texture 0x{texture_id:08x} not found"),
+        },
+    };
+    let mut file = BufWriter::new(File::create(path)?);
+    texture.write_png(&mut file)?;
+    file.flush()?;
+    Ok(true)
+}
+
+/// A magenta and black checkerboard standing in for a missing texture.
+fn placeholder_texture() -> DecodedTexture {
+    const SIZE: usize = 8;
+    let rgba = (0..SIZE * SIZE)
+        .flat_map(|i| match (i / SIZE + i % SIZE) % 2 {
+            0 => [255, 0, 255, 255],
+            _ => [0, 0, 0, 255],
+        })
+        .collect();
+    DecodedTexture {
+        width: SIZE,
+        height: SIZE,
+        rgba,
+    }
+}
+
+fn base_color_material(texture: gltf::TextureIndex) -> gltf::Material {
+    gltf::Material {
+        pbr_metallic_roughness: Some(gltf::PbrMetallicRoughness {
+            base_color_factor: None,
+            base_color_texture: Some(gltf::TextureInfo {
+                index: texture,
+                tex_coord: Some(0),
+                extensions: None,
+            }),
+            metallic_factor: Some(1.0),
+            roughness_factor: Some(0.25),
+            metallic_roughness_texture: None,
+        }),
+        alpha_mode: None,
+        double_sided: false,
+        extensions: None,
+    }
+}
+
+/// Bakes the TEV stages of each material the mesh uses into a texture, adding a glTF image,
+/// texture, and material for each one that bakes. Returns the glTF material index for each baked
+/// mesh material index.
+fn bake_surface_materials(
+    pak: &mut PakCache,
+    mesh: &CanonicalMesh,
+    files: &ExportFiles,
+    images: &mut Vec<gltf::Image>,
+    textures: &mut Vec<gltf::Texture>,
+    materials: &mut Vec<gltf::Material>,
+) -> Result<HashMap<usize, usize>> {
+    let mut decoded_textures = Vec::new();
+    for &texture_id in &mesh.texture_ids {
+        decoded_textures.push(match pak.data_with_fourcc(texture_id, "TXTR")? {
+            Some(texture_data) => txtr::decode(texture_data.as_slice())?,
+            // The missing texture policy was applied, with a warning, when the model's textures
+            // were exported.
+            None => placeholder_texture(),
+        });
+    }
+
+    let material_indices: BTreeSet<usize> = mesh
+        .surfaces
+        .iter()
+        .map(|surface| surface.material_index)
+        .collect();
+    let mut baked_materials = HashMap::new();
+    for material_index in material_indices {
+        let material = &mesh.materials[material_index];
+        let material_textures: Vec<_> = material
+            .texture_indices
+            .iter()
+            .map(|&index| &decoded_textures[index as usize])
+            .collect();
+        let Some(baked) = tev::bake(material, &material_textures)? else {
+            continue;
+        };
+
+        let filename = files.file_name(&format!("_baked_{material_index:02}.png"));
+        let mut file = BufWriter::new(File::create(files.dir.join(&filename))?);
+        baked.write_png(&mut file)?;
+        file.flush()?;
+        drop(file);
+
+        let texture_index = gltf::TextureIndex(textures.len());
+        textures.push(gltf::Texture {
+            sampler: Some(gltf::SamplerIndex(0)),
+            source: Some(gltf::ImageIndex(images.len())),
+        });
+        images.push(gltf::Image {
+            uri: Some(filename),
+            mime_type: None,
+            buffer_view: None,
+        });
+        baked_materials.insert(material_index, materials.len());
+        materials.push(base_color_material(texture_index));
+    }
+    Ok(baked_materials)
+}
+
+/// How a surface's glTF material differs from the base material for its texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct MaterialVariant {
+    /// Index of the glTF material to derive from.
+    base: usize,
+    unlit: bool,
+    /// Index of a glTF texture to approximate an environment reflection with.
+    reflection_texture: Option<usize>,
+}
+
+/// Returns the glTF material for a surface, deriving a variant of its base material on first use.
+fn surface_material(
+    materials: &mut Vec<gltf::Material>,
+    variants: &mut HashMap<MaterialVariant, gltf::MaterialIndex>,
+    variant: MaterialVariant,
+) -> gltf::MaterialIndex {
+    if !variant.unlit && variant.reflection_texture.is_none() {
+        return gltf::MaterialIndex(variant.base);
+    }
+    *variants.entry(variant).or_insert_with(|| {
+        let mut material = materials[variant.base].clone();
+        if let (Some(texture), Some(pbr)) = (
+            variant.reflection_texture,
+            material.pbr_metallic_roughness.as_mut(),
+        ) {
+            // There's no glTF equivalent of a reflection lookup, so render the surface as fully
+            // metallic and let the reflection texture modulate metalness and roughness.
+            pbr.metallic_factor = Some(1.0);
+            pbr.roughness_factor = Some(1.0);
+            pbr.metallic_roughness_texture = Some(gltf::TextureInfo {
+                index: gltf::TextureIndex(texture),
+                tex_coord: Some(0),
+                extensions: None,
+            });
+        }
+        if variant.unlit {
+            material.extensions = Some(gltf::MaterialExtensions {
+                khr_materials_unlit: Some(gltf::KhrMaterialsUnlit {}),
+            });
+        }
+        let index = gltf::MaterialIndex(materials.len());
+        materials.push(material);
+        index
+    })
+}
+
+/// Returns the glTF texture index of the reflection texture of a surface's material, if it has
+/// one.
+fn reflection_texture(mesh: &CanonicalMesh, surface: &CanonicalMeshSurface) -> Option<usize> {
+    let material = &mesh.materials[surface.material_index];
+    let slot = material.reflection_indirect_texture_slot?;
+    material
+        .texture_indices
+        .get(slot as usize)
+        .map(|&index| index as usize)
+}
+
+fn extensions_used(materials: &[gltf::Material]) -> Vec<String> {
+    let unlit = materials.iter().any(|material| {
+        material
+            .extensions
+            .as_ref()
+            .is_some_and(|extensions| extensions.khr_materials_unlit.is_some())
+    });
+    if unlit {
+        vec![gltf::KhrMaterialsUnlit::NAME.to_string()]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Groups mesh primitives, one per surface, into named glTF meshes.
+fn split_mesh_primitives(
+    primitives: Vec<gltf::MeshPrimitive>,
+    split_by: SplitBy,
+) -> Vec<(String, Vec<gltf::MeshPrimitive>)> {
+    match split_by {
+        SplitBy::None => vec![("mesh".to_string(), primitives)],
+        SplitBy::Surface => primitives
+            .into_iter()
+            .enumerate()
+            .map(|(index, primitive)| (format!("surface_{index:03}"), vec![primitive]))
+            .collect(),
+        SplitBy::Material => {
+            let mut primitives_by_material = BTreeMap::<_, Vec<_>>::new();
+            for primitive in primitives {
+                primitives_by_material
+                    .entry(primitive.material)
+                    .or_default()
+                    .push(primitive);
+            }
+            primitives_by_material
+                .into_iter()
+                .map(|(material, primitives)| {
+                    let name = match material {
+                        Some(material) => format!("material_{:02}", material.0),
+                        None => "unmaterialed".to_string(),
+                    };
+                    (name, primitives)
+                })
+                .collect()
+        }
+    }
+}
+
 fn extract_nodes_from_bone(
     nodes: &mut Vec<gltf::Node>,
     joints: &mut Vec<gltf::NodeIndex>,
@@ -846,7 +5036,7 @@ fn extract_nodes_from_bone(
     origin: Vector3<f32>,
     bone: &mesh::CanonicalMeshBone,
 ) -> gltf::NodeIndex {
-    let position = Vector3::from_column_slice(&bone.position).into();
+    let position = Vector3::from_column_slice(&bone.position);
     let children = bone
         .children
         .iter()
@@ -863,7 +5053,9 @@ fn extract_nodes_from_bone(
             scale: None,
         },
         mesh: None,
+        camera: None,
         skin: None,
+        extras: None,
     });
 
     let joint = joints.len();
@@ -873,24 +5065,632 @@ fn extract_nodes_from_bone(
     index
 }
 
-fn verify_disc(header: &Header) -> Result<()> {
-    if header.game_code() != "GM8E" {
-        bail!(
-            "Disc check: game code is {:?}, want \"GM8E\"",
-            header.game_code()
+/// Appends a cube spanning -1 to 1 on each axis to the static index and attribute buffers and
+/// returns a primitive that draws it, as a wireframe or, if `filled`, as triangles.
+fn write_unit_box(
+    index_buffer: &mut Vec<u8>,
+    attribute_buffer: &mut Vec<u8>,
+    accessors: &mut Vec<gltf::Accessor>,
+    filled: bool,
+) -> Result<gltf::MeshPrimitive> {
+    const EDGES: [u16; 24] = [
+        0, 1, 2, 3, 4, 5, 6, 7, 0, 2, 1, 3, 4, 6, 5, 7, 0, 4, 1, 5, 2, 6, 3, 7,
+    ];
+    // Two counterclockwise triangles per face, seen from outside.
+    const FACES: [u16; 36] = [
+        0, 4, 6, 0, 6, 2, 1, 3, 7, 1, 7, 5, 0, 1, 5, 0, 5, 4, 2, 6, 7, 2, 7, 3, 0, 2, 3, 0, 3, 1,
+        4, 5, 7, 4, 7, 6,
+    ];
+    let (mode, indices) = if filled {
+        (gltf::MeshPrimitiveMode::Triangles, &FACES[..])
+    } else {
+        (gltf::MeshPrimitiveMode::Lines, &EDGES[..])
+    };
+
+    let index_byte_offset = index_buffer.len();
+    let attribute_byte_offset = attribute_buffer.len();
+    for corner in 0..8 {
+        let axis = |bit: u32| if corner & (1 << bit) == 0 { -1.0 } else { 1.0 };
+        let position = [axis(0), axis(1), axis(2)];
+        StaticVertex {
+            position,
+            normal: Vector3::from(position).normalize().into(),
+            texcoord: [0.0, 0.0],
+            extra_texcoords: [[0.0; 2]; 7],
+            colors: [[0; 4]; 2],
+        }
+        .write_to(attribute_buffer)?;
+    }
+    for &index in indices {
+        index_buffer.write_u16::<LittleEndian>(index)?;
+    }
+
+    let accessor_base_index = accessors.len();
+    accessors.push(gltf::Accessor {
+        buffer_view: Some(gltf::BufferViewIndex(0)),
+        byte_offset: index_byte_offset,
+        type_: gltf::AccessorType::Scalar,
+        component_type: gltf::AccessorComponentType::UnsignedShort,
+        normalized: false,
+        count: indices.len(),
+        min: None,
+        max: None,
+    });
+    accessors.push(gltf::Accessor {
+        buffer_view: Some(gltf::BufferViewIndex(1)),
+        byte_offset: attribute_byte_offset,
+        type_: gltf::AccessorType::Vec3,
+        component_type: gltf::AccessorComponentType::Float,
+        normalized: false,
+        count: 8,
+        min: Some(vec![-1.0; 3]),
+        max: Some(vec![1.0; 3]),
+    });
+
+    Ok(gltf::MeshPrimitive {
+        mode,
+        indices: gltf::AccessorIndex(accessor_base_index),
+        attributes: [(
+            gltf::MeshAttribute::Position,
+            gltf::AccessorIndex(accessor_base_index + 1),
+        )]
+        .into_iter()
+        .collect(),
+        material: None,
+    })
+}
+
+/// Adds a node scaling `box_mesh` to each animation's bounds, plus one for their union, under a
+/// common parent node. Returns the parent.
+fn add_animation_bounds_nodes(
+    nodes: &mut Vec<gltf::Node>,
+    box_mesh: gltf::MeshIndex,
+    aabbs: &[AnimationAabb],
+) -> gltf::NodeIndex {
+    let mut union_min = Vector3::repeat(f32::INFINITY);
+    let mut union_max = Vector3::repeat(f32::NEG_INFINITY);
+    let mut children = Vec::new();
+    let mut push_box =
+        |nodes: &mut Vec<gltf::Node>, name: String, min: Vector3<f32>, max: Vector3<f32>| {
+            children.push(gltf::NodeIndex(nodes.len()));
+            nodes.push(gltf::Node {
+                name,
+                transform: gltf::Transform::Decomposed {
+                    translation: Some(((min + max) / 2.0).into()),
+                    rotation: None,
+                    scale: Some(((max - min) / 2.0).into()),
+                },
+                mesh: Some(box_mesh),
+                ..Default::default()
+            });
+        };
+    for aabb in aabbs {
+        let min = Vector3::new(aabb.min_x, aabb.min_y, aabb.min_z);
+        let max = Vector3::new(aabb.max_x, aabb.max_y, aabb.max_z);
+        union_min = union_min.inf(&min);
+        union_max = union_max.sup(&max);
+        push_box(nodes, aabb.name.clone(), min, max);
+    }
+    push_box(nodes, "union".to_string(), union_min, union_max);
+
+    let index = gltf::NodeIndex(nodes.len());
+    nodes.push(gltf::Node {
+        name: "animation_bounds".to_string(),
+        children,
+        ..Default::default()
+    });
+    index
+}
+
+/// Adds a node scaling `box_mesh` to each octree cell under a common parent node, recording the
+/// cell's node index and world models in extras. Returns the parent.
+fn add_octree_nodes(
+    nodes: &mut Vec<gltf::Node>,
+    box_mesh: gltf::MeshIndex,
+    cells: &[OctreeCell],
+) -> gltf::NodeIndex {
+    let mut children = Vec::new();
+    for cell in cells {
+        let min = Vector3::from(cell.min);
+        let max = Vector3::from(cell.max);
+        children.push(gltf::NodeIndex(nodes.len()));
+        nodes.push(gltf::Node {
+            name: format!("cell_{}", cell.node_index),
+            transform: gltf::Transform::Decomposed {
+                translation: Some(((min + max) / 2.0).into()),
+                rotation: None,
+                scale: Some(((max - min) / 2.0).into()),
+            },
+            mesh: Some(box_mesh),
+            extras: Some(serde_json::json!({
+                "octree_node": cell.node_index,
+                "world_models": cell.world_models,
+            })),
+            ..Default::default()
+        });
+    }
+
+    let index = gltf::NodeIndex(nodes.len());
+    nodes.push(gltf::Node {
+        name: "area_octree".to_string(),
+        children,
+        ..Default::default()
+    });
+    index
+}
+
+/// The translucent unit box drawn for script object volumes, made on first use and reused after.
+fn add_volume_mesh(
+    volume_mesh: &mut Option<gltf::MeshIndex>,
+    index_buffer: &mut Vec<u8>,
+    attribute_buffer: &mut Vec<u8>,
+    accessors: &mut Vec<gltf::Accessor>,
+    materials: &mut Vec<gltf::Material>,
+    meshes: &mut Vec<gltf::Mesh>,
+) -> Result<gltf::MeshIndex> {
+    if let Some(index) = *volume_mesh {
+        return Ok(index);
+    }
+    let mut faces = write_unit_box(index_buffer, attribute_buffer, accessors, true)?;
+    faces.material = Some(gltf::MaterialIndex(materials.len()));
+    materials.push(debug_box_material([1.0, 0.5, 0.1, 0.2]));
+    let index = gltf::MeshIndex(meshes.len());
+    meshes.push(gltf::Mesh {
+        name: "script_volume".to_string(),
+        primitives: vec![
+            faces,
+            write_unit_box(index_buffer, attribute_buffer, accessors, false)?,
+        ],
+    });
+    *volume_mesh = Some(index);
+    Ok(index)
+}
+
+/// The meshes drawn for script objects.
+#[derive(Clone, Copy)]
+struct ScriptGizmos {
+    /// A wireframe unit box, drawn at half size at each object.
+    point: gltf::MeshIndex,
+    /// A translucent unit box, scaled to each volume.
+    volume: gltf::MeshIndex,
+}
+
+/// Adds a node placing each script marker, with the object's type, instance ID, and scale in
+/// extras, under a node per script layer. The layer nodes share a common parent node, which is
+/// returned.
+fn add_script_marker_nodes(
+    nodes: &mut Vec<gltf::Node>,
+    markers: &[ScriptMarker],
+    gizmos: Option<ScriptGizmos>,
+) -> gltf::NodeIndex {
+    let mut layers = BTreeMap::<usize, Vec<gltf::NodeIndex>>::new();
+    for marker in markers {
+        let mut children = Vec::new();
+        if let Some(gizmos) = gizmos {
+            let (name, mesh, scale) = match marker.volume {
+                Some(volume) => ("volume", gizmos.volume, Vector3::from(volume) / 2.0),
+                None => ("gizmo", gizmos.point, Vector3::repeat(0.5)),
+            };
+            children.push(gltf::NodeIndex(nodes.len()));
+            nodes.push(gltf::Node {
+                name: name.to_string(),
+                transform: gltf::Transform::Decomposed {
+                    translation: None,
+                    rotation: None,
+                    scale: Some(scale.into()),
+                },
+                mesh: Some(mesh),
+                ..Default::default()
+            });
+        }
+        layers
+            .entry(marker.layer)
+            .or_default()
+            .push(gltf::NodeIndex(nodes.len()));
+        nodes.push(gltf::Node {
+            name: marker.display_name(),
+            children,
+            transform: gltf::Transform::Matrix(marker.transform),
+            extras: Some(serde_json::json!({
+                "type": marker.type_name,
+                "object_type": marker.object_type,
+                "instance_id": format!("0x{:08x}", marker.instance_id),
+                "scale": marker.scale,
+                "volume": marker.volume,
+                "destination": marker.destination.as_ref().map(|destination| serde_json::json!({
+                    "mrea_id": format!("0x{:08x}", destination.mrea_id),
+                    "world": destination.world,
+                    "area": destination.area,
+                })),
+            })),
+            ..Default::default()
+        });
+    }
+
+    let mut children = Vec::new();
+    for (layer, layer_children) in layers {
+        children.push(gltf::NodeIndex(nodes.len()));
+        nodes.push(gltf::Node {
+            name: format!("layer_{layer}"),
+            children: layer_children,
+            ..Default::default()
+        });
+    }
+    let index = gltf::NodeIndex(nodes.len());
+    nodes.push(gltf::Node {
+        name: "script_objects".to_string(),
+        children,
+        ..Default::default()
+    });
+    index
+}
+
+/// The world holding an area, and the area's index among its areas, from any pak.
+fn find_area_world(resources: &DiscResources, mrea_id: u32) -> Result<Option<(Mlvl, usize)>> {
+    for mlvl_id in resources.iter_by_type("MLVL") {
+        let Some(data) = resources.get("MLVL", mlvl_id)? else {
+            continue;
+        };
+        let mlvl: Mlvl = parse::read_resource("MLVL", &data)?;
+        if let Some(index) = mlvl.areas.iter().position(|area| area.mrea_id == mrea_id) {
+            return Ok(Some((mlvl, index)));
+        }
+    }
+    Ok(None)
+}
+
+/// Appends a line through `points` to the static index and attribute buffers and returns a
+/// primitive that draws it.
+fn write_line_strip(
+    index_buffer: &mut Vec<u8>,
+    attribute_buffer: &mut Vec<u8>,
+    accessors: &mut Vec<gltf::Accessor>,
+    points: &[Vector3<f32>],
+) -> Result<gltf::MeshPrimitive> {
+    let index_byte_offset = index_buffer.len();
+    let attribute_byte_offset = attribute_buffer.len();
+    let mut min = Vector3::repeat(f32::INFINITY);
+    let mut max = Vector3::repeat(f32::NEG_INFINITY);
+    for (index, point) in points.iter().enumerate() {
+        min = min.inf(point);
+        max = max.sup(point);
+        StaticVertex {
+            position: (*point).into(),
+            normal: [0.0, 0.0, 1.0],
+            texcoord: [0.0, 0.0],
+            extra_texcoords: [[0.0; 2]; 7],
+            colors: [[0; 4]; 2],
+        }
+        .write_to(attribute_buffer)?;
+        index_buffer.write_u16::<LittleEndian>(index.try_into()?)?;
+    }
+
+    let accessor_base_index = accessors.len();
+    accessors.push(gltf::Accessor {
+        buffer_view: Some(gltf::BufferViewIndex(0)),
+        byte_offset: index_byte_offset,
+        type_: gltf::AccessorType::Scalar,
+        component_type: gltf::AccessorComponentType::UnsignedShort,
+        normalized: false,
+        count: points.len(),
+        min: None,
+        max: None,
+    });
+    accessors.push(gltf::Accessor {
+        buffer_view: Some(gltf::BufferViewIndex(1)),
+        byte_offset: attribute_byte_offset,
+        type_: gltf::AccessorType::Vec3,
+        component_type: gltf::AccessorComponentType::Float,
+        normalized: false,
+        count: points.len(),
+        min: Some(min.iter().copied().collect()),
+        max: Some(max.iter().copied().collect()),
+    });
+
+    Ok(gltf::MeshPrimitive {
+        mode: gltf::MeshPrimitiveMode::LineStrip,
+        indices: gltf::AccessorIndex(accessor_base_index),
+        attributes: [(
+            gltf::MeshAttribute::Position,
+            gltf::AccessorIndex(accessor_base_index + 1),
+        )]
+        .into_iter()
+        .collect(),
+        material: None,
+    })
+}
+
+/// The field of view of Prime's cameras when a camera object doesn't set one, in degrees.
+const DEFAULT_FIELD_OF_VIEW: f32 = 55.0;
+
+/// Adds a glTF camera placed at each camera script marker, and a polyline for each cinematic
+/// camera straight through the waypoints it follows, under a common parent node. Returns the
+/// parent, or `None` if there are no camera markers.
+fn add_camera_nodes(
+    index_buffer: &mut Vec<u8>,
+    attribute_buffer: &mut Vec<u8>,
+    accessors: &mut Vec<gltf::Accessor>,
+    meshes: &mut Vec<gltf::Mesh>,
+    cameras: &mut Vec<gltf::Camera>,
+    nodes: &mut Vec<gltf::Node>,
+    markers: &[ScriptMarker],
+) -> Result<Option<gltf::NodeIndex>> {
+    const CAMERA_TYPES: [&str; 4] = ["Camera", "CameraWaypoint", "CameraHint", "SpindleCamera"];
+    let camera_markers: Vec<&ScriptMarker> = markers
+        .iter()
+        .filter(|marker| CAMERA_TYPES.contains(&marker.type_name))
+        .collect();
+    if camera_markers.is_empty() {
+        return Ok(None);
+    }
+    let position = |marker: &ScriptMarker| marker.transform.column(3).xyz();
+
+    let mut children = Vec::new();
+    for &marker in &camera_markers {
+        let camera = gltf::CameraIndex(cameras.len());
+        cameras.push(gltf::Camera {
+            name: marker.display_name(),
+            type_: gltf::CameraType::Perspective,
+            perspective: Some(gltf::CameraPerspective {
+                aspect_ratio: None,
+                yfov: marker
+                    .field_of_view
+                    .unwrap_or(DEFAULT_FIELD_OF_VIEW)
+                    .to_radians(),
+                zfar: None,
+                znear: 0.2,
+            }),
+        });
+        // glTF cameras look down -Z with +Y up, where Prime's objects face +Y with +Z up.
+        let camera_node = gltf::NodeIndex(nodes.len());
+        nodes.push(gltf::Node {
+            name: "camera".to_string(),
+            transform: gltf::Transform::Decomposed {
+                translation: None,
+                rotation: Some(UnitQuaternion::from_axis_angle(
+                    &Vector3::x_axis(),
+                    FRAC_PI_2,
+                )),
+                scale: None,
+            },
+            camera: Some(camera),
+            ..Default::default()
+        });
+        children.push(gltf::NodeIndex(nodes.len()));
+        nodes.push(gltf::Node {
+            name: marker.display_name(),
+            children: vec![camera_node],
+            transform: gltf::Transform::Matrix(marker.transform),
+            extras: Some(serde_json::json!({
+                "type": marker.type_name,
+                "instance_id": format!("0x{:08x}", marker.instance_id),
+                "field_of_view": marker.field_of_view,
+            })),
+            ..Default::default()
+        });
+    }
+
+    // A cinematic camera messages the first waypoint of its path, and each waypoint the next.
+    let waypoints: HashMap<u32, &ScriptMarker> = camera_markers
+        .iter()
+        .filter(|marker| marker.type_name == "CameraWaypoint")
+        .map(|&marker| (marker.instance_id, marker))
+        .collect();
+    for &marker in camera_markers
+        .iter()
+        .filter(|marker| marker.type_name != "CameraWaypoint")
+    {
+        let mut points = vec![position(marker)];
+        let mut path = Vec::new();
+        let mut current = marker;
+        while let Some(&next) = current.targets.iter().find_map(|id| waypoints.get(id)) {
+            if path.contains(&next.instance_id) {
+                break;
+            }
+            points.push(position(next));
+            path.push(next.instance_id);
+            current = next;
+        }
+        if path.is_empty() {
+            continue;
+        }
+        let mesh = gltf::MeshIndex(meshes.len());
+        meshes.push(gltf::Mesh {
+            name: "camera_path".to_string(),
+            primitives: vec![write_line_strip(
+                index_buffer,
+                attribute_buffer,
+                accessors,
+                &points,
+            )?],
+        });
+        children.push(gltf::NodeIndex(nodes.len()));
+        nodes.push(gltf::Node {
+            name: format!("{} path", marker.display_name()),
+            mesh: Some(mesh),
+            extras: Some(serde_json::json!({
+                "camera": format!("0x{:08x}", marker.instance_id),
+                "waypoints": path
+                    .iter()
+                    .map(|id| format!("0x{id:08x}"))
+                    .collect::<Vec<_>>(),
+            })),
+            ..Default::default()
+        });
+    }
+
+    let index = gltf::NodeIndex(nodes.len());
+    nodes.push(gltf::Node {
+        name: "cameras".to_string(),
+        children,
+        ..Default::default()
+    });
+    Ok(Some(index))
+}
+
+/// Adds a node scaling `box_mesh` to the volume of each script marker that has one, recording
+/// the object's type, instance ID, and the volume's bounds in extras. The bounds are in game
+/// coordinates, as the object states them, whatever transform places the node. Returns the new
+/// nodes.
+fn add_trigger_volume_nodes(
+    nodes: &mut Vec<gltf::Node>,
+    box_mesh: gltf::MeshIndex,
+    markers: &[ScriptMarker],
+) -> Vec<gltf::NodeIndex> {
+    let mut indices = Vec::new();
+    for marker in markers {
+        let Some(volume) = marker.volume else {
+            continue;
+        };
+        let half_extent = Vector3::from(volume) / 2.0;
+        let center = Vector3::from(marker.position);
+        indices.push(gltf::NodeIndex(nodes.len()));
+        nodes.push(gltf::Node {
+            name: marker.display_name(),
+            transform: gltf::Transform::Matrix(
+                marker.transform * Matrix4::new_nonuniform_scaling(&half_extent),
+            ),
+            mesh: Some(box_mesh),
+            extras: Some(serde_json::json!({
+                "type": marker.type_name,
+                "instance_id": format!("0x{:08x}", marker.instance_id),
+                "layer": marker.layer,
+                "min": <[f32; 3]>::from(center - half_extent),
+                "max": <[f32; 3]>::from(center + half_extent),
+            })),
+            ..Default::default()
+        });
+    }
+    indices
+}
+
+/// The leaf cells of an area's render octree, or none if it has no octree.
+fn octree_cells(sections: &MreaSections) -> Result<Vec<OctreeCell>> {
+    match sections.octree()? {
+        Some(octree) => octree.cells(),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// A translucent, unlit, double-sided material for debug boxes, so they don't hide the geometry
+/// inside them and stay visible from within. `color`'s alpha sets how translucent.
+fn debug_box_material(color: [f32; 4]) -> gltf::Material {
+    gltf::Material {
+        pbr_metallic_roughness: Some(gltf::PbrMetallicRoughness {
+            base_color_factor: Some(color),
+            base_color_texture: None,
+            metallic_factor: Some(0.0),
+            roughness_factor: Some(1.0),
+            metallic_roughness_texture: None,
+        }),
+        alpha_mode: Some(gltf::AlphaMode::Blend),
+        double_sided: true,
+        extensions: Some(gltf::MaterialExtensions {
+            khr_materials_unlit: Some(gltf::KhrMaterialsUnlit {}),
+        }),
+    }
+}
+
+/// Adds an empty node for each effect component as a child of the bone it's attached to, recording
+/// the particle asset and scale in extras. Returns the nodes whose bone isn't among `joints`, for
+/// the caller to place at the scene root.
+fn add_effect_nodes(
+    nodes: &mut Vec<gltf::Node>,
+    joints: &[gltf::NodeIndex],
+    effects: &[Effect],
+) -> Vec<gltf::NodeIndex> {
+    let bones_by_name: HashMap<String, gltf::NodeIndex> = joints
+        .iter()
+        .map(|&index| (nodes[index.0].name.clone(), index))
+        .collect();
+
+    let mut detached = Vec::new();
+    for effect in effects {
+        for component in &effect.components {
+            let index = gltf::NodeIndex(nodes.len());
+            nodes.push(gltf::Node {
+                name: format!("{}/{}", effect.name, component.name),
+                extras: Some(serde_json::json!({
+                    "effect": effect.name,
+                    "bone": component.bone_name,
+                    "particleAssetType": component.particle_asset_type,
+                    "particleAssetId": format!("0x{:08x}", component.particle_asset_id),
+                    "scale": component.scale,
+                    "parented": component.parented != 0,
+                    "flags": component.flags,
+                })),
+                ..Default::default()
+            });
+            match bones_by_name.get(&component.bone_name) {
+                Some(bone) => nodes[bone.0].children.push(index),
+                None => detached.push(index),
+            }
+        }
+    }
+    detached
+}
+
+/// Prints how much of a resource its parser read, then each range it didn't.
+fn print_trace(trace: &Trace, data: &[u8]) {
+    println!("Read {} of {} bytes", trace.read_len(), trace.len());
+    for hole in trace.holes() {
+        let zero = if data[hole.clone()].iter().all(|&b| b == 0) {
+            ", all zero"
+        } else {
+            ""
+        };
+        println!(
+            "  unread 0x{:x}..0x{:x} ({} bytes{zero})",
+            hole.start,
+            hole.end,
+            hole.len(),
         );
     }
+}
+
+/// The revisions of the USA release: 1.00, 1.01, and the Player's Choice 1.02. Each is accepted;
+/// resources are parsed with the layouts the parsers know, falling back on layout detection where
+/// a resource doesn't parse as the version it states.
+const KNOWN_DISC_VERSIONS: [u8; 3] = [0, 1, 2];
+
+/// The game codes of the PAL and Japanese releases, whose resources differ from the USA release's.
+const OTHER_REGION_GAME_CODES: [&str; 2] = ["GM8P", "GM8J"];
+
+/// Checks that a disc is the USA release of Metroid Prime, or a copy of it made by Rebrand. With
+/// `allow_any_version`, a disc that isn't only gets warnings, and its resources are parsed as best
+/// they can be.
+fn verify_disc(header: &Header, allow_any_version: bool) -> Result<()> {
+    let mut problems = Vec::new();
+    let game_code = header.game_code();
+    if OTHER_REGION_GAME_CODES.contains(&game_code) {
+        problems.push(format!("game code is {game_code:?}, want \"GM8E\""));
+    } else if game_code != "GM8E" {
+        // Rebrand changes the game code, along with the title and banner, which aren't checked.
+        println!("Disc check: game code is {game_code:?}, not \"GM8E\"; taking it as rebranded");
+    }
     if header.maker_code() != "01" {
-        bail!(
-            "Disc check: maker code is {:?}, want \"01\"",
+        problems.push(format!(
+            "maker code is {:?}, want \"01\"",
             header.maker_code()
-        );
+        ));
     }
     if header.disc_id() != 0 {
-        bail!("Disc check: disc ID is {}, want 0", header.disc_id());
+        problems.push(format!("disc ID is {}, want 0", header.disc_id()));
     }
-    if header.version() != 0 {
-        bail!("Disc check: game code is {}, want 0", header.version());
+    if !KNOWN_DISC_VERSIONS.contains(&header.version()) {
+        problems.push(format!(
+            "version is {}, want one of {KNOWN_DISC_VERSIONS:?}",
+            header.version()
+        ));
+    }
+    match problems.first() {
+        None => Ok(()),
+        Some(_) if allow_any_version => {
+            for problem in &problems {
+                println!("Warning: Disc check: {problem}");
+            }
+            Ok(())
+        }
+        Some(problem) => bail!("Disc check: {problem} (pass --allow-any-version to go on anyway)"),
     }
-    Ok(())
 }
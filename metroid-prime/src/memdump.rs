@@ -0,0 +1,250 @@
+//! Reading a raw MEM1 dump, as saved by Dolphin's "Dump MEM1" debug option, to recover resources
+//! as the game holds them at runtime. The game's own resource tables are walked from a root
+//! pointer taken from the build's symbol map; without one, resources are found by scanning for
+//! their magic numbers instead, which only recovers CMDLs so far.
+
+use anyhow::{anyhow, bail, Context, Result};
+use gamecube::bytes::ReadAsciiCStringExt;
+use gamecube::{ReadBytesExt, ReadTypedExt, SliceReader};
+use serde::Serialize;
+
+use crate::cmdl::Cmdl;
+use crate::resource::Resource;
+
+/// A snapshot of main memory, addressed by the game's cached virtual addresses.
+pub struct MemDump<'a> {
+    data: &'a [u8],
+}
+
+/// A resource found in a memory dump.
+pub struct FoundResource<'a> {
+    pub address: u32,
+    pub fourcc: &'static str,
+    pub data: &'a [u8],
+}
+
+/// Where the game keeps its resource tables, as byte offsets into its own structures. The defaults
+/// follow the community decompilation of the NTSC 0-00 build; other builds may move them.
+#[derive(Clone, Copy, Debug)]
+pub struct ResourceTableLayout {
+    /// The offset of the loaded pak list's sentinel node within the resource loader.
+    pub loader_pak_list: u32,
+    /// The offsets of a list node's next pointer and of the pointer to its pak.
+    pub node_next: u32,
+    pub node_pak: u32,
+    /// The offset of the pointer to a pak's NUL-terminated file name.
+    pub pak_name: u32,
+    /// The offsets of the length and data pointer of a pak's resource table vector.
+    pub pak_resource_count: u32,
+    pub pak_resource_data: u32,
+    /// The size of one resource table entry, and the offsets of its fields within it.
+    pub resource_size: u32,
+    pub resource_file_id: u32,
+    pub resource_fourcc: u32,
+    pub resource_offset: u32,
+    pub resource_length: u32,
+}
+
+impl Default for ResourceTableLayout {
+    fn default() -> Self {
+        Self {
+            loader_pak_list: 0x1c,
+            node_next: 0x4,
+            node_pak: 0x8,
+            pak_name: 0x20,
+            pak_resource_count: 0x58,
+            pak_resource_data: 0x60,
+            resource_size: 0x14,
+            resource_file_id: 0x0,
+            resource_fourcc: 0x8,
+            resource_offset: 0xc,
+            resource_length: 0x10,
+        }
+    }
+}
+
+/// A pak the game had open when the dump was taken, as found by [`MemDump::walk_paks`].
+#[derive(Serialize)]
+pub struct LoadedPak {
+    pub address: String,
+    pub name: String,
+    pub resources: Vec<LoadedResource>,
+}
+
+/// One entry of a loaded pak's resource table, locating the resource within the pak file.
+#[derive(Serialize)]
+pub struct LoadedResource {
+    pub file_id: String,
+    pub fourcc: String,
+    pub offset: u32,
+    pub size: u32,
+}
+
+impl<'a> MemDump<'a> {
+    /// The virtual address MEM1 is mapped at.
+    pub const BASE_ADDRESS: u32 = 0x8000_0000;
+    /// The size of MEM1 on retail hardware.
+    pub const SIZE: usize = 24 * 1024 * 1024;
+
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        if data.len() != Self::SIZE {
+            bail!(
+                "Memory dump is {} bytes, want {} for MEM1",
+                data.len(),
+                Self::SIZE,
+            );
+        }
+        Ok(Self { data })
+    }
+
+    /// Whether `address` points into MEM1.
+    pub fn contains(&self, address: u32) -> bool {
+        address
+            .checked_sub(Self::BASE_ADDRESS)
+            .is_some_and(|offset| (offset as usize) < self.data.len())
+    }
+
+    /// Borrows `len` bytes at a virtual address.
+    pub fn slice(&self, address: u32, len: usize) -> Result<&'a [u8]> {
+        if !self.contains(address) {
+            bail!("Address 0x{address:08x} is outside MEM1");
+        }
+        let offset = (address - Self::BASE_ADDRESS) as usize;
+        Ok(SliceReader::new(self.data).slice_at(offset, len)?)
+    }
+
+    pub fn read_u32(&self, address: u32) -> Result<u32> {
+        Ok(self.slice(address, 4)?.read_u32()?)
+    }
+
+    /// Reads a pointer, failing if it's null or points outside MEM1.
+    pub fn read_pointer(&self, address: u32) -> Result<u32> {
+        let pointer = self.read_u32(address)?;
+        if !self.contains(pointer) {
+            bail!("Pointer 0x{pointer:08x} at 0x{address:08x} is null or outside MEM1");
+        }
+        Ok(pointer)
+    }
+
+    /// Follows a chain of pointers: reads the pointer at `base + offsets[0]`, then the pointer at
+    /// that address plus `offsets[1]`, and so on, returning the final pointer.
+    pub fn follow(&self, base: u32, offsets: &[u32]) -> Result<u32> {
+        offsets.iter().try_fold(base, |address, &offset| {
+            self.read_pointer(address.wrapping_add(offset))
+                .map_err(|e| anyhow!("Following pointer chain from 0x{base:08x}: {e}"))
+        })
+    }
+
+    /// Reads a NUL-terminated ASCII string at a virtual address.
+    pub fn read_c_string(&self, address: u32) -> Result<String> {
+        let end = Self::BASE_ADDRESS + self.data.len() as u32;
+        let mut r = self.slice(address, end.saturating_sub(address).min(0x100) as usize)?;
+        Ok(r.read_ascii_c_string()?)
+    }
+
+    /// Walks the resource loader's list of open paks, reading each one's name and resource table.
+    /// `root` is the address of the global pointer to the resource loader, as given by the build's
+    /// symbol map.
+    pub fn walk_paks(&self, root: u32, layout: &ResourceTableLayout) -> Result<Vec<LoadedPak>> {
+        // The list is circular through a sentinel node embedded in the loader.
+        let sentinel = self.follow(root, &[0])? + layout.loader_pak_list;
+        let mut node = self.follow(sentinel, &[layout.node_next])?;
+        let mut paks = Vec::new();
+        while node != sentinel {
+            if paks.len() >= 0x100 {
+                bail!("Pak list at 0x{sentinel:08x} doesn't lead back to its sentinel");
+            }
+            let pak = self.follow(node, &[layout.node_pak])?;
+            paks.push(self.read_pak(pak, layout)?);
+            node = self.follow(node, &[layout.node_next])?;
+        }
+        Ok(paks)
+    }
+
+    fn read_pak(&self, pak: u32, layout: &ResourceTableLayout) -> Result<LoadedPak> {
+        let name = self.read_c_string(self.follow(pak, &[layout.pak_name])?)?;
+        let count = self.read_u32(pak + layout.pak_resource_count)?;
+        if count > 0x10000 {
+            bail!("Implausible resource count {count} in pak at 0x{pak:08x}");
+        }
+        let mut resources = Vec::with_capacity(count as usize);
+        if count > 0 {
+            let table = self.follow(pak, &[layout.pak_resource_data])?;
+            for index in 0..count {
+                let entry = table + index * layout.resource_size;
+                let fourcc = self.slice(entry + layout.resource_fourcc, 4)?;
+                resources.push(LoadedResource {
+                    file_id: format!("0x{:08x}", self.read_u32(entry + layout.resource_file_id)?),
+                    fourcc: String::from_utf8_lossy(fourcc).into_owned(),
+                    offset: self.read_u32(entry + layout.resource_offset)?,
+                    size: self.read_u32(entry + layout.resource_length)?,
+                });
+            }
+        }
+        Ok(LoadedPak {
+            address: format!("0x{pak:08x}"),
+            name,
+            resources,
+        })
+    }
+
+    /// Finds resources by their magic number at 32-byte aligned addresses, which is how the game's
+    /// allocator places them. Only formats whose size can be recovered from their header are
+    /// returned, and only if they parse. This is the fallback for when [`Self::walk_paks`] can't be
+    /// used.
+    pub fn find_resources(&self) -> Vec<FoundResource<'a>> {
+        let mut found = Vec::new();
+        let mut offset = 0;
+        while offset + 4 <= self.data.len() {
+            let data = &self.data[offset..];
+            let size = match Resource::sniff(data) {
+                Some("CMDL") => cmdl_size(data).ok(),
+                _ => None,
+            };
+            if let Some(data) = size.and_then(|size| data.get(..size)) {
+                let mut r = data;
+                if r.read_typed::<Cmdl>().is_ok() {
+                    found.push(FoundResource {
+                        address: Self::BASE_ADDRESS + offset as u32,
+                        fourcc: "CMDL",
+                        data,
+                    });
+                    offset += data.len().next_multiple_of(32);
+                    continue;
+                }
+            }
+            offset += 32;
+        }
+        found
+    }
+}
+
+/// Parses a `0x`-prefixed hex address from the command line.
+pub fn parse_address(s: &str) -> Result<u32> {
+    let hex = s
+        .strip_prefix("0x")
+        .ok_or_else(|| anyhow!("Address {s} isn't 0x-prefixed hex"))?;
+    u32::from_str_radix(hex, 16).with_context(|| format!("Couldn't parse the address {s}"))
+}
+
+/// Computes a CMDL's total size from its section table, checking the version first so stray magic
+/// numbers are rejected before anything is parsed.
+fn cmdl_size(data: &[u8]) -> Result<usize> {
+    let mut r = SliceReader::new(data);
+    r.seek_to(4)?;
+    let version = r.read_u32()?;
+    if version != 2 {
+        bail!("unexpected CMDL version: {version}");
+    }
+    r.seek_to(0x24)?;
+    let section_count = r.read_u32()?;
+    if section_count > 0x1000 {
+        bail!("implausible CMDL section count: {section_count}");
+    }
+    let _material_set_count = r.read_u32()?;
+    let mut size = (0x2c + 4 * section_count as usize).next_multiple_of(32);
+    for _ in 0..section_count {
+        size += (r.read_u32()? as usize).next_multiple_of(32);
+    }
+    Ok(size)
+}
@@ -1,22 +1,217 @@
-use anyhow::{anyhow, Result};
-use gamecube::ReadTypedExt;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::ops::Range;
 
-use crate::ancs::Ancs;
+use anyhow::{anyhow, bail, Result};
+use nalgebra::{Matrix3, Matrix4, Point3, Rotation3, Vector3};
+
+use crate::ancs::{Ancs, AnimationAabb, Effect};
+use crate::anim::Anim;
 use crate::cinf::Cinf;
-use crate::cmdl::Cmdl;
-use crate::cskr::Cskr;
-use crate::gx::{SkinnedVertexDescriptor, StaticVertexDescriptor};
+use crate::cmdl::{Cmdl, Material, MaterialSet, Surface};
+use crate::cskr::{Cskr, Weight};
+use crate::gx::{SkinnedVertexDescriptor, StaticVertexDescriptor, VertexArrays};
+use crate::mlvl::Mlvl;
+use crate::mrea::{MreaSections, OctreeCell};
+use crate::names::AreaNames;
 use crate::pak::PakCache;
+use crate::parse;
+use crate::scly::Link;
+use crate::simplify;
 
 pub struct CanonicalMesh {
     pub skin: Option<CanonicalMeshSkin>,
     pub surfaces: Vec<CanonicalMeshSurface>,
     pub texture_ids: Vec<u32>,
+    /// The materials of the selected material set, indexed by
+    /// `CanonicalMeshSurface::material_index`.
+    pub materials: Vec<Material>,
+    /// The character's particle effects, attached to skeleton bones by name.
+    pub effects: Vec<Effect>,
+    /// The character's per-animation culling bounds.
+    pub animation_aabbs: Vec<AnimationAabb>,
+    /// An area's render octree cells, exported as debug geometry when present.
+    pub octree_cells: Vec<OctreeCell>,
+    /// An area's placed script objects, exported as marker nodes when present.
+    pub script_markers: Vec<ScriptMarker>,
+    /// Groups of surfaces exported as separately placed nodes, or empty to export the surfaces
+    /// together.
+    pub parts: Vec<CanonicalMeshPart>,
+}
+
+/// A named run of surfaces, such as one area of a world, whose vertices are in their own space.
+pub struct CanonicalMeshPart {
+    pub name: String,
+    pub surfaces: Range<usize>,
+    pub octree_cells: Range<usize>,
+    pub script_markers: Range<usize>,
+    pub transform: Matrix4<f32>,
+}
+
+/// A script object placed in an area, such as an actor, trigger, or pickup.
+#[derive(Clone, Debug)]
+pub struct ScriptMarker {
+    pub name: String,
+    pub layer: usize,
+    pub type_name: &'static str,
+    pub object_type: u8,
+    pub instance_id: u32,
+    /// Places the object by its position and rotation. Its scale is kept apart, since it sizes
+    /// the object's model rather than the marker.
+    pub transform: Matrix4<f32>,
+    /// The position as the script object states it, in game coordinates, which transforms
+    /// applied to the mesh leave alone.
+    pub position: [f32; 3],
+    pub scale: [f32; 3],
+    /// The extent of the box the object covers, for triggers and other volumes.
+    pub volume: Option<[f32; 3]>,
+    /// Where the object leads, for docks and world transporters and the doors and triggers that
+    /// message them.
+    pub destination: Option<Destination>,
+    /// The vertical field of view in degrees, for cameras and camera waypoints.
+    pub field_of_view: Option<f32>,
+    /// The instance IDs of the objects this one sends messages to.
+    pub targets: Vec<u32>,
+}
+
+/// The area a door or elevator leads to.
+#[derive(Clone, Debug)]
+pub struct Destination {
+    pub mrea_id: u32,
+    pub world: Option<String>,
+    pub area: Option<String>,
+}
+
+/// An area's place in its world, for finding where its doors and elevators lead.
+pub struct WorldContext<'a> {
+    pub mlvl: &'a Mlvl,
+    pub area_index: usize,
+    pub names: &'a AreaNames,
+}
+
+impl WorldContext<'_> {
+    /// The MREA ID of the area a dock of this area connects to.
+    fn dock_destination(&self, dock_index: u32) -> Option<u32> {
+        let dock = self
+            .mlvl
+            .areas
+            .get(self.area_index)?
+            .docks
+            .get(dock_index as usize)?;
+        let &(area_index, _) = dock.connections.first()?;
+        Some(self.mlvl.areas.get(area_index as usize)?.mrea_id)
+    }
+
+    fn destination(&self, mrea_id: u32) -> Destination {
+        let name = self.names.get(mrea_id);
+        Destination {
+            mrea_id,
+            world: name.map(|name| name.world.clone()),
+            area: name.map(|name| name.area.clone()),
+        }
+    }
+}
+
+impl ScriptMarker {
+    /// The placed objects of every script layer of an area, in area space. Objects whose
+    /// placement doesn't parse are left out with a warning. Given the area's world, doors and
+    /// elevators are marked with where they lead.
+    pub fn from_mrea(sections: &MreaSections, world: Option<&WorldContext>) -> Result<Vec<Self>> {
+        let layers = sections.script_layers()?;
+
+        // Where each dock and world transporter leads, by instance ID.
+        let mut destinations = HashMap::new();
+        if let Some(world) = world {
+            for object in layers.iter().flat_map(|layer| &layer.objects) {
+                let mrea_id = match object.link() {
+                    Ok(Some(Link::Dock { dock_index })) => world.dock_destination(dock_index),
+                    Ok(Some(Link::World { mrea_id, .. })) => Some(mrea_id),
+                    Ok(None) => None,
+                    Err(e) => {
+                        parse::warn(format!(
+                            "Script object 0x{:08x} has an unreadable destination: {e}",
+                            object.instance_id
+                        ));
+                        None
+                    }
+                };
+                if let Some(mrea_id) = mrea_id {
+                    destinations.insert(object.instance_id, world.destination(mrea_id));
+                }
+            }
+        }
+
+        let mut markers = Vec::new();
+        for (layer, script_layer) in layers.iter().enumerate() {
+            for object in &script_layer.objects {
+                let placement = match object.placement() {
+                    Ok(Some(placement)) => placement,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        parse::warn(format!(
+                            "Script object 0x{:08x} has an unreadable placement: {e}",
+                            object.instance_id
+                        ));
+                        continue;
+                    }
+                };
+                let field_of_view = object.field_of_view().unwrap_or_else(|e| {
+                    parse::warn(format!(
+                        "Script object 0x{:08x} has an unreadable field of view: {e}",
+                        object.instance_id
+                    ));
+                    None
+                });
+                let [x, y, z] = placement.rotation.map(f32::to_radians);
+                markers.push(Self {
+                    name: placement.name,
+                    layer,
+                    type_name: object.type_name().unwrap_or_default(),
+                    object_type: object.object_type,
+                    instance_id: object.instance_id,
+                    transform: Matrix4::new_translation(&Vector3::from(placement.position))
+                        * Rotation3::from_euler_angles(x, y, z).to_homogeneous(),
+                    position: placement.position,
+                    scale: placement.scale,
+                    volume: placement.volume,
+                    // Doors and elevator triggers lead where the dock or transporter they
+                    // message does.
+                    destination: destinations
+                        .get(&object.instance_id)
+                        .or_else(|| {
+                            object
+                                .connections
+                                .iter()
+                                .find_map(|connection| destinations.get(&connection.target_id))
+                        })
+                        .cloned(),
+                    field_of_view,
+                    targets: object
+                        .connections
+                        .iter()
+                        .map(|connection| connection.target_id)
+                        .collect(),
+                });
+            }
+        }
+        Ok(markers)
+    }
+
+    /// The object's name, or its type for objects without one.
+    pub fn display_name(&self) -> String {
+        if self.name.is_empty() {
+            self.type_name.to_string()
+        } else {
+            self.name.clone()
+        }
+    }
 }
 
 pub struct CanonicalMeshSkin {
     pub skeleton: CanonicalMeshBone,
     pub skin: Cskr,
+    /// Animations to export with the skeleton, by name.
+    pub animations: Vec<(String, Anim)>,
 }
 
 #[derive(Debug)]
@@ -27,57 +222,270 @@ pub struct CanonicalMeshBone {
     pub children: Vec<CanonicalMeshBone>,
 }
 
+#[derive(Clone)]
 pub struct CanonicalMeshSurface {
+    pub material_index: usize,
     pub texture_indices: Vec<usize>,
+    /// Whether the surface's material ignores scene lighting.
+    pub unlit: bool,
     pub positions: Vec<[f32; 3]>,
     pub normals: Vec<[f32; 3]>,
-    pub texcoords: Vec<[f32; 2]>,
-    pub bone_ids: Vec<u32>,
-    pub weights: Vec<f32>,
+    /// Coordinates for each texture coordinate set the material uses, in order and stopping at the
+    /// first it skips. Sets past the first usually map lightmaps or detail textures.
+    pub texcoords: Vec<Vec<[f32; 2]>>,
+    /// RGBA vertex colors from 0 to 1 for each of the two color channels, each empty if the
+    /// material doesn't use it. World geometry uses the first for baked lighting, which multiplies
+    /// the texture color.
+    pub colors: [Vec<[f32; 4]>; 2],
+    /// The bones skinning each vertex, as many as glTF allows. Unused slots repeat the first bone.
+    pub bone_ids: Vec<[u32; 4]>,
+    /// The weight of each of `bone_ids`, summing to one, with unused slots weighted zero.
+    pub weights: Vec<[f32; 4]>,
 }
 
-impl CanonicalMesh {
-    pub fn from_cmdl(cmdl: &Cmdl, material_set_index: usize) -> Result<Self> {
-        let material_set = &cmdl.materials[material_set_index];
-        let mut surfaces = Vec::new();
-        for surface in &cmdl.surfaces {
-            let mut positions = Vec::new();
-            let mut normals = Vec::new();
-            let mut texcoords = Vec::new();
+/// A reference from one part of a model to another that doesn't exist.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    MaterialSet {
+        index: usize,
+        count: usize,
+    },
+    Material {
+        surface: usize,
+        index: usize,
+        count: usize,
+    },
+    Texture {
+        material: usize,
+        index: usize,
+        count: usize,
+    },
+    /// The skeleton's root bone, or a bone listed as a child or skinned to, isn't in the skeleton.
+    Bone {
+        bone_id: u32,
+    },
+    BoneName {
+        bone_id: u32,
+    },
+}
 
-            let material = &material_set.materials[surface.material_index as usize];
-            let batches = surface.display_list.parse::<StaticVertexDescriptor>(
-                material.vertex_attr_flags,
-                &cmdl.position_data,
-                &cmdl.normal_data,
-                &cmdl.uv_float_data,
-                &(),
-                &(),
-            )?;
-            for batch in batches {
-                positions.extend_from_slice(&batch.positions);
-                normals.extend_from_slice(&batch.normals);
-                texcoords.extend_from_slice(&batch.texcoords);
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MaterialSet { index, count } => {
+                write!(
+                    f,
+                    "Material set {index} is out of range; the model has {count}"
+                )
             }
+            Self::Material {
+                surface,
+                index,
+                count,
+            } => write!(
+                f,
+                "Surface {surface} uses material {index}, but the material set has {count}",
+            ),
+            Self::Texture {
+                material,
+                index,
+                count,
+            } => write!(
+                f,
+                "Material {material} uses texture {index}, but the material set has {count}",
+            ),
+            Self::Bone { bone_id } => write!(f, "Bone {bone_id} isn't in the skeleton"),
+            Self::BoneName { bone_id } => write!(f, "Bone {bone_id} has no name"),
+        }
+    }
+}
 
-            surfaces.push(CanonicalMeshSurface {
-                texture_indices: material
-                    .texture_indices
-                    .iter()
-                    .map(|&x| x as usize)
-                    .collect(),
-                positions,
-                normals,
-                texcoords,
-                bone_ids: Vec::new(),
-                weights: Vec::new(),
+/// Every dangling reference found in a model, reported together so one run shows all of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationErrors(pub Vec<ValidationError>);
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Model has {} invalid references", self.0.len())?;
+        for error in &self.0 {
+            write!(f, "\n  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+impl ValidationErrors {
+    fn into_result(self) -> Result<(), Self> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+/// Checks every index a model's surfaces and materials hold against what they index.
+fn validate_cmdl(cmdl: &Cmdl, material_set_index: usize) -> Vec<ValidationError> {
+    let Some(material_set) = cmdl.materials.get(material_set_index) else {
+        return vec![ValidationError::MaterialSet {
+            index: material_set_index,
+            count: cmdl.materials.len(),
+        }];
+    };
+    let mut errors = validate_material_set(material_set);
+    for (surface, s) in cmdl.surfaces.iter().enumerate() {
+        if s.material_index as usize >= material_set.materials.len() {
+            errors.push(ValidationError::Material {
+                surface,
+                index: s.material_index as usize,
+                count: material_set.materials.len(),
+            });
+        }
+    }
+    errors
+}
+
+/// Checks every area surface's material, numbering surfaces across all world models.
+fn validate_mrea(
+    sections: &MreaSections,
+    material_set: &MaterialSet,
+) -> Result<Vec<ValidationError>> {
+    let mut errors = validate_material_set(material_set);
+    let mut surface = 0;
+    for model in sections.world_models()? {
+        for index in 0..model.surfaces.len() {
+            let s = model.surface(index)?;
+            if s.material_index as usize >= material_set.materials.len() {
+                errors.push(ValidationError::Material {
+                    surface,
+                    index: s.material_index as usize,
+                    count: material_set.materials.len(),
+                });
+            }
+            surface += 1;
+        }
+    }
+    Ok(errors)
+}
+
+/// Checks that every material's textures are in the material set.
+fn validate_material_set(material_set: &MaterialSet) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    for (material, m) in material_set.materials.iter().enumerate() {
+        for &index in &m.texture_indices {
+            if index as usize >= material_set.texture_ids.len() {
+                errors.push(ValidationError::Texture {
+                    material,
+                    index: index as usize,
+                    count: material_set.texture_ids.len(),
+                });
+            }
+        }
+    }
+    errors
+}
+
+/// Checks that the skeleton has a named root, that every bone linked as a child exists and is
+/// named, and that the skin only weights vertices to bones in the skeleton.
+fn validate_skin(skeleton: &Cinf, skin: &Cskr) -> Vec<ValidationError> {
+    let bone_exists = |bone_id: u32| skeleton.bones.iter().any(|b| b.bone_id == bone_id);
+    let mut errors = Vec::new();
+    match skeleton.build_order_ids.first() {
+        Some(&root_id) if bone_exists(root_id) => (),
+        root_id => errors.push(ValidationError::Bone {
+            bone_id: root_id.copied().unwrap_or_default(),
+        }),
+    }
+    for bone in &skeleton.bones {
+        if !skeleton.bone_names.iter().any(|n| n.id == bone.bone_id) {
+            errors.push(ValidationError::BoneName {
+                bone_id: bone.bone_id,
             });
         }
+        // A bone's links include its parent, which for the root is outside the skeleton.
+        for &bone_id in &bone.linked_bones {
+            if bone_id != bone.parent_bone_id && !bone_exists(bone_id) {
+                errors.push(ValidationError::Bone { bone_id });
+            }
+        }
+    }
+    let skinned_bone_ids: BTreeSet<u32> = skin
+        .vertex_groups
+        .iter()
+        .flat_map(|group| group.weights.iter().map(|w| w.bone_id))
+        .collect();
+    for bone_id in skinned_bone_ids {
+        if !bone_exists(bone_id) {
+            errors.push(ValidationError::Bone { bone_id });
+        }
+    }
+    errors
+}
+
+impl CanonicalMesh {
+    pub fn from_cmdl(cmdl: &Cmdl, material_set_index: usize) -> Result<Self> {
+        ValidationErrors(validate_cmdl(cmdl, material_set_index)).into_result()?;
+        let material_set = &cmdl.materials[material_set_index];
+        let surfaces = cmdl
+            .surfaces
+            .iter()
+            .map(|surface| static_surface(material_set, surface, &cmdl.vertex_arrays()))
+            .collect::<Result<_>>()?;
 
         Ok(Self {
             skin: None,
             surfaces,
             texture_ids: material_set.texture_ids.clone(),
+            materials: material_set.materials.clone(),
+            effects: Vec::new(),
+            animation_aabbs: Vec::new(),
+            octree_cells: Vec::new(),
+            script_markers: Vec::new(),
+            parts: Vec::new(),
+        })
+    }
+
+    /// Builds an area's world geometry in area space, with each world model placed by its
+    /// transform.
+    pub fn from_mrea(sections: &MreaSections) -> Result<Self> {
+        let material_set = sections.material_set()?;
+        ValidationErrors(validate_mrea(sections, &material_set)?).into_result()?;
+        let mut surfaces = Vec::new();
+        for model in sections.world_models()? {
+            let mut model_mesh = Self {
+                skin: None,
+                surfaces: Vec::new(),
+                texture_ids: Vec::new(),
+                materials: Vec::new(),
+                effects: Vec::new(),
+                animation_aabbs: Vec::new(),
+                octree_cells: Vec::new(),
+                script_markers: Vec::new(),
+                parts: Vec::new(),
+            };
+            for index in 0..model.surfaces.len() {
+                model_mesh.surfaces.push(static_surface(
+                    &material_set,
+                    &model.surface(index)?,
+                    &model.vertex_arrays(),
+                )?);
+            }
+            model_mesh.apply_transform(&transform_from_rows(&model.transform()?));
+            surfaces.extend(model_mesh.surfaces);
+        }
+
+        Ok(Self {
+            skin: None,
+            surfaces,
+            texture_ids: material_set.texture_ids,
+            materials: material_set.materials,
+            effects: Vec::new(),
+            animation_aabbs: Vec::new(),
+            octree_cells: Vec::new(),
+            script_markers: Vec::new(),
+            parts: Vec::new(),
         })
     }
 
@@ -92,25 +500,29 @@ impl CanonicalMesh {
         let cmdl_data = pak
             .data_with_fourcc(character.model_id, "CMDL")?
             .ok_or_else(|| anyhow!("Model 0x{:08x} not found", character.model_id))?;
-        let cmdl: Cmdl = cmdl_data.as_slice().read_typed()?;
+        let cmdl: Cmdl = parse::read_resource("CMDL", &cmdl_data)?;
 
         let skeleton_data = pak
             .data_with_fourcc(character.skeleton_id, "CINF")?
             .ok_or_else(|| anyhow!("Skeleton 0x{:08x} not found", character.skeleton_id))?;
-        let skeleton: Cinf = skeleton_data.as_slice().read_typed()?;
-        let skeleton = interpret_bone(&skeleton, skeleton.build_order_ids[0]);
+        let skeleton: Cinf = parse::read_resource("CINF", &skeleton_data)?;
 
         let skin_data = pak
             .data_with_fourcc(character.skin_id, "CSKR")?
             .ok_or_else(|| anyhow!("Skin 0x{:08x} not found", character.skin_id))?;
-        let skin: Cskr = skin_data.as_slice().read_typed()?;
+        let skin: Cskr = parse::read_resource("CSKR", &skin_data)?;
+
+        let mut errors = validate_cmdl(&cmdl, material_set_index);
+        errors.extend(validate_skin(&skeleton, &skin));
+        ValidationErrors(errors).into_result()?;
+        let skeleton = interpret_bone(&skeleton, skeleton.build_order_ids[0]);
         let mut vertex_bone_ids = Vec::new();
         let mut vertex_weights = Vec::new();
         for vertex_group in &skin.vertex_groups {
+            let (bone_ids, weights) = vertex_influences(&vertex_group.weights, skeleton.id);
             for _ in 0..vertex_group.vertex_count {
-                assert_eq!(vertex_group.weights.len(), 1);
-                vertex_bone_ids.push(vertex_group.weights[0].bone_id);
-                vertex_weights.push(vertex_group.weights[0].weight);
+                vertex_bone_ids.push(bone_ids);
+                vertex_weights.push(weights);
             }
         }
 
@@ -119,49 +531,366 @@ impl CanonicalMesh {
         for surface in &cmdl.surfaces {
             let mut positions = Vec::new();
             let mut normals = Vec::new();
-            let mut texcoords = Vec::new();
+            let mut texcoords: [Vec<[f32; 2]>; 8] = Default::default();
+            let mut colors: [Vec<[f32; 4]>; 2] = Default::default();
             let mut bone_ids = Vec::new();
             let mut weights = Vec::new();
 
             let material = &material_set.materials[surface.material_index as usize];
             let batches = surface.display_list.parse::<SkinnedVertexDescriptor>(
-                material.vertex_attr_flags,
-                &cmdl.position_data,
-                &cmdl.normal_data,
-                &cmdl.uv_float_data,
+                &material.vertex_inputs(),
+                &cmdl.vertex_arrays(),
                 &vertex_bone_ids,
                 &vertex_weights,
             )?;
             for batch in batches {
                 positions.extend_from_slice(&batch.positions);
                 normals.extend_from_slice(&batch.normals);
-                texcoords.extend_from_slice(&batch.texcoords);
+                for (texcoords, batch_texcoords) in texcoords.iter_mut().zip(&batch.texcoords) {
+                    texcoords.extend_from_slice(batch_texcoords);
+                }
+                for (colors, batch_colors) in colors.iter_mut().zip(&batch.colors) {
+                    colors.extend_from_slice(batch_colors);
+                }
                 bone_ids.extend_from_slice(&batch.bone_ids);
                 weights.extend_from_slice(&batch.weights);
             }
 
             surfaces.push(CanonicalMeshSurface {
+                material_index: surface.material_index as usize,
                 texture_indices: material
                     .texture_indices
                     .iter()
                     .map(|&x| x as usize)
                     .collect(),
+                unlit: material.is_unlit(),
                 positions,
                 normals,
-                texcoords,
+                texcoords: texcoord_sets(texcoords),
+                colors,
                 bone_ids,
                 weights,
             });
         }
 
         Ok(Self {
-            skin: Some(CanonicalMeshSkin { skeleton, skin }),
+            skin: Some(CanonicalMeshSkin {
+                skeleton,
+                skin,
+                animations: Vec::new(),
+            }),
             surfaces,
             texture_ids: material_set.texture_ids.clone(),
+            materials: material_set.materials.clone(),
+            effects: character.effects.clone(),
+            animation_aabbs: character.animation_aabbs.clone(),
+            octree_cells: Vec::new(),
+            script_markers: Vec::new(),
+            parts: Vec::new(),
         })
     }
 }
 
+impl CanonicalMesh {
+    /// The smallest box containing every surface vertex, or `None` for an empty mesh.
+    pub fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        let mut positions = self.surfaces.iter().flat_map(|s| s.positions.iter());
+        let first = *positions.next()?;
+        Some(
+            positions.fold((first, first), |(mut min, mut max), position| {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(position[axis]);
+                    max[axis] = max[axis].max(position[axis]);
+                }
+                (min, max)
+            }),
+        )
+    }
+
+    /// Moves the whole model, including its skeleton and animation bounds, by `offset`.
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        self.apply_transform(&Matrix4::new_translation(&Vector3::from(offset)));
+    }
+
+    /// Reduces each surface to about `ratio` of its triangles with quadric error decimation.
+    pub fn simplify(&mut self, ratio: f32) -> Result<()> {
+        if !(ratio > 0.0 && ratio <= 1.0) {
+            bail!("Simplification ratio must be greater than 0 and at most 1, not {ratio}");
+        }
+        for surface in &mut self.surfaces {
+            simplify::simplify_surface(surface, ratio);
+        }
+        Ok(())
+    }
+
+    /// Transforms the whole model, including its skeleton and bounding boxes. Normals are
+    /// transformed by the inverse transpose so they stay perpendicular under non-uniform scales,
+    /// and boxes grow to contain their transformed corners.
+    pub fn apply_transform(&mut self, transform: &Matrix4<f32>) {
+        let transform_point =
+            |p: [f32; 3]| transform.transform_point(&Point3::from(p)).coords.into();
+        let normal_transform = transform
+            .fixed_slice::<3, 3>(0, 0)
+            .try_inverse()
+            .unwrap_or_else(Matrix3::identity)
+            .transpose();
+        for surface in &mut self.surfaces {
+            for position in &mut surface.positions {
+                *position = transform_point(*position);
+            }
+            for normal in &mut surface.normals {
+                let n = normal_transform * Vector3::from(*normal);
+                *normal = n.try_normalize(f32::EPSILON).unwrap_or(n).into();
+            }
+        }
+        if let Some(skin) = &mut self.skin {
+            skin.skeleton.apply_transform(&transform_point);
+        }
+        for aabb in &mut self.animation_aabbs {
+            let (min, max) = transformed_bounds(
+                transform_point,
+                [aabb.min_x, aabb.min_y, aabb.min_z],
+                [aabb.max_x, aabb.max_y, aabb.max_z],
+            );
+            [aabb.min_x, aabb.min_y, aabb.min_z] = min;
+            [aabb.max_x, aabb.max_y, aabb.max_z] = max;
+        }
+        for cell in &mut self.octree_cells {
+            (cell.min, cell.max) = transformed_bounds(transform_point, cell.min, cell.max);
+        }
+        for marker in &mut self.script_markers {
+            marker.transform = transform * marker.transform;
+        }
+    }
+
+    /// Combines static meshes into one, each placed by its transform. Materials are concatenated,
+    /// and textures shared between meshes are listed once.
+    pub fn merge(meshes: &[CanonicalMesh], transforms: &[Matrix4<f32>]) -> Result<Self> {
+        if meshes.len() != transforms.len() {
+            bail!(
+                "Merging {} meshes with {} transforms",
+                meshes.len(),
+                transforms.len(),
+            );
+        }
+        let mut merged = Self {
+            skin: None,
+            surfaces: Vec::new(),
+            texture_ids: Vec::new(),
+            materials: Vec::new(),
+            effects: Vec::new(),
+            animation_aabbs: Vec::new(),
+            octree_cells: Vec::new(),
+            script_markers: Vec::new(),
+            parts: Vec::new(),
+        };
+        for (mesh, transform) in meshes.iter().zip(transforms) {
+            if mesh.skin.is_some() {
+                bail!("Merging skinned meshes isn't supported");
+            }
+            let texture_indices: Vec<usize> = mesh
+                .texture_ids
+                .iter()
+                .map(|&id| {
+                    merged
+                        .texture_ids
+                        .iter()
+                        .position(|&merged_id| merged_id == id)
+                        .unwrap_or_else(|| {
+                            merged.texture_ids.push(id);
+                            merged.texture_ids.len() - 1
+                        })
+                })
+                .collect();
+            let remap = |index: usize| {
+                texture_indices
+                    .get(index)
+                    .copied()
+                    .ok_or_else(|| anyhow!("Texture index {index} is out of range"))
+            };
+
+            let material_offset = merged.materials.len();
+            for material in &mesh.materials {
+                let mut material = material.clone();
+                for index in &mut material.texture_indices {
+                    *index = remap(*index as usize)? as u32;
+                }
+                merged.materials.push(material);
+            }
+
+            let mut mesh = Self {
+                skin: None,
+                surfaces: mesh.surfaces.clone(),
+                texture_ids: Vec::new(),
+                materials: Vec::new(),
+                effects: Vec::new(),
+                animation_aabbs: Vec::new(),
+                octree_cells: mesh.octree_cells.clone(),
+                script_markers: mesh.script_markers.clone(),
+                parts: Vec::new(),
+            };
+            mesh.apply_transform(transform);
+            for mut surface in mesh.surfaces {
+                surface.material_index += material_offset;
+                for index in &mut surface.texture_indices {
+                    *index = remap(*index)?;
+                }
+                merged.surfaces.push(surface);
+            }
+            merged.octree_cells.extend(mesh.octree_cells);
+            merged.script_markers.extend(mesh.script_markers);
+        }
+        Ok(merged)
+    }
+
+    /// Combines static meshes into one like [`Self::merge`], but leaves their vertices in place
+    /// and records each mesh as a part placed by its transform, so each can be exported as its
+    /// own node.
+    pub fn merge_parts(parts: Vec<(String, CanonicalMesh, Matrix4<f32>)>) -> Result<Self> {
+        let mut ranges = Vec::new();
+        let mut meshes = Vec::new();
+        let mut start = 0;
+        let mut cells_start = 0;
+        let mut markers_start = 0;
+        for (name, mesh, transform) in parts {
+            let end = start + mesh.surfaces.len();
+            let cells_end = cells_start + mesh.octree_cells.len();
+            let markers_end = markers_start + mesh.script_markers.len();
+            ranges.push(CanonicalMeshPart {
+                name,
+                surfaces: start..end,
+                octree_cells: cells_start..cells_end,
+                script_markers: markers_start..markers_end,
+                transform,
+            });
+            meshes.push(mesh);
+            start = end;
+            cells_start = cells_end;
+            markers_start = markers_end;
+        }
+        let mut merged = Self::merge(&meshes, &vec![Matrix4::identity(); meshes.len()])?;
+        merged.parts = ranges;
+        Ok(merged)
+    }
+}
+
+/// Keeps the texture coordinate sets up to the first one the vertices don't have, since glTF
+/// numbers sets without gaps.
+fn texcoord_sets(sets: [Vec<[f32; 2]>; 8]) -> Vec<Vec<[f32; 2]>> {
+    sets.into_iter().take_while(|set| !set.is_empty()).collect()
+}
+
+/// Decodes a surface whose vertices aren't skinned. The surface's material must be in the set.
+fn static_surface(
+    material_set: &MaterialSet,
+    surface: &Surface,
+    arrays: &VertexArrays,
+) -> Result<CanonicalMeshSurface> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut texcoords: [Vec<[f32; 2]>; 8] = Default::default();
+    let mut colors: [Vec<[f32; 4]>; 2] = Default::default();
+
+    let material = &material_set.materials[surface.material_index as usize];
+    let batches = surface.display_list.parse::<StaticVertexDescriptor>(
+        &material.vertex_inputs(),
+        arrays,
+        &(),
+        &(),
+    )?;
+    for batch in batches {
+        positions.extend_from_slice(&batch.positions);
+        normals.extend_from_slice(&batch.normals);
+        for (texcoords, batch_texcoords) in texcoords.iter_mut().zip(&batch.texcoords) {
+            texcoords.extend_from_slice(batch_texcoords);
+        }
+        for (colors, batch_colors) in colors.iter_mut().zip(&batch.colors) {
+            colors.extend_from_slice(batch_colors);
+        }
+    }
+
+    Ok(CanonicalMeshSurface {
+        material_index: surface.material_index as usize,
+        texture_indices: material
+            .texture_indices
+            .iter()
+            .map(|&x| x as usize)
+            .collect(),
+        unlit: material.is_unlit(),
+        positions,
+        normals,
+        texcoords: texcoord_sets(texcoords),
+        colors,
+        bone_ids: Vec::new(),
+        weights: Vec::new(),
+    })
+}
+
+/// Converts the row-major 3x4 transforms that MLVL and MREA resources store.
+pub fn transform_from_rows(rows: &[f32; 12]) -> Matrix4<f32> {
+    let mut matrix = Matrix4::identity();
+    for row in 0..3 {
+        for column in 0..4 {
+            matrix[(row, column)] = rows[4 * row + column];
+        }
+    }
+    matrix
+}
+
+/// The bounds of a box's eight corners after transforming them.
+fn transformed_bounds(
+    transform_point: impl Fn([f32; 3]) -> [f32; 3],
+    min: [f32; 3],
+    max: [f32; 3],
+) -> ([f32; 3], [f32; 3]) {
+    let mut bounds = ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]);
+    for corner in 0..8 {
+        let p = transform_point(std::array::from_fn(|axis| {
+            if corner & (1 << axis) == 0 {
+                min[axis]
+            } else {
+                max[axis]
+            }
+        }));
+        for (axis, p) in p.into_iter().enumerate() {
+            bounds.0[axis] = bounds.0[axis].min(p);
+            bounds.1[axis] = bounds.1[axis].max(p);
+        }
+    }
+    bounds
+}
+
+impl CanonicalMeshBone {
+    fn apply_transform(&mut self, transform_point: &impl Fn([f32; 3]) -> [f32; 3]) {
+        self.position = transform_point(self.position);
+        for child in &mut self.children {
+            child.apply_transform(transform_point);
+        }
+    }
+}
+
+/// Packs a vertex group's weights into the four slots a vertex has, keeping the heaviest four and
+/// scaling them to sum to one. A group without weights is bound fully to `fallback_bone_id`.
+fn vertex_influences(weights: &[Weight], fallback_bone_id: u32) -> ([u32; 4], [f32; 4]) {
+    let mut sorted: Vec<&Weight> = weights.iter().collect();
+    sorted.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+    sorted.truncate(4);
+    let total: f32 = sorted.iter().map(|w| w.weight).sum();
+
+    let first_bone_id = sorted.first().map_or(fallback_bone_id, |w| w.bone_id);
+    let mut bone_ids = [first_bone_id; 4];
+    let mut normalized = [0.0; 4];
+    normalized[0] = 1.0;
+    if total > 0.0 {
+        for (i, weight) in sorted.iter().enumerate() {
+            bone_ids[i] = weight.bone_id;
+            normalized[i] = weight.weight / total;
+        }
+    }
+    (bone_ids, normalized)
+}
+
+/// Builds the bone hierarchy below a bone. The skeleton must have passed [`validate_skin`].
 fn interpret_bone(cinf: &Cinf, bone_id: u32) -> CanonicalMeshBone {
     let bone = cinf.bones.iter().find(|x| x.bone_id == bone_id).unwrap();
     let name = cinf
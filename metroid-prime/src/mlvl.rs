@@ -0,0 +1,204 @@
+use std::io::Read;
+
+use anyhow::{bail, Result};
+use gamecube::bytes::ReadFrom;
+use gamecube::{ReadBytesExt, ReadTypedExt};
+
+use crate::parse;
+
+/// An MLVL resource: a world, listing its areas and where they sit relative to each other.
+#[derive(Clone, Debug)]
+pub struct Mlvl {
+    pub version: u32,
+    pub world_name_id: u32,
+    pub save_world_id: u32,
+    pub skybox_model_id: u32,
+    pub memory_relays: Vec<MemoryRelay>,
+    pub areas: Vec<Area>,
+    /// The world map, script layers, and audio groups that follow the areas, which aren't parsed
+    /// yet.
+    pub unparsed: Vec<u8>,
+}
+
+impl Mlvl {
+    const MAGIC: u32 = 0xdeafbabe;
+    /// The version used by Metroid Prime.
+    const VERSION: u32 = 0x11;
+}
+
+impl ReadFrom for Mlvl {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let magic = r.read_u32()?;
+        if magic != Self::MAGIC {
+            bail!("unexpected MLVL magic: 0x{magic:08x}");
+        }
+        let version = r.read_u32()?;
+        if version != Self::VERSION {
+            bail!("unexpected MLVL version: 0x{version:x}");
+        }
+        let world_name_id = r.read_u32()?;
+        let save_world_id = r.read_u32()?;
+        let skybox_model_id = r.read_u32()?;
+
+        let memory_relay_count = r.read_u32()?;
+        let mut memory_relays = Vec::new();
+        for _ in 0..memory_relay_count {
+            memory_relays.push(r.read_typed()?);
+        }
+
+        let area_count = r.read_u32()?;
+        let _unknown = r.read_u32()?;
+        let mut areas = Vec::new();
+        for _ in 0..area_count {
+            areas.push(r.read_typed()?);
+        }
+
+        let unparsed = parse::read_unparsed(r)?;
+        Ok(Self {
+            version,
+            world_name_id,
+            save_world_id,
+            skybox_model_id,
+            memory_relays,
+            areas,
+            unparsed,
+        })
+    }
+}
+
+/// A script connection that crosses areas, so it's kept at the world level.
+#[derive(Clone, Debug)]
+pub struct MemoryRelay {
+    pub relay_id: u32,
+    pub target_id: u32,
+    pub message: u16,
+    pub active: bool,
+}
+
+impl ReadFrom for MemoryRelay {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        Ok(Self {
+            relay_id: r.read_u32()?,
+            target_id: r.read_u32()?,
+            message: r.read_u16()?,
+            active: r.read_u8()? != 0,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Area {
+    pub name_id: u32,
+    /// Row-major 3x4 transform from area space to world space.
+    pub transform: [f32; 12],
+    pub aabb_min: [f32; 3],
+    pub aabb_max: [f32; 3],
+    pub mrea_id: u32,
+    pub internal_id: u32,
+    pub attached_areas: Vec<u16>,
+    pub dependencies: Vec<Dependency>,
+    /// For each script layer, the index of its first entry in `dependencies`.
+    pub dependency_offsets: Vec<u32>,
+    pub docks: Vec<Dock>,
+}
+
+impl ReadFrom for Area {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let name_id = r.read_u32()?;
+        let mut transform = [0.0; 12];
+        for x in &mut transform {
+            *x = f32::from_bits(r.read_u32()?);
+        }
+        let aabb_min = read_vec3(r)?;
+        let aabb_max = read_vec3(r)?;
+        let mrea_id = r.read_u32()?;
+        let internal_id = r.read_u32()?;
+
+        let attached_area_count = r.read_u32()?;
+        let mut attached_areas = Vec::new();
+        for _ in 0..attached_area_count {
+            attached_areas.push(r.read_u16()?);
+        }
+
+        let _unknown = r.read_u32()?;
+        let dependency_count = r.read_u32()?;
+        let mut dependencies = Vec::new();
+        for _ in 0..dependency_count {
+            dependencies.push(r.read_typed()?);
+        }
+        let dependency_offset_count = r.read_u32()?;
+        let mut dependency_offsets = Vec::new();
+        for _ in 0..dependency_offset_count {
+            dependency_offsets.push(r.read_u32()?);
+        }
+
+        let dock_count = r.read_u32()?;
+        let mut docks = Vec::new();
+        for _ in 0..dock_count {
+            docks.push(r.read_typed()?);
+        }
+
+        Ok(Self {
+            name_id,
+            transform,
+            aabb_min,
+            aabb_max,
+            mrea_id,
+            internal_id,
+            attached_areas,
+            dependencies,
+            dependency_offsets,
+            docks,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Dependency {
+    pub id: u32,
+    pub fourcc: u32,
+}
+
+impl ReadFrom for Dependency {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        Ok(Self {
+            id: r.read_u32()?,
+            fourcc: r.read_u32()?,
+        })
+    }
+}
+
+/// A doorway between two areas.
+#[derive(Clone, Debug)]
+pub struct Dock {
+    /// Pairs of area index and dock index within that area.
+    pub connections: Vec<(u32, u32)>,
+    pub coordinates: Vec<[f32; 3]>,
+}
+
+impl ReadFrom for Dock {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let connection_count = r.read_u32()?;
+        let mut connections = Vec::new();
+        for _ in 0..connection_count {
+            connections.push((r.read_u32()?, r.read_u32()?));
+        }
+        let coordinate_count = r.read_u32()?;
+        let mut coordinates = Vec::new();
+        for _ in 0..coordinate_count {
+            coordinates.push(read_vec3(r)?);
+        }
+        Ok(Self {
+            connections,
+            coordinates,
+        })
+    }
+}
+
+fn read_vec3<R: Read>(r: &mut R) -> Result<[f32; 3]> {
+    Ok([
+        f32::from_bits(r.read_u32()?),
+        f32::from_bits(r.read_u32()?),
+        f32::from_bits(r.read_u32()?),
+    ])
+}
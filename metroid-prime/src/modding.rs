@@ -0,0 +1,287 @@
+//! Mod projects: editable copies of assets taken from the disc, with a manifest recording where
+//! each came from, and the build step that encodes edited assets back into their paks.
+//!
+//! Textures are extracted as PNGs, strings as JSON, and anything else as its decompressed data.
+//! The manifest keeps a hash of every file as extracted, so building only re-encodes what was
+//! edited and everything else stays byte for byte as the disc had it.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use gamecube::bytes::WriteTo;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::cache::ResourceCache;
+use crate::pak::{Pak, PakFiles};
+use crate::parse;
+use crate::strg::Strg;
+use crate::txtr::{self, DecodedTexture, TextureHeader};
+
+/// Name of the manifest within a project directory.
+pub const MANIFEST_FILE_NAME: &str = "mod.json";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Game code of the disc the assets came from. Projects only build against that disc.
+    pub game_code: String,
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Asset {
+    pub fourcc: String,
+    pub file_id: String,
+    pub name: Option<String>,
+    /// Disc paths of every pak holding the resource, all of which are updated when it's edited.
+    pub paks: Vec<String>,
+    /// Path of the editable file, relative to the project directory.
+    pub path: PathBuf,
+    /// SHA-256 of the file as extracted, in hex.
+    pub sha256: String,
+}
+
+impl Manifest {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE_NAME);
+        serde_json::from_slice(&fs::read(&path)?)
+            .with_context(|| format!("Couldn't parse the mod manifest {}", path.display()))
+    }
+
+    fn save(&self, dir: &Path) -> Result<()> {
+        let mut w = BufWriter::new(File::create(dir.join(MANIFEST_FILE_NAME))?);
+        serde_json::to_writer_pretty(&mut w, self)?;
+        writeln!(w)?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+/// Extracts every resource of the types in `fourccs` from the paks in `pak_paths`, or from every
+/// pak if it's empty, into a new project in `dir`. A resource in several paks is extracted once.
+/// Resources that can't be extracted in an editable form are reported and left out.
+pub fn init(
+    paks: &PakFiles,
+    cache: Option<&ResourceCache>,
+    game_code: &str,
+    pak_paths: &[String],
+    fourccs: &[String],
+    dir: &Path,
+) -> Result<Manifest> {
+    if dir.join(MANIFEST_FILE_NAME).exists() {
+        bail!("{} already holds a mod project", dir.display());
+    }
+
+    let mut assets: BTreeMap<(String, u32), Asset> = BTreeMap::new();
+    let mut paths = BTreeSet::new();
+    for file in paks.iter() {
+        let file = file?;
+        let pak_path = file.path().to_string();
+        if !pak_paths.is_empty() && !pak_paths.contains(&pak_path) {
+            continue;
+        }
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        for resource in pak.iter_resources() {
+            let fourcc = resource.fourcc().to_string();
+            if !fourccs.contains(&fourcc) {
+                continue;
+            }
+            let file_id = resource.file_id();
+            if let Some(asset) = assets.get_mut(&(fourcc.clone(), file_id)) {
+                asset.paks.push(pak_path.clone());
+                continue;
+            }
+            let editable = match resource.data().and_then(|data| export(&fourcc, &data)) {
+                Ok(editable) => editable,
+                Err(e) => {
+                    println!("Error in {pak_path} {fourcc} 0x{file_id:08x}: {e}");
+                    continue;
+                }
+            };
+
+            let name = pak
+                .iter_names()
+                .find(|e| e.file_id() == file_id)
+                .map(|e| e.name().to_string());
+            let extension = extension(&fourcc);
+            let mut path = Path::new(&fourcc).join(format!(
+                "{}.{extension}",
+                name.clone().unwrap_or_else(|| format!("0x{file_id:08x}")),
+            ));
+            if !paths.insert(path.clone()) {
+                path = Path::new(&fourcc).join(format!("0x{file_id:08x}.{extension}"));
+                paths.insert(path.clone());
+            }
+            fs::create_dir_all(dir.join(&fourcc))?;
+            fs::write(dir.join(&path), &editable)?;
+
+            assets.insert(
+                (fourcc.clone(), file_id),
+                Asset {
+                    fourcc,
+                    file_id: format!("0x{file_id:08x}"),
+                    name,
+                    paks: vec![pak_path.clone()],
+                    path,
+                    sha256: sha256_hex(&editable),
+                },
+            );
+        }
+    }
+
+    let manifest = Manifest {
+        game_code: game_code.to_string(),
+        assets: assets.into_values().collect(),
+    };
+    manifest.save(dir)?;
+    Ok(manifest)
+}
+
+/// Encodes every asset in the project in `dir` that was edited since it was extracted, returning
+/// the rebuilt paks by disc path.
+pub fn build(
+    paks: &PakFiles,
+    cache: Option<&ResourceCache>,
+    game_code: &str,
+    dir: &Path,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let manifest = Manifest::load(dir)?;
+    if manifest.game_code != game_code {
+        bail!(
+            "The mod was made from a {} disc, but this one is {game_code}",
+            manifest.game_code,
+        );
+    }
+
+    let mut edited_by_pak: BTreeMap<&str, Vec<&Asset>> = BTreeMap::new();
+    for asset in &manifest.assets {
+        let data = fs::read(dir.join(&asset.path))
+            .with_context(|| format!("Couldn't read {}", asset.path.display()))?;
+        if sha256_hex(&data) == asset.sha256 {
+            continue;
+        }
+        println!("Encoding {}", asset.path.display());
+        for pak_path in &asset.paks {
+            edited_by_pak.entry(pak_path).or_default().push(asset);
+        }
+    }
+
+    let mut encoded: HashMap<u32, Vec<u8>> = HashMap::new();
+    let mut rebuilt = Vec::new();
+    for (pak_path, assets) in edited_by_pak {
+        let pak_file = paks.load(pak_path)?;
+        let pak = Pak::new(pak_file.data())?.with_cache(cache.cloned());
+        let mut replacements = HashMap::new();
+        for asset in assets {
+            let file_id = u32::from_str_radix(asset.file_id.trim_start_matches("0x"), 16)?;
+            let data = match encoded.entry(file_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(slot) => {
+                    let original =
+                        pak.data_with_fourcc(file_id, &asset.fourcc)?
+                            .ok_or_else(|| {
+                                anyhow!(
+                                    "{} {} not found in {pak_path}",
+                                    asset.fourcc,
+                                    asset.file_id
+                                )
+                            })?;
+                    slot.insert(
+                        import(&asset.fourcc, &original, &dir.join(&asset.path))
+                            .with_context(|| format!("Couldn't encode {}", asset.path.display()))?,
+                    )
+                }
+            };
+            replacements.insert(file_id, data.clone());
+        }
+        rebuilt.push((pak_path.to_string(), pak.rebuild(&replacements)?));
+    }
+    Ok(rebuilt)
+}
+
+fn extension(fourcc: &str) -> &'static str {
+    match fourcc {
+        "TXTR" => "png",
+        "STRG" => "json",
+        _ => "bin",
+    }
+}
+
+/// Converts a resource's decompressed data to its editable file.
+fn export(fourcc: &str, data: &[u8]) -> Result<Vec<u8>> {
+    let mut editable = Vec::new();
+    match fourcc {
+        "TXTR" => {
+            if matches!(TextureHeader::read(data)?.format, 0x4..=0x6) {
+                bail!("paletted textures can't be re-encoded");
+            }
+            txtr::decode(data)?.write_png(&mut editable)?;
+        }
+        "STRG" => {
+            let strg: Strg = parse::read_resource(fourcc, data)?;
+            let strings: BTreeMap<&str, &[String]> = strg
+                .languages
+                .iter()
+                .map(|language| (language.id.as_str(), language.strings.as_slice()))
+                .collect();
+            serde_json::to_writer_pretty(&mut editable, &strings)?;
+            writeln!(editable)?;
+        }
+        _ => editable.extend_from_slice(data),
+    }
+    Ok(editable)
+}
+
+/// Converts an edited file back to a resource's decompressed data, keeping whatever the file
+/// doesn't hold, like a texture's format and mip count, from the `original`.
+fn import(fourcc: &str, original: &[u8], path: &Path) -> Result<Vec<u8>> {
+    match fourcc {
+        "TXTR" => {
+            let header = TextureHeader::read(original)?;
+            let texture = DecodedTexture::read_png(path)?;
+            // Encoding at the original size keeps the pak from growing past its space on the disc.
+            if (texture.width, texture.height) != (header.width, header.height) {
+                bail!(
+                    "the texture is {}x{}, but must stay {}x{}",
+                    texture.width,
+                    texture.height,
+                    header.width,
+                    header.height,
+                );
+            }
+            txtr::encode(&texture, header.format, header.mip_count)
+        }
+        "STRG" => {
+            let mut strg: Strg = parse::read_resource(fourcc, original)?;
+            let edits: BTreeMap<String, Vec<String>> = serde_json::from_slice(&fs::read(path)?)?;
+            for (language_id, strings) in edits {
+                let language = strg
+                    .language_mut(&language_id)
+                    .ok_or_else(|| anyhow!("there are no {language_id} strings"))?;
+                if strings.len() != language.strings.len() {
+                    bail!(
+                        "{language_id} has {} strings, but must keep {}",
+                        strings.len(),
+                        language.strings.len(),
+                    );
+                }
+                language.strings = strings;
+            }
+            let mut data = Vec::new();
+            strg.write_to(&mut data)?;
+            Ok(data)
+        }
+        _ => Ok(fs::read(path)?),
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
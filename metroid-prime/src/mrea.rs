@@ -0,0 +1,499 @@
+//! MREA area resources, up to the layer that splits them into data sections. Metroid Prime stores
+//! sections as is, while later games pack them into compressed blocks.
+
+use anyhow::{anyhow, bail, Result};
+use gamecube::{ReadBytesExt, ReadTypedExt, SliceReader};
+
+use crate::cmdl::{MaterialSet, Surface};
+use crate::compression::{Compression, Decompressor};
+use crate::detect::{self, Detection};
+use crate::gx::VertexArrays;
+use crate::parse;
+use crate::scly::{self, ScriptLayer};
+
+/// An area's header and its data sections, decompressed.
+#[derive(Clone, Debug)]
+pub struct MreaSections {
+    pub version: u32,
+    /// Row-major 3x4 transform from area space to world space.
+    pub transform: [f32; 12],
+    pub world_model_count: u32,
+    /// The index of the first section of each kind, keyed by a fourcc-style name. Example: SCLY
+    pub section_indices: Vec<(String, u32)>,
+    pub sections: Vec<Vec<u8>>,
+}
+
+impl MreaSections {
+    const MAGIC: u32 = 0xdeadbeef;
+    pub const VERSION_PRIME: u32 = 0x0f;
+    pub const VERSION_ECHOES: u32 = 0x19;
+    pub const VERSION_CORRUPTION: u32 = 0x1e;
+
+    /// Section kinds in the order the Metroid Prime header lists their indices.
+    const PRIME_SECTIONS: &'static [&'static str] = &[
+        "GEOM", "SCLY", "COLI", "UNKN", "LITE", "VISI", "PATH", "AROT",
+    ];
+    /// Section kinds in the order the Metroid Prime 2 header lists their indices.
+    const ECHOES_SECTIONS: &'static [&'static str] = &[
+        "GEOM", "SCLY", "SCGN", "COLI", "UNKN", "LITE", "VISI", "PATH", "AROT", "PTLA", "EGMC",
+    ];
+
+    /// Parses the header as laid out in the version it states, or if it doesn't parse that way,
+    /// in whichever known layout [`detect`] finds fits best, with a warning.
+    pub fn new(data: &[u8]) -> Result<Self> {
+        let version = SliceReader::new(data).slice_at(4, 4)?.read_u32()?;
+        let error = match Self::new_with_layout(data, version) {
+            Ok(sections) => return Ok(sections),
+            Err(e) => e,
+        };
+        let detection = detect::detect("MREA", data);
+        let Some((name, layout)) = detection
+            .as_ref()
+            .and_then(Detection::best)
+            .and_then(|probe| Some((probe.layout.name, probe.layout.version?)))
+        else {
+            return Err(error);
+        };
+        parse::warn(format!(
+            "MREA version 0x{version:x} doesn't parse as stated ({error}), so it's parsed in the \
+             {name} layout"
+        ));
+        // The rest of the area is read as that layout's version lays it out too.
+        let mut sections = Self::new_with_layout(data, layout)?;
+        sections.version = layout;
+        Ok(sections)
+    }
+
+    /// Parses the header as laid out in the given version, whatever version it states, so other
+    /// layouts can be tried on a resource. The stated version is kept in `version`.
+    pub fn new_with_layout(data: &[u8], layout: u32) -> Result<Self> {
+        let mut r = SliceReader::new(data);
+        let magic = r.read_u32()?;
+        if magic != Self::MAGIC {
+            bail!("unexpected MREA magic: 0x{magic:08x}");
+        }
+        let version = r.read_u32()?;
+        let mut transform = [0.0; 12];
+        for x in &mut transform {
+            *x = f32::from_bits(r.read_u32()?);
+        }
+        let world_model_count = r.read_u32()?;
+
+        let mut section_indices = Vec::new();
+        let section_count;
+        let block_count;
+        let mut section_number_count = 0;
+        match layout {
+            Self::VERSION_PRIME => {
+                section_count = r.read_u32()?;
+                for name in Self::PRIME_SECTIONS {
+                    section_indices.push((name.to_string(), r.read_u32()?));
+                }
+                block_count = None;
+            }
+            Self::VERSION_ECHOES => {
+                let _script_layer_count = r.read_u32()?;
+                section_count = r.read_u32()?;
+                for name in Self::ECHOES_SECTIONS {
+                    section_indices.push((name.to_string(), r.read_u32()?));
+                }
+                block_count = Some(r.read_u32()?);
+            }
+            Self::VERSION_CORRUPTION => {
+                let _script_layer_count = r.read_u32()?;
+                section_count = r.read_u32()?;
+                block_count = Some(r.read_u32()?);
+                section_number_count = r.read_u32()?;
+            }
+            _ => bail!("unexpected MREA version: 0x{layout:x}"),
+        }
+        r.align_to(32)?;
+
+        let mut section_sizes = Vec::new();
+        for _ in 0..section_count {
+            section_sizes.push(r.read_u32()? as usize);
+        }
+        r.align_to(32)?;
+
+        let blocks = match block_count {
+            Some(block_count) => {
+                let mut blocks = Vec::new();
+                for _ in 0..block_count {
+                    blocks.push(CompressedBlock {
+                        _buffer_size: r.read_u32()?,
+                        decompressed_size: r.read_u32()? as usize,
+                        compressed_size: r.read_u32()? as usize,
+                        _section_count: r.read_u32()?,
+                    });
+                }
+                r.align_to(32)?;
+                Some(blocks)
+            }
+            None => None,
+        };
+
+        // Corruption names sections by fourcc instead of fixing their order in the header.
+        for _ in 0..section_number_count {
+            let name = r.read_u32()?.to_be_bytes();
+            let index = r.read_u32()?;
+            section_indices.push((String::from_utf8_lossy(&name).into_owned(), index));
+        }
+        if section_number_count > 0 {
+            r.align_to(32)?;
+        }
+
+        let section_data = match blocks {
+            Some(blocks) => {
+                let mut section_data = Vec::new();
+                for block in blocks {
+                    if block.compressed_size == 0 {
+                        section_data.extend_from_slice(r.take(block.decompressed_size)?);
+                    } else {
+                        // Compressed blocks are padded to 32 bytes at the start rather than the
+                        // end.
+                        r.take((32 - block.compressed_size % 32) % 32)?;
+                        let compressed = r.take(block.compressed_size)?;
+                        section_data.extend_from_slice(
+                            &Compression::Segmented
+                                .decompress(compressed, block.decompressed_size)?,
+                        );
+                    }
+                }
+                section_data
+            }
+            None => data[r.position()..].to_vec(),
+        };
+
+        let mut sections = Vec::new();
+        let mut section_r = SliceReader::new(&section_data);
+        for (i, size) in section_sizes.into_iter().enumerate() {
+            sections.push(
+                section_r
+                    .take(size)
+                    .map_err(|e| anyhow!("MREA section {i}: {e}"))?
+                    .to_vec(),
+            );
+        }
+
+        Ok(Self {
+            version,
+            transform,
+            world_model_count,
+            section_indices,
+            sections,
+        })
+    }
+
+    /// The index of the first section of a kind, if the area has one.
+    pub fn section_index(&self, name: &str) -> Option<usize> {
+        self.section_indices
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|&(_, index)| index as usize)
+            .filter(|&index| index < self.sections.len())
+    }
+}
+
+impl MreaSections {
+    const LIGHTS_MAGIC: u32 = 0xbabedead;
+    const OCTREE_MAGIC: u32 = 0x41524f54; // AROT
+    const OCTREE_VERSION: u32 = 1;
+    /// The size of one light in a Metroid Prime lights section.
+    const PRIME_LIGHT_SIZE: usize = 0x41;
+
+    /// The materials all of the area's world models share, from the first geometry section.
+    pub fn material_set(&self) -> Result<MaterialSet> {
+        let mut data = self.section("GEOM")?;
+        data.read_typed()
+    }
+
+    /// Splits the geometry sections after the materials into world models. Only Metroid Prime's
+    /// layout is supported.
+    pub fn world_models(&self) -> Result<Vec<WorldModelSections<'_>>> {
+        if self.version != Self::VERSION_PRIME {
+            bail!("MREA version 0x{:x} geometry isn't supported", self.version);
+        }
+        let first = self
+            .section_index("GEOM")
+            .ok_or_else(|| anyhow!("MREA has no geometry"))?;
+        let mut sections = self.sections[first + 1..].iter().map(Vec::as_slice);
+        let mut next_section = || {
+            sections
+                .next()
+                .ok_or_else(|| anyhow!("MREA has too few geometry sections"))
+        };
+
+        let mut models = Vec::new();
+        for _ in 0..self.world_model_count {
+            let header = next_section()?;
+            let positions = next_section()?;
+            let normals = next_section()?;
+            let colors = next_section()?;
+            let uv_float = next_section()?;
+            let uv_short = next_section()?;
+            let surface_count = next_section()?.read_u32()?;
+            let mut surfaces = Vec::new();
+            for _ in 0..surface_count {
+                surfaces.push(next_section()?);
+            }
+            models.push(WorldModelSections {
+                header,
+                positions,
+                normals,
+                colors,
+                uv_float,
+                uv_short,
+                surfaces,
+            });
+        }
+        Ok(models)
+    }
+
+    /// The number of lights across both light layers, if the area's version is understood.
+    pub fn light_count(&self) -> Result<Option<usize>> {
+        if self.version != Self::VERSION_PRIME {
+            return Ok(None);
+        }
+        let mut r = SliceReader::new(self.section("LITE")?);
+        let magic = r.read_u32()?;
+        if magic != Self::LIGHTS_MAGIC {
+            bail!("unexpected MREA lights magic: 0x{magic:08x}");
+        }
+        let mut count = 0;
+        for _ in 0..2 {
+            let layer_count = r.read_u32()? as usize;
+            r.take(layer_count * Self::PRIME_LIGHT_SIZE)?;
+            count += layer_count;
+        }
+        Ok(Some(count))
+    }
+
+    /// The layers of the area's script. Only Metroid Prime's layout is supported.
+    pub fn script_layers(&self) -> Result<Vec<ScriptLayer>> {
+        if self.version != Self::VERSION_PRIME {
+            bail!("MREA version 0x{:x} scripts aren't supported", self.version);
+        }
+        scly::read_layers(self.section("SCLY")?)
+    }
+
+    /// The area's render octree, if it has one. Some areas leave the section empty.
+    pub fn octree(&self) -> Result<Option<AreaOctree>> {
+        let data = match self.section_index("AROT") {
+            Some(index) if !self.sections[index].is_empty() => self.sections[index].as_slice(),
+            _ => return Ok(None),
+        };
+        let mut r = SliceReader::new(data);
+        let magic = r.read_u32()?;
+        if magic != Self::OCTREE_MAGIC {
+            bail!("unexpected MREA octree magic: 0x{magic:08x}");
+        }
+        let version = r.read_u32()?;
+        if version != Self::OCTREE_VERSION {
+            bail!("unexpected MREA octree version: {version}");
+        }
+        let bitmap_count = r.read_u32()? as usize;
+        let world_model_count = r.read_u32()?;
+        let node_count = r.read_u32()? as usize;
+        let mut min = [0.0; 3];
+        let mut max = [0.0; 3];
+        for x in min.iter_mut().chain(&mut max) {
+            *x = f32::from_bits(r.read_u32()?);
+        }
+        r.align_to(32)?;
+
+        let words_per_bitmap = (world_model_count as usize).div_ceil(32);
+        let mut bitmaps = Vec::with_capacity(bitmap_count);
+        for _ in 0..bitmap_count {
+            let mut bitmap = Vec::with_capacity(words_per_bitmap);
+            for _ in 0..words_per_bitmap {
+                bitmap.push(r.read_u32()?);
+            }
+            bitmaps.push(bitmap);
+        }
+        let mut node_offsets = Vec::with_capacity(node_count);
+        for _ in 0..node_count {
+            node_offsets.push(r.read_u32()? as usize);
+        }
+        let node_data = &data[r.position()..];
+        let mut nodes = Vec::with_capacity(node_count);
+        for (index, offset) in node_offsets.into_iter().enumerate() {
+            let mut r = SliceReader::new(node_data);
+            r.take(offset)
+                .map_err(|e| anyhow!("MREA octree node {index}: {e}"))?;
+            let bitmap_index = r.read_u16()?;
+            let split_axes = r.read_u16()?;
+            let child_count = if split_axes == 0 {
+                0
+            } else {
+                1 << (split_axes & 7).count_ones()
+            };
+            let mut children = Vec::with_capacity(child_count);
+            for _ in 0..child_count {
+                children.push(r.read_u16()?);
+            }
+            nodes.push(OctreeNode {
+                bitmap_index,
+                split_axes,
+                children,
+            });
+        }
+
+        Ok(Some(AreaOctree {
+            min,
+            max,
+            world_model_count,
+            bitmaps,
+            nodes,
+        }))
+    }
+
+    fn section(&self, name: &str) -> Result<&[u8]> {
+        self.section_index(name)
+            .map(|index| self.sections[index].as_slice())
+            .ok_or_else(|| anyhow!("MREA has no {name} section"))
+    }
+}
+
+/// One world model's sections, laid out like a CMDL without its own materials.
+pub struct WorldModelSections<'a> {
+    /// Visor flags, a 3x4 transform, and a bounding box.
+    pub header: &'a [u8],
+    pub positions: &'a [u8],
+    pub normals: &'a [u8],
+    pub colors: &'a [u8],
+    pub uv_float: &'a [u8],
+    pub uv_short: &'a [u8],
+    pub surfaces: Vec<&'a [u8]>,
+}
+
+impl<'a> WorldModelSections<'a> {
+    /// The row-major 3x4 transform from model space to area space.
+    pub fn transform(&self) -> Result<[f32; 12]> {
+        let mut r = SliceReader::new(self.header);
+        let _visor_flags = r.read_u32()?;
+        let mut transform = [0.0; 12];
+        for x in &mut transform {
+            *x = f32::from_bits(r.read_u32()?);
+        }
+        Ok(transform)
+    }
+
+    pub fn vertex_arrays(&self) -> VertexArrays<'a> {
+        VertexArrays {
+            positions: self.positions,
+            normals: self.normals,
+            colors: self.colors,
+            texcoords: self.uv_float,
+            short_texcoords: self.uv_short,
+        }
+    }
+
+    pub fn surface(&self, index: usize) -> Result<Surface> {
+        let mut data = *self
+            .surfaces
+            .get(index)
+            .ok_or_else(|| anyhow!("World model has no surface {index}"))?;
+        data.read_typed()
+    }
+}
+
+/// An area's render octree, the AROT section: a subdivision of the area's bounds whose leaves
+/// list the world models overlapping them, so the game only draws models in visible cells.
+#[derive(Clone, Debug)]
+pub struct AreaOctree {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    pub world_model_count: u32,
+    /// Sets of world models, one bit per model, shared by the nodes overlapping the same set.
+    pub bitmaps: Vec<Vec<u32>>,
+    /// The root node first.
+    pub nodes: Vec<OctreeNode>,
+}
+
+#[derive(Clone, Debug)]
+pub struct OctreeNode {
+    pub bitmap_index: u16,
+    /// The axes the node is split along at its center: bit 0 for X, 1 for Y, and 2 for Z.
+    pub split_axes: u16,
+    /// Node indices of the node's children. Each split axis, from X to Z, takes the next bit of
+    /// the child's position in this list, clear for the lower half and set for the upper.
+    pub children: Vec<u16>,
+}
+
+/// A leaf of an area octree.
+#[derive(Clone, Debug)]
+pub struct OctreeCell {
+    pub node_index: usize,
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    /// Indices of the world models overlapping the cell.
+    pub world_models: Vec<u32>,
+}
+
+impl AreaOctree {
+    /// The leaves of the tree with their bounds, in depth-first order. A node reached twice fails,
+    /// so a corrupt tree that loops or shares subtrees can't blow up the walk.
+    pub fn cells(&self) -> Result<Vec<OctreeCell>> {
+        let mut cells = Vec::new();
+        if self.nodes.is_empty() {
+            return Ok(cells);
+        }
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = vec![(0, self.min, self.max)];
+        while let Some((node_index, min, max)) = stack.pop() {
+            if std::mem::replace(&mut visited[node_index], true) {
+                bail!("MREA octree node {node_index} is reached more than once");
+            }
+            let node = &self.nodes[node_index];
+            if node.children.is_empty() {
+                cells.push(OctreeCell {
+                    node_index,
+                    min,
+                    max,
+                    world_models: self.world_models(node.bitmap_index as usize)?,
+                });
+                continue;
+            }
+            // Pushed in reverse so children come off the stack in order.
+            for (position, &child) in node.children.iter().enumerate().rev() {
+                let child = child as usize;
+                if child >= self.nodes.len() {
+                    bail!("MREA octree node {node_index} has out of range child {child}");
+                }
+                let (mut child_min, mut child_max) = (min, max);
+                let mut bit = 0;
+                for axis in 0..3 {
+                    if node.split_axes & (1 << axis) == 0 {
+                        continue;
+                    }
+                    let center = (min[axis] + max[axis]) / 2.0;
+                    if position & (1 << bit) == 0 {
+                        child_max[axis] = center;
+                    } else {
+                        child_min[axis] = center;
+                    }
+                    bit += 1;
+                }
+                stack.push((child, child_min, child_max));
+            }
+        }
+        Ok(cells)
+    }
+
+    fn world_models(&self, bitmap_index: usize) -> Result<Vec<u32>> {
+        let bitmap = self
+            .bitmaps
+            .get(bitmap_index)
+            .ok_or_else(|| anyhow!("MREA octree has no bitmap {bitmap_index}"))?;
+        Ok((0..self.world_model_count)
+            .filter(|&model| bitmap[model as usize / 32] & (1 << (model % 32)) != 0)
+            .collect())
+    }
+}
+
+struct CompressedBlock {
+    _buffer_size: u32,
+    decompressed_size: usize,
+    compressed_size: usize,
+    _section_count: u32,
+}
@@ -0,0 +1,183 @@
+//! Human-readable world and area names, looked up through the STRG resources each MLVL points to,
+//! for reports and file names that would otherwise only show MREA IDs.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use crate::cache::ResourceCache;
+use crate::mlvl::Mlvl;
+use crate::pak::{Pak, PakFiles};
+use crate::parse;
+use crate::strg::Strg;
+
+/// The language names are taken from, when a STRG has it.
+const LANGUAGE: &str = "ENGL";
+
+#[derive(Clone, Debug)]
+pub struct AreaName {
+    /// The position of the world among all worlds on the disc, to keep them in game order.
+    pub world_index: usize,
+    pub world: String,
+    pub area: String,
+}
+
+impl AreaName {
+    /// A relative path naming the area within a directory for its world.
+    /// Example: 02_Tallon_Overworld/Landing_Site
+    pub fn path(&self) -> PathBuf {
+        [
+            format!(
+                "{:02}_{}",
+                self.world_index,
+                sanitize_file_name(&self.world)
+            ),
+            sanitize_file_name(&self.area),
+        ]
+        .iter()
+        .collect()
+    }
+}
+
+/// World and area names for every MREA referenced by an MLVL.
+#[derive(Clone, Debug, Default)]
+pub struct AreaNames {
+    areas: HashMap<u32, AreaName>,
+}
+
+impl AreaNames {
+    /// Reads every MLVL in every pak. Worlds that fail to parse are reported and left out, as are
+    /// names that can't be found, so lookups fall back to IDs.
+    pub fn load(paks: &PakFiles, cache: Option<&ResourceCache>) -> Result<Self> {
+        let mut areas = HashMap::new();
+        let mut world_index = 0;
+        for file in paks.load_all() {
+            // A pak that doesn't parse only costs the names of its worlds.
+            let Ok(pak) = Pak::new(file.data()) else {
+                continue;
+            };
+            let pak = pak.with_cache(cache.cloned());
+            for entry in pak.iter_resources() {
+                if entry.fourcc() != "MLVL" {
+                    continue;
+                }
+                let result = (|| {
+                    let mlvl: Mlvl = parse::read_resource("MLVL", &entry.data()?)?;
+                    let name = |id| -> Result<String> {
+                        let data = pak
+                            .data_with_fourcc(id, "STRG")?
+                            .ok_or_else(|| anyhow!("STRG 0x{id:08x} not found"))?;
+                        first_string(&parse::read_resource::<Strg>("STRG", &data)?)
+                            .ok_or_else(|| anyhow!("STRG 0x{id:08x} is empty"))
+                    };
+                    let world = name(mlvl.world_name_id)?;
+                    for area in &mlvl.areas {
+                        let area_name = name(area.name_id)
+                            .unwrap_or_else(|_| format!("0x{:08x}", area.mrea_id));
+                        areas.insert(
+                            area.mrea_id,
+                            AreaName {
+                                world_index,
+                                world: world.clone(),
+                                area: area_name,
+                            },
+                        );
+                    }
+                    anyhow::Ok(())
+                })();
+                match result {
+                    Ok(()) => world_index += 1,
+                    Err(e) => println!(
+                        "Error in {} MLVL 0x{:08x}: {e}",
+                        file.path(),
+                        entry.file_id(),
+                    ),
+                }
+            }
+        }
+        Ok(Self { areas })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &AreaName)> {
+        self.areas.iter().map(|(&mrea_id, name)| (mrea_id, name))
+    }
+
+    pub fn get(&self, mrea_id: u32) -> Option<&AreaName> {
+        self.areas.get(&mrea_id)
+    }
+
+    /// The area's name followed by its ID, or just the ID if it has no name.
+    pub fn describe(&self, mrea_id: u32) -> String {
+        match self.get(mrea_id) {
+            Some(name) => format!("{} / {} (0x{mrea_id:08x})", name.world, name.area),
+            None => format!("0x{mrea_id:08x}"),
+        }
+    }
+
+    /// A relative path to export the area to, without an extension. Unnamed areas go in a
+    /// directory of their own, named by ID.
+    pub fn path(&self, mrea_id: u32) -> PathBuf {
+        match self.get(mrea_id) {
+            Some(name) => name.path(),
+            None => ["unnamed".to_string(), format!("0x{mrea_id:08x}")]
+                .iter()
+                .collect(),
+        }
+    }
+}
+
+/// The first string of a STRG, preferring English, with formatting tags removed.
+fn first_string(strg: &Strg) -> Option<String> {
+    let language = strg.language(LANGUAGE).or(strg.languages.first())?;
+    language.strings.first().map(|s| strip_formatting(s))
+}
+
+/// Removes the game's inline formatting tags, like `&just=center;`, leaving the displayed text.
+pub fn strip_formatting(s: &str) -> String {
+    let mut result = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        match rest[start..].find(';') {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Turns a display name into a name that's safe as a file or directory on every common host:
+/// anything other than ASCII letters, digits, `-`, and `.` becomes `_`, runs of `_` collapse, and
+/// names reserved on Windows get a trailing `_`. Example: "Landing Site" becomes Landing_Site
+pub fn sanitize_file_name(name: &str) -> String {
+    const RESERVED: &[&str] = &[
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+        "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+
+    let mut sanitized = String::new();
+    for c in name.chars() {
+        let c = if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+            c
+        } else {
+            '_'
+        };
+        if !(c == '_' && sanitized.ends_with('_')) {
+            sanitized.push(c);
+        }
+    }
+    let mut sanitized = sanitized.trim_matches(['_', '.']).to_string();
+    if sanitized.is_empty() {
+        sanitized.push_str("unnamed");
+    }
+    let stem = sanitized.split('.').next().unwrap_or_default();
+    if RESERVED.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        sanitized.push('_');
+    }
+    sanitized
+}
@@ -0,0 +1,110 @@
+//! Writing patched disc images without risking the original. Patching commands write a copy
+//! unless asked to modify the input, and every patch is checked afterwards to have changed
+//! nothing outside the ranges it meant to write.
+
+use std::fs::{self, OpenOptions};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Result};
+use gamecube::Disc;
+use memmap::MmapMut;
+use sha2::{Digest, Sha256};
+
+use crate::workspace::DiscImage;
+
+/// The patched copy of a disc image, or the image itself when patching in place.
+pub struct OutputImage {
+    path: PathBuf,
+    mmap: MmapMut,
+    /// Hashes of the original image in [`Self::CHUNK_SIZE`] chunks, taken before any writes.
+    original_chunks: Vec<[u8; 32]>,
+    written: Vec<Range<usize>>,
+}
+
+impl OutputImage {
+    const CHUNK_SIZE: usize = 1 << 20;
+
+    /// Opens the image a patch is written to: a fresh copy of `image` at `output_path`, or the
+    /// image itself if `in_place` is set. Writing over the image by passing its own path as the
+    /// output is refused.
+    pub fn create(image: &DiscImage, output_path: Option<&str>, in_place: bool) -> Result<Self> {
+        let original_chunks = hash_chunks(image.data());
+        let path = if in_place {
+            image.path().to_path_buf()
+        } else {
+            let output_path = Path::new(
+                output_path
+                    .ok_or_else(|| anyhow!("An output path is needed without --in-place"))?,
+            );
+            if is_same_file(output_path, image.path()) {
+                bail!(
+                    "{} is the input disc image; pass --in-place to patch it directly",
+                    output_path.display(),
+                );
+            }
+            fs::copy(image.path(), output_path)?;
+            output_path.to_path_buf()
+        };
+
+        let file = OpenOptions::new().read(true).write(true).open(&path)?;
+        let mmap = unsafe { MmapMut::map_mut(&file) }?;
+        Ok(Self {
+            path,
+            mmap,
+            original_chunks,
+            written: Vec::new(),
+        })
+    }
+
+    /// A range of the image to write, which verification then skips.
+    pub fn range_mut(&mut self, range: Range<usize>) -> &mut [u8] {
+        self.written.push(range.clone());
+        &mut self.mmap[range]
+    }
+
+    /// Injects a file over the one at `path` on the disc, as [`Disc::inject_file`] does.
+    pub fn inject_file(&mut self, path: &Path, data: &[u8]) -> Result<()> {
+        let written = Disc::inject_file(&mut self.mmap, path, data)?;
+        self.written.extend(written);
+        Ok(())
+    }
+
+    /// Flushes the image, then checks that every chunk without a write in it still matches the
+    /// original. Returns the path of the image.
+    pub fn finish(self) -> Result<PathBuf> {
+        self.mmap.flush()?;
+        for (index, (chunk, original)) in self
+            .mmap
+            .chunks(Self::CHUNK_SIZE)
+            .zip(&self.original_chunks)
+            .enumerate()
+        {
+            let start = index * Self::CHUNK_SIZE;
+            let end = start + chunk.len();
+            if self.written.iter().any(|w| w.start < end && start < w.end) {
+                continue;
+            }
+            if Sha256::digest(chunk).as_slice() != original {
+                bail!(
+                    "{} changed outside the patched ranges, between 0x{start:x} and 0x{end:x}",
+                    self.path.display(),
+                );
+            }
+        }
+        Ok(self.path)
+    }
+}
+
+fn hash_chunks(data: &[u8]) -> Vec<[u8; 32]> {
+    data.chunks(OutputImage::CHUNK_SIZE)
+        .map(|chunk| Sha256::digest(chunk).into())
+        .collect()
+}
+
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
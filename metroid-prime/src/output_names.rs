@@ -0,0 +1,121 @@
+//! File names for resources written by batch exports. Resource names can hold characters,
+//! reserved names, or lengths that some hosts reject, and names differing only in case collide on
+//! Windows and macOS, so each name is sanitized and made unique within its directory. A mapping
+//! file records the original of every name that changed.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::names::sanitize_file_name;
+use crate::template::{OutputTemplate, TemplateValues};
+
+/// The file in the export's root directory mapping changed names back to the originals.
+pub const MAPPING_FILE_NAME: &str = "file_names.json";
+
+/// The longest name given out, leaving room under the 260 character path limit on Windows for
+/// the directories above it and the suffixes and extensions added to it.
+const MAX_NAME_LEN: usize = 64;
+
+pub struct OutputNames {
+    root: PathBuf,
+    /// The name given to each original name, by directory.
+    given: HashMap<(PathBuf, String), String>,
+    /// The lowercased names taken in each directory.
+    taken: HashMap<PathBuf, HashSet<String>>,
+    /// The original of each name that changed, by its path relative to the root.
+    renamed: BTreeMap<String, String>,
+}
+
+impl OutputNames {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            given: HashMap::new(),
+            taken: HashMap::new(),
+            renamed: BTreeMap::new(),
+        }
+    }
+
+    /// A name for `original` in `dir`, safe on every common host and unique within `dir` ignoring
+    /// case. Asking again for the same original in the same directory gives the same name.
+    pub fn name(&mut self, dir: &Path, original: &str) -> String {
+        let key = (dir.to_path_buf(), original.to_string());
+        if let Some(name) = self.given.get(&key) {
+            return name.clone();
+        }
+
+        let mut base = sanitize_file_name(original);
+        base.truncate(MAX_NAME_LEN);
+        // Windows drops trailing dots, which truncating can expose.
+        base.truncate(base.trim_end_matches('.').len());
+        let taken = self.taken.entry(dir.to_path_buf()).or_default();
+        let mut name = base.clone();
+        for n in 2.. {
+            if taken.insert(name.to_lowercase()) {
+                break;
+            }
+            name = format!("{base}_{n}");
+        }
+
+        if name != original {
+            let path = dir.strip_prefix(&self.root).unwrap_or(dir).join(&name);
+            let path = path.to_string_lossy().replace('\\', "/");
+            self.renamed.insert(path, original.to_string());
+        }
+        self.given.insert(key, name.clone());
+        name
+    }
+
+    /// The directory for a resource's files, which it creates, and the stem they share: as
+    /// `template` places them, or else in `default_dir` named for the resource.
+    pub fn resource(
+        &mut self,
+        template: Option<&OutputTemplate>,
+        default_dir: &Path,
+        values: &TemplateValues,
+    ) -> Result<(PathBuf, String)> {
+        let (dir, original) = match template {
+            Some(template) => {
+                let stem = self.root.join(template.render_stem(values));
+                let dir = stem.parent().unwrap_or(&self.root).to_path_buf();
+                let original = stem.file_name().unwrap_or_default().to_string_lossy();
+                (dir, original.into_owned())
+            }
+            None => (
+                default_dir.to_path_buf(),
+                values
+                    .name
+                    .map_or_else(|| format!("0x{:08x}", values.file_id), str::to_string),
+            ),
+        };
+        std::fs::create_dir_all(&dir)?;
+        let name = self.name(&dir, &original);
+        Ok((dir, name))
+    }
+
+    /// `dir` joined with the name for `original`.
+    pub fn join(&mut self, dir: &Path, original: &str) -> PathBuf {
+        dir.join(self.name(dir, original))
+    }
+
+    /// Writes the changed names and their originals as JSON to [`MAPPING_FILE_NAME`] in the root
+    /// directory, if any changed.
+    pub fn write_mapping(&self) -> Result<()> {
+        if self.renamed.is_empty() {
+            return Ok(());
+        }
+        let mut w = BufWriter::new(File::create(self.root.join(MAPPING_FILE_NAME))?);
+        serde_json::to_writer_pretty(&mut w, &self.renamed)?;
+        writeln!(w)?;
+        w.flush()?;
+        println!(
+            "Renamed {} files for the host; {MAPPING_FILE_NAME} lists their original names",
+            self.renamed.len(),
+        );
+        Ok(())
+    }
+}
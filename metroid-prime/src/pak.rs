@@ -1,23 +1,36 @@
+use std::borrow::Cow;
 use std::collections::{hash_map, HashMap};
+use std::ffi::OsStr;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
-use anyhow::{bail, Result};
-use flate2::{Decompress, FlushDecompress};
-use gamecube::bytes::ReadFixedCapacityAsciiCStringExt;
-use gamecube::ReadBytesExt;
+use anyhow::{anyhow, bail, Context, Result};
+use byteorder::{BigEndian, WriteBytesExt};
+use flate2::write::ZlibEncoder;
+use gamecube::bytes::{ReadFixedCapacityAsciiCStringExt, ReadFromSlice};
+use gamecube::{Disc, ReadBytesExt, SliceReader};
+
+use crate::cache::ResourceCache;
+use crate::compression::{Compression, Decompressor};
+use crate::disc_resources::DiscResources;
 
 pub struct Pak<'a> {
     name_table: Vec<NameTableEntry>,
     resource_table: Vec<ResourceTableEntry<'a>>,
+    cache: Option<ResourceCache>,
 }
 
-impl<'a> Pak<'a> {
-    pub fn new(data: &'a [u8]) -> Result<Self> {
-        let mut r = data;
+impl<'a> ReadFromSlice<'a> for Pak<'a> {
+    fn read_from_slice(r: &mut SliceReader<'a>) -> Result<Self> {
         let version = r.read_u32()?;
-        assert_eq!(version, 0x00030005);
+        if version != 0x00030005 {
+            bail!("unexpected pak version: 0x{version:08x}");
+        }
         let reserved = r.read_u32()?;
-        assert_eq!(reserved, 0);
+        if reserved != 0 {
+            bail!("unexpected pak reserved field: 0x{reserved:08x}");
+        }
 
         let name_count = r.read_u32()?;
         let mut name_table = Vec::new();
@@ -25,8 +38,7 @@ impl<'a> Pak<'a> {
             let fourcc = r.read_fixed_capacity_ascii_c_string(4)?;
             let file_id = r.read_u32()?;
             let name_len = r.read_u32()?;
-            let name = std::str::from_utf8(&r[..name_len as usize])?.to_string();
-            r = &r[name_len as usize..];
+            let name = std::str::from_utf8(r.take(name_len as usize)?)?.to_string();
             name_table.push(NameTableEntry {
                 fourcc,
                 file_id,
@@ -44,17 +56,33 @@ impl<'a> Pak<'a> {
             let offset = r.read_u32()?;
             resource_table.push(ResourceTableEntry {
                 compression,
-                fourcc: fourcc,
+                fourcc,
                 file_id,
-                data: &data[offset as usize..(offset + size) as usize],
+                offset,
+                data: r
+                    .slice_at(offset as usize, size as usize)
+                    .with_context(|| format!("Resource 0x{file_id:08x}"))?,
             });
         }
 
         Ok(Self {
             name_table,
             resource_table,
+            cache: None,
         })
     }
+}
+
+impl<'a> Pak<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        SliceReader::new(data).read_borrowed()
+    }
+
+    /// Serves decompressed resources from `cache` when present, filling it as needed.
+    pub fn with_cache(mut self, cache: Option<ResourceCache>) -> Self {
+        self.cache = cache;
+        self
+    }
 
     pub fn iter_names(&self) -> IterNames<'_> {
         IterNames {
@@ -72,21 +100,120 @@ impl<'a> Pak<'a> {
         self.name_table.iter().find(|entry| entry.name == name)
     }
 
+    /// The file ID a name given on the command line refers to: either a `0x`-prefixed hex file ID,
+    /// for the many resources without a name, or a name from the name table.
+    pub fn file_id(&self, name: &str) -> Result<u32> {
+        match name.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16)
+                .with_context(|| format!("Couldn't parse the file ID {name}")),
+            None => Ok(self
+                .entry(name)
+                .ok_or_else(|| anyhow!("Couldn't find the pak entry {name}"))?
+                .file_id()),
+        }
+    }
+
     pub fn data(&self, file_id: u32) -> Result<Option<Vec<u8>>> {
         self.resource_table
             .iter()
             .find(|entry| entry.file_id == file_id)
-            .map(ResourceTableEntry::data)
+            .map(|entry| self.load(entry))
+            .transpose()
+    }
+
+    /// Describes how a resource is stored without decompressing it.
+    pub fn resource_info(&self, file_id: u32) -> Result<Option<ResourceInfo>> {
+        self.resource_table
+            .iter()
+            .find(|entry| entry.file_id == file_id)
+            .map(ResourceTableEntry::info)
             .transpose()
     }
 
     pub fn data_with_fourcc(&self, file_id: u32, fourcc: &str) -> Result<Option<Vec<u8>>> {
         self.resource_table
             .iter()
-            .find(|entry| entry.file_id == file_id && &entry.fourcc == fourcc)
-            .map(ResourceTableEntry::data)
+            .find(|entry| entry.file_id == file_id && entry.fourcc == fourcc)
+            .map(|entry| self.load(entry))
             .transpose()
     }
+
+    /// Writes a copy of the pak with the decompressed data of some resources, keyed by file ID,
+    /// replaced. Replacements are compressed if the original was. Everything else is copied as
+    /// stored and keeps its order, so only the replaced resources and the offsets after them
+    /// change.
+    pub fn rebuild(&self, replacements: &HashMap<u32, Vec<u8>>) -> Result<Vec<u8>> {
+        const VERSION: u32 = 0x00030005;
+        const ALIGNMENT: usize = 32;
+
+        // Resources are stored once per original offset, in the original order.
+        let mut chunks: Vec<(u32, Vec<u8>)> = Vec::new();
+        let mut entries_by_offset: Vec<&ResourceTableEntry> = self.resource_table.iter().collect();
+        entries_by_offset.sort_by_key(|entry| entry.offset);
+        for entry in entries_by_offset {
+            if chunks
+                .last()
+                .is_some_and(|&(offset, _)| offset == entry.offset)
+            {
+                continue;
+            }
+            let mut data = match replacements.get(&entry.file_id) {
+                Some(data) if entry.compression != 0 => compress(data)?,
+                Some(data) => data.clone(),
+                None => entry.data.to_vec(),
+            };
+            data.resize(data.len().next_multiple_of(ALIGNMENT), 0);
+            chunks.push((entry.offset, data));
+        }
+
+        let mut header = Vec::new();
+        header.write_u32::<BigEndian>(VERSION)?;
+        header.write_u32::<BigEndian>(0)?;
+        header.write_u32::<BigEndian>(self.name_table.len() as u32)?;
+        for entry in &self.name_table {
+            header.write_all(entry.fourcc.as_bytes())?;
+            header.write_u32::<BigEndian>(entry.file_id)?;
+            header.write_u32::<BigEndian>(entry.name.len() as u32)?;
+            header.write_all(entry.name.as_bytes())?;
+        }
+        header.write_u32::<BigEndian>(self.resource_table.len() as u32)?;
+        let resource_table_start = header.len();
+        header.resize(
+            (resource_table_start + 20 * self.resource_table.len()).next_multiple_of(ALIGNMENT),
+            0,
+        );
+
+        let mut new_offsets = HashMap::new();
+        let mut next_offset = header.len();
+        for (offset, data) in &chunks {
+            new_offsets.insert(*offset, (next_offset as u32, data.len() as u32));
+            next_offset += data.len();
+        }
+        let mut table = &mut header[resource_table_start..];
+        for entry in &self.resource_table {
+            let (offset, size) = new_offsets[&entry.offset];
+            table.write_u32::<BigEndian>(entry.compression)?;
+            table.write_all(entry.fourcc.as_bytes())?;
+            table.write_u32::<BigEndian>(entry.file_id)?;
+            table.write_u32::<BigEndian>(size)?;
+            table.write_u32::<BigEndian>(offset)?;
+        }
+
+        let mut pak = header;
+        for (_, data) in chunks {
+            pak.extend_from_slice(&data);
+        }
+        Ok(pak)
+    }
+
+    fn load(&self, entry: &ResourceTableEntry) -> Result<Vec<u8>> {
+        match &self.cache {
+            Some(cache) if entry.compression != 0 => {
+                cache.get_or_insert_with(entry.data, || entry.data())
+            }
+            _ => entry.data(),
+        }
+    }
 }
 
 pub struct IterNames<'a> {
@@ -139,6 +266,7 @@ pub struct ResourceTableEntry<'a> {
     compression: u32,
     fourcc: String,
     file_id: u32,
+    offset: u32,
     data: &'a [u8],
 }
 
@@ -151,33 +279,61 @@ impl<'a> ResourceTableEntry<'a> {
         self.file_id
     }
 
+    /// Describes how the resource is stored. For compressed resources, the decompressed size comes
+    /// from the size field that precedes the zlib stream, so nothing is inflated.
+    pub fn info(&self) -> Result<ResourceInfo> {
+        let compressed_size = self.data.len() as u32;
+        match self.compression {
+            0 => Ok(ResourceInfo {
+                compressed: false,
+                compressed_size,
+                decompressed_size: compressed_size,
+            }),
+            1 => Ok(ResourceInfo {
+                compressed: true,
+                compressed_size,
+                decompressed_size: (&self.data[..]).read_u32()?,
+            }),
+            _ => bail!("Unexpected compression: {}", self.compression),
+        }
+    }
+
     pub fn data(&self) -> Result<Vec<u8>> {
         match self.compression {
             0 => Ok(self.data.to_vec()),
             1 => {
-                let uncompressed_size = self.data.clone().read_u32()? as usize;
+                let uncompressed_size = (&self.data[..]).read_u32()? as usize;
                 let compressed = &self.data[4..];
-
-                let mut uncompressed = Vec::with_capacity(uncompressed_size);
-                uncompressed.resize(uncompressed_size, 0);
-                assert_eq!(
-                    Decompress::new(true).decompress(
-                        compressed,
-                        &mut uncompressed,
-                        FlushDecompress::Finish
-                    )?,
-                    flate2::Status::StreamEnd,
-                );
-
-                Ok(uncompressed)
+                // Metroid Prime stores a zlib stream, and Metroid Prime 2 stores LZO segments.
+                Compression::detect(compressed).decompress(compressed, uncompressed_size)
             }
             _ => bail!("Unexpected compression: {}", self.compression),
         }
     }
 }
 
+/// Compresses resource data the way paks store it: the decompressed size, then a zlib stream.
+pub(crate) fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(
+        (data.len() as u32).to_be_bytes().to_vec(),
+        flate2::Compression::best(),
+    );
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResourceInfo {
+    /// Whether the resource is compressed, with zlib or LZO.
+    pub compressed: bool,
+    /// Size of the resource as stored in the pak, including the size prefix if compressed.
+    pub compressed_size: u32,
+    pub decompressed_size: u32,
+}
+
 pub struct PakCache<'a> {
     pak: Pak<'a>,
+    resources: Option<&'a DiscResources<'a>>,
     data_by_file_id: HashMap<(u32, String), Option<Rc<Vec<u8>>>>,
 }
 
@@ -185,22 +341,149 @@ impl<'a> PakCache<'a> {
     pub fn new(pak: Pak<'a>) -> Self {
         Self {
             pak,
+            resources: None,
             data_by_file_id: HashMap::new(),
         }
     }
 
+    /// Serves resources the pak doesn't have from `resources`, so references into other paks
+    /// resolve.
+    pub fn with_resources(mut self, resources: &'a DiscResources<'a>) -> Self {
+        self.resources = Some(resources);
+        self
+    }
+
     pub fn entry(&self, name: &str) -> Option<&NameTableEntry> {
         self.pak.entry(name)
     }
 
+    pub fn file_id(&self, name: &str) -> Result<u32> {
+        self.pak.file_id(name)
+    }
+
     pub fn data_with_fourcc(&mut self, file_id: u32, fourcc: &str) -> Result<Option<Rc<Vec<u8>>>> {
         Ok(
             match self.data_by_file_id.entry((file_id, fourcc.to_string())) {
                 hash_map::Entry::Occupied(entry) => entry.get().clone(),
-                hash_map::Entry::Vacant(entry) => entry
-                    .insert(self.pak.data_with_fourcc(file_id, fourcc)?.map(Rc::new))
-                    .clone(),
+                hash_map::Entry::Vacant(entry) => {
+                    let data = match (self.pak.data_with_fourcc(file_id, fourcc)?, self.resources) {
+                        (None, Some(resources)) => resources.get(fourcc, file_id)?,
+                        (data, _) => data,
+                    };
+                    entry.insert(data.map(Rc::new)).clone()
+                }
             },
         )
     }
 }
+
+/// Where commands find pak files: the selected disc image, if any, and loose pak files on the
+/// host, as extracted by other tools.
+pub struct PakFiles<'a> {
+    disc: Option<&'a Disc<'a>>,
+    loose_paths: Vec<PathBuf>,
+}
+
+/// The raw data of one pak file, borrowed from the disc image or read from the host.
+pub struct PakFile<'a> {
+    path: String,
+    on_disc: bool,
+    data: Cow<'a, [u8]>,
+}
+
+impl<'a> PakFiles<'a> {
+    pub fn new(disc: Option<&'a Disc<'a>>, loose_paths: Vec<PathBuf>) -> Self {
+        Self { disc, loose_paths }
+    }
+
+    /// Loads one pak file. A path naming a file on the host is read from there, so loose paks work
+    /// with or without a disc image; anything else is looked up in the disc filesystem.
+    pub fn load(&self, path: &str) -> Result<PakFile<'a>> {
+        if Path::new(path).is_file() {
+            return PakFile::read(Path::new(path));
+        }
+        let disc = self.disc.ok_or_else(|| {
+            anyhow!("Couldn't find the pak file {path} on the host, and no disc image is given")
+        })?;
+        let file = disc
+            .find_file(Path::new(path))?
+            .ok_or_else(|| anyhow!("Couldn't find the pak file {path}"))?;
+        Ok(PakFile {
+            path: path.to_string(),
+            on_disc: true,
+            data: Cow::Borrowed(file.data()),
+        })
+    }
+
+    /// Every pak file that loads, in the order of [`Self::iter`]. Those that don't are reported
+    /// and left out.
+    pub fn load_all(&self) -> Vec<PakFile<'a>> {
+        self.iter()
+            .filter_map(|file| {
+                file.map_err(|e| println!("Skipping a pak that doesn't load: {e}"))
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Every pak file on the disc, followed by the loose ones.
+    pub fn iter(&self) -> impl Iterator<Item = Result<PakFile<'a>>> + '_ {
+        let disc_paks = self
+            .disc
+            .into_iter()
+            .flat_map(|disc| disc.iter_files())
+            .filter_map(|file| match file {
+                Ok(file) if file.path().extension().and_then(OsStr::to_str) == Some("pak") => {
+                    Some(Ok(PakFile {
+                        path: file.path().display().to_string(),
+                        on_disc: true,
+                        data: Cow::Borrowed(file.data()),
+                    }))
+                }
+                Ok(_) => None,
+                // Entries pointing into trimmed padding are only a problem if they're paks.
+                Err(gamecube::Error::FileOutOfBounds { path, .. })
+                    if path.extension().and_then(OsStr::to_str) != Some("pak") =>
+                {
+                    None
+                }
+                Err(e) => Some(Err(e.into())),
+            });
+        let loose_paks = self.loose_paths.iter().map(|path| PakFile::read(path));
+        disc_paks.chain(loose_paks)
+    }
+}
+
+impl<'a> PakFile<'a> {
+    fn read(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)
+            .map_err(|e| anyhow!("Couldn't read the pak file {}: {e}", path.display()))?;
+        Ok(Self {
+            path: path.display().to_string(),
+            on_disc: false,
+            data: Cow::Owned(data),
+        })
+    }
+
+    /// The disc or host path the pak was loaded from, for messages.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The path within the disc filesystem, unless the pak was loaded from the host.
+    pub fn disc_path(&self) -> Option<&Path> {
+        self.on_disc.then(|| Path::new(&self.path))
+    }
+
+    /// The file name alone, for naming dumped files.
+    pub fn file_name(&self) -> &str {
+        Path::new(&self.path)
+            .file_name()
+            .and_then(OsStr::to_str)
+            .unwrap_or(&self.path)
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
@@ -0,0 +1,241 @@
+//! Options and warnings shared by every resource parser. Parsers are reached through `ReadFrom`
+//! impls, which take no arguments beyond the reader, so both live in per-thread state instead.
+
+use std::cell::{Cell, RefCell};
+use std::fmt::Display;
+use std::io::Read;
+use std::ops::Range;
+
+use anyhow::{bail, Result};
+use gamecube::bytes::ReadFrom;
+use serde::Serialize;
+
+use crate::detect::Revision;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ParseOptions {
+    /// Fail on data the parsers don't understand, such as unread trailing bytes, instead of
+    /// skipping it with a warning.
+    pub strict: bool,
+    /// The release of the game being parsed, if known, which layout detection favors.
+    pub revision: Option<Revision>,
+}
+
+/// Something odd a parser skipped over, like unread data or a suspicious count.
+#[derive(Clone, Debug, Serialize)]
+#[serde(transparent)]
+pub struct Warning {
+    pub message: String,
+}
+
+/// A parsed value along with the warnings recorded while parsing it.
+#[derive(Debug)]
+pub struct Parsed<T> {
+    pub value: T,
+    pub warnings: Vec<Warning>,
+}
+
+thread_local! {
+    static OPTIONS: Cell<ParseOptions> = const {
+        Cell::new(ParseOptions {
+            strict: false,
+            revision: None,
+        })
+    };
+    /// Collects warnings while a [`Parsed::capture`] is running; printed otherwise.
+    static WARNINGS: RefCell<Option<Vec<Warning>>> = const { RefCell::new(None) };
+    /// The trace a [`Trace::capture`] is filling in, if one is running.
+    static TRACE: RefCell<Option<Trace>> = const { RefCell::new(None) };
+    /// Set while [`read_unparsed`] runs, so traced reads aren't counted as understood.
+    static SKIPPING: Cell<bool> = const { Cell::new(false) };
+}
+
+impl ParseOptions {
+    /// The options in effect on this thread.
+    pub fn current() -> Self {
+        OPTIONS.get()
+    }
+
+    /// Puts these options in effect for parsers run on this thread from now on.
+    pub fn install(self) {
+        OPTIONS.set(self);
+    }
+
+    /// Puts these options in effect on this thread until the returned guard is dropped, which
+    /// restores the options before, however the code in between returns.
+    pub fn install_scoped(self) -> ParseOptionsGuard {
+        ParseOptionsGuard {
+            previous: OPTIONS.replace(self),
+        }
+    }
+}
+
+/// Restores the parse options replaced by [`ParseOptions::install_scoped`] when dropped.
+pub struct ParseOptionsGuard {
+    previous: ParseOptions,
+}
+
+impl Drop for ParseOptionsGuard {
+    fn drop(&mut self) {
+        OPTIONS.set(self.previous);
+    }
+}
+
+impl<T> Parsed<T> {
+    /// Runs `f`, collecting the warnings recorded on this thread until it returns. Captures nest;
+    /// an inner capture keeps its warnings from the outer one.
+    pub fn capture(f: impl FnOnce() -> Result<T>) -> Result<Self> {
+        let outer = WARNINGS.replace(Some(Vec::new()));
+        let result = f();
+        let warnings = WARNINGS.replace(outer).unwrap_or_default();
+        Ok(Self {
+            value: result?,
+            warnings,
+        })
+    }
+}
+
+/// Reports data a parser can skip but doesn't understand: an error in strict mode, otherwise a
+/// warning.
+pub fn unexpected(message: impl Display) -> Result<()> {
+    if ParseOptions::current().strict {
+        bail!("{message}");
+    }
+    warn(message);
+    Ok(())
+}
+
+/// Records a non-fatal oddity for the innermost [`Parsed::capture`], or prints it if there is none.
+pub fn warn(message: impl Display) {
+    let message = message.to_string();
+    WARNINGS.with_borrow_mut(|warnings| match warnings {
+        Some(warnings) => warnings.push(Warning { message }),
+        None => println!("Warning: {message}"),
+    });
+}
+
+/// Reads the rest of a structure that the parsers don't understand yet. A [`Trace`] counts the
+/// bytes as unread.
+pub fn read_unparsed<R: Read>(r: &mut R) -> Result<Vec<u8>> {
+    let skipping = SKIPPING.replace(true);
+    let mut data = Vec::new();
+    let result = r.read_to_end(&mut data);
+    SKIPPING.set(skipping);
+    result?;
+    Ok(data)
+}
+
+/// The byte ranges of a resource that its parser read, for finding the parts of incompletely
+/// understood formats that nothing reads yet.
+#[derive(Clone, Debug)]
+pub struct Trace {
+    /// The address range of the traced data, to tell it apart from other data parsed meanwhile.
+    source: Range<usize>,
+    /// Sorted and merged, so no two ranges overlap or touch.
+    reads: Vec<Range<usize>>,
+}
+
+impl Trace {
+    /// Runs `f`, recording what [`read_resource`] reads from `data` until it returns. Only the
+    /// parsers reached through `read_resource` are traced; formats parsed another way show no
+    /// reads at all.
+    pub fn capture<T>(data: &[u8], f: impl FnOnce() -> Result<T>) -> (Result<T>, Self) {
+        let range = data.as_ptr_range();
+        let outer = TRACE.replace(Some(Self {
+            source: range.start as usize..range.end as usize,
+            reads: Vec::new(),
+        }));
+        let result = f();
+        let trace = TRACE.replace(outer).unwrap();
+        (result, trace)
+    }
+
+    pub fn len(&self) -> usize {
+        self.source.len()
+    }
+
+    /// The number of bytes read at least once.
+    pub fn read_len(&self) -> usize {
+        self.reads.iter().map(Range::len).sum()
+    }
+
+    /// The ranges nothing read, in order.
+    pub fn holes(&self) -> Vec<Range<usize>> {
+        let mut holes = Vec::new();
+        let mut start = 0;
+        for read in &self.reads {
+            if read.start > start {
+                holes.push(start..read.start);
+            }
+            start = read.end;
+        }
+        if start < self.len() {
+            holes.push(start..self.len());
+        }
+        holes
+    }
+
+    fn record(&mut self, range: Range<usize>) {
+        // Merge with every existing range it overlaps or touches.
+        let first = self.reads.partition_point(|read| read.end < range.start);
+        let last = self.reads.partition_point(|read| read.start <= range.end);
+        let merged = match self.reads[first..last] {
+            [] => range,
+            [ref first, .., ref last] | [ref first @ ref last] => {
+                first.start.min(range.start)..last.end.max(range.end)
+            }
+        };
+        self.reads.splice(first..last, [merged]);
+    }
+
+    /// Whether `data` is the traced resource.
+    fn is_tracing(data: &[u8]) -> bool {
+        let range = data.as_ptr_range();
+        TRACE.with_borrow(|trace| {
+            trace
+                .as_ref()
+                .is_some_and(|trace| trace.source == (range.start as usize..range.end as usize))
+        })
+    }
+}
+
+/// Reads a slice like `&[u8]` does, recording each read in the running [`Trace`].
+struct TracingReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Read for TracingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = (&self.data[self.position..]).read(buf)?;
+        let range = self.position..self.position + len;
+        self.position += len;
+        if !range.is_empty() && !SKIPPING.get() {
+            TRACE.with_borrow_mut(|trace| {
+                if let Some(trace) = trace {
+                    trace.record(range);
+                }
+            });
+        }
+        Ok(len)
+    }
+}
+
+/// Parses a whole resource, checking that nothing but zero padding is left unread.
+pub fn read_resource<T: ReadFrom>(fourcc: &str, mut data: &[u8]) -> Result<T> {
+    let value = if Trace::is_tracing(data) {
+        let mut r = TracingReader { data, position: 0 };
+        let value = T::read_from(&mut r)?;
+        data = &data[r.position..];
+        value
+    } else {
+        T::read_from(&mut data)?
+    };
+    if data.iter().any(|&b| b != 0) {
+        unexpected(format_args!(
+            "{fourcc} has {} unread trailing bytes",
+            data.len()
+        ))?;
+    }
+    Ok(value)
+}
@@ -0,0 +1,131 @@
+//! Texture references in PART particle descriptions. These are found by scanning for the elements
+//! of interest rather than by parsing the whole description, so only animated textures are
+//! recognized for now.
+
+use std::collections::BTreeSet;
+
+use anyhow::{anyhow, bail, Result};
+use gamecube::ReadBytesExt;
+use serde::Serialize;
+
+use crate::txtr::DecodedTexture;
+
+const MAGIC: &[u8] = b"GPSM";
+
+/// Particles are simulated at this rate, which animated texture timing is counted in.
+const FRAMES_PER_SECOND: u32 = 60;
+
+/// An `ATEX` UV element: a texture used as a sheet of equally sized tiles that play in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct AnimatedTexture {
+    pub texture_id: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    /// Horizontal distance between the left edges of neighboring tiles.
+    pub stride_width: u32,
+    /// Vertical distance between the top edges of neighboring tiles.
+    pub stride_height: u32,
+    /// Length of one pass through every tile, in simulation frames.
+    pub cycle_frames: u32,
+}
+
+/// Finds every distinct animated texture a PART resource uses. Data that isn't a PART, and
+/// elements that don't have the expected layout, are skipped.
+pub fn find_animated_textures(data: &[u8]) -> BTreeSet<AnimatedTexture> {
+    if !data.starts_with(MAGIC) {
+        return BTreeSet::new();
+    }
+    (0..data.len().saturating_sub(4))
+        .filter(|&offset| &data[offset..offset + 4] == b"ATEX")
+        .filter_map(|offset| read_animated_texture(&data[offset + 4..]).ok())
+        .collect()
+}
+
+/// Reads the body of an `ATEX` element: a constant texture followed by five constant integers.
+fn read_animated_texture(mut data: &[u8]) -> Result<AnimatedTexture> {
+    if !skip_tag(&mut data, b"CNST") {
+        bail!("ATEX texture isn't constant");
+    }
+    // Some versions of the format name the asset type before the ID.
+    skip_tag(&mut data, b"TXTR");
+    let texture_id = data.read_u32()?;
+
+    let mut read_int = || -> Result<u32> {
+        if !skip_tag(&mut data, b"CNST") {
+            bail!("ATEX parameter isn't constant");
+        }
+        let value = data.read_i32()?;
+        u32::try_from(value).map_err(|_| anyhow!("Negative ATEX parameter: {value}"))
+    };
+    Ok(AnimatedTexture {
+        texture_id,
+        tile_width: read_int()?,
+        tile_height: read_int()?,
+        stride_width: read_int()?,
+        stride_height: read_int()?,
+        cycle_frames: read_int()?,
+    })
+}
+
+/// Moves past `tag` if the data starts with it.
+fn skip_tag(data: &mut &[u8], tag: &[u8; 4]) -> bool {
+    match data.strip_prefix(tag) {
+        Some(rest) => {
+            *data = rest;
+            true
+        }
+        None => false,
+    }
+}
+
+impl AnimatedTexture {
+    /// Cuts a decoded copy of the texture into its tiles, left to right and then top to bottom.
+    pub fn frames(&self, texture: &DecodedTexture) -> Result<Vec<DecodedTexture>> {
+        let (tile_width, tile_height) = (self.tile_width as usize, self.tile_height as usize);
+        let (stride_width, stride_height) =
+            (self.stride_width as usize, self.stride_height as usize);
+        if tile_width == 0 || tile_height == 0 || stride_width == 0 || stride_height == 0 {
+            bail!(
+                "Animated texture 0x{:08x} has an empty tile",
+                self.texture_id
+            );
+        }
+        if tile_width > texture.width || tile_height > texture.height {
+            bail!(
+                "Animated texture 0x{:08x} has {tile_width}x{tile_height} tiles, larger than \
+                 the {}x{} texture",
+                self.texture_id,
+                texture.width,
+                texture.height,
+            );
+        }
+
+        let columns = (texture.width - tile_width) / stride_width + 1;
+        let rows = (texture.height - tile_height) / stride_height + 1;
+        let mut frames = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let (left, top) = (column * stride_width, row * stride_height);
+                let mut rgba = Vec::with_capacity(tile_width * tile_height * 4);
+                for y in top..top + tile_height {
+                    let start = (y * texture.width + left) * 4;
+                    rgba.extend_from_slice(&texture.rgba[start..start + tile_width * 4]);
+                }
+                frames.push(DecodedTexture {
+                    width: tile_width,
+                    height: tile_height,
+                    rgba,
+                });
+            }
+        }
+        Ok(frames)
+    }
+
+    /// How long each of `frame_count` tiles is shown, in milliseconds.
+    pub fn frame_duration_ms(&self, frame_count: usize) -> u32 {
+        let frames = FRAMES_PER_SECOND as u64 * frame_count.max(1) as u64;
+        (self.cycle_frames as u64 * 1000 / frames)
+            .try_into()
+            .unwrap_or(u32::MAX)
+    }
+}
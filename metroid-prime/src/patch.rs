@@ -0,0 +1,156 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use flate2::{Crc, CrcWriter};
+use gamecube::Disc;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum PatchFormat {
+    /// A single BPS patch file against the original image.
+    #[default]
+    Bps,
+    /// A Riivolution-style folder of replacement files plus an XML descriptor.
+    Riivolution,
+}
+
+/// The shortest run of matching bytes worth breaking a literal run for. Each BPS action costs at
+/// least one byte, so shorter matches are cheaper to emit as part of the surrounding literal.
+const MIN_SOURCE_READ_LEN: usize = 4;
+
+const BPS_SOURCE_READ: u64 = 0;
+const BPS_TARGET_READ: u64 = 1;
+
+/// Writes a BPS patch that transforms `source` into `target`.
+///
+/// Only `SourceRead` and `TargetRead` actions are emitted. This doesn't find moved data, but disc
+/// images keep files at fixed offsets unless rebuilt, so in-place differences are the common case.
+pub fn write_bps<W: Write>(source: &[u8], target: &[u8], w: W) -> Result<()> {
+    let mut w = CrcWriter::new(w);
+
+    w.write_all(b"BPS1")?;
+    write_bps_number(&mut w, source.len() as u64)?;
+    write_bps_number(&mut w, target.len() as u64)?;
+    // No metadata.
+    write_bps_number(&mut w, 0)?;
+
+    let mut offset = 0;
+    while offset < target.len() {
+        let matching = matching_run_len(source, target, offset);
+        if matching >= MIN_SOURCE_READ_LEN || (matching > 0 && offset + matching == target.len()) {
+            write_bps_number(&mut w, ((matching as u64 - 1) << 2) | BPS_SOURCE_READ)?;
+            offset += matching;
+            continue;
+        }
+
+        // Extend the literal run until the next worthwhile match.
+        let start = offset;
+        while offset < target.len() {
+            let matching = matching_run_len(source, target, offset);
+            if matching >= MIN_SOURCE_READ_LEN {
+                break;
+            }
+            offset += matching.max(1);
+        }
+        write_bps_number(
+            &mut w,
+            (((offset - start) as u64 - 1) << 2) | BPS_TARGET_READ,
+        )?;
+        w.write_all(&target[start..offset])?;
+    }
+
+    let mut source_crc = Crc::new();
+    source_crc.update(source);
+    let mut target_crc = Crc::new();
+    target_crc.update(target);
+    w.write_all(&source_crc.sum().to_le_bytes())?;
+    w.write_all(&target_crc.sum().to_le_bytes())?;
+    let patch_crc = w.crc().sum();
+    w.write_all(&patch_crc.to_le_bytes())?;
+    w.flush()?;
+
+    Ok(())
+}
+
+fn matching_run_len(source: &[u8], target: &[u8], offset: usize) -> usize {
+    if offset >= source.len() {
+        return 0;
+    }
+    source[offset..]
+        .iter()
+        .zip(&target[offset..])
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+fn write_bps_number<W: Write>(w: &mut W, mut value: u64) -> Result<()> {
+    loop {
+        let x = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            w.write_all(&[0x80 | x])?;
+            return Ok(());
+        }
+        w.write_all(&[x])?;
+        value -= 1;
+    }
+}
+
+/// Writes every file that differs between the original and modified discs into a Riivolution
+/// folder layout rooted at `out_dir`:
+///
+/// - `<out_dir>/riivolution/<game code>.xml` describes the replacements.
+/// - `<out_dir>/<game code>/files/<disc path>` holds the replacement file contents.
+///
+/// Returns the number of replaced files.
+pub fn write_riivolution(original: &Disc, modified: &Disc, out_dir: &Path) -> Result<usize> {
+    let game_code = original.header().game_code();
+    let files_dir = out_dir.join(game_code).join("files");
+
+    let mut replacements = Vec::new();
+    for file in modified.iter_files() {
+        let file = file?;
+        let unchanged = match original.find_file(file.path())? {
+            Some(original_file) => original_file.data() == file.data(),
+            None => false,
+        };
+        if unchanged {
+            continue;
+        }
+
+        let dump_path = files_dir.join(file.path());
+        fs::create_dir_all(dump_path.parent().unwrap())?;
+        fs::write(&dump_path, file.data())?;
+        replacements.push(file.path().to_path_buf());
+    }
+
+    let xml_dir = out_dir.join("riivolution");
+    fs::create_dir_all(&xml_dir)?;
+    let mut w = BufWriter::new(File::create(xml_dir.join(format!("{game_code}.xml")))?);
+    writeln!(w, r#"<wiidisc version="1">"#)?;
+    writeln!(w, r#"  <id game="{game_code}" />"#)?;
+    writeln!(w, r#"  <options>"#)?;
+    writeln!(w, r#"    <section name="{game_code}">"#)?;
+    writeln!(w, r#"      <option name="Modified files">"#)?;
+    writeln!(w, r#"        <choice name="Enabled">"#)?;
+    writeln!(w, r#"          <patch id="files" />"#)?;
+    writeln!(w, r#"        </choice>"#)?;
+    writeln!(w, r#"      </option>"#)?;
+    writeln!(w, r#"    </section>"#)?;
+    writeln!(w, r#"  </options>"#)?;
+    writeln!(w, r#"  <patch id="files">"#)?;
+    for path in &replacements {
+        let disc_path = path.to_string_lossy().replace('\\', "/");
+        writeln!(
+            w,
+            r#"    <file disc="/{disc_path}" external="/{game_code}/files/{disc_path}" />"#,
+        )?;
+    }
+    writeln!(w, r#"  </patch>"#)?;
+    writeln!(w, r#"</wiidisc>"#)?;
+    w.flush()?;
+
+    Ok(replacements.len())
+}
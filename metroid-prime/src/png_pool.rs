@@ -0,0 +1,163 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+
+use crate::txtr::DecodedTexture;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum PngCompression {
+    /// Larger files, written quickly.
+    Fast,
+    #[default]
+    Default,
+    /// Smaller files, written slowly.
+    Best,
+}
+
+impl From<PngCompression> for png::Compression {
+    fn from(compression: PngCompression) -> Self {
+        match compression {
+            PngCompression::Fast => Self::Fast,
+            PngCompression::Default => Self::Default,
+            PngCompression::Best => Self::Best,
+        }
+    }
+}
+
+/// A lossy or more compact format written next to each PNG, for galleries and browsers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PreviewFormat {
+    Webp,
+    Jpeg,
+}
+
+impl PreviewFormat {
+    const JPEG_QUALITY: u8 = 85;
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Webp => "webp",
+            Self::Jpeg => "jpg",
+        }
+    }
+
+    fn write<W: Write>(self, texture: &DecodedTexture, w: W) -> Result<()> {
+        match self {
+            Self::Webp => texture.write_webp(w),
+            Self::Jpeg => texture.write_jpeg(w, Self::JPEG_QUALITY),
+        }
+    }
+}
+
+/// Worker threads that encode decoded textures to PNG files. Encoding dominates texture dumps, so
+/// this lets the caller keep decoding while earlier images are compressed.
+///
+/// The queue is bounded, so a caller that decodes faster than the workers encode blocks rather
+/// than holding every decoded image in memory. Each file's contents depend only on its own job, so
+/// output is the same regardless of scheduling.
+pub struct PngPool {
+    sender: Option<SyncSender<PngJob>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+struct PngJob {
+    path: PathBuf,
+    texture: DecodedTexture,
+    compression: PngCompression,
+    preview: Option<PreviewFormat>,
+}
+
+impl PngJob {
+    fn run(&self) -> Result<()> {
+        let mut w = BufWriter::new(File::create(&self.path)?);
+        self.texture
+            .write_png_with_compression(&mut w, self.compression.into())?;
+        w.flush()?;
+
+        if let Some(preview) = self.preview {
+            let path = self.path.with_extension(preview.extension());
+            let mut w = BufWriter::new(File::create(path)?);
+            preview.write(&self.texture, &mut w)?;
+            w.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl PngPool {
+    /// Jobs allowed to wait per worker before `submit` blocks.
+    const QUEUE_DEPTH_PER_WORKER: usize = 2;
+
+    /// Starts one worker per available CPU.
+    pub fn new() -> Self {
+        Self::with_threads(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    pub fn with_threads(threads: usize) -> Self {
+        let threads = threads.max(1);
+        let (sender, receiver) = sync_channel(threads * Self::QUEUE_DEPTH_PER_WORKER);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                thread::spawn(move || Self::work(&receiver))
+            })
+            .collect();
+        Self {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    fn work(receiver: &Mutex<Receiver<PngJob>>) {
+        loop {
+            // Hold the lock only while taking a job, not while encoding it.
+            let job = match receiver.lock().unwrap().recv() {
+                Ok(job) => job,
+                Err(_) => return,
+            };
+            if let Err(e) = job.run() {
+                println!("Error writing {}: {e}", job.path.display());
+            }
+        }
+    }
+
+    /// Queues a texture to be written to `path`, and to a preview beside it if requested, blocking
+    /// while the queue is full. Failures to write are reported by the worker and don't stop the
+    /// pool.
+    pub fn submit(
+        &self,
+        path: PathBuf,
+        texture: DecodedTexture,
+        compression: PngCompression,
+        preview: Option<PreviewFormat>,
+    ) -> Result<()> {
+        self.sender
+            .as_ref()
+            .unwrap()
+            .send(PngJob {
+                path,
+                texture,
+                compression,
+                preview,
+            })
+            .map_err(|_| anyhow!("PNG workers have stopped"))
+    }
+
+    /// Waits for every queued texture to be written.
+    pub fn finish(mut self) -> Result<()> {
+        self.sender = None;
+        for worker in self.workers.drain(..) {
+            worker
+                .join()
+                .map_err(|_| anyhow!("A PNG worker panicked"))?;
+        }
+        Ok(())
+    }
+}
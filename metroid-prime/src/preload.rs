@@ -0,0 +1,255 @@
+//! What the game preloads for each area. The MLVL lists the resources each of an area's script
+//! layers needs, in the order the game loads them. The list is compared with the references the
+//! parsers can follow, from the area's geometry and from each listed resource, to find listed
+//! resources nothing refers to and referenced resources that aren't listed.
+//!
+//! Script objects' references to models, characters, and particles aren't followed, and neither
+//! are the static textures of particles, so an unreferenced resource is a lead rather than proof.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::ancs::Ancs;
+use crate::cache::ResourceCache;
+use crate::cmdl::Cmdl;
+use crate::font::Font;
+use crate::mlvl::{Area, Mlvl};
+use crate::mrea::MreaSections;
+use crate::names::AreaNames;
+use crate::pak::{Pak, PakFiles};
+use crate::parse;
+use crate::part;
+use crate::scan::Scan;
+
+/// Types that only other resources refer to, all through references the parsers follow. Listed
+/// resources of these types that nothing listed refers to are reported as unreferenced.
+const LEAF_TYPES: &[&str] = &["TXTR", "CSKR", "CINF", "ANIM", "EVNT"];
+
+/// Marks an unused reference.
+const NO_ID: u32 = 0xffffffff;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AreaPreload {
+    pub pak: String,
+    pub mrea_id: String,
+    pub world: Option<String>,
+    pub area: Option<String>,
+    /// Every listed resource, in load order.
+    pub resources: Vec<PreloadEntry>,
+    /// Listed resources of types only other resources refer to that nothing listed refers to.
+    pub unreferenced: Vec<PreloadEntry>,
+    /// Resources the area or a listed resource refers to that aren't listed, so the game loads
+    /// them when they're first used.
+    pub unlisted: Vec<Reference>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PreloadEntry {
+    /// The script layer whose list holds the resource.
+    pub layer: usize,
+    pub fourcc: String,
+    pub id: String,
+    pub name: Option<String>,
+}
+
+/// A resource another refers to.
+#[derive(Clone, Debug, Serialize)]
+pub struct Reference {
+    pub fourcc: &'static str,
+    pub id: String,
+    /// The resource that refers to it.
+    pub from: String,
+}
+
+impl AreaPreload {
+    /// Expands one area's preload list. Listed resources that don't parse are skipped with a
+    /// warning, and their references aren't followed.
+    pub fn new(pak_path: &str, pak: &Pak, area: &Area, names: &AreaNames) -> Result<Self> {
+        let mrea_id = area.mrea_id;
+        let data = pak
+            .data_with_fourcc(mrea_id, "MREA")?
+            .ok_or_else(|| anyhow!("MREA 0x{mrea_id:08x} not found"))?;
+        let sections = MreaSections::new(&data)?;
+        let resource_names: HashMap<u32, String> = pak
+            .iter_names()
+            .map(|entry| (entry.file_id(), entry.name().to_string()))
+            .collect();
+
+        let mut resources = Vec::new();
+        for (index, dependency) in area.dependencies.iter().enumerate() {
+            let layer = area
+                .dependency_offsets
+                .partition_point(|&offset| offset as usize <= index)
+                .saturating_sub(1);
+            resources.push(PreloadEntry {
+                layer,
+                fourcc: String::from_utf8_lossy(&dependency.fourcc.to_be_bytes()).into_owned(),
+                id: format!("0x{:08x}", dependency.id),
+                name: resource_names.get(&dependency.id).cloned(),
+            });
+        }
+
+        let mut references = Vec::new();
+        let area_id = format!("MREA 0x{mrea_id:08x}");
+        for &id in &sections.material_set()?.texture_ids {
+            references.push(reference("TXTR", id, &area_id));
+        }
+        for (entry, dependency) in resources.iter().zip(&area.dependencies) {
+            let Some(data) = pak.data_with_fourcc(dependency.id, &entry.fourcc)? else {
+                continue;
+            };
+            let from = format!("{} {}", entry.fourcc, entry.id);
+            match follow(&entry.fourcc, &data, &from) {
+                Ok(found) => references.extend(found),
+                Err(e) => parse::warn(format!("{from} doesn't parse: {e}")),
+            }
+        }
+
+        let listed: HashSet<u32> = area.dependencies.iter().map(|d| d.id).collect();
+        let referenced: HashSet<u32> = references.iter().map(|&(id, _)| id).collect();
+        let unreferenced = resources
+            .iter()
+            .zip(&area.dependencies)
+            .filter(|(entry, dependency)| {
+                LEAF_TYPES.contains(&entry.fourcc.as_str()) && !referenced.contains(&dependency.id)
+            })
+            .map(|(entry, _)| entry.clone())
+            .collect();
+        let mut seen = HashSet::new();
+        let unlisted = references
+            .into_iter()
+            .filter(|&(id, _)| !listed.contains(&id) && seen.insert(id))
+            .map(|(_, reference)| reference)
+            .collect();
+
+        let name = names.get(mrea_id);
+        Ok(Self {
+            pak: pak_path.to_string(),
+            mrea_id: format!("0x{mrea_id:08x}"),
+            world: name.map(|name| name.world.clone()),
+            area: name.map(|name| name.area.clone()),
+            resources,
+            unreferenced,
+            unlisted,
+        })
+    }
+}
+
+/// A reference to `id`, along with the ID for comparing.
+fn reference(fourcc: &'static str, id: u32, from: &str) -> (u32, Reference) {
+    let reference = Reference {
+        fourcc,
+        id: format!("0x{id:08x}"),
+        from: from.to_string(),
+    };
+    (id, reference)
+}
+
+/// The references the parsers can follow from a resource.
+fn follow(fourcc: &str, data: &[u8], from: &str) -> Result<Vec<(u32, Reference)>> {
+    let mut ids: Vec<(&'static str, u32)> = Vec::new();
+    match fourcc {
+        "CMDL" => {
+            let cmdl: Cmdl = parse::read_resource(fourcc, data)?;
+            for material_set in &cmdl.materials {
+                ids.extend(material_set.texture_ids.iter().map(|&id| ("TXTR", id)));
+            }
+        }
+        "ANCS" => {
+            let ancs: Ancs = parse::read_resource(fourcc, data)?;
+            for character in &ancs.character_set.characters {
+                ids.extend([
+                    ("CMDL", character.model_id),
+                    ("CSKR", character.skin_id),
+                    ("CINF", character.skeleton_id),
+                    ("CMDL", character.frozen_model_id),
+                    ("CSKR", character.frozen_skin_id),
+                ]);
+                let particles = &character.particle_resource_data;
+                ids.extend(
+                    particles
+                        .generic_particle_ids
+                        .iter()
+                        .map(|&id| ("PART", id)),
+                );
+                ids.extend(particles.swoosh_particle_ids.iter().map(|&id| ("SWHC", id)));
+                ids.extend(
+                    particles
+                        .electric_particle_ids
+                        .iter()
+                        .map(|&id| ("ELSC", id)),
+                );
+            }
+            for resource in &ancs.animation_set.animation_resources {
+                ids.extend([("ANIM", resource.animation_id), ("EVNT", resource.event_id)]);
+            }
+        }
+        "FONT" => {
+            let font: Font = parse::read_resource(fourcc, data)?;
+            ids.push(("TXTR", font.texture_id));
+        }
+        "SCAN" => {
+            let scan: Scan = parse::read_resource(fourcc, data)?;
+            ids.extend([("FRME", scan.frame_id), ("STRG", scan.string_id)]);
+            ids.extend(scan.images.iter().map(|image| ("TXTR", image.texture_id)));
+        }
+        "PART" => {
+            let textures = part::find_animated_textures(data);
+            ids.extend(textures.iter().map(|texture| ("TXTR", texture.texture_id)));
+        }
+        _ => {}
+    }
+    Ok(ids
+        .into_iter()
+        // Zero is an unset reference in older characters, which lack frozen models.
+        .filter(|&(_, id)| id != NO_ID && id != 0)
+        .map(|(fourcc, id)| reference(fourcc, id, from))
+        .collect())
+}
+
+/// Expands the preload lists of every area of every world, in world and area order. Areas that
+/// fail to parse are reported and left out.
+pub fn build(paks: &PakFiles, cache: Option<&ResourceCache>) -> Result<Vec<AreaPreload>> {
+    let names = AreaNames::load(paks, cache)?;
+    let mut preloads = Vec::new();
+    for file in paks.iter() {
+        let file = file?;
+        let pak_path = file.path().to_string();
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        for entry in pak.iter_resources() {
+            if entry.fourcc() != "MLVL" {
+                continue;
+            }
+            let mlvl: Mlvl = match entry
+                .data()
+                .and_then(|data| parse::read_resource("MLVL", &data))
+            {
+                Ok(mlvl) => mlvl,
+                Err(e) => {
+                    println!("Error in {pak_path} MLVL 0x{:08x}: {e}", entry.file_id());
+                    continue;
+                }
+            };
+            for area in &mlvl.areas {
+                match AreaPreload::new(&pak_path, &pak, area, &names) {
+                    Ok(preload) => preloads.push(preload),
+                    Err(e) => println!(
+                        "Error in {pak_path} MREA {}: {e}",
+                        names.describe(area.mrea_id),
+                    ),
+                }
+            }
+        }
+    }
+    Ok(preloads)
+}
+
+pub fn write<W: Write>(preloads: &[AreaPreload], mut w: W) -> Result<()> {
+    serde_json::to_writer_pretty(&mut w, preloads)?;
+    writeln!(w)?;
+    w.flush()?;
+    Ok(())
+}
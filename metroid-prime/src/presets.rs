@@ -0,0 +1,116 @@
+//! Sets of characters that artists commonly want together, exported in one run by ExportPreset.
+//! The paks don't say what a character is, so each preset recognizes its characters by name, by
+//! pak, or by what their ANCS resources contain.
+
+use std::collections::BTreeMap;
+
+use clap::ValueEnum;
+
+use crate::ancs::{Ancs, Character, MetaAnimation};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// Every variant of Samus's suit, found by the names of the ANCS entries and characters, in a
+    /// directory per suit.
+    Suits,
+    /// Every creature in the world paks, found by its pose-and-state database, in a directory
+    /// per ANCS.
+    Creatures,
+    /// The arm cannon's characters from SamusGun.pak, in a directory per ANCS, each under a root
+    /// node placing it where the first-person view shows it.
+    Gun,
+    /// The HUD and menu assets of the GUI paks rather than characters: FRME layouts as JSON widget
+    /// trees, fonts as JSON metrics beside their glyph textures, and the other textures as PNGs.
+    Hud,
+}
+
+/// Each suit's output folder and the word its names contain. Fusion is first so that Fusion
+/// variants of the other suits land in its folder.
+pub const SUITS: &[(&str, &str)] = &[
+    ("fusion", "fusion"),
+    ("power", "power"),
+    ("varia", "varia"),
+    ("gravity", "gravity"),
+    ("phazon", "phazon"),
+];
+
+impl Preset {
+    /// The directory the preset exports into by default.
+    pub fn default_out_dir(self) -> &'static str {
+        match self {
+            Self::Suits => "suits",
+            Self::Creatures => "creatures",
+            Self::Gun => "gun",
+            Self::Hud => "hud",
+        }
+    }
+
+    /// Whether the preset's characters can be in the pak with this file name.
+    pub fn includes_pak(self, file_name: &str) -> bool {
+        match self {
+            Self::Suits => true,
+            Self::Creatures => file_name.starts_with("Metroid") && file_name.ends_with(".pak"),
+            Self::Gun => file_name == "SamusGun.pak",
+            Self::Hud => file_name.to_lowercase().contains("gui"),
+        }
+    }
+
+    /// The folder a character of the preset is exported into, or `None` if the character isn't
+    /// part of the preset.
+    pub fn folder(self, ancs_name: &str, character: &Character) -> Option<String> {
+        match self {
+            Self::Suits => suit_folder(ancs_name, &character.name).map(str::to_string),
+            // The AI picks animations through the pose-and-state database, so only characters
+            // that move on their own have one.
+            Self::Creatures => {
+                (!character.pas_database.anim_states.is_empty()).then(|| ancs_name.to_string())
+            }
+            Self::Gun => Some(ancs_name.to_string()),
+            Self::Hud => None,
+        }
+    }
+}
+
+/// The folder of the suit a character belongs to, if it's one of Samus's suits at all.
+pub fn suit_folder(ancs_name: &str, character_name: &str) -> Option<&'static str> {
+    let name = format!("{ancs_name} {character_name}").to_lowercase();
+    if !name.contains("samus") && !name.contains("suit") {
+        return None;
+    }
+    SUITS
+        .iter()
+        .find(|(_, keyword)| name.contains(keyword))
+        .map(|&(folder, _)| folder)
+}
+
+/// The ANIM resources an animation set plays, by primitive name. Characters of one ANCS share
+/// them.
+pub fn animation_ids(ancs: &Ancs) -> BTreeMap<String, u32> {
+    fn collect(meta_animation: &MetaAnimation, ids: &mut BTreeMap<String, u32>) {
+        match meta_animation {
+            MetaAnimation::Play {
+                animation_id,
+                primitive_name,
+                ..
+            } => {
+                ids.insert(primitive_name.clone(), *animation_id);
+            }
+            MetaAnimation::Random(pairs) => {
+                for (meta_animation, _) in pairs {
+                    collect(meta_animation, ids);
+                }
+            }
+            MetaAnimation::Sequence(meta_animations) => {
+                for meta_animation in meta_animations {
+                    collect(meta_animation, ids);
+                }
+            }
+        }
+    }
+
+    let mut ids = BTreeMap::new();
+    for animation in &ancs.animation_set.animations {
+        collect(&animation.meta_animation, &mut ids);
+    }
+    ids
+}
@@ -0,0 +1,251 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{Recenter, ScriptMarkers, SplitBy, DEFAULT_EXPORT_NAME};
+
+/// A record of exports and the options they were made with, so an asset pack can be regenerated
+/// after the tool is upgraded.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Project {
+    /// Version of the tool that last wrote the project.
+    pub tool_version: String,
+    pub exports: Vec<Export>,
+}
+
+/// One recorded export. Replaying it reproduces the same command with the same options.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Export {
+    Cmdl {
+        pak_path: String,
+        name: String,
+        material_set_index: usize,
+        split_by: SplitBy,
+        #[serde(default)]
+        bake_materials: bool,
+        #[serde(default)]
+        approximate_reflections: bool,
+        #[serde(default)]
+        recenter: Recenter,
+        #[serde(default)]
+        simplify: Option<f32>,
+        #[serde(default)]
+        quantize: bool,
+        #[serde(default)]
+        no_vertex_colors: bool,
+        #[serde(default = "default_file_name")]
+        file_name: String,
+        out_dir: PathBuf,
+    },
+    Ancs {
+        pak_path: String,
+        ancs_name: String,
+        /// Every character, one scene each, if unset.
+        #[serde(default)]
+        character_name: Option<String>,
+        material_set_index: usize,
+        split_by: SplitBy,
+        #[serde(default)]
+        bake_materials: bool,
+        #[serde(default)]
+        approximate_reflections: bool,
+        #[serde(default)]
+        recenter: Recenter,
+        #[serde(default)]
+        simplify: Option<f32>,
+        #[serde(default)]
+        animation_bounds: bool,
+        #[serde(default)]
+        root_offset: Option<[f32; 3]>,
+        /// Skinned to the skeleton, with the ANCS's animations, rather than static.
+        #[serde(default)]
+        rigged: bool,
+        #[serde(default = "default_file_name")]
+        file_name: String,
+        out_dir: PathBuf,
+    },
+    World {
+        pak_path: String,
+        /// Name of the MLVL entry, or its file ID in hex. Defaults to the pak's only MLVL.
+        #[serde(default)]
+        world: Option<String>,
+        split_by: SplitBy,
+        #[serde(default)]
+        bake_materials: bool,
+        #[serde(default)]
+        approximate_reflections: bool,
+        #[serde(default)]
+        simplify: Option<f32>,
+        #[serde(default)]
+        quantize: bool,
+        #[serde(default)]
+        no_vertex_colors: bool,
+        #[serde(default)]
+        scene_per_area: bool,
+        #[serde(default)]
+        debug_geometry: bool,
+        #[serde(default)]
+        script_objects: Option<ScriptMarkers>,
+        #[serde(default)]
+        trigger_volumes: bool,
+        #[serde(default)]
+        cameras: bool,
+        #[serde(default = "default_file_name")]
+        file_name: String,
+        out_dir: PathBuf,
+    },
+    Mrea {
+        pak_path: String,
+        /// Name of the MREA entry, or its file ID in hex.
+        mrea: String,
+        split_by: SplitBy,
+        #[serde(default)]
+        bake_materials: bool,
+        #[serde(default)]
+        approximate_reflections: bool,
+        #[serde(default)]
+        recenter: Recenter,
+        #[serde(default)]
+        simplify: Option<f32>,
+        #[serde(default)]
+        quantize: bool,
+        #[serde(default)]
+        no_vertex_colors: bool,
+        #[serde(default)]
+        debug_geometry: bool,
+        #[serde(default)]
+        script_objects: Option<ScriptMarkers>,
+        #[serde(default)]
+        trigger_volumes: bool,
+        #[serde(default)]
+        cameras: bool,
+        #[serde(default = "default_file_name")]
+        file_name: String,
+        out_dir: PathBuf,
+    },
+}
+
+fn default_file_name() -> String {
+    DEFAULT_EXPORT_NAME.to_string()
+}
+
+impl Export {
+    pub fn out_dir(&self) -> &Path {
+        match self {
+            Self::Cmdl { out_dir, .. }
+            | Self::Ancs { out_dir, .. }
+            | Self::Mrea { out_dir, .. }
+            | Self::World { out_dir, .. } => out_dir,
+        }
+    }
+
+    pub fn file_name(&self) -> &str {
+        match self {
+            Self::Cmdl { file_name, .. }
+            | Self::Ancs { file_name, .. }
+            | Self::Mrea { file_name, .. }
+            | Self::World { file_name, .. } => file_name,
+        }
+    }
+
+    fn same_target(&self, other: &Self) -> bool {
+        if self.out_dir() != other.out_dir() || self.file_name() != other.file_name() {
+            return false;
+        }
+        match (self, other) {
+            (
+                Self::Cmdl { pak_path, name, .. },
+                Self::Cmdl {
+                    pak_path: other_pak_path,
+                    name: other_name,
+                    ..
+                },
+            ) => pak_path == other_pak_path && name == other_name,
+            (
+                Self::Ancs {
+                    pak_path,
+                    ancs_name,
+                    character_name,
+                    ..
+                },
+                Self::Ancs {
+                    pak_path: other_pak_path,
+                    ancs_name: other_ancs_name,
+                    character_name: other_character_name,
+                    ..
+                },
+            ) => {
+                pak_path == other_pak_path
+                    && ancs_name == other_ancs_name
+                    && character_name == other_character_name
+            }
+            (
+                Self::World {
+                    pak_path, world, ..
+                },
+                Self::World {
+                    pak_path: other_pak_path,
+                    world: other_world,
+                    ..
+                },
+            ) => pak_path == other_pak_path && world == other_world,
+            (
+                Self::Mrea { pak_path, mrea, .. },
+                Self::Mrea {
+                    pak_path: other_pak_path,
+                    mrea: other_mrea,
+                    ..
+                },
+            ) => pak_path == other_pak_path && mrea == other_mrea,
+            _ => false,
+        }
+    }
+}
+
+impl Project {
+    /// Loads a project file, or returns an empty project if the file doesn't exist yet.
+    pub fn load_or_default(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::load(path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = BufReader::new(
+            File::open(path)
+                .with_context(|| format!("Couldn't open project file {}", path.display()))?,
+        );
+        serde_json::from_reader(file)
+            .with_context(|| format!("Couldn't parse project file {}", path.display()))
+    }
+
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        self.tool_version = env!("CARGO_PKG_VERSION").to_string();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut file, self)?;
+        writeln!(file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Adds an export to the project. Exporting the same asset into the same directory again
+    /// replaces the earlier record rather than duplicating it.
+    pub fn record(&mut self, export: Export) {
+        match self
+            .exports
+            .iter_mut()
+            .find(|existing| existing.same_target(&export))
+        {
+            Some(existing) => *existing = export,
+            None => self.exports.push(export),
+        }
+    }
+}
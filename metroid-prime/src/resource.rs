@@ -0,0 +1,133 @@
+use anyhow::Result;
+
+use crate::ancs::Ancs;
+use crate::audio::{Agsc, Atbl};
+use crate::cinf::Cinf;
+use crate::cmdl::Cmdl;
+use crate::cskr::Cskr;
+use crate::font::Font;
+use crate::frme::Frme;
+use crate::mlvl::Mlvl;
+use crate::mrea::MreaSections;
+use crate::parse;
+use crate::scan::Scan;
+use crate::strg::Strg;
+use crate::txtr::{self, DecodedTexture};
+
+/// Any resource with a known format, parsed.
+pub enum Resource {
+    Agsc(Agsc),
+    Ancs(Ancs),
+    Atbl(Atbl),
+    Cinf(Cinf),
+    Cmdl(Cmdl),
+    Cskr(Cskr),
+    Font(Font),
+    Frme(Frme),
+    Mlvl(Mlvl),
+    Mrea(MreaSections),
+    Scan(Scan),
+    Strg(Strg),
+    Txtr(DecodedTexture),
+}
+
+impl Resource {
+    /// The fourccs of every resource type [`Resource::parse`] understands.
+    pub const FOURCCS: &'static [&'static str] = &[
+        "AGSC", "ANCS", "ATBL", "CINF", "CMDL", "CSKR", "FONT", "FRME", "MLVL", "MREA", "SCAN",
+        "STRG", "TXTR",
+    ];
+
+    /// Parses a resource's decompressed data according to its fourcc. Returns `None` for resource
+    /// types without a parser.
+    pub fn parse(fourcc: &str, data: &[u8]) -> Result<Option<Self>> {
+        Ok(Some(match fourcc {
+            "AGSC" => Self::Agsc(parse::read_resource(fourcc, data)?),
+            "ANCS" => Self::Ancs(parse::read_resource(fourcc, data)?),
+            "ATBL" => Self::Atbl(parse::read_resource(fourcc, data)?),
+            "CINF" => Self::Cinf(parse::read_resource(fourcc, data)?),
+            "CMDL" => Self::Cmdl(parse::read_resource(fourcc, data)?),
+            "CSKR" => Self::Cskr(parse::read_resource(fourcc, data)?),
+            "FONT" => Self::Font(parse::read_resource(fourcc, data)?),
+            "FRME" => Self::Frme(parse::read_resource(fourcc, data)?),
+            "MLVL" => Self::Mlvl(parse::read_resource(fourcc, data)?),
+            "MREA" => Self::Mrea(MreaSections::new(data)?),
+            "SCAN" => Self::Scan(parse::read_resource(fourcc, data)?),
+            "STRG" => Self::Strg(parse::read_resource(fourcc, data)?),
+            "TXTR" => Self::Txtr(txtr::decode(data)?),
+            _ => return Ok(None),
+        }))
+    }
+
+    /// Identifies a resource by the magic number its format starts with, for entries whose fourcc
+    /// is unknown or can't be trusted. Only some formats have one.
+    pub fn sniff(data: &[u8]) -> Option<&'static str> {
+        const MAGICS: &[(u32, &str)] = &[
+            (0xdeadbabe, "CMDL"),
+            (0xdeadbeef, "MREA"),
+            (0xdeafbabe, "MLVL"),
+            (0x87654321, "STRG"),
+            (0xc001d00d, "SAVW"),
+            (u32::from_be_bytes(*b"FONT"), "FONT"),
+        ];
+
+        let magic = u32::from_be_bytes(data.get(..4)?.try_into().unwrap());
+        MAGICS
+            .iter()
+            .find(|&&(m, _)| m == magic)
+            .map(|&(_, fourcc)| fourcc)
+    }
+
+    /// A one-line description of the resource's contents.
+    pub fn summary(&self) -> String {
+        match self {
+            Self::Agsc(agsc) => format!("{}, {} samples", agsc.name, agsc.samples.len()),
+            Self::Ancs(ancs) => format!(
+                "{} characters, {} animations",
+                ancs.character_set.characters.len(),
+                ancs.animation_set.animations.len(),
+            ),
+            Self::Atbl(atbl) => format!("{} sound IDs", atbl.sfx_ids.len()),
+            Self::Cinf(cinf) => format!("{} bones", cinf.bones.len()),
+            Self::Cmdl(cmdl) => format!(
+                "{} material sets, {} surfaces",
+                cmdl.materials.len(),
+                cmdl.surfaces.len(),
+            ),
+            Self::Cskr(cskr) => format!("{} vertex groups", cskr.vertex_groups.len()),
+            Self::Font(font) => format!("{}, {} glyphs", font.name, font.glyphs.len()),
+            Self::Frme(frme) => format!("{} widgets", frme.widgets.len()),
+            Self::Mlvl(mlvl) => format!("{} areas", mlvl.areas.len()),
+            Self::Mrea(mrea) => format!(
+                "version 0x{:x}, {} sections",
+                mrea.version,
+                mrea.sections.len(),
+            ),
+            Self::Scan(scan) => format!("{}, {} images", scan.category.name(), scan.images.len(),),
+            Self::Strg(strg) => format!(
+                "{} languages, {} strings",
+                strg.languages.len(),
+                strg.languages.first().map_or(0, |l| l.strings.len()),
+            ),
+            Self::Txtr(texture) => format!("{}x{}", texture.width, texture.height),
+        }
+    }
+
+    pub fn fourcc(&self) -> &'static str {
+        match self {
+            Self::Agsc(_) => "AGSC",
+            Self::Ancs(_) => "ANCS",
+            Self::Atbl(_) => "ATBL",
+            Self::Cinf(_) => "CINF",
+            Self::Cmdl(_) => "CMDL",
+            Self::Cskr(_) => "CSKR",
+            Self::Font(_) => "FONT",
+            Self::Frme(_) => "FRME",
+            Self::Mlvl(_) => "MLVL",
+            Self::Mrea(_) => "MREA",
+            Self::Scan(_) => "SCAN",
+            Self::Strg(_) => "STRG",
+            Self::Txtr(_) => "TXTR",
+        }
+    }
+}
@@ -0,0 +1,323 @@
+//! Checking a rigged model from a 3D editor against the skeleton it will be imported onto. The
+//! game animates bones by ID, so every joint must be one of the skeleton's bones, found by name,
+//! under the same parent, and each vertex can only follow a few of them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::cinf::Cinf;
+use crate::gltf_input::{self, Document, Node, Primitive, Skin};
+
+/// How far a vertex's weights may sum from one before it counts as unnormalized.
+const WEIGHT_SUM_TOLERANCE: f32 = 0.01;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RigProblem {
+    NoSkin,
+    /// A joint whose node name isn't a bone in the skeleton.
+    UnknownJoint {
+        joint: String,
+    },
+    DuplicateJoint {
+        bone: String,
+    },
+    /// A bone without a joint, which vertices then can't follow. Only a warning.
+    MissingBone {
+        bone: String,
+    },
+    WrongParent {
+        joint: String,
+        parent: Option<String>,
+        expected: Option<String>,
+    },
+    Unweighted {
+        primitive: String,
+    },
+    JointOutOfRange {
+        primitive: String,
+        index: usize,
+        count: usize,
+    },
+    TooManyInfluences {
+        primitive: String,
+        vertices: usize,
+        most: usize,
+        max: usize,
+    },
+    UnnormalizedWeights {
+        primitive: String,
+        vertices: usize,
+    },
+}
+
+impl RigProblem {
+    /// Whether importing would fail or mangle the model, rather than just leave bones unused.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Self::MissingBone { .. })
+    }
+}
+
+impl fmt::Display for RigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn or_root(name: &Option<String>) -> &str {
+            name.as_deref().unwrap_or("the root")
+        }
+        match self {
+            Self::NoSkin => write!(f, "No node has both a mesh and a skin"),
+            Self::UnknownJoint { joint } => write!(
+                f,
+                "Joint {joint} isn't a bone of the skeleton; rename it to match one or remove it \
+                 from the armature",
+            ),
+            Self::DuplicateJoint { bone } => {
+                write!(f, "More than one joint is named {bone}; keep only one")
+            }
+            Self::MissingBone { bone } => write!(
+                f,
+                "Warning: bone {bone} has no joint, so no vertex can follow it",
+            ),
+            Self::WrongParent {
+                joint,
+                parent,
+                expected,
+            } => write!(
+                f,
+                "Joint {joint} is parented to {}, but its bone's parent is {}",
+                or_root(parent),
+                or_root(expected),
+            ),
+            Self::Unweighted { primitive } => write!(
+                f,
+                "{primitive} has no joint weights; parent it to the armature with weights",
+            ),
+            Self::JointOutOfRange {
+                primitive,
+                index,
+                count,
+            } => write!(
+                f,
+                "{primitive} is weighted to joint {index}, but the skin has {count}",
+            ),
+            Self::TooManyInfluences {
+                primitive,
+                vertices,
+                most,
+                max,
+            } => write!(
+                f,
+                "{primitive} has {vertices} vertices weighted to more than {max} bones, up to \
+                 {most}; limit the total bone weights per vertex to {max}",
+            ),
+            Self::UnnormalizedWeights {
+                primitive,
+                vertices,
+            } => write!(
+                f,
+                "{primitive} has {vertices} vertices whose weights don't sum to one; normalize \
+                 the weights",
+            ),
+        }
+    }
+}
+
+/// Checks every skin in the glTF model at `path` against `skeleton`, allowing vertices up to
+/// `max_influences` bones. Returns every problem found, so one run shows all of them.
+pub fn check(path: &Path, skeleton: &Cinf, max_influences: usize) -> Result<Vec<RigProblem>> {
+    let (document, buffers) = gltf_input::load(path)?;
+    let mut problems = Vec::new();
+    let skinned_nodes: Vec<&Node> = document
+        .nodes
+        .iter()
+        .filter(|node| node.mesh.is_some() && node.skin.is_some())
+        .collect();
+    if skinned_nodes.is_empty() {
+        problems.push(RigProblem::NoSkin);
+    }
+
+    let mut parents = HashMap::new();
+    for (index, node) in document.nodes.iter().enumerate() {
+        for &child in &node.children {
+            parents.insert(child, index);
+        }
+    }
+    let node_name = |index: usize| match document.nodes.get(index).and_then(|n| n.name.as_ref()) {
+        Some(name) => name.clone(),
+        None => format!("node {index}"),
+    };
+    let bone_ids: HashMap<&str, u32> = skeleton
+        .bone_names
+        .iter()
+        .map(|n| (n.name.as_str(), n.id))
+        .collect();
+    let bone_name = |bone_id: u32| {
+        skeleton
+            .bone_names
+            .iter()
+            .find(|n| n.id == bone_id)
+            .map(|n| n.name.clone())
+    };
+
+    for skin in &document.skins {
+        // Map the joints to bones by name, then compare each joint's nearest joint ancestor with
+        // its bone's parent.
+        let mut joint_bones: BTreeMap<usize, u32> = BTreeMap::new();
+        let mut seen = HashMap::new();
+        for &joint in &skin.joints {
+            let name = node_name(joint);
+            match bone_ids.get(name.as_str()) {
+                Some(&bone_id) => {
+                    if seen.insert(bone_id, joint).is_some() {
+                        problems.push(RigProblem::DuplicateJoint { bone: name });
+                    }
+                    joint_bones.insert(joint, bone_id);
+                }
+                None => problems.push(RigProblem::UnknownJoint { joint: name }),
+            }
+        }
+        for bone in &skeleton.bones {
+            if !seen.contains_key(&bone.bone_id) {
+                if let Some(bone) = bone_name(bone.bone_id) {
+                    problems.push(RigProblem::MissingBone { bone });
+                }
+            }
+        }
+        for (&joint, &bone_id) in &joint_bones {
+            let mut ancestor = parents.get(&joint).copied();
+            while let Some(index) = ancestor {
+                if joint_bones.contains_key(&index) {
+                    break;
+                }
+                ancestor = parents.get(&index).copied();
+            }
+            let parent = ancestor.map(node_name);
+            let expected = skeleton
+                .bones
+                .iter()
+                .find(|b| b.bone_id == bone_id)
+                .and_then(|b| bone_name(b.parent_bone_id));
+            if parent != expected {
+                problems.push(RigProblem::WrongParent {
+                    joint: node_name(joint),
+                    parent,
+                    expected,
+                });
+            }
+        }
+    }
+
+    for node in skinned_nodes {
+        let mesh_index = node.mesh.unwrap();
+        let skin = document
+            .skins
+            .get(node.skin.unwrap())
+            .ok_or_else(|| anyhow!("Skin {} is out of range", node.skin.unwrap()))?;
+        let mesh = document
+            .meshes
+            .get(mesh_index)
+            .ok_or_else(|| anyhow!("Mesh {mesh_index} is out of range"))?;
+        let mesh_name = mesh
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("Mesh {mesh_index}"));
+        for (primitive_index, primitive) in mesh.primitives.iter().enumerate() {
+            let primitive_name = format!("{mesh_name} primitive {primitive_index}");
+            let problem_count = problems.len();
+            check_primitive(
+                &document,
+                &buffers,
+                primitive,
+                skin,
+                max_influences,
+                &primitive_name,
+                &mut problems,
+            )?;
+            // A mesh instanced by several nodes only needs reporting once.
+            let new_problems = problems.split_off(problem_count);
+            for problem in new_problems {
+                if !problems.contains(&problem) {
+                    problems.push(problem);
+                }
+            }
+        }
+    }
+    Ok(problems)
+}
+
+fn check_primitive(
+    document: &Document,
+    buffers: &[Vec<u8>],
+    primitive: &Primitive,
+    skin: &Skin,
+    max_influences: usize,
+    name: &str,
+    problems: &mut Vec<RigProblem>,
+) -> Result<()> {
+    let mut influences: Vec<Vec<(usize, f32)>> = Vec::new();
+    for set in 0.. {
+        let (Some(&joints), Some(&weights)) = (
+            primitive.attributes.get(&format!("JOINTS_{set}")),
+            primitive.attributes.get(&format!("WEIGHTS_{set}")),
+        ) else {
+            break;
+        };
+        let joints = gltf_input::read_accessor(document, buffers, joints)?;
+        let weights = gltf_input::read_accessor(document, buffers, weights)?;
+        influences.resize(joints.len(), Vec::new());
+        for ((vertex, joints), weights) in influences.iter_mut().zip(&joints).zip(&weights) {
+            for (&joint, &weight) in joints.iter().zip(weights) {
+                if weight > 0.0 {
+                    vertex.push((joint as usize, weight));
+                }
+            }
+        }
+    }
+    if influences.is_empty() {
+        problems.push(RigProblem::Unweighted {
+            primitive: name.to_string(),
+        });
+        return Ok(());
+    }
+
+    let mut out_of_range = None;
+    let mut too_many = 0;
+    let mut most = 0;
+    let mut unnormalized = 0;
+    for vertex in &influences {
+        if let Some(&(index, _)) = vertex.iter().find(|(j, _)| *j >= skin.joints.len()) {
+            out_of_range.get_or_insert(index);
+        }
+        if vertex.len() > max_influences {
+            too_many += 1;
+            most = most.max(vertex.len());
+        }
+        let sum: f32 = vertex.iter().map(|(_, w)| w).sum();
+        if (sum - 1.0).abs() > WEIGHT_SUM_TOLERANCE {
+            unnormalized += 1;
+        }
+    }
+    if let Some(index) = out_of_range {
+        problems.push(RigProblem::JointOutOfRange {
+            primitive: name.to_string(),
+            index,
+            count: skin.joints.len(),
+        });
+    }
+    if too_many > 0 {
+        problems.push(RigProblem::TooManyInfluences {
+            primitive: name.to_string(),
+            vertices: too_many,
+            most,
+            max: max_influences,
+        });
+    }
+    if unnormalized > 0 {
+        problems.push(RigProblem::UnnormalizedWeights {
+            primitive: name.to_string(),
+            vertices: unnormalized,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,59 @@
+//! Checks that resource writers reproduce the data they parsed, so repacking a disc can't silently
+//! corrupt resources nobody touched.
+
+use anyhow::Result;
+use gamecube::bytes::{ReadFrom, WriteTo};
+
+use crate::cinf::Cinf;
+use crate::cskr::Cskr;
+use crate::parse;
+use crate::strg::Strg;
+
+/// How a rewritten resource compares to the original.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundTrip {
+    Identical,
+    /// Equal except for trailing zero padding, which paks add and writers don't.
+    PaddingDiffers,
+    /// The first offset at which the rewritten data differs.
+    Differs {
+        offset: usize,
+    },
+}
+
+/// The fourccs of every resource type [`check`] can rewrite.
+pub const FOURCCS: &[&str] = &["CINF", "CSKR", "STRG"];
+
+/// Parses a resource's decompressed data, writes it back, and compares the result to the original.
+/// Returns `None` for resource types without a writer.
+pub fn check(fourcc: &str, data: &[u8]) -> Result<Option<RoundTrip>> {
+    let written = match fourcc {
+        "CINF" => rewrite::<Cinf>(fourcc, data)?,
+        "CSKR" => rewrite::<Cskr>(fourcc, data)?,
+        "STRG" => rewrite::<Strg>(fourcc, data)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(compare(data, &written)))
+}
+
+fn rewrite<T: ReadFrom + WriteTo>(fourcc: &str, data: &[u8]) -> Result<Vec<u8>> {
+    let value: T = parse::read_resource(fourcc, data)?;
+    let mut written = Vec::new();
+    value.write_to(&mut written)?;
+    Ok(written)
+}
+
+fn compare(original: &[u8], written: &[u8]) -> RoundTrip {
+    let common_len = original.len().min(written.len());
+    if let Some(offset) = (0..common_len).find(|&i| original[i] != written[i]) {
+        return RoundTrip::Differs { offset };
+    }
+    let (original_tail, written_tail) = (&original[common_len..], &written[common_len..]);
+    if original_tail.is_empty() && written_tail.is_empty() {
+        RoundTrip::Identical
+    } else if original_tail.iter().chain(written_tail).all(|&b| b == 0) {
+        RoundTrip::PaddingDiffers
+    } else {
+        RoundTrip::Differs { offset: common_len }
+    }
+}
@@ -0,0 +1,129 @@
+use std::io::Read;
+
+use anyhow::{bail, Result};
+use gamecube::bytes::ReadFrom;
+use gamecube::ReadBytesExt;
+use serde::Serialize;
+
+/// A SCAN resource: what the scan visor shows for an object, and where the logbook files it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Scan {
+    /// The FRME laying out the scan's images.
+    pub frame_id: u32,
+    /// The STRG holding the scan's text.
+    pub string_id: u32,
+    pub speed: ScanSpeed,
+    pub category: LogbookCategory,
+    /// Whether the scan is marked in red as one the player shouldn't miss.
+    pub important: bool,
+    /// The images shown beside the text. Unused slots are left out.
+    pub images: Vec<ScanImage>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanSpeed {
+    Normal,
+    Slow,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogbookCategory {
+    /// Not recorded in the logbook.
+    None,
+    PirateData,
+    ChozoLore,
+    Creatures,
+    Research,
+    Artifacts,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ScanImage {
+    pub texture_id: u32,
+    /// How far into the scan, as a percentage, the image appears.
+    pub appearance_percent: f32,
+    /// Which of the FRME's image panes the image fills.
+    pub position: u32,
+    /// The image's size in panes, for images split into a grid of tiles.
+    pub width: u32,
+    pub height: u32,
+    /// Seconds between the tiles of a split image appearing.
+    pub interval: f32,
+    pub fade_duration: f32,
+}
+
+impl Scan {
+    const VERSION: u32 = 5;
+    const MAGIC: u32 = 0x0badbeef;
+    const IMAGE_SLOTS: usize = 4;
+}
+
+impl LogbookCategory {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::PirateData => "Pirate Data",
+            Self::ChozoLore => "Chozo Lore",
+            Self::Creatures => "Creatures",
+            Self::Research => "Research",
+            Self::Artifacts => "Artifacts",
+        }
+    }
+}
+
+impl ReadFrom for Scan {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let version = r.read_u32()?;
+        if version != Self::VERSION {
+            bail!("unexpected SCAN version: {version}");
+        }
+        let magic = r.read_u32()?;
+        if magic != Self::MAGIC {
+            bail!("unexpected SCAN magic: 0x{magic:08x}");
+        }
+        let frame_id = r.read_u32()?;
+        let string_id = r.read_u32()?;
+        let speed = match r.read_u32()? {
+            0 => ScanSpeed::Normal,
+            1 => ScanSpeed::Slow,
+            speed => bail!("unexpected SCAN speed: {speed}"),
+        };
+        let category = match r.read_u32()? {
+            0 => LogbookCategory::None,
+            1 => LogbookCategory::PirateData,
+            2 => LogbookCategory::ChozoLore,
+            3 => LogbookCategory::Creatures,
+            4 => LogbookCategory::Research,
+            5 => LogbookCategory::Artifacts,
+            category => bail!("unexpected SCAN logbook category: {category}"),
+        };
+        let important = r.read_u8()? != 0;
+
+        let mut images = Vec::new();
+        for _ in 0..Self::IMAGE_SLOTS {
+            let image = ScanImage {
+                texture_id: r.read_u32()?,
+                appearance_percent: f32::from_bits(r.read_u32()?),
+                position: r.read_u32()?,
+                width: r.read_u32()?,
+                height: r.read_u32()?,
+                interval: f32::from_bits(r.read_u32()?),
+                fade_duration: f32::from_bits(r.read_u32()?),
+            };
+            if image.texture_id != u32::MAX {
+                images.push(image);
+            }
+        }
+
+        Ok(Self {
+            frame_id,
+            string_id,
+            speed,
+            category,
+            important,
+            images,
+        })
+    }
+}
@@ -0,0 +1,346 @@
+//! SCLY script layers: the objects of an area's script, such as actors, triggers, and pickups, and
+//! the connections between them. Only Metroid Prime's layout is supported.
+//!
+//! Object properties differ by type and aren't parsed in general. Types placed in the world start
+//! their properties with a name and a position, which [`ScriptObject::placement`] reads, and
+//! [`ScriptObject::link`] reads where docks and world transporters lead, and
+//! [`ScriptObject::field_of_view`] reads how wide cameras see, and [`ScriptObject::sound`] what
+//! sound objects play.
+
+use anyhow::{anyhow, bail, Result};
+use gamecube::bytes::ReadAsciiCStringExt;
+use gamecube::{ReadBytesExt, SliceReader};
+
+const MAGIC: u32 = 0x53434c59; // SCLY
+const VERSION: u32 = 1;
+
+#[derive(Clone, Debug)]
+pub struct ScriptLayer {
+    pub objects: Vec<ScriptObject>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ScriptObject {
+    pub object_type: u8,
+    pub instance_id: u32,
+    pub connections: Vec<Connection>,
+    pub property_count: u32,
+    /// The properties after their count, laid out by type.
+    pub properties: Vec<u8>,
+}
+
+/// A message the object sends another when it enters a state.
+#[derive(Clone, Copy, Debug)]
+pub struct Connection {
+    pub state: u32,
+    pub message: u32,
+    pub target_id: u32,
+}
+
+/// Where an object sits in its area.
+#[derive(Clone, Debug)]
+pub struct Placement {
+    pub name: String,
+    pub position: [f32; 3],
+    /// Rotation in degrees about each axis, applied X first, then Y, then Z.
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+    /// The size of the box the object covers, centered on its position, for triggers and other
+    /// volumes.
+    pub volume: Option<[f32; 3]>,
+}
+
+/// Where an object leads the player.
+#[derive(Clone, Copy, Debug)]
+pub enum Link {
+    /// A doorway, by its index among the area's docks in the MLVL, which lists where each leads.
+    Dock { dock_index: u32 },
+    /// An elevator to another world.
+    World { mlvl_id: u32, mrea_id: u32 },
+}
+
+/// What a sound object plays, and how.
+#[derive(Clone, Copy, Debug)]
+pub struct Sound {
+    /// The sound ID, which the ATBL translates to a sound effect.
+    pub sound_id: u32,
+    pub active: bool,
+    /// How far away the sound can be heard.
+    pub max_distance: f32,
+    /// Seconds to wait before playing.
+    pub start_delay: f32,
+    /// Volume out of 127.
+    pub volume: u32,
+    pub looped: bool,
+    /// Whether the sound plays without a position, as loud anywhere in the area.
+    pub non_emitter: bool,
+    pub auto_start: bool,
+}
+
+/// How a type's properties begin, after the object's name.
+#[derive(Clone, Copy)]
+enum Layout {
+    Position,
+    PositionRotation,
+    PositionRotationScale,
+    /// Which enemy variant the object is, then a position, rotation, and scale.
+    FlavorPositionRotationScale,
+    /// A position and the extent of an axis-aligned box centered on it.
+    PositionVolume,
+    /// Whether the object starts active, then a position and volume.
+    ActivePositionVolume,
+}
+
+/// Names and layouts of the object types placed in the world. Types missing here, like relays and
+/// timers, have no position.
+const PLACED_TYPES: &[(u8, &str, Layout)] = &[
+    (0x00, "Actor", Layout::PositionRotationScale),
+    (0x02, "Waypoint", Layout::PositionRotation),
+    (0x03, "DoorArea", Layout::PositionRotationScale),
+    (0x04, "Trigger", Layout::PositionVolume),
+    (0x07, "Effect", Layout::PositionRotationScale),
+    (0x08, "Platform", Layout::PositionRotationScale),
+    (ScriptObject::SOUND, "Sound", Layout::PositionRotation),
+    (ScriptObject::DOCK, "Dock", Layout::ActivePositionVolume),
+    (ScriptObject::CAMERA, "Camera", Layout::PositionRotation),
+    (
+        ScriptObject::CAMERA_WAYPOINT,
+        "CameraWaypoint",
+        Layout::PositionRotation,
+    ),
+    (0x0e, "NewIntroBoss", Layout::PositionRotationScale),
+    (0x0f, "SpawnPoint", Layout::PositionRotation),
+    (0x10, "CameraHint", Layout::PositionRotation),
+    (0x11, "Pickup", Layout::PositionRotationScale),
+    (0x16, "Beetle", Layout::FlavorPositionRotationScale),
+    (0x1a, "DamageableTrigger", Layout::PositionVolume),
+    (0x1b, "Debris", Layout::PositionRotationScale),
+    (0x20, "Water", Layout::PositionVolume),
+    (0x21, "Warwasp", Layout::FlavorPositionRotationScale),
+    (0x24, "SpacePirate", Layout::PositionRotationScale),
+    (0x25, "FlyingPirate", Layout::PositionRotationScale),
+    (0x26, "ElitePirate", Layout::PositionRotationScale),
+    (0x27, "MetroidBeta", Layout::PositionRotationScale),
+    (0x28, "ChozoGhost", Layout::PositionRotationScale),
+    (0x2a, "CoverPoint", Layout::PositionRotation),
+    (0x2c, "SpiderBallWaypoint", Layout::PositionRotation),
+    (0x2d, "BloodFlower", Layout::PositionRotationScale),
+    (0x2e, "FlickerBat", Layout::FlavorPositionRotationScale),
+    (0x2f, "PathCamera", Layout::PositionRotation),
+    (0x30, "GrapplePoint", Layout::PositionRotation),
+    (0x31, "PuddleSpore", Layout::FlavorPositionRotationScale),
+    (
+        0x33,
+        "SpiderBallAttractionSurface",
+        Layout::PositionRotationScale,
+    ),
+    (0x34, "PuddleToadGamma", Layout::FlavorPositionRotationScale),
+    (0x36, "FireFlea", Layout::PositionRotationScale),
+    (0x37, "Metaree", Layout::PositionRotationScale),
+    (0x3a, "SpecialFunction", Layout::PositionRotation),
+    (0x3b, "SpankWeed", Layout::PositionRotationScale),
+    (0x3d, "Parasite", Layout::FlavorPositionRotationScale),
+    (0x3e, "PlayerHint", Layout::PositionRotation),
+    (0x3f, "Ripper", Layout::PositionRotationScale),
+    (0x40, "PickupGenerator", Layout::Position),
+    (0x42, "PointOfInterest", Layout::PositionRotation),
+    (0x43, "Drone", Layout::FlavorPositionRotationScale),
+    (0x44, "Metroid", Layout::PositionRotationScale),
+    (0x45, "DebrisExtended", Layout::PositionRotationScale),
+    (0x46, "Steam", Layout::PositionVolume),
+    (0x47, "Ripple", Layout::Position),
+    (0x48, "BallTrigger", Layout::PositionVolume),
+    (0x49, "TargetingPoint", Layout::PositionRotation),
+    (0x71, "SpindleCamera", Layout::PositionRotation),
+];
+
+/// Reads the layers of a SCLY section.
+pub fn read_layers(data: &[u8]) -> Result<Vec<ScriptLayer>> {
+    let mut r = SliceReader::new(data);
+    let magic = r.read_u32()?;
+    if magic != MAGIC {
+        bail!("unexpected SCLY magic: 0x{magic:08x}");
+    }
+    let version = r.read_u32()?;
+    if version != VERSION {
+        bail!("unexpected SCLY version: {version}");
+    }
+    let layer_count = r.read_u32()?;
+    let mut layer_sizes = Vec::new();
+    for _ in 0..layer_count {
+        layer_sizes.push(r.read_u32()? as usize);
+    }
+
+    let mut layers = Vec::new();
+    for (index, size) in layer_sizes.into_iter().enumerate() {
+        let data = r
+            .take(size)
+            .map_err(|e| anyhow!("SCLY layer {index}: {e}"))?;
+        layers.push(read_layer(data).map_err(|e| anyhow!("SCLY layer {index}: {e}"))?);
+    }
+    Ok(layers)
+}
+
+fn read_layer(data: &[u8]) -> Result<ScriptLayer> {
+    let mut r = SliceReader::new(data);
+    let _unknown = r.read_u8()?;
+    let object_count = r.read_u32()?;
+    let mut objects = Vec::new();
+    for _ in 0..object_count {
+        let object_type = r.read_u8()?;
+        let size = r.read_u32()? as usize;
+        let mut r = SliceReader::new(r.take(size)?);
+        let instance_id = r.read_u32()?;
+        let connection_count = r.read_u32()?;
+        let mut connections = Vec::new();
+        for _ in 0..connection_count {
+            connections.push(Connection {
+                state: r.read_u32()?,
+                message: r.read_u32()?,
+                target_id: r.read_u32()?,
+            });
+        }
+        let property_count = r.read_u32()?;
+        objects.push(ScriptObject {
+            object_type,
+            instance_id,
+            connections,
+            property_count,
+            properties: r.data()[r.position()..].to_vec(),
+        });
+    }
+    Ok(ScriptLayer { objects })
+}
+
+impl ScriptObject {
+    const SOUND: u8 = 0x09;
+    const DOCK: u8 = 0x0b;
+    const CAMERA: u8 = 0x0c;
+    const CAMERA_WAYPOINT: u8 = 0x0d;
+    const WORLD_TRANSPORTER: u8 = 0x62;
+
+    /// The name of the object's type, if it's one placed in the world.
+    pub fn type_name(&self) -> Option<&'static str> {
+        self.placed_type().map(|&(_, name, _)| name)
+    }
+
+    /// The object's name and where it sits, or `None` for types without a position.
+    pub fn placement(&self) -> Result<Option<Placement>> {
+        let Some(&(_, _, layout)) = self.placed_type() else {
+            return Ok(None);
+        };
+        let mut r = SliceReader::new(&self.properties);
+        let name = r.read_ascii_c_string()?;
+        match layout {
+            Layout::ActivePositionVolume => {
+                let _active = r.read_u8()?;
+            }
+            Layout::FlavorPositionRotationScale => {
+                let _flavor = r.read_u32()?;
+            }
+            _ => {}
+        }
+        let mut read_vector = || -> Result<[f32; 3]> {
+            let mut v = [0.0; 3];
+            for x in &mut v {
+                *x = f32::from_bits(r.read_u32()?);
+            }
+            Ok(v)
+        };
+        let position = read_vector()?;
+        let mut placement = Placement {
+            name,
+            position,
+            rotation: [0.0; 3],
+            scale: [1.0; 3],
+            volume: None,
+        };
+        match layout {
+            Layout::Position => {}
+            Layout::PositionRotation => placement.rotation = read_vector()?,
+            Layout::PositionRotationScale | Layout::FlavorPositionRotationScale => {
+                placement.rotation = read_vector()?;
+                placement.scale = read_vector()?;
+            }
+            Layout::PositionVolume | Layout::ActivePositionVolume => {
+                placement.volume = Some(read_vector()?)
+            }
+        }
+        Ok(Some(placement))
+    }
+
+    /// Where the object leads, for docks and world transporters.
+    pub fn link(&self) -> Result<Option<Link>> {
+        let mut r = SliceReader::new(&self.properties);
+        match self.object_type {
+            Self::DOCK => {
+                let _name = r.read_ascii_c_string()?;
+                let _active = r.read_u8()?;
+                let _position_and_volume = r.take(24)?;
+                Ok(Some(Link::Dock {
+                    dock_index: r.read_u32()?,
+                }))
+            }
+            Self::WORLD_TRANSPORTER => {
+                let _name = r.read_ascii_c_string()?;
+                let _active = r.read_u8()?;
+                Ok(Some(Link::World {
+                    mlvl_id: r.read_u32()?,
+                    mrea_id: r.read_u32()?,
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The vertical field of view in degrees of cameras and camera waypoints.
+    pub fn field_of_view(&self) -> Result<Option<f32>> {
+        let skipped = match self.object_type {
+            // A shot duration and seven flags come between whether the camera starts active and
+            // its field of view.
+            Self::CAMERA => 11,
+            Self::CAMERA_WAYPOINT => 0,
+            _ => return Ok(None),
+        };
+        let mut r = SliceReader::new(&self.properties);
+        let _name = r.read_ascii_c_string()?;
+        let _position_rotation_and_active = r.take(25)?;
+        let _ = r.take(skipped)?;
+        Ok(Some(f32::from_bits(r.read_u32()?)))
+    }
+
+    /// What the object plays, for sound objects.
+    pub fn sound(&self) -> Result<Option<Sound>> {
+        if self.object_type != Self::SOUND {
+            return Ok(None);
+        }
+        let mut r = SliceReader::new(&self.properties);
+        let _name = r.read_ascii_c_string()?;
+        let _position_and_rotation = r.take(24)?;
+        let sound_id = r.read_u32()?;
+        let active = r.read_u8()? != 0;
+        let max_distance = f32::from_bits(r.read_u32()?);
+        let _distance_compression = r.read_u32()?;
+        let start_delay = f32::from_bits(r.read_u32()?);
+        let _min_volume = r.read_u32()?;
+        let volume = r.read_u32()?;
+        let _priority = r.read_u32()?;
+        let _pan = r.read_u32()?;
+        Ok(Some(Sound {
+            sound_id,
+            active,
+            max_distance,
+            start_delay,
+            volume,
+            looped: r.read_u8()? != 0,
+            non_emitter: r.read_u8()? != 0,
+            auto_start: r.read_u8()? != 0,
+        }))
+    }
+
+    fn placed_type(&self) -> Option<&'static (u8, &'static str, Layout)> {
+        PLACED_TYPES
+            .iter()
+            .find(|(object_type, _, _)| *object_type == self.object_type)
+    }
+}
@@ -0,0 +1,318 @@
+//! Quadric error decimation, for exporting meshes at preview quality.
+//!
+//! Surfaces are welded by position and simplified with half-edge collapses, which move one vertex
+//! onto a neighbor chosen to minimize the summed squared distance to the planes of the triangles
+//! around both (Garland and Heckbert's quadric error metric). Collapsing onto an existing vertex
+//! rather than an optimal new position means corners keep their own normals, texture coordinates,
+//! and skin weights, so nothing has to be interpolated.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+
+use nalgebra::{Matrix4, Vector3, Vector4};
+
+use crate::mesh::CanonicalMeshSurface;
+
+/// How much more an edge on the boundary of a surface costs to move than an interior edge, to
+/// keep holes and outlines in place.
+const BOUNDARY_WEIGHT: f32 = 1000.0;
+
+/// Reduces a surface to about `ratio` of its triangles. Surfaces too small to reduce, or whose
+/// remaining collapses would fold triangles over, keep more.
+pub fn simplify_surface(surface: &mut CanonicalMeshSurface, ratio: f32) {
+    let triangle_count = surface.positions.len() / 3;
+    let target = (triangle_count as f32 * ratio).ceil() as usize;
+    if target >= triangle_count {
+        return;
+    }
+    let mut mesh = WeldedMesh::new(&surface.positions);
+    mesh.decimate(target);
+    mesh.write_back(surface);
+}
+
+struct WeldedMesh {
+    positions: Vec<Vector3<f32>>,
+    /// Each triangle's vertices, and for each corner, the index of the original corner whose
+    /// attributes it keeps.
+    triangles: Vec<Triangle>,
+    /// The triangles around each vertex, including removed ones until they're skipped.
+    vertex_triangles: Vec<Vec<usize>>,
+    quadrics: Vec<Matrix4<f32>>,
+    /// Bumped when a vertex moves or is removed, invalidating queued collapses that involve it.
+    versions: Vec<u32>,
+    removed: Vec<bool>,
+    live_triangle_count: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Triangle {
+    vertices: [usize; 3],
+    corners: [usize; 3],
+    removed: bool,
+}
+
+/// A queued collapse of `from` onto `to`, valid while both vertices' versions match.
+struct Collapse {
+    cost: f32,
+    from: usize,
+    to: usize,
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost
+            .total_cmp(&other.cost)
+            .then_with(|| (self.from, self.to).cmp(&(other.from, other.to)))
+    }
+}
+
+impl WeldedMesh {
+    fn new(corner_positions: &[[f32; 3]]) -> Self {
+        let mut positions = Vec::new();
+        let mut vertex_by_position = HashMap::new();
+        let mut triangles = Vec::new();
+        for (triangle_index, corners) in corner_positions.chunks_exact(3).enumerate() {
+            let vertices = [0, 1, 2].map(|i| {
+                *vertex_by_position
+                    .entry(corners[i].map(f32::to_bits))
+                    .or_insert_with(|| {
+                        positions.push(Vector3::from(corners[i]));
+                        positions.len() - 1
+                    })
+            });
+            triangles.push(Triangle {
+                vertices,
+                corners: [0, 1, 2].map(|i| 3 * triangle_index + i),
+                // Triangles that weld to a line or point contribute nothing.
+                removed: vertices[0] == vertices[1]
+                    || vertices[1] == vertices[2]
+                    || vertices[2] == vertices[0],
+            });
+        }
+
+        let mut vertex_triangles = vec![Vec::new(); positions.len()];
+        for (index, triangle) in triangles.iter().enumerate() {
+            if !triangle.removed {
+                for vertex in triangle.vertices {
+                    vertex_triangles[vertex].push(index);
+                }
+            }
+        }
+        let live_triangle_count = triangles.iter().filter(|t| !t.removed).count();
+        let vertex_count = positions.len();
+        let mut mesh = Self {
+            positions,
+            triangles,
+            vertex_triangles,
+            quadrics: vec![Matrix4::zeros(); vertex_count],
+            versions: vec![0; vertex_count],
+            removed: vec![false; vertex_count],
+            live_triangle_count,
+        };
+        mesh.init_quadrics();
+        mesh
+    }
+
+    fn init_quadrics(&mut self) {
+        let mut edge_counts: HashMap<(usize, usize), u32> = HashMap::new();
+        for triangle in self.triangles.iter().filter(|t| !t.removed) {
+            let [a, b, c] = triangle.vertices.map(|v| self.positions[v]);
+            let cross = (b - a).cross(&(c - a));
+            let area = cross.norm() / 2.0;
+            let Some(normal) = cross.try_normalize(f32::EPSILON) else {
+                continue;
+            };
+            let quadric = plane_quadric(normal, a) * area;
+            for vertex in triangle.vertices {
+                self.quadrics[vertex] += quadric;
+            }
+            for i in 0..3 {
+                let (u, v) = (triangle.vertices[i], triangle.vertices[(i + 1) % 3]);
+                *edge_counts.entry((u.min(v), u.max(v))).or_default() += 1;
+            }
+        }
+
+        // Edges with only one triangle are boundaries. Constrain their vertices to a plane through
+        // the edge, perpendicular to the triangle.
+        for triangle in self.triangles.iter().filter(|t| !t.removed) {
+            let [a, b, c] = triangle.vertices.map(|v| self.positions[v]);
+            let Some(normal) = (b - a).cross(&(c - a)).try_normalize(f32::EPSILON) else {
+                continue;
+            };
+            for i in 0..3 {
+                let (u, v) = (triangle.vertices[i], triangle.vertices[(i + 1) % 3]);
+                if edge_counts[&(u.min(v), u.max(v))] != 1 {
+                    continue;
+                }
+                let edge = self.positions[v] - self.positions[u];
+                let Some(edge_normal) = edge.cross(&normal).try_normalize(f32::EPSILON) else {
+                    continue;
+                };
+                let quadric = plane_quadric(edge_normal, self.positions[u])
+                    * (BOUNDARY_WEIGHT * edge.norm_squared());
+                self.quadrics[u] += quadric;
+                self.quadrics[v] += quadric;
+            }
+        }
+    }
+
+    fn decimate(&mut self, target_triangle_count: usize) {
+        let mut queue = BinaryHeap::new();
+        for vertex in 0..self.positions.len() {
+            self.queue_collapses(vertex, &mut queue);
+        }
+        while self.live_triangle_count > target_triangle_count {
+            let Some(Reverse(collapse)) = queue.pop() else {
+                break;
+            };
+            if self.removed[collapse.from]
+                || self.removed[collapse.to]
+                || (self.versions[collapse.from], self.versions[collapse.to]) != collapse.versions
+            {
+                continue;
+            }
+            if self.flips_triangles(collapse.from, collapse.to) {
+                continue;
+            }
+            self.collapse(collapse.from, collapse.to);
+            self.queue_collapses(collapse.to, &mut queue);
+            for neighbor in self.neighbors(collapse.to) {
+                self.queue_collapses(neighbor, &mut queue);
+            }
+        }
+    }
+
+    /// Queues collapsing `vertex` onto each of its neighbors.
+    fn queue_collapses(&self, vertex: usize, queue: &mut BinaryHeap<Reverse<Collapse>>) {
+        for to in self.neighbors(vertex) {
+            let quadric = self.quadrics[vertex] + self.quadrics[to];
+            let p = self.positions[to];
+            let p = Vector4::new(p.x, p.y, p.z, 1.0);
+            queue.push(Reverse(Collapse {
+                cost: (p.transpose() * quadric * p)[0].max(0.0),
+                from: vertex,
+                to,
+                versions: (self.versions[vertex], self.versions[to]),
+            }));
+        }
+    }
+
+    fn live_triangles(&self, vertex: usize) -> impl Iterator<Item = &Triangle> {
+        self.vertex_triangles[vertex]
+            .iter()
+            .map(|&t| &self.triangles[t])
+            .filter(|t| !t.removed)
+    }
+
+    fn neighbors(&self, vertex: usize) -> Vec<usize> {
+        let mut neighbors: Vec<usize> = self
+            .live_triangles(vertex)
+            .flat_map(|t| t.vertices)
+            .filter(|&v| v != vertex)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        neighbors
+    }
+
+    /// Whether moving `from` onto `to` would turn any surviving triangle around `from` over.
+    fn flips_triangles(&self, from: usize, to: usize) -> bool {
+        self.live_triangles(from)
+            .filter(|t| !t.vertices.contains(&to))
+            .any(|t| {
+                let [a, b, c] = t.vertices.map(|v| self.positions[v]);
+                let before = (b - a).cross(&(c - a));
+                let [a, b, c] = t
+                    .vertices
+                    .map(|v| self.positions[if v == from { to } else { v }]);
+                let after = (b - a).cross(&(c - a));
+                before.dot(&after) <= 0.0
+            })
+    }
+
+    fn collapse(&mut self, from: usize, to: usize) {
+        for t in std::mem::take(&mut self.vertex_triangles[from]) {
+            let triangle = &mut self.triangles[t];
+            if triangle.removed {
+                continue;
+            }
+            if triangle.vertices.contains(&to) {
+                triangle.removed = true;
+                self.live_triangle_count -= 1;
+            } else {
+                for vertex in &mut triangle.vertices {
+                    if *vertex == from {
+                        *vertex = to;
+                    }
+                }
+                self.vertex_triangles[to].push(t);
+            }
+        }
+        let quadric = self.quadrics[from];
+        self.quadrics[to] += quadric;
+        self.removed[from] = true;
+        self.versions[from] += 1;
+        self.versions[to] += 1;
+    }
+
+    fn write_back(&self, surface: &mut CanonicalMeshSurface) {
+        let source = surface.clone();
+        surface.positions.clear();
+        surface.normals.clear();
+        for texcoords in &mut surface.texcoords {
+            texcoords.clear();
+        }
+        for colors in &mut surface.colors {
+            colors.clear();
+        }
+        surface.bone_ids.clear();
+        surface.weights.clear();
+        for triangle in self.triangles.iter().filter(|t| !t.removed) {
+            for (vertex, corner) in triangle.vertices.into_iter().zip(triangle.corners) {
+                surface.positions.push(self.positions[vertex].into());
+                if let Some(&normal) = source.normals.get(corner) {
+                    surface.normals.push(normal);
+                }
+                for (texcoords, source_texcoords) in
+                    surface.texcoords.iter_mut().zip(&source.texcoords)
+                {
+                    if let Some(&texcoord) = source_texcoords.get(corner) {
+                        texcoords.push(texcoord);
+                    }
+                }
+                for (colors, source_colors) in surface.colors.iter_mut().zip(&source.colors) {
+                    if let Some(&color) = source_colors.get(corner) {
+                        colors.push(color);
+                    }
+                }
+                if let Some(&bone_id) = source.bone_ids.get(corner) {
+                    surface.bone_ids.push(bone_id);
+                }
+                if let Some(&weight) = source.weights.get(corner) {
+                    surface.weights.push(weight);
+                }
+            }
+        }
+    }
+}
+
+/// The quadric measuring squared distance to the plane through `point` with unit `normal`.
+fn plane_quadric(normal: Vector3<f32>, point: Vector3<f32>) -> Matrix4<f32> {
+    let plane = Vector4::new(normal.x, normal.y, normal.z, -normal.dot(&point));
+    plane * plane.transpose()
+}
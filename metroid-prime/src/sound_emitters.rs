@@ -0,0 +1,164 @@
+//! Per-area reports of the sounds script objects play, like ambient hums and machinery, with where
+//! each plays from and the sound effect it plays, for restoring or remastering a room's audio.
+
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::audio::Atbl;
+use crate::cache::ResourceCache;
+use crate::mlvl::Mlvl;
+use crate::mrea::MreaSections;
+use crate::names::AreaNames;
+use crate::pak::{Pak, PakFiles};
+use crate::parse;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AreaSounds {
+    pub pak: String,
+    pub mrea_id: String,
+    pub world: Option<String>,
+    pub area: Option<String>,
+    pub emitters: Vec<SoundEmitter>,
+}
+
+/// One sound object.
+#[derive(Clone, Debug, Serialize)]
+pub struct SoundEmitter {
+    pub name: String,
+    pub instance_id: String,
+    pub layer: usize,
+    pub position: [f32; 3],
+    pub sound_id: u32,
+    /// The sound effect the ATBL translates the sound ID to, if there is one.
+    pub sfx_id: Option<u16>,
+    pub active: bool,
+    pub auto_start: bool,
+    pub looped: bool,
+    /// Whether the sound plays without a position, as loud anywhere in the area.
+    pub non_emitter: bool,
+    pub max_distance: f32,
+    pub start_delay: f32,
+    pub volume: u32,
+}
+
+impl AreaSounds {
+    /// Lists one area's sound objects. Objects that don't parse are left out with a warning.
+    pub fn new(
+        pak_path: &str,
+        pak: &Pak,
+        mrea_id: u32,
+        names: &AreaNames,
+        atbl: Option<&Atbl>,
+    ) -> Result<Self> {
+        let data = pak
+            .data_with_fourcc(mrea_id, "MREA")?
+            .ok_or_else(|| anyhow!("MREA 0x{mrea_id:08x} not found"))?;
+        let sections = MreaSections::new(&data)?;
+
+        let mut emitters = Vec::new();
+        for (layer, script_layer) in sections.script_layers()?.into_iter().enumerate() {
+            for object in &script_layer.objects {
+                let (sound, placement) = match object
+                    .sound()
+                    .and_then(|sound| Ok(sound.zip(object.placement()?)))
+                {
+                    Ok(Some(parsed)) => parsed,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        parse::warn(format!(
+                            "Sound 0x{:08x} in area 0x{mrea_id:08x} doesn't parse: {e}",
+                            object.instance_id
+                        ));
+                        continue;
+                    }
+                };
+                emitters.push(SoundEmitter {
+                    name: placement.name,
+                    instance_id: format!("0x{:08x}", object.instance_id),
+                    layer,
+                    position: placement.position,
+                    sound_id: sound.sound_id,
+                    sfx_id: atbl.and_then(|atbl| atbl.translate(sound.sound_id)),
+                    active: sound.active,
+                    auto_start: sound.auto_start,
+                    looped: sound.looped,
+                    non_emitter: sound.non_emitter,
+                    max_distance: sound.max_distance,
+                    start_delay: sound.start_delay,
+                    volume: sound.volume,
+                });
+            }
+        }
+
+        let name = names.get(mrea_id);
+        Ok(Self {
+            pak: pak_path.to_string(),
+            mrea_id: format!("0x{mrea_id:08x}"),
+            world: name.map(|name| name.world.clone()),
+            area: name.map(|name| name.area.clone()),
+            emitters,
+        })
+    }
+}
+
+/// Lists the sound objects of every area of every world, in world and area order. Areas that fail
+/// to parse are reported and left out. Without an ATBL in any pak, sound IDs aren't translated.
+pub fn build(paks: &PakFiles, cache: Option<&ResourceCache>) -> Result<Vec<AreaSounds>> {
+    let names = AreaNames::load(paks, cache)?;
+    let atbl = find_atbl(paks, cache)?;
+    if atbl.is_none() {
+        println!("No ATBL found, so sound IDs aren't translated to sound effects");
+    }
+    let mut sounds = Vec::new();
+    for file in paks.iter() {
+        let file = file?;
+        let pak_path = file.path().to_string();
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        for entry in pak.iter_resources() {
+            if entry.fourcc() != "MLVL" {
+                continue;
+            }
+            let mlvl: Mlvl = match entry
+                .data()
+                .and_then(|data| parse::read_resource("MLVL", &data))
+            {
+                Ok(mlvl) => mlvl,
+                Err(e) => {
+                    println!("Error in {pak_path} MLVL 0x{:08x}: {e}", entry.file_id());
+                    continue;
+                }
+            };
+            for area in &mlvl.areas {
+                match AreaSounds::new(&pak_path, &pak, area.mrea_id, &names, atbl.as_ref()) {
+                    Ok(area_sounds) => sounds.push(area_sounds),
+                    Err(e) => println!(
+                        "Error in {pak_path} MREA {}: {e}",
+                        names.describe(area.mrea_id),
+                    ),
+                }
+            }
+        }
+    }
+    Ok(sounds)
+}
+
+/// The first ATBL in any pak. The game has just one.
+fn find_atbl(paks: &PakFiles, cache: Option<&ResourceCache>) -> Result<Option<Atbl>> {
+    for file in paks.iter() {
+        let file = file?;
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        if let Some(entry) = pak.iter_resources().find(|entry| entry.fourcc() == "ATBL") {
+            return Ok(Some(parse::read_resource("ATBL", &entry.data()?)?));
+        }
+    }
+    Ok(None)
+}
+
+pub fn write<W: Write>(sounds: &[AreaSounds], mut w: W) -> Result<()> {
+    serde_json::to_writer_pretty(&mut w, sounds)?;
+    writeln!(w)?;
+    w.flush()?;
+    Ok(())
+}
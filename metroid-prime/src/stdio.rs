@@ -0,0 +1,32 @@
+//! `-` as a file name, meaning standard input or output, so single-resource commands compose with
+//! shell pipelines.
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+/// The file name that stands for standard input or output.
+pub const STDIO: &str = "-";
+
+/// Reads a whole file, or standard input for `-`.
+pub fn read_input(path: &str) -> Result<Vec<u8>> {
+    if path == STDIO {
+        let mut data = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut data)?;
+        Ok(data)
+    } else {
+        std::fs::read(path).map_err(|e| anyhow!("Couldn't read {path}: {e}"))
+    }
+}
+
+/// Creates a file for writing, or writes to standard output for `-`. Commands writing to standard
+/// output must keep their own messages off it.
+pub fn create_output(path: &str) -> Result<Box<dyn Write>> {
+    Ok(if path == STDIO {
+        Box::new(std::io::stdout().lock())
+    } else {
+        Box::new(BufWriter::new(File::create(Path::new(path))?))
+    })
+}
@@ -0,0 +1,166 @@
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, bail, Result};
+use byteorder::{BigEndian, WriteBytesExt};
+use gamecube::bytes::{ReadFixedCapacityAsciiCStringExt, ReadFrom, WriteTo};
+use gamecube::{ReadBytesExt, SliceReader};
+use serde::Serialize;
+
+/// A STRG resource: a table of UTF-16 strings, with one translation per language.
+#[derive(Clone, Debug, Serialize)]
+pub struct Strg {
+    pub languages: Vec<Language>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Language {
+    /// The language's fourcc. Example: ENGL
+    pub id: String,
+    pub strings: Vec<String>,
+}
+
+impl Strg {
+    const MAGIC: u32 = 0x87654321;
+    const VERSION: u32 = 0;
+
+    pub fn language(&self, id: &str) -> Option<&Language> {
+        self.languages.iter().find(|language| language.id == id)
+    }
+
+    /// Every string containing `pattern`, ignoring case, as its language, index, and text.
+    pub fn search<'a>(
+        &'a self,
+        pattern: &'a str,
+    ) -> impl Iterator<Item = (&'a str, usize, &'a str)> + 'a {
+        let pattern = pattern.to_lowercase();
+        self.languages.iter().flat_map(move |language| {
+            let pattern = pattern.clone();
+            language
+                .strings
+                .iter()
+                .enumerate()
+                .filter(move |(_, s)| s.to_lowercase().contains(&pattern))
+                .map(|(index, s)| (language.id.as_str(), index, s.as_str()))
+        })
+    }
+
+    pub fn language_mut(&mut self, id: &str) -> Option<&mut Language> {
+        self.languages.iter_mut().find(|language| language.id == id)
+    }
+}
+
+impl ReadFrom for Strg {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let mut data = Vec::new();
+        r.read_to_end(&mut data)?;
+        let mut r = SliceReader::new(&data);
+
+        let magic = r.read_u32()?;
+        if magic != Self::MAGIC {
+            bail!("unexpected STRG magic: 0x{magic:08x}");
+        }
+        let version = r.read_u32()?;
+        if version != Self::VERSION {
+            bail!("unexpected STRG version: {version}");
+        }
+        let language_count = r.read_u32()?;
+        let string_count = r.read_u32()?;
+        let mut language_table = Vec::new();
+        for _ in 0..language_count {
+            let id = r.read_fixed_capacity_ascii_c_string(4)?;
+            let offset = r.read_u32()? as usize;
+            language_table.push((id, offset));
+        }
+
+        // Language offsets count from the end of the language table, and string offsets from the
+        // end of each language's size field.
+        let tables_start = r.position();
+        let mut languages = Vec::new();
+        for (id, offset) in language_table {
+            r.seek_to(tables_start + offset)?;
+            let _size = r.read_u32()?;
+            let strings_start = r.position();
+            let mut string_offsets = Vec::new();
+            for _ in 0..string_count {
+                string_offsets.push(r.read_u32()? as usize);
+            }
+            let strings = string_offsets
+                .into_iter()
+                .map(|offset| {
+                    r.seek_to(strings_start + offset)?;
+                    read_utf16_c_string(&mut r)
+                })
+                .collect::<Result<_>>()?;
+            languages.push(Language { id, strings });
+        }
+        Ok(Self { languages })
+    }
+}
+
+impl WriteTo for Strg {
+    fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        let string_count = self.languages.first().map_or(0, |l| l.strings.len());
+        if self
+            .languages
+            .iter()
+            .any(|l| l.strings.len() != string_count)
+        {
+            bail!("Every STRG language must have the same number of strings");
+        }
+
+        // Each language's block is its string offset table followed by the strings.
+        let blocks = self
+            .languages
+            .iter()
+            .map(|language| {
+                let mut offsets = Vec::new();
+                let mut strings = Vec::new();
+                for s in &language.strings {
+                    offsets.push((4 * string_count + strings.len()) as u32);
+                    for unit in s.encode_utf16().chain([0]) {
+                        strings.write_u16::<BigEndian>(unit)?;
+                    }
+                }
+                let mut block = Vec::new();
+                for offset in offsets {
+                    block.write_u32::<BigEndian>(offset)?;
+                }
+                block.extend_from_slice(&strings);
+                Ok(block)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        w.write_u32::<BigEndian>(Self::MAGIC)?;
+        w.write_u32::<BigEndian>(Self::VERSION)?;
+        w.write_u32::<BigEndian>(self.languages.len() as u32)?;
+        w.write_u32::<BigEndian>(string_count as u32)?;
+        let mut offset = 0;
+        for (language, block) in self.languages.iter().zip(&blocks) {
+            if language.id.len() != 4 || !language.id.is_ascii() {
+                bail!(
+                    "STRG language ID must be four ASCII characters: {:?}",
+                    language.id
+                );
+            }
+            w.write_all(language.id.as_bytes())?;
+            w.write_u32::<BigEndian>(offset)?;
+            offset += 4 + block.len() as u32;
+        }
+        for block in &blocks {
+            w.write_u32::<BigEndian>(block.len() as u32)?;
+            w.write_all(block)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_utf16_c_string(r: &mut SliceReader) -> Result<String> {
+    let mut units = Vec::new();
+    loop {
+        match r.read_u16()? {
+            0 => break,
+            unit => units.push(unit),
+        }
+    }
+    String::from_utf16(&units).map_err(|e| anyhow!("Invalid UTF-16 string: {e}"))
+}
@@ -0,0 +1,308 @@
+//! A disc image built entirely from made-up data: a header that passes the disc check, a file
+//! table, and one pak holding a textured triangle. Integration tests and fuzz corpora can use it
+//! where distributing game data isn't an option.
+//!
+//! The writers here are deliberately independent of the parsers, so a mistake in one doesn't
+//! cancel out in the other.
+
+use std::io::Write;
+
+use anyhow::Result;
+use byteorder::{BigEndian, WriteBytesExt};
+
+use crate::pak;
+
+/// Disc path of the pak holding the synthetic resources.
+pub const PAK_PATH: &str = "Synthetic.pak";
+/// Name table entry of the triangle model.
+pub const CMDL_NAME: &str = "CMDL_SyntheticTriangle";
+pub const CMDL_ID: u32 = 0x5e000001;
+/// Name table entry of the model's texture.
+pub const TXTR_NAME: &str = "TXTR_SyntheticChecker";
+pub const TXTR_ID: u32 = 0x5e000002;
+
+/// The vertex attribute flags of the triangle's material: 16-bit indices for the position,
+/// normal, and first texture coordinate.
+const VERTEX_ATTR_FLAGS: u32 = 0x30f;
+const POSITIONS: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]];
+const TEXCOORDS: [[f32; 2]; 3] = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+
+/// Builds the disc image. It's only as long as its contents; a file standing in for a real image
+/// must be extended to [`gamecube::disc::SIZE`].
+pub fn disc() -> Result<Vec<u8>> {
+    const FILE_TABLE_OFFSET: usize = 0x440;
+    const FILE_ALIGNMENT: usize = 0x8000;
+
+    let pak = pak()?;
+    let mut string_table = vec![0];
+    let name_offset = string_table.len() as u32;
+    string_table.extend_from_slice(PAK_PATH.as_bytes());
+    string_table.push(0);
+
+    // The root directory, then the one file.
+    let file_table_len = 2 * 12 + string_table.len();
+    let pak_offset = (FILE_TABLE_OFFSET + file_table_len).next_multiple_of(FILE_ALIGNMENT);
+    let mut file_table = Vec::new();
+    file_table.write_u32::<BigEndian>(0x0100_0000)?;
+    file_table.write_u32::<BigEndian>(0)?;
+    file_table.write_u32::<BigEndian>(2)?;
+    file_table.write_u32::<BigEndian>(name_offset)?;
+    file_table.write_u32::<BigEndian>(pak_offset as u32)?;
+    file_table.write_u32::<BigEndian>(pak.len() as u32)?;
+    file_table.extend_from_slice(&string_table);
+
+    let mut image = vec![0; pak_offset + pak.len()];
+    image[0..4].copy_from_slice(b"GM8E");
+    image[4..6].copy_from_slice(b"01");
+    let game_name = b"Synthetic Test Disc";
+    image[0x20..0x20 + game_name.len()].copy_from_slice(game_name);
+    // The main executable is left empty.
+    (&mut image[0x424..]).write_u32::<BigEndian>(FILE_TABLE_OFFSET as u32)?;
+    (&mut image[0x428..]).write_u32::<BigEndian>(file_table.len() as u32)?;
+    image[FILE_TABLE_OFFSET..FILE_TABLE_OFFSET + file_table.len()].copy_from_slice(&file_table);
+    image[pak_offset..].copy_from_slice(&pak);
+    Ok(image)
+}
+
+/// Builds the pak, with the model stored as is and the texture compressed, so both ways of
+/// loading a resource are covered.
+pub fn pak() -> Result<Vec<u8>> {
+    const ALIGNMENT: usize = 32;
+
+    let resources = [
+        ("CMDL", CMDL_ID, CMDL_NAME, 0, cmdl()?),
+        ("TXTR", TXTR_ID, TXTR_NAME, 1, pak::compress(&txtr()?)?),
+    ];
+
+    let mut header = Vec::new();
+    header.write_u32::<BigEndian>(0x00030005)?;
+    header.write_u32::<BigEndian>(0)?;
+    header.write_u32::<BigEndian>(resources.len() as u32)?;
+    for (fourcc, file_id, name, _, _) in &resources {
+        header.write_all(fourcc.as_bytes())?;
+        header.write_u32::<BigEndian>(*file_id)?;
+        header.write_u32::<BigEndian>(name.len() as u32)?;
+        header.write_all(name.as_bytes())?;
+    }
+    header.write_u32::<BigEndian>(resources.len() as u32)?;
+    let mut offset = (header.len() + 20 * resources.len()).next_multiple_of(ALIGNMENT);
+    let mut body = Vec::new();
+    for (fourcc, file_id, _, compression, data) in &resources {
+        let size = data.len().next_multiple_of(ALIGNMENT);
+        header.write_u32::<BigEndian>(*compression)?;
+        header.write_all(fourcc.as_bytes())?;
+        header.write_u32::<BigEndian>(*file_id)?;
+        header.write_u32::<BigEndian>(size as u32)?;
+        header.write_u32::<BigEndian>(offset as u32)?;
+        body.extend_from_slice(data);
+        body.resize(body.len().next_multiple_of(ALIGNMENT), 0);
+        offset += size;
+    }
+    header.resize(header.len().next_multiple_of(ALIGNMENT), 0);
+    header.extend_from_slice(&body);
+    Ok(header)
+}
+
+/// Builds a model of one textured triangle with a single unlit material.
+pub fn cmdl() -> Result<Vec<u8>> {
+    let mut positions = Vec::new();
+    for component in POSITIONS.as_flattened() {
+        positions.write_f32::<BigEndian>(*component)?;
+    }
+    let mut normals = Vec::new();
+    for component in [0.0, 1.0, 0.0] {
+        normals.write_f32::<BigEndian>(component)?;
+    }
+    let mut texcoords = Vec::new();
+    for component in TEXCOORDS.as_flattened() {
+        texcoords.write_f32::<BigEndian>(*component)?;
+    }
+    let sections = [
+        material_set()?,
+        positions,
+        normals,
+        Vec::new(),
+        texcoords,
+        1u32.to_be_bytes().to_vec(),
+        surface()?,
+    ];
+
+    let mut w = Vec::new();
+    w.write_u32::<BigEndian>(0xdeadbabe)?;
+    w.write_u32::<BigEndian>(2)?;
+    w.write_u32::<BigEndian>(0)?;
+    for component in [0.0, 0.0, 0.0, 1.0, 0.0, 1.0] {
+        w.write_f32::<BigEndian>(component)?;
+    }
+    w.write_u32::<BigEndian>(sections.len() as u32)?;
+    w.write_u32::<BigEndian>(1)?;
+    for section in &sections {
+        w.write_u32::<BigEndian>(section.len().next_multiple_of(32) as u32)?;
+    }
+    w.resize(w.len().next_multiple_of(32), 0);
+    for section in &sections {
+        w.extend_from_slice(section);
+        w.resize(w.len().next_multiple_of(32), 0);
+    }
+    Ok(w)
+}
+
+fn material_set() -> Result<Vec<u8>> {
+    let mut material = Vec::new();
+    material.write_u32::<BigEndian>(0)?;
+    material.write_u32::<BigEndian>(1)?;
+    material.write_u32::<BigEndian>(0)?;
+    material.write_u32::<BigEndian>(VERTEX_ATTR_FLAGS)?;
+    material.write_u32::<BigEndian>(0)?;
+    // Opaque blending: zero for the destination, one for the source.
+    material.write_u16::<BigEndian>(0)?;
+    material.write_u16::<BigEndian>(1)?;
+    // One color channel, unlit.
+    material.write_u32::<BigEndian>(1)?;
+    material.write_u32::<BigEndian>(0)?;
+    // One TEV stage passing the texture through: d = texture color and alpha, clamped, with no
+    // rasterized color.
+    material.write_u32::<BigEndian>(1)?;
+    material.write_u32::<BigEndian>(0xf | 0xf << 5 | 0xf << 10 | 0x8 << 15)?;
+    material.write_u32::<BigEndian>(0x7 | 0x7 << 5 | 0x7 << 10 | 0x4 << 15)?;
+    material.write_u32::<BigEndian>(0x100)?;
+    material.write_u32::<BigEndian>(0x100)?;
+    material.write_all(&[0, 0, 0, 0xff])?;
+    material.write_u16::<BigEndian>(0xffff)?;
+    material.write_all(&[0, 0])?;
+    // One texture coordinate generator, then an empty UV animation section.
+    material.write_u32::<BigEndian>(1)?;
+    material.write_u32::<BigEndian>(0)?;
+    material.write_u32::<BigEndian>(4)?;
+    material.write_u32::<BigEndian>(0)?;
+
+    let mut w = Vec::new();
+    w.write_u32::<BigEndian>(1)?;
+    w.write_u32::<BigEndian>(TXTR_ID)?;
+    w.write_u32::<BigEndian>(1)?;
+    w.write_u32::<BigEndian>(material.len() as u32)?;
+    w.extend_from_slice(&material);
+    Ok(w)
+}
+
+fn surface() -> Result<Vec<u8>> {
+    let display_list = display_list()?;
+    let mut w = Vec::new();
+    for component in [1.0 / 3.0, 0.0, 1.0 / 3.0] {
+        w.write_f32::<BigEndian>(component)?;
+    }
+    w.write_u32::<BigEndian>(0)?;
+    w.write_u16::<BigEndian>(0)?;
+    w.write_u16::<BigEndian>(display_list.len().next_multiple_of(32) as u16)?;
+    w.write_u32::<BigEndian>(0)?;
+    w.write_u32::<BigEndian>(0)?;
+    w.write_u32::<BigEndian>(0)?;
+    for component in [0.0, 1.0, 0.0] {
+        w.write_f32::<BigEndian>(component)?;
+    }
+    w.write_u16::<BigEndian>(0)?;
+    w.write_u16::<BigEndian>(0)?;
+    w.resize(w.len().next_multiple_of(32), 0);
+    w.extend_from_slice(&display_list);
+    Ok(w)
+}
+
+/// The triangle's display list: one triangle batch in vertex format 0, then the end marker.
+fn display_list() -> Result<Vec<u8>> {
+    let mut w = vec![0x90];
+    w.write_u16::<BigEndian>(3)?;
+    for index in 0..3 {
+        w.write_u16::<BigEndian>(index)?;
+        w.write_u16::<BigEndian>(0)?;
+        w.write_u16::<BigEndian>(index)?;
+    }
+    w.push(0);
+    Ok(w)
+}
+
+/// Builds a 4x4 RGB565 checkerboard, which is exactly one tile.
+pub fn txtr() -> Result<Vec<u8>> {
+    let mut w = Vec::new();
+    w.write_u32::<BigEndian>(0x7)?;
+    w.write_u16::<BigEndian>(4)?;
+    w.write_u16::<BigEndian>(4)?;
+    w.write_u32::<BigEndian>(1)?;
+    for y in 0..4 {
+        for x in 0..4 {
+            w.write_u16::<BigEndian>(if (x + y) % 2 == 0 { 0xffff } else { 0xf800 })?;
+        }
+    }
+    Ok(w)
+}
+
+/// A seed input for the `display_list` fuzz target: the vertex attribute flags, the split byte,
+/// one array serving every attribute, then the display list padded so the split lands exactly at
+/// the array's end.
+pub fn display_list_fuzz_input() -> Result<Vec<u8>> {
+    let mut array = Vec::new();
+    for (position, texcoord) in POSITIONS.iter().zip(TEXCOORDS) {
+        for component in position.iter().chain(&texcoord) {
+            array.write_f32::<BigEndian>(*component)?;
+        }
+    }
+    let mut input = VERTEX_ATTR_FLAGS.to_be_bytes().to_vec();
+    input.push(array.len() as u8);
+    input.extend_from_slice(&array);
+    input.extend_from_slice(&display_list()?);
+    input.resize(5 + 255, 0);
+    Ok(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use gamecube::Disc;
+
+    use super::*;
+    use crate::disc_resources::DiscResources;
+    use crate::pak::{Pak, PakFiles};
+    use crate::project::Export;
+    use crate::{run_export, verify_disc, MissingTexture, Recenter, SplitBy};
+
+    #[test]
+    fn disc_reads_back_and_exports() -> Result<()> {
+        let image = disc()?;
+        let disc = Disc::new(&image)?;
+        verify_disc(disc.header(), false)?;
+
+        let paks = PakFiles::new(Some(&disc), Vec::new());
+        let pak_file = paks.load(PAK_PATH)?;
+        let pak = Pak::new(pak_file.data())?;
+        assert_eq!(pak.file_id(CMDL_NAME)?, CMDL_ID);
+        assert_eq!(pak.file_id(TXTR_NAME)?, TXTR_ID);
+        let cmdl_data = pak.data_with_fourcc(CMDL_ID, "CMDL")?.unwrap();
+        assert!(cmdl_data.starts_with(&cmdl()?));
+        let txtr_data = pak.data_with_fourcc(TXTR_ID, "TXTR")?.unwrap();
+        assert!(txtr_data.starts_with(&txtr()?));
+
+        let out_dir = std::env::temp_dir().join(format!("synthetic-disc-{}", std::process::id()));
+        let pak_files = paks.load_all();
+        let resources = DiscResources::new(&pak_files, None);
+        let export = Export::Cmdl {
+            pak_path: PAK_PATH.to_string(),
+            name: CMDL_NAME.to_string(),
+            material_set_index: 0,
+            split_by: SplitBy::default(),
+            bake_materials: false,
+            approximate_reflections: false,
+            recenter: Recenter::default(),
+            simplify: None,
+            quantize: false,
+            no_vertex_colors: false,
+            file_name: "triangle".to_string(),
+            out_dir: out_dir.clone(),
+        };
+        let result = run_export(&paks, None, &resources, &export, MissingTexture::default());
+        let gltf = std::fs::read_to_string(out_dir.join("triangle.gltf"));
+        std::fs::remove_dir_all(&out_dir)?;
+        result?;
+        let gltf: serde_json::Value = serde_json::from_str(&gltf?)?;
+        assert_eq!(gltf["meshes"].as_array().map(Vec::len), Some(1));
+        assert_eq!(gltf["images"].as_array().map(Vec::len), Some(1));
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+//! Output path templates, which let users choose how batch extractions arrange their files.
+//!
+//! A template is a relative path with fields in braces, like `{pak}/{fourcc}/{name|id}.{ext}`.
+//! A field can list alternatives separated by `|`, and takes the first that has a value, since
+//! not every resource has a name.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+use crate::names::sanitize_file_name;
+
+#[derive(Clone, Debug)]
+pub struct OutputTemplate {
+    /// Everything before the final `.{ext}`, which every template ends with.
+    stem: Vec<Part>,
+}
+
+#[derive(Clone, Debug)]
+enum Part {
+    Literal(String),
+    Field(Vec<Field>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    Pak,
+    Fourcc,
+    Name,
+    Id,
+    Ext,
+}
+
+/// What a template's fields are filled in with for one resource.
+pub struct TemplateValues<'a> {
+    /// The pak's file name without its extension.
+    pub pak: &'a str,
+    pub fourcc: &'a str,
+    pub name: Option<&'a str>,
+    pub file_id: u32,
+    /// The extension of the resource's main file, without a dot.
+    pub ext: &'a str,
+}
+
+impl OutputTemplate {
+    /// Whether the template has a `{pak}` field. Without one, a resource shared by several paks
+    /// has one path, so it's written once.
+    pub fn uses_pak(&self) -> bool {
+        self.stem
+            .iter()
+            .any(|part| matches!(part, Part::Field(fields) if fields.contains(&Field::Pak)))
+    }
+
+    /// Parses a template for a clap argument.
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while !rest.is_empty() {
+            match rest.find(['{', '}']) {
+                Some(0) if rest.starts_with('{') => {
+                    let Some(end) = rest.find('}') else {
+                        bail!("unclosed {{ in output template {template:?}");
+                    };
+                    let fields = rest[1..end]
+                        .split('|')
+                        .map(|field| {
+                            Ok(match field {
+                                "pak" => Field::Pak,
+                                "fourcc" => Field::Fourcc,
+                                "name" => Field::Name,
+                                "id" => Field::Id,
+                                "ext" => Field::Ext,
+                                _ => bail!(
+                                    "unknown field {{{field}}} in output template; the fields are \
+                                     pak, fourcc, name, id, and ext"
+                                ),
+                            })
+                        })
+                        .collect::<Result<_>>()?;
+                    parts.push(Part::Field(fields));
+                    rest = &rest[end + 1..];
+                }
+                Some(0) => bail!("unmatched }} in output template {template:?}"),
+                Some(index) => {
+                    parts.push(Part::Literal(rest[..index].to_string()));
+                    rest = &rest[index..];
+                }
+                None => {
+                    parts.push(Part::Literal(rest.to_string()));
+                    rest = "";
+                }
+            }
+        }
+
+        // Resources write one or more files sharing a stem, each with its own extension.
+        let ends_with_ext = matches!(
+            parts.as_slice(),
+            [.., Part::Literal(dot), Part::Field(fields)]
+                if dot.ends_with('.') && fields == &[Field::Ext]
+        );
+        if !ends_with_ext {
+            bail!("output template {template:?} must end with .{{ext}}");
+        }
+        parts.pop();
+        if let Some(Part::Literal(dot)) = parts.last_mut() {
+            dot.pop();
+        }
+        let stem = Self { stem: parts };
+        if stem.render_stem(&TemplateValues::EXAMPLE).is_absolute()
+            || Path::new(template)
+                .components()
+                .any(|c| c.as_os_str() == "..")
+        {
+            bail!("output template {template:?} must stay inside the output directory");
+        }
+        Ok(stem)
+    }
+
+    /// The path, relative to the output directory, of a resource's files without their
+    /// extension. Field values are sanitized so they can't add directories.
+    pub fn render_stem(&self, values: &TemplateValues) -> PathBuf {
+        let mut path = String::new();
+        for part in &self.stem {
+            match part {
+                Part::Literal(literal) => path.push_str(literal),
+                Part::Field(fields) => {
+                    let value = fields
+                        .iter()
+                        .find_map(|&field| values.get(field))
+                        .unwrap_or_default();
+                    path.push_str(&sanitize_file_name(&value));
+                }
+            }
+        }
+        PathBuf::from(path)
+    }
+}
+
+impl TemplateValues<'_> {
+    const EXAMPLE: TemplateValues<'static> = TemplateValues {
+        pak: "Metroid1",
+        fourcc: "TXTR",
+        name: None,
+        file_id: 0,
+        ext: "png",
+    };
+
+    fn get(&self, field: Field) -> Option<String> {
+        match field {
+            Field::Pak => Some(self.pak.to_string()),
+            Field::Fourcc => Some(self.fourcc.to_string()),
+            Field::Name => self.name.map(str::to_string),
+            Field::Id => Some(format!("0x{:08x}", self.file_id)),
+            Field::Ext => Some(self.ext.to_string()),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! A software model of the GX texture environment (TEV), used to bake a material's texture stages
+//! into a single base color texture.
+
+use anyhow::Result;
+
+use crate::cmdl::Material;
+use crate::gx::{TevAlphaArg, TevBias, TevColorArg, TevCombiner, TevOp, TevRegister};
+use crate::txtr::DecodedTexture;
+
+/// Marks an unused texture or texture coordinate slot in a TEV stage.
+const NO_INPUT: u8 = 0xff;
+
+type Color = [f32; 4];
+
+/// Evaluates a material's TEV stages over its textures and returns the composited image, or
+/// `None` if the material can't be baked faithfully.
+///
+/// `textures` holds the decoded texture for each of the material's texture slots. Rasterized color
+/// is taken to be opaque white, so the result carries no scene lighting, and color registers start
+/// out black. Materials are only baked when every textured stage samples the same texture
+/// coordinates and no stage uses a comparison op.
+pub fn bake(material: &Material, textures: &[&DecodedTexture]) -> Result<Option<DecodedTexture>> {
+    let mut tex_coord = None;
+    for (stage, input) in material.tev_stages.iter().zip(&material.tev_texture_inputs) {
+        for combiner in [&stage.color_op, &stage.alpha_op] {
+            if !matches!(combiner.op, TevOp::Add | TevOp::Subtract) {
+                return Ok(None);
+            }
+        }
+        if input.texture_tev_input == NO_INPUT {
+            continue;
+        }
+        if textures.get(input.texture_tev_input as usize).is_none() {
+            return Ok(None);
+        }
+        match tex_coord {
+            None => tex_coord = Some(input.tex_coord_tev_input),
+            Some(x) if x == input.tex_coord_tev_input => (),
+            Some(_) => return Ok(None),
+        }
+    }
+    if tex_coord.is_none() {
+        return Ok(None);
+    }
+
+    let width = textures.iter().map(|t| t.width).max().unwrap_or(0);
+    let height = textures.iter().map(|t| t.height).max().unwrap_or(0);
+    let konsts: Vec<Color> = material.konsts.iter().copied().map(unpack_rgba).collect();
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let u = (x as f32 + 0.5) / width as f32;
+            let v = (y as f32 + 0.5) / height as f32;
+
+            let mut registers = [[0.0; 4]; 4];
+            for (stage, input) in material.tev_stages.iter().zip(&material.tev_texture_inputs) {
+                let texture = match input.texture_tev_input {
+                    NO_INPUT => [1.0; 4],
+                    slot => sample(textures[slot as usize], u, v),
+                };
+                let rasterized = [1.0; 4];
+                let konst_color = konst_color(stage.color_konst, &konsts);
+                let konst_alpha = konst_alpha(stage.alpha_konst, &konsts);
+
+                let color_args = stage
+                    .color_inputs
+                    .map(|arg| color_arg(arg, &registers, texture, rasterized, konst_color));
+                let alpha_args = stage
+                    .alpha_inputs
+                    .map(|arg| alpha_arg(arg, &registers, texture, rasterized, konst_alpha));
+
+                let mut color = [0.0; 3];
+                for (i, out) in color.iter_mut().enumerate() {
+                    *out = combine(&stage.color_op, color_args.map(|arg| arg[i]));
+                }
+                let alpha = combine(&stage.alpha_op, alpha_args);
+
+                let color_out = &mut registers[register_index(stage.color_op.output)];
+                color_out[..3].copy_from_slice(&color);
+                registers[register_index(stage.alpha_op.output)][3] = alpha;
+            }
+
+            let prev = registers[register_index(TevRegister::Prev)];
+            rgba.extend(prev.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+        }
+    }
+
+    Ok(Some(DecodedTexture {
+        width,
+        height,
+        rgba,
+    }))
+}
+
+fn combine(combiner: &TevCombiner, [a, b, c, d]: [f32; 4]) -> f32 {
+    let blended = (1.0 - c) * a + c * b;
+    let value = match combiner.op {
+        TevOp::Subtract => d - blended,
+        _ => d + blended,
+    };
+    let bias = match combiner.bias {
+        TevBias::Zero => 0.0,
+        TevBias::AddHalf => 0.5,
+        TevBias::SubtractHalf => -0.5,
+    };
+    let value = (value + bias) * combiner.scale.factor();
+    if combiner.clamp {
+        value.clamp(0.0, 1.0)
+    } else {
+        value
+    }
+}
+
+fn register_index(register: TevRegister) -> usize {
+    match register {
+        TevRegister::Prev => 0,
+        TevRegister::Register0 => 1,
+        TevRegister::Register1 => 2,
+        TevRegister::Register2 => 3,
+    }
+}
+
+fn color_arg(
+    arg: TevColorArg,
+    registers: &[Color; 4],
+    texture: Color,
+    rasterized: Color,
+    konst: Color,
+) -> Color {
+    let alpha = |c: Color| [c[3]; 4];
+    match arg {
+        TevColorArg::PrevColor => registers[0],
+        TevColorArg::PrevAlpha => alpha(registers[0]),
+        TevColorArg::Color0 => registers[1],
+        TevColorArg::Alpha0 => alpha(registers[1]),
+        TevColorArg::Color1 => registers[2],
+        TevColorArg::Alpha1 => alpha(registers[2]),
+        TevColorArg::Color2 => registers[3],
+        TevColorArg::Alpha2 => alpha(registers[3]),
+        TevColorArg::TextureColor => texture,
+        TevColorArg::TextureAlpha => alpha(texture),
+        TevColorArg::RasterizedColor => rasterized,
+        TevColorArg::RasterizedAlpha => alpha(rasterized),
+        TevColorArg::One => [1.0; 4],
+        TevColorArg::Half => [0.5; 4],
+        TevColorArg::Konst => konst,
+        TevColorArg::Zero => [0.0; 4],
+    }
+}
+
+fn alpha_arg(
+    arg: TevAlphaArg,
+    registers: &[Color; 4],
+    texture: Color,
+    rasterized: Color,
+    konst: f32,
+) -> f32 {
+    match arg {
+        TevAlphaArg::PrevAlpha => registers[0][3],
+        TevAlphaArg::Alpha0 => registers[1][3],
+        TevAlphaArg::Alpha1 => registers[2][3],
+        TevAlphaArg::Alpha2 => registers[3][3],
+        TevAlphaArg::TextureAlpha => texture[3],
+        TevAlphaArg::RasterizedAlpha => rasterized[3],
+        TevAlphaArg::Konst => konst,
+        TevAlphaArg::Zero => 0.0,
+    }
+}
+
+/// Resolves a `GXTevKColorSel`.
+fn konst_color(selection: u8, konsts: &[Color]) -> Color {
+    let konst = |index: usize| konsts.get(index).copied().unwrap_or([0.0; 4]);
+    match selection {
+        0x00..=0x07 => [fixed_konst(selection); 4],
+        0x0c..=0x0f => konst(selection as usize - 0x0c),
+        0x10..=0x1f => {
+            let component = (selection as usize - 0x10) / 4;
+            let value = konst((selection as usize - 0x10) % 4)[component];
+            [value; 4]
+        }
+        _ => [0.0; 4],
+    }
+}
+
+/// Resolves a `GXTevKAlphaSel`.
+fn konst_alpha(selection: u8, konsts: &[Color]) -> f32 {
+    match selection {
+        0x00..=0x07 => fixed_konst(selection),
+        0x10..=0x1f => {
+            let component = (selection as usize - 0x10) / 4;
+            konsts
+                .get((selection as usize - 0x10) % 4)
+                .map_or(0.0, |konst| konst[component])
+        }
+        _ => 0.0,
+    }
+}
+
+/// The fixed fractions `1, 7/8, 6/8, ..., 1/8` selectable as TEV constants.
+fn fixed_konst(selection: u8) -> f32 {
+    (8 - selection) as f32 / 8.0
+}
+
+fn unpack_rgba(packed: u32) -> Color {
+    packed.to_be_bytes().map(|c| c as f32 / 255.0)
+}
+
+/// Samples a texture with nearest filtering.
+fn sample(texture: &DecodedTexture, u: f32, v: f32) -> Color {
+    let x = ((u * texture.width as f32) as usize).min(texture.width - 1);
+    let y = ((v * texture.height as f32) as usize).min(texture.height - 1);
+    let offset = 4 * (texture.width * y + x);
+    let mut color = [0.0; 4];
+    for (c, &byte) in color.iter_mut().zip(&texture.rgba[offset..offset + 4]) {
+        *c = byte as f32 / 255.0;
+    }
+    color
+}
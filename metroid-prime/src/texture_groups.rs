@@ -0,0 +1,213 @@
+//! Grouping textures that look the same, so a texture pack can replace one representative per
+//! group instead of every copy. Textures are compared by a difference hash (dHash) of their
+//! brightness, which survives rescaling and format changes, plus their average color, which the
+//! hash can't see.
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::cache::ResourceCache;
+use crate::pak::{Pak, PakFiles};
+use crate::txtr::{self, DecodedTexture};
+
+/// How far apart two textures' average colors may be, per channel out of 255, for them to count
+/// as the same.
+const MAX_COLOR_DIFFERENCE: u8 = 16;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TextureGroup {
+    /// Whether every member decodes to exactly the same pixels.
+    pub exact: bool,
+    /// The file ID of the member with the most pixels, which is the best one to retexture.
+    pub representative: String,
+    pub members: Vec<TextureMember>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TextureMember {
+    pub file_id: String,
+    pub name: Option<String>,
+    /// Every pak holding the texture.
+    pub paks: Vec<String>,
+    pub width: usize,
+    pub height: usize,
+}
+
+struct Fingerprint {
+    pixels: [u8; 32],
+    dhash: u64,
+    average: [u8; 4],
+}
+
+impl Fingerprint {
+    fn new(texture: &DecodedTexture) -> Self {
+        let mut sums = [0u64; 4];
+        for pixel in texture.rgba.chunks_exact(4) {
+            for (sum, &channel) in sums.iter_mut().zip(pixel) {
+                *sum += channel as u64;
+            }
+        }
+        let pixel_count = (texture.width * texture.height).max(1) as u64;
+        Self {
+            pixels: Sha256::digest(&texture.rgba).into(),
+            dhash: dhash(texture),
+            average: sums.map(|sum| (sum / pixel_count) as u8),
+        }
+    }
+
+    fn is_similar(&self, other: &Self, max_distance: u32) -> bool {
+        (self.dhash ^ other.dhash).count_ones() <= max_distance
+            && self
+                .average
+                .iter()
+                .zip(other.average)
+                .all(|(&a, b)| a.abs_diff(b) <= MAX_COLOR_DIFFERENCE)
+    }
+}
+
+/// Shrinks the texture's brightness to 9x8 cells and sets one bit per horizontally adjacent pair
+/// where the left cell is brighter.
+fn dhash(texture: &DecodedTexture) -> u64 {
+    const WIDTH: usize = 9;
+    const HEIGHT: usize = 8;
+
+    let mut sums = [[0.0f32; WIDTH]; HEIGHT];
+    let mut counts = [[0u32; WIDTH]; HEIGHT];
+    for (index, pixel) in texture.rgba.chunks_exact(4).enumerate() {
+        let (x, y) = (index % texture.width, index / texture.width);
+        let (cell_x, cell_y) = (x * WIDTH / texture.width, y * HEIGHT / texture.height);
+        sums[cell_y][cell_x] +=
+            0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32;
+        counts[cell_y][cell_x] += 1;
+    }
+
+    // Textures narrower than the grid leave cells empty, which then copy their left neighbor.
+    let mut hash = 0;
+    for (row_sums, row_counts) in sums.iter().zip(&counts) {
+        let mut previous = None;
+        for (&sum, &count) in row_sums.iter().zip(row_counts) {
+            let value = match count {
+                0 => previous.unwrap_or(0.0),
+                _ => sum / count as f32,
+            };
+            if let Some(previous) = previous {
+                hash = (hash << 1) | (previous > value) as u64;
+            }
+            previous = Some(value);
+        }
+    }
+    hash
+}
+
+/// Groups every texture on the disc with any others it looks the same as. Textures are identified
+/// by file ID, so copies of one texture in several paks count once. Only groups of two or more are
+/// returned, largest first.
+pub fn build(
+    paks: &PakFiles,
+    cache: Option<&ResourceCache>,
+    max_distance: u32,
+) -> Result<Vec<TextureGroup>> {
+    let mut members: BTreeMap<u32, (TextureMember, Fingerprint)> = BTreeMap::new();
+    for file in paks.iter() {
+        let file = file?;
+        let pak_path = file.path().to_string();
+        let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+        for resource in pak.iter_resources().filter(|r| r.fourcc() == "TXTR") {
+            let file_id = resource.file_id();
+            let slot = match members.entry(file_id) {
+                Entry::Occupied(entry) => {
+                    entry.into_mut().0.paks.push(pak_path.clone());
+                    continue;
+                }
+                Entry::Vacant(slot) => slot,
+            };
+            let texture = match resource.data().and_then(|data| txtr::decode(&data)) {
+                Ok(texture) => texture,
+                Err(e) => {
+                    println!("Error in {pak_path} TXTR 0x{file_id:08x}: {e}");
+                    continue;
+                }
+            };
+            let name = pak
+                .iter_names()
+                .find(|e| e.file_id() == file_id)
+                .map(|e| e.name().to_string());
+            slot.insert((
+                TextureMember {
+                    file_id: format!("0x{file_id:08x}"),
+                    name,
+                    paks: vec![pak_path.clone()],
+                    width: texture.width,
+                    height: texture.height,
+                },
+                Fingerprint::new(&texture),
+            ));
+        }
+    }
+
+    // Join similar textures with a union-find over every pair.
+    let members: Vec<_> = members.into_values().collect();
+    let mut parents: Vec<usize> = (0..members.len()).collect();
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+    for i in 0..members.len() {
+        for j in i + 1..members.len() {
+            if members[i].1.is_similar(&members[j].1, max_distance) {
+                let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut grouped: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for index in 0..members.len() {
+        grouped
+            .entry(root(&mut parents, index))
+            .or_default()
+            .push(index);
+    }
+    let mut groups: Vec<TextureGroup> = grouped
+        .into_values()
+        .filter(|indices| indices.len() > 1)
+        .map(|indices| {
+            let fingerprint = &members[indices[0]].1;
+            let exact = indices
+                .iter()
+                .all(|&index| members[index].1.pixels == fingerprint.pixels);
+            let representative = indices
+                .iter()
+                .map(|&index| &members[index].0)
+                .max_by_key(|member| member.width * member.height)
+                .unwrap()
+                .file_id
+                .clone();
+            TextureGroup {
+                exact,
+                representative,
+                members: indices
+                    .into_iter()
+                    .map(|index| members[index].0.clone())
+                    .collect(),
+            }
+        })
+        .collect();
+    groups.sort_by_key(|group| std::cmp::Reverse(group.members.len()));
+    Ok(groups)
+}
+
+pub fn write<W: Write>(groups: &[TextureGroup], mut w: W) -> Result<()> {
+    serde_json::to_writer_pretty(&mut w, groups)?;
+    writeln!(w)?;
+    w.flush()?;
+    Ok(())
+}
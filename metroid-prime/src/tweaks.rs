@@ -0,0 +1,56 @@
+//! Tweaks: CTWK resources holding the game's tuning constants, one resource per subsystem. Only
+//! the fields this tool uses are read.
+
+use std::io::Read;
+
+use anyhow::Result;
+use gamecube::bytes::ReadFrom;
+use gamecube::ReadBytesExt;
+
+use crate::cache::ResourceCache;
+use crate::pak::{Pak, PakFiles};
+
+/// The start of the player gun tweaks, up to where the arm cannon sits in first person.
+#[derive(Clone, Copy, Debug)]
+pub struct PlayerGunTweaks {
+    /// The arm cannon's offset from the first-person camera, in game units.
+    pub gun_position: [f32; 3],
+}
+
+impl PlayerGunTweaks {
+    /// The name of the CTWK entry holding these tweaks.
+    pub const NAME: &'static str = "PlayerGun";
+
+    /// Finds the player gun tweaks in the first pak that has them.
+    pub fn find(paks: &PakFiles, cache: Option<&ResourceCache>) -> Result<Option<Self>> {
+        for file in paks.iter() {
+            let file = file?;
+            let pak = Pak::new(file.data())?.with_cache(cache.cloned());
+            let Some(entry) = pak
+                .iter_names()
+                .find(|entry| entry.fourcc() == "CTWK" && entry.name() == Self::NAME)
+            else {
+                continue;
+            };
+            if let Some(data) = pak.data(entry.file_id())? {
+                return Ok(Some(Self::read_from(&mut data.as_slice())?));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl ReadFrom for PlayerGunTweaks {
+    fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        // Aiming angles and spreads, aim speeds, bomb timing, gun transition times, the fixed
+        // vertical aim, and the gun extend distance.
+        for _ in 0..18 {
+            r.read_u32()?;
+        }
+        let mut gun_position = [0.0; 3];
+        for x in &mut gun_position {
+            *x = f32::from_bits(r.read_u32()?);
+        }
+        Ok(Self { gun_position })
+    }
+}
@@ -1,28 +1,322 @@
+use std::fs::File;
 use std::io::Write;
+use std::path::Path;
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+use clap::ValueEnum;
 use gamecube::ReadBytesExt;
-use png::{BitDepth, ColorType};
+use png::{BitDepth, ColorType, Compression};
 
-pub fn dump<W: Write>(mut data: &[u8], w: &mut W) -> Result<()> {
-    let format = data.read_u32()?;
-    let width = data.read_u16()? as usize;
-    let height = data.read_u16()? as usize;
-    let mip_count = data.read_u32()?;
+/// A texture's mip level, decoded to RGBA8 pixels in row-major order.
+pub struct DecodedTexture {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
 
-    match format {
-        0x0 => dump_i4(data, width, height, mip_count, w),
-        0x1 => dump_i8(data, width, height, mip_count, w),
-        0x2 => dump_ia4(data, width, height, mip_count, w),
-        0x3 => dump_ia8(data, width, height, mip_count, w),
-        0x4 => dump_c4(data, width, height, mip_count, w),
-        0x5 => dump_c8(data, width, height, mip_count, w),
-        0x7 => dump_rgb565(data, width, height, mip_count, w),
-        0x8 => dump_rgb5a3(data, width, height, mip_count, w),
-        0x9 => dump_rgba8(data, width, height, mip_count, w),
-        0xa => dump_cmpr(data, width, height, mip_count, w),
-        _ => bail!("unknown texture format: {}", format),
+impl DecodedTexture {
+    /// Reads a PNG file, converting any color type to RGBA8.
+    pub fn read_png(path: &Path) -> Result<Self> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        let buf = &buf[..info.buffer_size()];
+
+        Ok(Self {
+            width: info.width as usize,
+            height: info.height as usize,
+            rgba: match info.color_type {
+                ColorType::Rgba => buf.to_vec(),
+                ColorType::Rgb => buf
+                    .chunks_exact(3)
+                    .flat_map(|p| [p[0], p[1], p[2], 0xff])
+                    .collect(),
+                ColorType::GrayscaleAlpha => buf
+                    .chunks_exact(2)
+                    .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                    .collect(),
+                ColorType::Grayscale => buf.iter().flat_map(|&i| [i, i, i, 0xff]).collect(),
+                ColorType::Indexed => unreachable!("palettes are expanded by the decoder"),
+            },
+        })
+    }
+
+    pub fn write_png<W: Write>(&self, w: &mut W) -> Result<()> {
+        self.write_png_with_compression(w, Compression::Default)
+    }
+
+    pub fn write_png_with_compression<W: Write>(
+        &self,
+        w: &mut W,
+        compression: Compression,
+    ) -> Result<()> {
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.set_compression(compression);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgba)?;
+        Ok(())
     }
+
+    /// Writes mip levels, largest first, as a KTX2 container of uncompressed sRGB RGBA8 images.
+    pub fn write_ktx2<W: Write>(levels: &[Self], mut w: W) -> Result<()> {
+        const IDENTIFIER: [u8; 12] = [
+            0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+        ];
+        const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+        const HEADER_SIZE: usize = 80;
+        const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+        // A basic data format descriptor block with one sample per channel.
+        const DFD_BLOCK_SIZE: u32 = 24 + 16 * 4;
+        const DFD_SIZE: u32 = 4 + DFD_BLOCK_SIZE;
+
+        let Some(top) = levels.first() else {
+            bail!("a KTX2 file needs at least one mip level");
+        };
+        let dfd_offset = HEADER_SIZE + LEVEL_INDEX_ENTRY_SIZE * levels.len();
+        let data_offset = dfd_offset + DFD_SIZE as usize;
+
+        w.write_all(&IDENTIFIER)?;
+        for value in [
+            VK_FORMAT_R8G8B8A8_SRGB,
+            1, // typeSize
+            top.width.try_into()?,
+            top.height.try_into()?,
+            0, // pixelDepth
+            0, // layerCount
+            1, // faceCount
+            levels.len().try_into()?,
+            0, // supercompressionScheme
+            dfd_offset.try_into()?,
+            DFD_SIZE,
+            0, // kvdByteOffset
+            0, // kvdByteLength
+        ] {
+            w.write_u32::<LittleEndian>(value)?;
+        }
+        w.write_u64::<LittleEndian>(0)?; // sgdByteOffset
+        w.write_u64::<LittleEndian>(0)?; // sgdByteLength
+
+        // The level index lists the largest level first, but the data stores it last.
+        let mut offsets = vec![0; levels.len()];
+        let mut offset = data_offset;
+        for (index, level) in levels.iter().enumerate().rev() {
+            offsets[index] = offset;
+            offset += level.rgba.len();
+        }
+        for (level, offset) in levels.iter().zip(offsets) {
+            w.write_u64::<LittleEndian>(offset as u64)?;
+            w.write_u64::<LittleEndian>(level.rgba.len() as u64)?;
+            w.write_u64::<LittleEndian>(level.rgba.len() as u64)?;
+        }
+
+        w.write_u32::<LittleEndian>(DFD_SIZE)?;
+        w.write_u32::<LittleEndian>(0)?; // vendorId and descriptorType
+        w.write_u32::<LittleEndian>(2 | DFD_BLOCK_SIZE << 16)?; // versionNumber
+
+        // The RGBSDA color model with BT.709 primaries, the sRGB transfer function, and straight
+        // alpha.
+        w.write_u32::<LittleEndian>(1 | 1 << 8 | 2 << 16)?;
+        w.write_u32::<LittleEndian>(0)?; // texelBlockDimension, one texel
+        w.write_u32::<LittleEndian>(4)?; // bytesPlane0
+        w.write_u32::<LittleEndian>(0)?;
+        // Red, green, blue, and alpha, which isn't sRGB-encoded.
+        for (index, channel) in [0u32, 1, 2, 0x10 | 15].into_iter().enumerate() {
+            w.write_u32::<LittleEndian>((8 * index as u32) | (7 << 16) | (channel << 24))?;
+            w.write_u32::<LittleEndian>(0)?; // samplePosition
+            w.write_u32::<LittleEndian>(0)?; // sampleLower
+            w.write_u32::<LittleEndian>(255)?; // sampleUpper
+        }
+
+        for level in levels.iter().rev() {
+            w.write_all(&level.rgba)?;
+        }
+        Ok(())
+    }
+
+    /// Writes equally sized frames as an endlessly looping animated PNG, showing each for
+    /// `delay` seconds as a fraction.
+    pub fn write_apng<W: Write>(frames: &[Self], delay: (u16, u16), w: W) -> Result<()> {
+        let Some(first) = frames.first() else {
+            bail!("An animated PNG needs at least one frame");
+        };
+        let mut encoder = png::Encoder::new(w, first.width as u32, first.height as u32);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.set_animated(frames.len() as u32, 0)?;
+        encoder.set_frame_delay(delay.0, delay.1)?;
+        let mut writer = encoder.write_header()?;
+        for frame in frames {
+            writer.write_image_data(&frame.rgba)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Writes a lossless WebP, which is typically smaller than the equivalent PNG.
+    pub fn write_webp<W: Write>(&self, w: W) -> Result<()> {
+        image_webp::WebPEncoder::new(w).encode(
+            &self.rgba,
+            self.width as u32,
+            self.height as u32,
+            image_webp::ColorType::Rgba8,
+        )?;
+        Ok(())
+    }
+
+    /// Writes a JPEG at the given quality from 1 to 100. Alpha is dropped.
+    pub fn write_jpeg<W: Write>(&self, w: W, quality: u8) -> Result<()> {
+        jpeg_encoder::Encoder::new(w, quality).encode(
+            &self.rgba,
+            self.width.try_into()?,
+            self.height.try_into()?,
+            jpeg_encoder::ColorType::Rgba,
+        )?;
+        Ok(())
+    }
+
+    /// The pixel at `(x, y)`, with coordinates past the edges clamped to them.
+    fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let offset = 4 * (self.width * y.min(self.height - 1) + x.min(self.width - 1));
+        self.rgba[offset..offset + 4].try_into().unwrap()
+    }
+
+    /// The next mip level: half the size, rounding down to at least one pixel, with each pixel
+    /// averaging the two by two it replaces.
+    fn half_size(&self) -> Self {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut rgba = Vec::with_capacity(4 * width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let pixels = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .map(|(dx, dy)| self.pixel(2 * x + dx, 2 * y + dy));
+                for channel in 0..4 {
+                    let sum: u32 = pixels.iter().map(|p| p[channel] as u32).sum();
+                    rgba.push(((sum + 2) / 4) as u8);
+                }
+            }
+        }
+        Self {
+            width,
+            height,
+            rgba,
+        }
+    }
+}
+
+/// The fields at the start of every TXTR resource.
+#[derive(Clone, Copy, Debug)]
+pub struct TextureHeader {
+    pub format: u32,
+    pub width: usize,
+    pub height: usize,
+    pub mip_count: u32,
+}
+
+impl TextureHeader {
+    pub const SIZE: usize = 12;
+
+    pub fn read(mut data: &[u8]) -> Result<Self> {
+        Ok(Self {
+            format: data.read_u32()?,
+            width: data.read_u16()? as usize,
+            height: data.read_u16()? as usize,
+            mip_count: data.read_u32()?,
+        })
+    }
+}
+
+pub fn dump<W: Write>(data: &[u8], w: &mut W) -> Result<()> {
+    decode(data)?.write_png(w)
+}
+
+pub fn decode(data: &[u8]) -> Result<DecodedTexture> {
+    let TextureHeader {
+        format,
+        width,
+        height,
+        ..
+    } = TextureHeader::read(data)?;
+    decode_level(format, &data[TextureHeader::SIZE..], width, height)
+}
+
+/// Decodes every mip level of a texture, largest first.
+pub fn decode_mips(data: &[u8]) -> Result<Vec<DecodedTexture>> {
+    let TextureHeader {
+        format,
+        width,
+        height,
+        mip_count,
+    } = TextureHeader::read(data)?;
+    let data = &data[TextureHeader::SIZE..];
+    // Paletted textures have one palette, before the first level, for all of them.
+    let palette_size = match format {
+        0x4..=0x6 => 8 + 2 * palette_entry_count(data)?,
+        _ => 0,
+    };
+    if data.len() < palette_size {
+        bail!("texture data ends within its palette");
+    }
+    let (palette, mut data) = data.split_at(palette_size);
+    let (block_width, block_height, block_size) = block_layout(format)?;
+
+    let mut levels = Vec::new();
+    for level in 0..mip_count as usize {
+        let width = (width >> level).max(1);
+        let height = (height >> level).max(1);
+        let size = width.div_ceil(block_width) * height.div_ceil(block_height) * block_size;
+        if data.len() < size {
+            bail!("texture data ends within mip level {level} of {mip_count}");
+        }
+        let (level_data, rest) = data.split_at(size);
+        data = rest;
+        levels.push(decode_level(
+            format,
+            &[palette, level_data].concat(),
+            width,
+            height,
+        )?);
+    }
+    Ok(levels)
+}
+
+/// The width and height in pixels of a format's blocks, and their size in bytes.
+fn block_layout(format: u32) -> Result<(usize, usize, usize)> {
+    Ok(match format {
+        0x0 | 0x4 | 0xa => (8, 8, 32),
+        0x1 | 0x2 | 0x5 => (8, 4, 32),
+        0x3 | 0x6 | 0x7 | 0x8 => (4, 4, 32),
+        0x9 => (4, 4, 64),
+        _ => bail!("unknown texture format: {}", format),
+    })
+}
+
+/// Decodes one level of image data, preceded by the palette if the format has one.
+fn decode_level(format: u32, data: &[u8], width: usize, height: usize) -> Result<DecodedTexture> {
+    let rgba = match format {
+        0x0 => decode_i4_image(data, width, height)?,
+        0x1 => decode_i8_image(data, width, height)?,
+        0x2 => decode_ia4_image(data, width, height)?,
+        0x3 => decode_ia8_image(data, width, height)?,
+        0x4 => decode_c4_image(data, width, height)?,
+        0x5 => decode_c8_image(data, width, height)?,
+        0x6 => decode_c14x2_image(data, width, height)?,
+        0x7 => decode_rgb565_image(data, width, height)?,
+        0x8 => decode_rgb5a3_image(data, width, height)?,
+        0x9 => decode_rgba8_image(data, width, height)?,
+        0xa => decode_cmpr_image(data, width, height)?,
+        _ => bail!("unknown texture format: {}", format),
+    };
+    Ok(DecodedTexture {
+        width,
+        height,
+        rgba,
+    })
 }
 
 fn decode_rgb5a3(encoded: u16) -> [u8; 4] {
@@ -57,23 +351,34 @@ fn decode_rgb565(encoded: u16) -> [u8; 4] {
     ]
 }
 
-fn palette_fetcher(format: u32) -> Result<fn(&[u8], usize) -> Result<[u8; 4]>> {
+type PaletteFetcher = fn(&[u8], usize) -> Result<[u8; 4]>;
+
+fn palette_fetcher(format: u32) -> Result<PaletteFetcher> {
     match format {
-        0x1 => Ok(|data, index| Ok(decode_rgb565((&data[2 * index..]).read_u16()?))),
-        0x2 => Ok(|data, index| Ok(decode_rgb5a3((&data[2 * index..]).read_u16()?))),
+        0x0 => Ok(|data, index| {
+            let entry = data.get(2 * index..2 * index + 2).unwrap_or_default();
+            let [i, a] = entry
+                .try_into()
+                .map_err(|_| anyhow!("palette has no entry {index}"))?;
+            Ok([i, i, i, a])
+        }),
+        0x1 => Ok(|data, index| {
+            Ok(decode_rgb565(
+                data.get(2 * index..).unwrap_or_default().read_u16()?,
+            ))
+        }),
+        0x2 => Ok(|data, index| {
+            Ok(decode_rgb5a3(
+                data.get(2 * index..).unwrap_or_default().read_u16()?,
+            ))
+        }),
         _ => bail!("unknown palette format: {}", format),
     }
 }
 
-fn dump_i4<W: Write>(
-    data: &[u8],
-    width: usize,
-    height: usize,
-    _mip_count: u32,
-    w: &mut W,
-) -> Result<()> {
+fn decode_i4_image(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
     let mut decoded = Vec::with_capacity(width * height * 4);
-    let blocks_wide = (width + 7) / 8;
+    let blocks_wide = width.div_ceil(8);
     for y in (0..height).rev() {
         let y = height - y - 1;
         let coarse_y = y / 8;
@@ -89,23 +394,12 @@ fn dump_i4<W: Write>(
         }
     }
 
-    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
-    encoder.set_color(ColorType::Rgba);
-    encoder.set_depth(BitDepth::Eight);
-    let mut writer = encoder.write_header()?;
-    writer.write_image_data(&decoded)?;
-    Ok(())
+    Ok(decoded)
 }
 
-fn dump_i8<W: Write>(
-    data: &[u8],
-    width: usize,
-    height: usize,
-    _mip_count: u32,
-    w: &mut W,
-) -> Result<()> {
+fn decode_i8_image(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
     let mut decoded = Vec::with_capacity(width * height * 4);
-    let blocks_wide = (width + 7) / 8;
+    let blocks_wide = width.div_ceil(8);
     for y in (0..height).rev() {
         let y = height - y - 1;
         let coarse_y = y / 4;
@@ -113,29 +407,18 @@ fn dump_i8<W: Write>(
         for x in 0..width {
             let coarse_x = x / 8;
             let fine_x = x % 8;
-            let offset = 32 * (blocks_wide * coarse_y + coarse_x) + 1 * (8 * fine_y + fine_x);
+            let offset = 32 * (blocks_wide * coarse_y + coarse_x) + (8 * fine_y + fine_x);
             let i = data[offset];
             decoded.extend_from_slice(&[i, i, i, 255]);
         }
     }
 
-    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
-    encoder.set_color(ColorType::Rgba);
-    encoder.set_depth(BitDepth::Eight);
-    let mut writer = encoder.write_header()?;
-    writer.write_image_data(&decoded)?;
-    Ok(())
+    Ok(decoded)
 }
 
-fn dump_ia4<W: Write>(
-    data: &[u8],
-    width: usize,
-    height: usize,
-    _mip_count: u32,
-    w: &mut W,
-) -> Result<()> {
+fn decode_ia4_image(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
     let mut decoded = Vec::with_capacity(width * height * 4);
-    let blocks_wide = (width + 7) / 8;
+    let blocks_wide = width.div_ceil(8);
     for y in (0..height).rev() {
         let y = height - y - 1;
         let coarse_y = y / 4;
@@ -143,7 +426,7 @@ fn dump_ia4<W: Write>(
         for x in 0..width {
             let coarse_x = x / 8;
             let fine_x = x % 8;
-            let offset = 32 * (blocks_wide * coarse_y + coarse_x) + 1 * (8 * fine_y + fine_x);
+            let offset = 32 * (blocks_wide * coarse_y + coarse_x) + (8 * fine_y + fine_x);
             let encoded = data[offset];
             let i = encoded >> 4;
             let i = (i << 4) | i;
@@ -153,23 +436,12 @@ fn dump_ia4<W: Write>(
         }
     }
 
-    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
-    encoder.set_color(ColorType::Rgba);
-    encoder.set_depth(BitDepth::Eight);
-    let mut writer = encoder.write_header()?;
-    writer.write_image_data(&decoded)?;
-    Ok(())
+    Ok(decoded)
 }
 
-fn dump_ia8<W: Write>(
-    data: &[u8],
-    width: usize,
-    height: usize,
-    _mip_count: u32,
-    w: &mut W,
-) -> Result<()> {
+fn decode_ia8_image(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
     let mut decoded = Vec::with_capacity(width * height * 4);
-    let blocks_wide = (width + 3) / 4;
+    let blocks_wide = width.div_ceil(4);
     for y in (0..height).rev() {
         let y = height - y - 1;
         let coarse_y = y / 4;
@@ -184,21 +456,10 @@ fn dump_ia8<W: Write>(
         }
     }
 
-    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
-    encoder.set_color(ColorType::Rgba);
-    encoder.set_depth(BitDepth::Eight);
-    let mut writer = encoder.write_header()?;
-    writer.write_image_data(&decoded)?;
-    Ok(())
+    Ok(decoded)
 }
 
-fn dump_c4<W: Write>(
-    mut data: &[u8],
-    width: usize,
-    height: usize,
-    _mip_count: u32,
-    w: &mut W,
-) -> Result<()> {
+fn decode_c4_image(mut data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
     let palette_fetcher = palette_fetcher(data.read_u32()?)?;
     assert_eq!(data.read_u16()?, 1);
     assert_eq!(data.read_u16()?, 16);
@@ -206,7 +467,7 @@ fn dump_c4<W: Write>(
     let data = &data[32..];
 
     let mut decoded = Vec::with_capacity(width * height * 4);
-    let blocks_wide = (width + 7) / 8;
+    let blocks_wide = width.div_ceil(8);
     for y in (0..height).rev() {
         let y = height - y - 1;
         let coarse_y = y / 8;
@@ -221,21 +482,10 @@ fn dump_c4<W: Write>(
         }
     }
 
-    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
-    encoder.set_color(ColorType::Rgba);
-    encoder.set_depth(BitDepth::Eight);
-    let mut writer = encoder.write_header()?;
-    writer.write_image_data(&decoded)?;
-    Ok(())
+    Ok(decoded)
 }
 
-fn dump_c8<W: Write>(
-    mut data: &[u8],
-    width: usize,
-    height: usize,
-    _mip_count: u32,
-    w: &mut W,
-) -> Result<()> {
+fn decode_c8_image(mut data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
     let palette_fetcher = palette_fetcher(data.read_u32()?)?;
     assert_eq!(data.read_u16()?, 256);
     assert_eq!(data.read_u16()?, 1);
@@ -243,7 +493,7 @@ fn dump_c8<W: Write>(
     let data = &data[512..];
 
     let mut decoded = Vec::with_capacity(width * height * 4);
-    let blocks_wide = (width + 7) / 8;
+    let blocks_wide = width.div_ceil(8);
     for y in (0..height).rev() {
         let y = height - y - 1;
         let coarse_y = y / 4;
@@ -251,29 +501,51 @@ fn dump_c8<W: Write>(
         for x in 0..width {
             let coarse_x = x / 8;
             let fine_x = x % 8;
-            let offset = 32 * (blocks_wide * coarse_y + coarse_x) + 1 * (8 * fine_y + fine_x);
+            let offset = 32 * (blocks_wide * coarse_y + coarse_x) + (8 * fine_y + fine_x);
             let c = data[offset];
             decoded.extend_from_slice(&palette_fetcher(palette, c as usize)?);
         }
     }
 
-    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
-    encoder.set_color(ColorType::Rgba);
-    encoder.set_depth(BitDepth::Eight);
-    let mut writer = encoder.write_header()?;
-    writer.write_image_data(&decoded)?;
-    Ok(())
+    Ok(decoded)
+}
+
+/// The number of entries in the palette at the start of a paletted texture's data, from the
+/// dimensions in its header.
+fn palette_entry_count(mut data: &[u8]) -> Result<usize> {
+    let _format = data.read_u32()?;
+    Ok(data.read_u16()? as usize * data.read_u16()? as usize)
+}
+
+fn decode_c14x2_image(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
+    let palette_fetcher = palette_fetcher((&data[..]).read_u32()?)?;
+    let palette_size = 2 * palette_entry_count(data)?;
+    let palette = data
+        .get(8..8 + palette_size)
+        .ok_or_else(|| anyhow!("texture data ends within its palette"))?;
+    let data = &data[8 + palette_size..];
+
+    let mut decoded = Vec::with_capacity(width * height * 4);
+    let blocks_wide = width.div_ceil(4);
+    for y in (0..height).rev() {
+        let y = height - y - 1;
+        let coarse_y = y / 4;
+        let fine_y = y % 4;
+        for x in 0..width {
+            let coarse_x = x / 4;
+            let fine_x = x % 4;
+            let offset = 32 * (blocks_wide * coarse_y + coarse_x) + 2 * (4 * fine_y + fine_x);
+            let c = (&data[offset..]).read_u16()? & 0x3fff;
+            decoded.extend_from_slice(&palette_fetcher(palette, c as usize)?);
+        }
+    }
+
+    Ok(decoded)
 }
 
-fn dump_rgb565<W: Write>(
-    data: &[u8],
-    width: usize,
-    height: usize,
-    _mip_count: u32,
-    w: &mut W,
-) -> Result<()> {
+fn decode_rgb565_image(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
     let mut decoded = Vec::with_capacity(width * height * 4);
-    let blocks_wide = (width + 3) / 4;
+    let blocks_wide = width.div_ceil(4);
     for y in (0..height).rev() {
         let y = height - y - 1;
         let coarse_y = y / 4;
@@ -287,23 +559,12 @@ fn dump_rgb565<W: Write>(
         }
     }
 
-    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
-    encoder.set_color(ColorType::Rgba);
-    encoder.set_depth(BitDepth::Eight);
-    let mut writer = encoder.write_header()?;
-    writer.write_image_data(&decoded)?;
-    Ok(())
+    Ok(decoded)
 }
 
-fn dump_rgb5a3<W: Write>(
-    data: &[u8],
-    width: usize,
-    height: usize,
-    _mip_count: u32,
-    w: &mut W,
-) -> Result<()> {
+fn decode_rgb5a3_image(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
     let mut decoded = Vec::with_capacity(width * height * 4);
-    let blocks_wide = (width + 3) / 4;
+    let blocks_wide = width.div_ceil(4);
     for y in (0..height).rev() {
         let y = height - y - 1;
         let coarse_y = y / 4;
@@ -317,23 +578,12 @@ fn dump_rgb5a3<W: Write>(
         }
     }
 
-    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
-    encoder.set_color(ColorType::Rgba);
-    encoder.set_depth(BitDepth::Eight);
-    let mut writer = encoder.write_header()?;
-    writer.write_image_data(&decoded)?;
-    Ok(())
+    Ok(decoded)
 }
 
-fn dump_rgba8<W: Write>(
-    data: &[u8],
-    width: usize,
-    height: usize,
-    _mip_count: u32,
-    w: &mut W,
-) -> Result<()> {
+fn decode_rgba8_image(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
     let mut decoded = Vec::with_capacity(width * height * 4);
-    let blocks_wide = (width + 3) / 4;
+    let blocks_wide = width.div_ceil(4);
     for y in (0..height).rev() {
         let y = height - y - 1;
         let coarse_y = y / 4;
@@ -350,23 +600,12 @@ fn dump_rgba8<W: Write>(
         }
     }
 
-    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
-    encoder.set_color(ColorType::Rgba);
-    encoder.set_depth(BitDepth::Eight);
-    let mut writer = encoder.write_header()?;
-    writer.write_image_data(&decoded)?;
-    Ok(())
+    Ok(decoded)
 }
 
-fn dump_cmpr<W: Write>(
-    data: &[u8],
-    width: usize,
-    height: usize,
-    _mip_count: u32,
-    w: &mut W,
-) -> Result<()> {
+fn decode_cmpr_image(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
     let mut decoded = Vec::with_capacity(width * height * 4);
-    let blocks_wide = (width + 7) / 8;
+    let blocks_wide = width.div_ceil(8);
     for y in (0..height).rev() {
         let y = height - y - 1;
         let coarse_y = y / 8;
@@ -379,45 +618,257 @@ fn dump_cmpr<W: Write>(
             let color_b_encoded = dxt1_data.read_u16()?;
             let color_a = decode_rgb565(color_a_encoded);
             let color_b = decode_rgb565(color_b_encoded);
-            let palette = if color_a > color_b {
-                [
-                    color_a,
-                    color_b,
-                    [
-                        ((2 * color_a[0] as u16 + color_b[0] as u16) / 3) as u8,
-                        ((2 * color_a[1] as u16 + color_b[1] as u16) / 3) as u8,
-                        ((2 * color_a[2] as u16 + color_b[2] as u16) / 3) as u8,
-                        ((2 * color_a[3] as u16 + color_b[3] as u16) / 3) as u8,
-                    ],
-                    [
-                        ((color_a[0] as u16 + 2 * color_b[0] as u16) / 3) as u8,
-                        ((color_a[1] as u16 + 2 * color_b[1] as u16) / 3) as u8,
-                        ((color_a[2] as u16 + 2 * color_b[2] as u16) / 3) as u8,
-                        ((color_a[3] as u16 + 2 * color_b[3] as u16) / 3) as u8,
-                    ],
-                ]
-            } else {
-                [
-                    color_a,
-                    color_b,
-                    [
-                        ((color_a[0] as u16 + color_b[0] as u16) / 2) as u8,
-                        ((color_a[1] as u16 + color_b[1] as u16) / 2) as u8,
-                        ((color_a[2] as u16 + color_b[2] as u16) / 2) as u8,
-                        ((color_a[3] as u16 + color_b[3] as u16) / 2) as u8,
-                    ],
-                    [0, 0, 0, 0],
-                ]
-            };
+            let palette = cmpr_palette(color_a, color_b);
             let index = (dxt1_data[y % 4] >> (2 * (3 - x % 4))) & 3;
             decoded.extend_from_slice(&palette[index as usize]);
         }
     }
 
-    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
-    encoder.set_color(ColorType::Rgba);
-    encoder.set_depth(BitDepth::Eight);
-    let mut writer = encoder.write_header()?;
-    writer.write_image_data(&decoded)?;
-    Ok(())
+    Ok(decoded)
+}
+
+/// The colors a CMPR block's indices select from: four blended between the endpoints if the first
+/// is greater, otherwise three and transparent black.
+fn cmpr_palette(color_a: [u8; 4], color_b: [u8; 4]) -> [[u8; 4]; 4] {
+    if color_a > color_b {
+        [
+            color_a,
+            color_b,
+            [0, 1, 2, 3].map(|i| ((2 * color_a[i] as u16 + color_b[i] as u16) / 3) as u8),
+            [0, 1, 2, 3].map(|i| ((color_a[i] as u16 + 2 * color_b[i] as u16) / 3) as u8),
+        ]
+    } else {
+        [
+            color_a,
+            color_b,
+            [0, 1, 2, 3].map(|i| ((color_a[i] as u16 + color_b[i] as u16) / 2) as u8),
+            [0, 0, 0, 0],
+        ]
+    }
+}
+
+/// The texture formats [`encode`] can write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TextureFormat {
+    /// 4-bit intensity.
+    I4,
+    /// 8-bit intensity.
+    I8,
+    /// 4-bit intensity with 4-bit alpha.
+    Ia4,
+    /// 8-bit intensity with 8-bit alpha.
+    Ia8,
+    /// 16-bit color without alpha.
+    Rgb565,
+    /// 16-bit color with either 3-bit alpha or none.
+    Rgb5a3,
+    /// 32-bit color with alpha.
+    Rgba8,
+    /// Compressed color in 4x4 blocks, with 1-bit alpha.
+    Cmpr,
+}
+
+impl TextureFormat {
+    /// The format's value in the TXTR header.
+    pub fn id(self) -> u32 {
+        match self {
+            Self::I4 => 0x0,
+            Self::I8 => 0x1,
+            Self::Ia4 => 0x2,
+            Self::Ia8 => 0x3,
+            Self::Rgb565 => 0x7,
+            Self::Rgb5a3 => 0x8,
+            Self::Rgba8 => 0x9,
+            Self::Cmpr => 0xa,
+        }
+    }
+}
+
+/// The number of mip levels for a `width` by `height` texture in `format` if each is halved until
+/// the next would be smaller than one block.
+pub fn full_mip_count(format: u32, width: usize, height: usize) -> Result<u32> {
+    let (block_width, block_height, _) = block_layout(format)?;
+    let (mut width, mut height) = (width, height);
+    let mut count = 1;
+    while width / 2 >= block_width && height / 2 >= block_height {
+        width /= 2;
+        height /= 2;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Writes one block of a format's tiled image data from the pixels starting at `(x, y)`.
+type BlockEncoder = fn(&DecodedTexture, usize, usize, &mut [u8]);
+
+/// Encodes a texture in `format` with `mip_count` levels, each half the size of the one before.
+/// Pixels are stored the way [`decode`] reads them, so re-encoding a decoded texture in its own
+/// format reproduces it. Paletted formats aren't supported, since they'd need a palette chosen.
+pub fn encode(texture: &DecodedTexture, format: u32, mip_count: u32) -> Result<Vec<u8>> {
+    let encode_block: BlockEncoder = match format {
+        0x0 => encode_i4_block,
+        0x1 => encode_i8_block,
+        0x2 => encode_ia4_block,
+        0x3 => encode_ia8_block,
+        0x7 => encode_rgb565_block,
+        0x8 => encode_rgb5a3_block,
+        0x9 => encode_rgba8_block,
+        0xa => encode_cmpr_block,
+        0x4..=0x6 => bail!("encoding paletted textures isn't supported"),
+        _ => bail!("unknown texture format: {}", format),
+    };
+    let (block_width, block_height, block_size) = block_layout(format)?;
+
+    let mut w = Vec::new();
+    w.write_u32::<BigEndian>(format)?;
+    w.write_u16::<BigEndian>(texture.width.try_into()?)?;
+    w.write_u16::<BigEndian>(texture.height.try_into()?)?;
+    w.write_u32::<BigEndian>(mip_count)?;
+    let mut level = None;
+    for _ in 0..mip_count {
+        let texture = level.as_ref().unwrap_or(texture);
+        for y in (0..texture.height).step_by(block_height) {
+            for x in (0..texture.width).step_by(block_width) {
+                let start = w.len();
+                w.resize(start + block_size, 0);
+                encode_block(texture, x, y, &mut w[start..]);
+            }
+        }
+        level = Some(texture.half_size());
+    }
+    Ok(w)
+}
+
+fn intensity(pixel: [u8; 4]) -> u8 {
+    ((299 * pixel[0] as u32 + 587 * pixel[1] as u32 + 114 * pixel[2] as u32) / 1000) as u8
+}
+
+fn encode_rgb565(pixel: [u8; 4]) -> u16 {
+    (pixel[0] as u16 >> 3) << 11 | (pixel[1] as u16 >> 2) << 5 | pixel[2] as u16 >> 3
+}
+
+fn encode_rgb5a3(pixel: [u8; 4]) -> u16 {
+    if pixel[3] >= 0xe0 {
+        0x8000 | (pixel[0] as u16 >> 3) << 10 | (pixel[1] as u16 >> 3) << 5 | pixel[2] as u16 >> 3
+    } else {
+        (pixel[3] as u16 >> 5) << 12
+            | (pixel[0] as u16 >> 4) << 8
+            | (pixel[1] as u16 >> 4) << 4
+            | pixel[2] as u16 >> 4
+    }
+}
+
+fn encode_i4_block(texture: &DecodedTexture, x: usize, y: usize, block: &mut [u8]) {
+    for fine_y in 0..8 {
+        for fine_x in 0..8 {
+            let i = intensity(texture.pixel(x + fine_x, y + fine_y)) >> 4;
+            let shift = if fine_x % 2 == 0 { 4 } else { 0 };
+            block[(8 * fine_y + fine_x) / 2] |= i << shift;
+        }
+    }
+}
+
+fn encode_i8_block(texture: &DecodedTexture, x: usize, y: usize, block: &mut [u8]) {
+    for fine_y in 0..4 {
+        for fine_x in 0..8 {
+            block[8 * fine_y + fine_x] = intensity(texture.pixel(x + fine_x, y + fine_y));
+        }
+    }
+}
+
+fn encode_ia4_block(texture: &DecodedTexture, x: usize, y: usize, block: &mut [u8]) {
+    for fine_y in 0..4 {
+        for fine_x in 0..8 {
+            let pixel = texture.pixel(x + fine_x, y + fine_y);
+            block[8 * fine_y + fine_x] = intensity(pixel) & 0xf0 | pixel[3] >> 4;
+        }
+    }
+}
+
+fn encode_ia8_block(texture: &DecodedTexture, x: usize, y: usize, block: &mut [u8]) {
+    for fine_y in 0..4 {
+        for fine_x in 0..4 {
+            let pixel = texture.pixel(x + fine_x, y + fine_y);
+            let offset = 2 * (4 * fine_y + fine_x);
+            block[offset] = intensity(pixel);
+            block[offset + 1] = pixel[3];
+        }
+    }
+}
+
+fn encode_rgb565_block(texture: &DecodedTexture, x: usize, y: usize, block: &mut [u8]) {
+    for fine_y in 0..4 {
+        for fine_x in 0..4 {
+            let offset = 2 * (4 * fine_y + fine_x);
+            block[offset..offset + 2].copy_from_slice(
+                &encode_rgb565(texture.pixel(x + fine_x, y + fine_y)).to_be_bytes(),
+            );
+        }
+    }
+}
+
+fn encode_rgb5a3_block(texture: &DecodedTexture, x: usize, y: usize, block: &mut [u8]) {
+    for fine_y in 0..4 {
+        for fine_x in 0..4 {
+            let offset = 2 * (4 * fine_y + fine_x);
+            block[offset..offset + 2].copy_from_slice(
+                &encode_rgb5a3(texture.pixel(x + fine_x, y + fine_y)).to_be_bytes(),
+            );
+        }
+    }
+}
+
+fn encode_rgba8_block(texture: &DecodedTexture, x: usize, y: usize, block: &mut [u8]) {
+    for fine_y in 0..4 {
+        for fine_x in 0..4 {
+            let [r, g, b, a] = texture.pixel(x + fine_x, y + fine_y);
+            let offset = 2 * (4 * fine_y + fine_x);
+            block[offset..offset + 2].copy_from_slice(&[a, r]);
+            block[offset + 32..offset + 34].copy_from_slice(&[g, b]);
+        }
+    }
+}
+
+/// Encodes the four 4x4 sub-blocks of a CMPR block, using the darkest and brightest opaque pixels
+/// of each as its endpoints. Sub-blocks with transparent pixels use the three color mode.
+fn encode_cmpr_block(texture: &DecodedTexture, x: usize, y: usize, block: &mut [u8]) {
+    for (sub_block, w) in block.chunks_exact_mut(8).enumerate() {
+        let (x, y) = (x + 4 * (sub_block & 1), y + 4 * (sub_block >> 1));
+        let pixels: Vec<[u8; 4]> = (0..16)
+            .map(|i| texture.pixel(x + i % 4, y + i / 4))
+            .collect();
+        let transparent = pixels.iter().any(|p| p[3] < 0x80);
+        let opaque = pixels.iter().filter(|p| p[3] >= 0x80);
+        let mut endpoints = [
+            opaque.clone().max_by_key(|&&p| intensity(p)),
+            opaque.min_by_key(|&&p| intensity(p)),
+        ]
+        .map(|p| encode_rgb565(p.copied().unwrap_or_default()));
+        let [color_a, color_b] = endpoints.map(decode_rgb565);
+        // The decoder picks the four color mode when the first endpoint is greater.
+        if (color_a > color_b) == transparent {
+            endpoints.swap(0, 1);
+        }
+        let palette = cmpr_palette(decode_rgb565(endpoints[0]), decode_rgb565(endpoints[1]));
+        let candidates = if palette[3] == [0, 0, 0, 0] { 3 } else { 4 };
+
+        w[0..2].copy_from_slice(&endpoints[0].to_be_bytes());
+        w[2..4].copy_from_slice(&endpoints[1].to_be_bytes());
+        for (i, pixel) in pixels.iter().enumerate() {
+            let index = if pixel[3] < 0x80 {
+                3
+            } else {
+                (0..candidates)
+                    .min_by_key(|&c| {
+                        (0..3)
+                            .map(|channel| {
+                                (palette[c][channel] as i32 - pixel[channel] as i32).pow(2)
+                            })
+                            .sum::<i32>()
+                    })
+                    .unwrap()
+            };
+            w[4 + i / 4] |= (index as u8) << (2 * (3 - i % 4));
+        }
+    }
 }
@@ -0,0 +1,64 @@
+use std::io::Write;
+
+use anyhow::Result;
+use byteorder::{LittleEndian, WriteBytesExt};
+
+/// Writes mono 16-bit PCM as a WAV file. A loop, given as first and last sample inclusive, is
+/// recorded in a `smpl` chunk, which samplers and most audio editors honor.
+pub fn write<W: Write>(
+    mut w: W,
+    sample_rate: u32,
+    samples: &[i16],
+    loop_range: Option<(u32, u32)>,
+) -> Result<()> {
+    const CHANNELS: u16 = 1;
+    const BITS_PER_SAMPLE: u16 = 16;
+    const FMT_SIZE: u32 = 16;
+    const SMPL_SIZE: u32 = 36 + 24;
+
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let data_size = (samples.len() * 2) as u32;
+    let smpl_chunk_size = loop_range.map_or(0, |_| 8 + SMPL_SIZE);
+    let riff_size = 4 + (8 + FMT_SIZE) + smpl_chunk_size + (8 + data_size);
+
+    w.write_all(b"RIFF")?;
+    w.write_u32::<LittleEndian>(riff_size)?;
+    w.write_all(b"WAVE")?;
+
+    w.write_all(b"fmt ")?;
+    w.write_u32::<LittleEndian>(FMT_SIZE)?;
+    w.write_u16::<LittleEndian>(1)?; // PCM
+    w.write_u16::<LittleEndian>(CHANNELS)?;
+    w.write_u32::<LittleEndian>(sample_rate)?;
+    w.write_u32::<LittleEndian>(sample_rate * block_align as u32)?;
+    w.write_u16::<LittleEndian>(block_align)?;
+    w.write_u16::<LittleEndian>(BITS_PER_SAMPLE)?;
+
+    if let Some((loop_start, loop_end)) = loop_range {
+        w.write_all(b"smpl")?;
+        w.write_u32::<LittleEndian>(SMPL_SIZE)?;
+        w.write_u32::<LittleEndian>(0)?; // Manufacturer
+        w.write_u32::<LittleEndian>(0)?; // Product
+        w.write_u32::<LittleEndian>(1_000_000_000 / sample_rate.max(1))?; // Sample period in ns
+        w.write_u32::<LittleEndian>(60)?; // MIDI unity note
+        w.write_u32::<LittleEndian>(0)?; // MIDI pitch fraction
+        w.write_u32::<LittleEndian>(0)?; // SMPTE format
+        w.write_u32::<LittleEndian>(0)?; // SMPTE offset
+        w.write_u32::<LittleEndian>(1)?; // Loop count
+        w.write_u32::<LittleEndian>(0)?; // Sampler data size
+        w.write_u32::<LittleEndian>(0)?; // Cue point ID
+        w.write_u32::<LittleEndian>(0)?; // Forward loop
+        w.write_u32::<LittleEndian>(loop_start)?;
+        w.write_u32::<LittleEndian>(loop_end)?;
+        w.write_u32::<LittleEndian>(0)?; // Fraction
+        w.write_u32::<LittleEndian>(0)?; // Play forever
+    }
+
+    w.write_all(b"data")?;
+    w.write_u32::<LittleEndian>(data_size)?;
+    for &sample in samples {
+        w.write_i16::<LittleEndian>(sample)?;
+    }
+    w.flush()?;
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Result};
+use memmap::Mmap;
+
+/// Name given to the disc image passed as the positional argument.
+pub const DEFAULT_GAME: &str = "default";
+
+/// Filesystem types where mapping a large file performs poorly, because page faults turn into
+/// small synchronous network reads.
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "9p",
+    "afs",
+    "ceph",
+    "cifs",
+    "fuse.sshfs",
+    "glusterfs",
+    "nfs",
+    "nfs4",
+    "smb3",
+    "smbfs",
+];
+
+/// A disc image registered with the workspace under a short name.
+pub struct DiscImage {
+    name: String,
+    path: PathBuf,
+    data: ImageData,
+}
+
+enum ImageData {
+    Mapped(Mmap),
+    /// Read into memory up front, for filesystems where mapping is slow.
+    Read(Vec<u8>),
+}
+
+impl DiscImage {
+    fn open(name: String, path: PathBuf, no_mmap: bool) -> Result<Self> {
+        let file = File::open(&path)
+            .map_err(|e| anyhow!("Couldn't open disc image {}: {e}", path.display()))?;
+        let data = if no_mmap || is_on_network_filesystem(&path) {
+            ImageData::Read(std::fs::read(&path)?)
+        } else {
+            ImageData::Mapped(unsafe { Mmap::map(&file) }?)
+        };
+        let image = Self { name, path, data };
+        if image.data().len() != gamecube::disc::SIZE as usize {
+            bail!(
+                "Disc image {} is {} bytes, want {}",
+                image.path.display(),
+                image.data().len(),
+                gamecube::disc::SIZE,
+            );
+        }
+        Ok(image)
+    }
+
+    /// Hints that the image will be read front to back, so the kernel reads ahead aggressively
+    /// and drops pages behind. Has no effect on images that aren't mapped.
+    pub fn advise_sequential(&self) {
+        #[cfg(unix)]
+        if let ImageData::Mapped(mmap) = &self.data {
+            // This is only a hint, so failure is harmless.
+            unsafe {
+                libc::madvise(
+                    mmap.as_ptr() as *mut libc::c_void,
+                    mmap.len(),
+                    libc::MADV_SEQUENTIAL,
+                );
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn data(&self) -> &[u8] {
+        match &self.data {
+            ImageData::Mapped(mmap) => mmap,
+            ImageData::Read(data) => data,
+        }
+    }
+}
+
+/// Every disc image available to one invocation, so commands can compare assets across game
+/// versions and regions.
+pub struct Workspace {
+    images: Vec<DiscImage>,
+}
+
+impl Workspace {
+    /// Opens the positional image (registered as [`DEFAULT_GAME`]) and any `NAME=PATH`
+    /// registrations, of which there may be none when working on loose pak files. Images are
+    /// mapped unless `no_mmap` is set or they're on a network filesystem, in which case they're
+    /// read into memory.
+    pub fn open(image_path: Option<&str>, registrations: &[String], no_mmap: bool) -> Result<Self> {
+        let mut images: Vec<DiscImage> = Vec::new();
+        if let Some(image_path) = image_path {
+            images.push(DiscImage::open(
+                DEFAULT_GAME.to_string(),
+                image_path.into(),
+                no_mmap,
+            )?);
+        }
+        for registration in registrations {
+            let (name, path) = registration
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Disc registration {registration:?} isn't NAME=PATH"))?;
+            if images.iter().any(|image| image.name == name) {
+                bail!("Disc name {name:?} is registered more than once");
+            }
+            images.push(DiscImage::open(name.to_string(), path.into(), no_mmap)?);
+        }
+        Ok(Self { images })
+    }
+
+    /// Looks up a disc image by name. Without a name, this is the positional image, or the only
+    /// registered one.
+    pub fn select(&self, game: Option<&str>) -> Result<&DiscImage> {
+        match game {
+            Some(game) => self.get(game),
+            None if self.images.is_empty() => {
+                bail!("No disc image given; pass an image path or --disc NAME=PATH")
+            }
+            None if self.images.len() == 1 => Ok(&self.images[0]),
+            None => self
+                .get(DEFAULT_GAME)
+                .map_err(|_| anyhow!("Several disc images are registered; choose one with --game")),
+        }
+    }
+
+    pub fn get(&self, game: &str) -> Result<&DiscImage> {
+        self.images
+            .iter()
+            .find(|image| image.name == game)
+            .ok_or_else(|| anyhow!("No disc image is registered as {game:?}"))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DiscImage> {
+        self.images.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+}
+
+/// Guesses whether `path` is on a network filesystem by finding its mount in `/proc/mounts`.
+/// Always false where that isn't available.
+fn is_on_network_filesystem(path: &Path) -> bool {
+    let (Ok(path), Ok(mounts)) = (path.canonicalize(), std::fs::read_to_string("/proc/mounts"))
+    else {
+        return false;
+    };
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = fields.nth(1)?.replace("\\040", " ");
+            let fs_type = fields.next()?;
+            path.starts_with(&mount_point)
+                .then_some((mount_point.len(), fs_type))
+        })
+        .max_by_key(|&(len, _)| len)
+        .is_some_and(|(_, fs_type)| NETWORK_FILESYSTEMS.contains(&fs_type))
+}